@@ -0,0 +1,31 @@
+//! Measures the stress maps and fails if anything did more work than its
+//! budget. Timings are printed but never fail the bench.
+//!
+//! Run with `cargo bench --features stress`.
+
+extern crate hexadventure;
+
+use hexadventure::stress;
+use std::process;
+
+/// How many times each operation is timed.
+const RUNS: u32 = 50;
+
+fn main() {
+    let mut regressed = false;
+    for measurement in stress::run(RUNS) {
+        let mark = if measurement.regressed() {
+            regressed = true;
+            "OVER"
+        } else {
+            "ok"
+        };
+        println!(
+            "{:<40} {:>10.3?} {:>8} (budget {}) {}",
+            measurement.name, measurement.time, measurement.work, measurement.budget, mark
+        );
+    }
+    if regressed {
+        process::exit(1);
+    }
+}
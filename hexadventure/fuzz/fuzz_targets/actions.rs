@@ -0,0 +1,37 @@
+//! Plays arbitrary actions on a seeded world, checking invariants after each.
+//!
+//! The first eight bytes are the seed. Each byte after that is an action:
+//! resting, or walking or sneaking in one of the six directions.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate hexadventure;
+
+use hexadventure::grid::DIRECTIONS;
+use hexadventure::level::GenParams;
+use hexadventure::world::action::Action;
+use hexadventure::world::World;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 8 {
+        return;
+    }
+    let mut seed = [0; 8];
+    seed.copy_from_slice(&data[..8]);
+    let mut world = World::from_seed(u64::from_le_bytes(seed), GenParams::default());
+    for &byte in &data[8..] {
+        if world.is_over() {
+            break;
+        }
+        let action = match byte % 13 {
+            12 => Action::Rest,
+            i if i < 6 => Action::Walk(DIRECTIONS[i as usize]),
+            i => Action::Sneak(DIRECTIONS[i as usize - 6]),
+        };
+        let _ = world.perform_confirmed(action);
+        if let Err(violations) = world.validate_invariants() {
+            panic!("after {:?}: {:?}", action, violations);
+        }
+    }
+});
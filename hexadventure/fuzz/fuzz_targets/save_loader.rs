@@ -0,0 +1,40 @@
+//! Feeds arbitrary bytes to the save loader, which must reject bad saves
+//! with an error rather than panicking.
+//!
+//! Half the inputs are sealed in a valid envelope first, so the fuzzer
+//! also reaches deserializing the world itself. A world that loads and
+//! passes validation must then be playable, so it is looked at and ticked.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate bincode;
+extern crate hexadventure;
+
+use hexadventure::content::Content;
+use hexadventure::save;
+use hexadventure::world::World;
+
+/// The most a save may allocate while being read.
+const LIMIT: u64 = 1 << 24;
+
+fuzz_target!(|data: &[u8]| {
+    let content = Content::default().fingerprint();
+    let sealed;
+    let bytes = match data.split_first() {
+        Some((&flag, rest)) if flag % 2 == 0 => {
+            sealed = save::seal(rest, content);
+            &sealed[..]
+        }
+        _ => data,
+    };
+    if let Ok(payload) = save::open(bytes, content) {
+        let world: Result<World, _> = bincode::config().limit(LIMIT).deserialize(payload);
+        if let Ok(mut world) = world {
+            if world.validate_invariants().is_ok() {
+                let _ = world.fov();
+                world.tick();
+            }
+        }
+    }
+});
@@ -0,0 +1,257 @@
+//! Drawing hex maps as plain text.
+//!
+//! Rows are offset the same way they are on screen:
+//!
+//! ```text
+//! # # # #
+//!  # . @ #
+//! # . s #
+//!  # # # #
+//! ```
+
+use camera::Camera;
+use grid::{pos_to_location, Location, HEIGHT, WIDTH};
+use level::tile::{Terrain, Tile};
+use prelude::*;
+use protocol::{SeenTile, Snapshot};
+use std::fmt::Write;
+use world::mob::{Npcs, Species};
+
+/// The characters used to draw each kind of tile and mob.
+pub struct GlyphTheme {
+    pub wall: char,
+    pub floor: char,
+    pub short_grass: char,
+    pub tall_grass: char,
+    pub brownberry: char,
+    pub exit: char,
+    pub entrance: char,
+    pub water: char,
+    pub shallow_water: char,
+    pub web: char,
+    pub door: char,
+    pub open_door: char,
+    pub broken_door: char,
+    pub portal: char,
+    pub unknown: char,
+    pub hero: char,
+    pub skeleton: char,
+    pub behemoth: char,
+    pub spider: char,
+    pub archer: char,
+    pub thief: char,
+    pub necromancer: char,
+    pub serpent: char,
+}
+
+/// The part of the map to draw, in screen locations.
+struct Region {
+    top_left: Location,
+    width: i32,
+    height: i32,
+}
+
+impl GlyphTheme {
+    /// Traditional roguelike characters, safe for any terminal.
+    pub fn classic() -> Self {
+        GlyphTheme {
+            wall: '#',
+            floor: '.',
+            short_grass: ',',
+            tall_grass: '"',
+            brownberry: '%',
+            exit: '>',
+            entrance: '<',
+            water: '~',
+            shallow_water: ':',
+            web: '*',
+            door: '+',
+            open_door: '\'',
+            broken_door: '`',
+            portal: '0',
+            unknown: ' ',
+            hero: '@',
+            skeleton: 's',
+            behemoth: 'B',
+            spider: 'S',
+            archer: 'a',
+            thief: 't',
+            necromancer: 'N',
+            serpent: 'w',
+        }
+    }
+
+    /// Box drawing and other symbols for terminals that support them.
+    pub fn unicode() -> Self {
+        GlyphTheme {
+            wall: '█',
+            floor: '·',
+            short_grass: '‚',
+            tall_grass: '♣',
+            brownberry: '♠',
+            exit: '▼',
+            entrance: '▲',
+            water: '≈',
+            shallow_water: '~',
+            portal: '◎',
+            ..GlyphTheme::classic()
+        }
+    }
+
+    pub fn terrain(&self, terrain: Terrain) -> char {
+        match terrain {
+            Terrain::Wall => self.wall,
+            Terrain::Floor => self.floor,
+            Terrain::ShortGrass => self.short_grass,
+            Terrain::TallGrass => self.tall_grass,
+            Terrain::Brownberry => self.brownberry,
+            Terrain::Exit => self.exit,
+            Terrain::Entrance => self.entrance,
+            Terrain::Water => self.water,
+            Terrain::ShallowWater => self.shallow_water,
+            Terrain::Web => self.web,
+            Terrain::Door => self.door,
+            Terrain::OpenDoor => self.open_door,
+            Terrain::BrokenDoor => self.broken_door,
+            Terrain::Portal => self.portal,
+        }
+    }
+
+    pub fn species(&self, species: Species) -> char {
+        match species {
+            Species::Hero => self.hero,
+            Species::Skeleton => self.skeleton,
+            Species::Behemoth => self.behemoth,
+            Species::Spider => self.spider,
+            Species::Archer => self.archer,
+            Species::Thief => self.thief,
+            Species::Necromancer => self.necromancer,
+            Species::Serpent => self.serpent,
+        }
+    }
+}
+
+/// Draw what a player has observed, clipped to a camera's viewport if there is one.
+pub fn render(snapshot: &Snapshot, theme: &GlyphTheme, camera: Option<&Camera>) -> String {
+    let region = match camera {
+        Some(camera) => {
+            let (width, height) = camera.size();
+            Region {
+                top_left: camera.top_left(),
+                width,
+                height,
+            }
+        }
+        None => Region::whole_map(),
+    };
+    layout(&region, |pos| {
+        if let Some(mob) = snapshot
+            .mobs
+            .iter()
+            .find(|mob| mob.species.size().covers(mob.pos, mob.facing, pos))
+        {
+            return theme.species(mob.species);
+        }
+        match snapshot.tiles[pos] {
+            SeenTile::Visible(terrain)
+            | SeenTile::Remembered(terrain)
+            | SeenTile::Mapped(terrain) => theme.terrain(terrain),
+            SeenTile::Unknown => theme.unknown,
+        }
+    })
+}
+
+/// Draw the whole terrain of a level, e.g. for debugging generation.
+pub fn render_terrain(terrain: &Grid<Terrain>, theme: &GlyphTheme) -> String {
+    layout(&Region::whole_map(), |pos| theme.terrain(terrain[pos]))
+}
+
+/// Draw the whole of a level as it was generated, with the npcs on it.
+pub fn render_level(level: &Grid<Tile>, npcs: &Npcs, theme: &GlyphTheme) -> String {
+    layout(&Region::whole_map(), |pos| match level[pos].mob_id {
+        Some(mob_id) => theme.species(npcs[mob_id].species),
+        None => theme.terrain(level[pos].terrain),
+    })
+}
+
+/// Explain every glyph that appears in a snapshot, one per line.
+pub fn legend(snapshot: &Snapshot, theme: &GlyphTheme) -> String {
+    let mut entries: Vec<(char, &str)> = Vec::new();
+    let mut add = |glyph: char, name: &'static str| {
+        if !entries.iter().any(|&(seen, _)| seen == glyph) {
+            entries.push((glyph, name));
+        }
+    };
+    for mob in &snapshot.mobs {
+        add(theme.species(mob.species), mob.species.name());
+    }
+    for tile in snapshot.tiles.iter() {
+        match *tile {
+            SeenTile::Visible(terrain)
+            | SeenTile::Remembered(terrain)
+            | SeenTile::Mapped(terrain) => {
+                add(theme.terrain(terrain), terrain.name())
+            }
+            SeenTile::Unknown => {}
+        }
+    }
+    let mut legend = String::new();
+    for (glyph, name) in entries {
+        writeln!(legend, "{} {}", glyph, name).unwrap();
+    }
+    legend
+}
+
+fn layout<F>(region: &Region, glyph: F) -> String
+where
+    F: Fn(Pos) -> char,
+{
+    let mut rows = vec![vec![' '; region.width as usize]; region.height as usize];
+    for pos in grid::positions() {
+        let Location { x, y } = pos_to_location(pos);
+        let column = x - region.top_left.x;
+        let row = y - region.top_left.y;
+        if column >= 0 && column < region.width && row >= 0 && row < region.height {
+            rows[row as usize][column as usize] = glyph(pos);
+        }
+    }
+    let mut text = String::new();
+    for row in rows {
+        let line: String = row.into_iter().collect();
+        writeln!(text, "{}", line.trim_end()).unwrap();
+    }
+    text
+}
+
+impl Region {
+    fn whole_map() -> Self {
+        Region {
+            top_left: Location { x: 0, y: 0 },
+            width: 2 * WIDTH as i32,
+            height: HEIGHT as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+
+    #[test]
+    fn test_render() {
+        let world = World::from_seed(4, GenParams::default());
+        let snapshot = Snapshot::of(&world);
+        let theme = GlyphTheme::classic();
+        let map = render(&snapshot, &theme, None);
+        assert_eq!(map.lines().count(), HEIGHT);
+        assert_eq!(map.matches('@').count(), 1);
+        assert!(legend(&snapshot, &theme).starts_with("@ hero\n"));
+
+        let camera = Camera::new(20, 10);
+        let clipped = render(&snapshot, &theme, Some(&camera));
+        assert_eq!(clipped.lines().count(), 10);
+        assert!(clipped.lines().all(|line| line.chars().count() <= 20));
+    }
+}
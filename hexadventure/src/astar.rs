@@ -1,74 +1,1921 @@
-use grid::{decompose, Direction, Pos, DIRECTIONS};
-use minheap::MinHeap;
-use std::collections::{HashMap, VecDeque};
+//! Pathfinding over hex grids.
+//!
+//! `find_path` runs jump point search over any map that implements
+//! `JPSearchable`, so other crates can path over their own hex maps, and
+//! `find_path_to` searches for a `Goal`, such as any tile next to a target,
+//! and `find_path_with` can search from both ends at once instead.
+//! `find_paths_batch` runs many such searches, across threads with the
+//! `parallel` feature.
+//! `find_path_avoiding` makes some tiles cost more to cross, so paths keep
+//! clear of them unless going around takes too long, and `find_path_among`
+//! blocks or avoids the tiles other mobs stand on, as its `Occupancy` says.
+//! `find_path_within` goes only as far toward a goal as some movement
+//! points allow, and `reachable_within` is every tile they reach.
+//! Every `Path` carries what it costs and how much searching it took.
+//! `dijkstra_map` instead works out how far every tile is from a set of
+//! sources, for when many paths lead to or away from the same places.
+//! `FlowField` wraps one so many mobs can share the way to the same targets,
+//! and `safety_map` turns one around, for fleeing from threats.
+//! `plan_group` steers several movers to the same goals without collisions.
+//! `PathCache` keeps paths around while the map they were found on stays the same.
+//! `PathHierarchy` finds long paths over maps far larger than a level, a
+//! chunk at a time, and `SearchState` spreads one long search over many
+//! turns or frames.
+//! `find_path_iter` walks a long path a step at a time instead of holding
+//! every position of it at once.
+//! `find_path_with_stats` also tells what the search did, and
+//! `start_recording` adds up what every jump point search, batch of them
+//! and dijkstra map does until `stop_recording`, to see where the time of
+//! a turn goes.
 
+use grid::{self, decompose, Direction, Grid, Pos, DIRECTIONS};
+use minheap::{BucketQueue, IndexedMinHeap, MinHeap, PriorityQueue};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::mem::{replace, take};
+use std::time::{Duration, Instant};
+
+/// How many paths a `PathCache` keeps.
+const PATH_CACHE_SIZE: usize = 8;
+/// How many tiles across each chunk of a `PathHierarchy` is.
+pub const CHUNK_SIZE: usize = 16;
+
+/// The distance `dijkstra_map` gives tiles no source can reach.
+pub const UNREACHABLE: u32 = u32::MAX;
+/// How strongly `safety_map` weighs distance from threats, as a fraction.
+///
+/// Above one, a fleeing mob will run past a threat to open ground beyond
+/// rather than back itself into the nearest dead end.
+const FLEE_WEIGHT: (i32, i32) = (6, 5);
+
+thread_local! {
+    /// What the searches on this thread have done since recording started.
+    static RECORDED: RefCell<Option<SearchStats>> = const { RefCell::new(None) };
+}
+
+/// A hex map that jump point search can run over.
+///
+/// Every step between neighboring tiles costs the same.
+pub trait JPSearchable {
+    /// Whether a path may end at a position.
+    fn is_goal(&self, pos: Pos) -> bool;
+
+    /// Whether a path may pass through a position.
+    ///
+    /// Must be false outside the map, or the search won't end.
+    fn passable(&self, pos: Pos) -> bool;
+
+    /// A guess at how many steps a position is from the nearest goal.
+    ///
+    /// It must never guess more than the real number of steps, or the path
+    /// found might not be the shortest. The default of 0 always holds.
+    fn heuristic(&self, _pos: Pos) -> u32 {
+        0
+    }
+}
+
+/// A path, and what it took to find it.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Path {
+    /// Every position along the path, from the origin to the goal.
+    positions: Vec<Pos>,
+    cost: u32,
+    expanded: u32,
+    truncated: bool,
+}
+
+impl Path {
+    /// A path that wasn't searched for, where every step costs one.
+    fn walked(positions: Vec<Pos>) -> Self {
+        Path {
+            cost: positions.len() as u32 - 1,
+            positions,
+            expanded: 0,
+            truncated: false,
+        }
+    }
+
+    /// Every position along the path, from the origin to the goal.
+    pub fn positions(&self) -> &[Pos] {
+        &self.positions
+    }
+
+    pub fn origin(&self) -> Pos {
+        self.positions[0]
+    }
+
+    pub fn goal(&self) -> Pos {
+        self.positions[self.positions.len() - 1]
+    }
+
+    /// The first position after the origin, or `None` if the origin is the goal.
+    pub fn next_step(&self) -> Option<Pos> {
+        self.positions.get(1).cloned()
+    }
+
+    /// How many steps the path takes.
+    pub fn steps(&self) -> u32 {
+        self.positions.len() as u32 - 1
+    }
+
+    /// What the path costs to follow, which is its steps unless they cost more.
+    pub fn cost(&self) -> u32 {
+        self.cost
+    }
+
+    /// How many nodes the search took from its open set before finding the path.
+    pub fn expanded(&self) -> u32 {
+        self.expanded
+    }
+
+    /// Whether the search passed over routes that cost more than it was allowed.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn into_positions(self) -> Vec<Pos> {
+        self.positions
+    }
+}
+
+/// The positions along a path found by jump point search, from the origin
+/// to the goal, worked out as they are needed.
+///
+/// Jump points are few even on a long path, so only the straight legs
+/// between them are kept, and each position is one step along a leg.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PathIter {
+    /// The legs still to walk, as a direction and a number of steps, with
+    /// the next leg last.
+    legs: Vec<(Direction, i32)>,
+    /// The position to give next, or `None` once the goal has been given.
+    next: Option<Pos>,
+    /// How many positions are left to give.
+    remaining: usize,
+}
+
+impl PathIter {
+    /// Walk back from a goal to the origin through the jump points of a search.
+    fn new(parents: &HashMap<Pos, JumpPoint>, goal: Pos) -> Self {
+        let mut legs = Vec::new();
+        let mut steps = 0;
+        let mut pos = goal;
+        while let Some(&JumpPoint {
+            pos: parent_pos,
+            direction: stem_direction,
+            chirality,
+        }) = parents.get(&pos)
+        {
+            let leaf_direction = chirality.rotate(stem_direction, 1);
+            let (stem_cost, leaf_cost) =
+                decompose(pos - parent_pos, stem_direction, leaf_direction);
+            legs.push((leaf_direction, leaf_cost));
+            legs.push((stem_direction, stem_cost));
+            steps += (stem_cost + leaf_cost) as usize;
+            pos = parent_pos;
+        }
+        PathIter {
+            legs,
+            next: Some(pos),
+            remaining: steps + 1,
+        }
+    }
+}
+
+impl Iterator for PathIter {
+    type Item = Pos;
+
+    fn next(&mut self) -> Option<Pos> {
+        let pos = self.next?;
+        self.next = loop {
+            match self.legs.last_mut() {
+                None => break None,
+                Some((_, 0)) => {
+                    self.legs.pop();
+                }
+                Some((direction, steps)) => {
+                    *steps -= 1;
+                    break Some(pos + *direction);
+                }
+            }
+        };
+        self.remaining -= 1;
+        Some(pos)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for PathIter {}
+
+/// Find a shortest path from an origin to any goal of a map.
+pub fn find_path<M: JPSearchable>(map: &M, origin: Pos) -> Option<Path> {
+    jps(
+        origin,
+        |pos| map.is_goal(pos),
+        |pos| map.passable(pos),
+        |pos| map.heuristic(pos),
+        None,
+    )
+}
+
+/// Find a shortest path from an origin to any goal of a map, along with
+/// what the search did, whether or not it found one.
+pub fn find_path_with_stats<M: JPSearchable>(map: M, origin: Pos) -> (Option<Path>, SearchStats) {
+    let mut search = SearchState::new(map, origin);
+    search.timed = true;
+    let path = match search.step(u32::MAX) {
+        SearchStatus::Found(path) => Some(path),
+        _ => None,
+    };
+    (path, search.stats())
+}
+
+/// The positions along a shortest path from an origin to any goal of a map,
+/// from the origin on, worked out only as they are walked.
+pub fn find_path_iter<M: JPSearchable>(map: M, origin: Pos) -> Option<PathIter> {
+    let mut search = SearchState::new(map, origin);
+    if let SearchStatus::Found(_) = search.status {
+        return Some(PathIter::new(&search.parents, origin));
+    }
+    let goal = search.advance(u32::MAX)?;
+    Some(PathIter::new(&search.parents, goal))
+}
+
+/// What a jump point search, or the flood of a dijkstra map, did.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct SearchStats {
+    /// How many nodes were pushed onto the open set.
+    pub pushed: u32,
+    /// How many nodes were popped off the open set.
+    pub popped: u32,
+    /// How many tiles were looked at while jumping, or flooding.
+    pub scanned: u32,
+    /// The most nodes the open set held at once.
+    pub max_open: u32,
+    /// How long the search took. Only measured for `find_path_with_stats`,
+    /// or while recording, to keep the clock out of other searches.
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    /// Add what another search did, keeping the largest open set of either.
+    pub fn add(&mut self, other: &SearchStats) {
+        self.pushed += other.pushed;
+        self.popped += other.popped;
+        self.scanned += other.scanned;
+        self.max_open = self.max_open.max(other.max_open);
+        self.elapsed += other.elapsed;
+    }
+
+    /// What was done since an earlier snapshot of the same search.
+    fn since(&self, earlier: &SearchStats) -> SearchStats {
+        SearchStats {
+            pushed: self.pushed - earlier.pushed,
+            popped: self.popped - earlier.popped,
+            scanned: self.scanned - earlier.scanned,
+            max_open: self.max_open,
+            elapsed: self.elapsed - earlier.elapsed,
+        }
+    }
+}
+
+/// Start adding up what every jump point search on this thread does,
+/// starting over if already recording.
+pub fn start_recording() {
+    RECORDED.with(|recorded| *recorded.borrow_mut() = Some(SearchStats::default()));
+}
+
+/// Stop recording, and return what the searches did since recording started.
+pub fn stop_recording() -> Option<SearchStats> {
+    RECORDED.with(|recorded| recorded.borrow_mut().take())
+}
+
+/// Whether this thread is recording what its searches do.
+fn is_recording() -> bool {
+    RECORDED.with(|recorded| recorded.borrow().is_some())
+}
+
+/// Run a function while recording what its searches do on this thread,
+/// apart from any recording already under way, which is left as it was.
+fn recorded<T, F: FnOnce() -> T>(f: F) -> (T, SearchStats) {
+    let outer = RECORDED.with(|recorded| recorded.replace(Some(SearchStats::default())));
+    let result = f();
+    let stats = RECORDED.with(|recorded| recorded.replace(outer));
+    (result, stats.unwrap_or_default())
+}
+
+/// Add what a search did to the recording, if there is one.
+fn record(stats: &SearchStats) {
+    RECORDED.with(|recorded| {
+        if let Some(total) = recorded.borrow_mut().as_mut() {
+            total.add(stats);
+        }
+    });
+}
+
+/// Find a shortest path from an origin to whichever of several goals is closest.
+///
+/// `Path::goal` tells which goal was reached. Ties go to whichever goal the
+/// search comes across first.
+pub fn find_path_to_any<FP>(origin: Pos, goals: &[Pos], passable: FP) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+{
+    find_path(
+        &AnyGoal {
+            goals: goals.iter().cloned().collect(),
+            passable,
+        },
+        origin,
+    )
+}
+
+/// Where a path should end, around a target.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Goal {
+    /// The target itself.
+    Tile(Pos),
+    /// Any neighbor of the target, such as to attack it in melee when it
+    /// stands on its own tile.
+    Adjacent(Pos),
+    /// Any tile no more than some steps from the target.
+    Within(Pos, u32),
+}
+
+impl Goal {
+    pub fn target(self) -> Pos {
+        match self {
+            Goal::Tile(target) | Goal::Adjacent(target) | Goal::Within(target, _) => target,
+        }
+    }
+
+    /// Whether a path may end at a position.
+    pub fn contains(self, pos: Pos) -> bool {
+        let distance = pos.distance(self.target());
+        match self {
+            Goal::Tile(_) => distance == 0,
+            Goal::Adjacent(_) => distance == 1,
+            Goal::Within(_, steps) => distance <= steps,
+        }
+    }
+
+    /// The fewest steps a position could be from the goal, which never
+    /// guesses too high.
+    pub fn heuristic(self, pos: Pos) -> u32 {
+        let distance = pos.distance(self.target());
+        match self {
+            Goal::Tile(_) => distance,
+            Goal::Adjacent(_) => distance.saturating_sub(1),
+            Goal::Within(_, steps) => distance.saturating_sub(steps),
+        }
+    }
+
+    /// Every position a path may end at.
+    fn tiles(self) -> Vec<Pos> {
+        let target = self.target();
+        let (nearest, farthest) = match self {
+            Goal::Tile(_) => (0, 0),
+            Goal::Adjacent(_) => (1, 1),
+            Goal::Within(_, steps) => (0, steps),
+        };
+        let mut tiles: Vec<Pos> = (nearest.max(1)..=farthest)
+            .flat_map(|size| target.ring(size))
+            .collect();
+        if nearest == 0 {
+            tiles.push(target);
+        }
+        tiles
+    }
+}
+
+/// How to search for a path between two points.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Strategy {
+    /// Jump point search from the origin, guided toward the goal.
+    JumpPoint,
+    /// Breadth first search from the origin and the goal at once, until the
+    /// two meet. Each side only has to search half as far, so on open levels
+    /// it expands far fewer nodes than searching from one end.
+    Bidirectional,
+}
+
+/// Find a shortest path from an origin to a goal.
+pub fn find_path_to<FP>(origin: Pos, goal: Goal, passable: FP) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+{
+    find_path_with(origin, goal, passable, Strategy::JumpPoint)
+}
+
+/// Find a shortest path from an origin to a goal with a chosen strategy.
+pub fn find_path_with<FP>(origin: Pos, goal: Goal, passable: FP, strategy: Strategy) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+{
+    match strategy {
+        Strategy::JumpPoint => jps_to(origin, goal, passable, None),
+        Strategy::Bidirectional => bidirectional(origin, goal, passable),
+    }
+}
+
+/// Find a shortest path for each of many origins and goals.
+///
+/// Every search shares `passable`, so it must only read the level, and the
+/// paths come back in the order they were asked for. With the `parallel`
+/// feature the searches run on rayon's threads, and what they do is added
+/// to the recording of this one. The npc AI doesn't batch its searches,
+/// since each npc's move changes the paths of the npcs that act after it.
+pub fn find_paths_batch<FP>(requests: &[(Pos, Goal)], passable: FP) -> Vec<Option<Path>>
+where
+    FP: Fn(Pos) -> bool + Sync,
+{
+    #[cfg(feature = "parallel")]
+    let requests = requests.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let requests = requests.iter();
+    if !is_recording() {
+        return requests
+            .map(|&(origin, goal)| find_path_to(origin, goal, &passable))
+            .collect();
+    }
+    let results: Vec<(Option<Path>, SearchStats)> = requests
+        .map(|&(origin, goal)| recorded(|| find_path_to(origin, goal, &passable)))
+        .collect();
+    results
+        .into_iter()
+        .map(|(path, stats)| {
+            record(&stats);
+            path
+        })
+        .collect()
+}
+
+/// Find the cheapest path from an origin to a goal, where entering some
+/// tiles costs more than a step.
+///
+/// `penalty` is what entering a tile costs on top of the step. Unlike an
+/// impassable tile, a penalized one is still crossed if the way around
+/// costs more.
+pub fn find_path_avoiding<FP, FX>(
+    origin: Pos,
+    goal: Goal,
+    passable: FP,
+    penalty: FX,
+) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+    FX: Fn(Pos) -> u32,
+{
+    cheapest_path_to(origin, goal, |pos| {
+        if passable(pos) {
+            Some(1 + penalty(pos))
+        } else {
+            None
+        }
+    })
+}
+
+/// How a search treats tiles other mobs stand on.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Occupancy {
+    /// Path through them as if they were empty.
+    Ignore,
+    /// Never path through them.
+    Block,
+    /// Path through them only if going around costs this many more steps.
+    Penalize(u32),
+}
+
+impl Occupancy {
+    /// What entering a tile costs, given what it costs when empty and
+    /// whether another mob stands on it.
+    pub fn cost(self, empty_cost: Option<u32>, occupied: bool) -> Option<u32> {
+        match self {
+            _ if !occupied => empty_cost,
+            Occupancy::Ignore => empty_cost,
+            Occupancy::Block => None,
+            Occupancy::Penalize(penalty) => empty_cost.map(|cost| cost + penalty),
+        }
+    }
+}
+
+/// Find a shortest path from an origin to a goal, treating the tiles other
+/// mobs stand on as `occupancy` says.
+///
+/// A goal tile is never treated as occupied, so a path can still end at a
+/// mob. With a `max_cost`, paths costing more are given up on.
+pub fn find_path_among<FP, FO>(
+    origin: Pos,
+    goal: Goal,
+    passable: FP,
+    occupied: FO,
+    occupancy: Occupancy,
+    max_cost: Option<u32>,
+) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+    FO: Fn(Pos) -> bool,
+{
+    let occupied = |pos| !goal.contains(pos) && occupied(pos);
+    match occupancy {
+        Occupancy::Ignore => jps_to(origin, goal, passable, max_cost),
+        Occupancy::Block => jps_to(
+            origin,
+            goal,
+            |pos| passable(pos) && !occupied(pos),
+            max_cost,
+        ),
+        Occupancy::Penalize(_) => bounded_cheapest_path(
+            origin,
+            |pos| goal.contains(pos),
+            |pos| occupancy.cost(Some(1).filter(|_| passable(pos)), occupied(pos)),
+            |pos| goal.heuristic(pos),
+            max_cost,
+        ),
+    }
+}
+
+/// Find how far toward a goal a mover gets on `points` movement points,
+/// where `cost` is what entering a tile costs, or `None` if it can't.
+///
+/// The path follows the cheapest path to the goal for as long as the points
+/// last. If the goal can't be reached at all, it instead leads to the
+/// affordable tile nearest the goal, cheapest first. Either way its last
+/// position is the best the mover can do, which is the origin if it can't
+/// afford a single step.
+pub fn find_path_within<FC>(origin: Pos, goal: Goal, cost: FC, points: u32) -> Path
+where
+    FC: Fn(Pos) -> Option<u32>,
+{
+    if let Some(path) = cheapest_path_to(origin, goal, &cost) {
+        let mut spent = 0;
+        let mut positions = vec![origin];
+        for &pos in &path.positions[1..] {
+            match cost(pos) {
+                Some(step) if spent + step <= points => spent += step,
+                _ => break,
+            }
+            positions.push(pos);
+        }
+        return Path {
+            positions,
+            cost: spent,
+            expanded: path.expanded,
+            truncated: path.truncated,
+        };
+    }
+    let (costs, parents) = spend(origin, &cost, points);
+    let nearest = costs
+        .iter()
+        .min_by_key(|&(&pos, &spent)| (goal.heuristic(pos), spent, grid::pos_to_offset(pos)))
+        .map_or(origin, |(&pos, _)| pos);
+    let mut positions = vec![nearest];
+    while let Some(&parent) = parents.get(&positions[positions.len() - 1]) {
+        positions.push(parent);
+    }
+    positions.reverse();
+    Path {
+        positions,
+        cost: costs[&nearest],
+        expanded: costs.len() as u32,
+        truncated: false,
+    }
+}
+
+/// Every tile a mover can reach on `points` movement points, with the
+/// fewest points it takes to get there.
+pub fn reachable_within<FC>(origin: Pos, cost: FC, points: u32) -> HashMap<Pos, u32>
+where
+    FC: Fn(Pos) -> Option<u32>,
+{
+    spend(origin, &cost, points).0
+}
+
+/// The cheapest way to every tile reachable from an origin on some points,
+/// as what each costs and the tile each is entered from.
+fn spend<FC>(origin: Pos, cost: &FC, points: u32) -> (HashMap<Pos, u32>, HashMap<Pos, Pos>)
+where
+    FC: Fn(Pos) -> Option<u32>,
+{
+    let mut open = MinHeap::new();
+    let mut costs: HashMap<Pos, u32> = HashMap::new();
+    let mut parents: HashMap<Pos, Pos> = HashMap::new();
+    open.push(origin, 0);
+    costs.insert(origin, 0);
+    while let Some(pos) = open.pop() {
+        for neighbor in pos.neighbors() {
+            let new_cost = match cost(neighbor) {
+                Some(step) => costs[&pos] + step,
+                None => continue,
+            };
+            if new_cost > points || costs.get(&neighbor).is_some_and(|&cost| cost <= new_cost) {
+                continue;
+            }
+            costs.insert(neighbor, new_cost);
+            parents.insert(neighbor, pos);
+            open.push(neighbor, new_cost);
+        }
+    }
+    (costs, parents)
+}
+
+/// Find a shortest path from an origin to a goal, where stepping onto a
+/// portal carries on from its twin at no cost.
+///
+/// `portals` maps each portal to its twin. A path never stops on a portal,
+/// so the position after one in the path is its twin. The hop between them
+/// is free, so a path through portals costs less than its steps.
+pub fn find_path_through_portals<FP>(
+    origin: Pos,
+    goal: Goal,
+    passable: FP,
+    portals: &HashMap<Pos, Pos>,
+) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+{
+    // Any portal might lead straight to the goal.
+    let heuristic = |pos: Pos| {
+        portals
+            .keys()
+            .map(|&portal| pos.distance(portal))
+            .fold(goal.heuristic(pos), u32::min)
+    };
+    let mut open = IndexedMinHeap::new();
+    let mut costs: HashMap<Pos, u32> = HashMap::new();
+    // Where each position was reached from, and the portal passed through on the way, if any.
+    let mut parents: HashMap<Pos, (Pos, Option<Pos>)> = HashMap::new();
+    open.push(origin, heuristic(origin));
+    costs.insert(origin, 0);
+    let mut expanded = 0;
+    while let Some(pos) = open.pop() {
+        if goal.contains(pos) {
+            let mut positions = vec![pos];
+            let mut step = pos;
+            while let Some(&(parent, portal)) = parents.get(&step) {
+                positions.extend(portal);
+                positions.push(parent);
+                step = parent;
+            }
+            positions.reverse();
+            return Some(Path {
+                positions,
+                cost: costs[&pos],
+                expanded,
+                truncated: false,
+            });
+        }
+        expanded += 1;
+        let new_cost = costs[&pos] + 1;
+        for neighbor in pos.neighbors() {
+            if !passable(neighbor) {
+                continue;
+            }
+            let (landing, portal) = match portals.get(&neighbor) {
+                Some(&twin) => (twin, Some(neighbor)),
+                None => (neighbor, None),
+            };
+            if costs.get(&landing).is_some_and(|&cost| cost <= new_cost) {
+                continue;
+            }
+            costs.insert(landing, new_cost);
+            parents.insert(landing, (pos, portal));
+            open.push(landing, new_cost + heuristic(landing));
+        }
+    }
+    None
+}
+
+/// One side of a bidirectional search.
+struct Frontier {
+    /// Every position reached, the position it was reached from and how
+    /// many steps it is from where this side started.
+    reached: HashMap<Pos, (Option<Pos>, u32)>,
+    /// The positions reached last, to expand next.
+    layer: Vec<Pos>,
+}
+
+impl Frontier {
+    fn new(starts: Vec<Pos>) -> Self {
+        Frontier {
+            reached: starts.iter().map(|&pos| (pos, (None, 0))).collect(),
+            layer: starts,
+        }
+    }
+
+    /// The positions from one reached back to where this side started.
+    fn trace(&self, pos: Pos) -> Vec<Pos> {
+        let mut positions = vec![pos];
+        let mut step = pos;
+        while let Some(&(Some(parent), _)) = self.reached.get(&step) {
+            positions.push(parent);
+            step = parent;
+        }
+        positions
+    }
+}
+
+/// Breadth first search from an origin and a goal at once.
+///
+/// Each round expands a whole layer of whichever side has fewer positions
+/// waiting, and the first round in which the sides touch gives the shortest
+/// path, through the cheapest place they touched.
+fn bidirectional<FP>(origin: Pos, goal: Goal, passable: FP) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+{
+    if goal.contains(origin) {
+        return Some(Path::walked(vec![origin]));
+    }
+    let ends = goal
+        .tiles()
+        .into_iter()
+        .filter(|&pos| passable(pos))
+        .collect();
+    let mut forward = Frontier::new(vec![origin]);
+    let mut backward = Frontier::new(ends);
+    let mut expanded = 0;
+    while !forward.layer.is_empty() && !backward.layer.is_empty() {
+        let from_origin = forward.layer.len() <= backward.layer.len();
+        let (growing, other) = if from_origin {
+            (&mut forward, &backward)
+        } else {
+            (&mut backward, &forward)
+        };
+        let mut meeting: Option<(u32, Pos, Pos)> = None;
+        for pos in take(&mut growing.layer) {
+            expanded += 1;
+            let steps = growing.reached[&pos].1;
+            for neighbor in pos.neighbors() {
+                if let Some(&(_, other_steps)) = other.reached.get(&neighbor) {
+                    let cost = steps + 1 + other_steps;
+                    if meeting.map_or(true, |(best, _, _)| cost < best) {
+                        meeting = Some((cost, pos, neighbor));
+                    }
+                    continue;
+                }
+                if !passable(neighbor) || growing.reached.contains_key(&neighbor) {
+                    continue;
+                }
+                growing.reached.insert(neighbor, (Some(pos), steps + 1));
+                growing.layer.push(neighbor);
+            }
+        }
+        if let Some((cost, near, far)) = meeting {
+            let (forward_end, backward_end) = if from_origin {
+                (near, far)
+            } else {
+                (far, near)
+            };
+            let mut positions = forward.trace(forward_end);
+            positions.reverse();
+            positions.extend(backward.trace(backward_end));
+            return Some(Path {
+                positions,
+                cost,
+                expanded,
+                truncated: false,
+            });
+        }
+    }
+    None
+}
+
+/// A map whose goals are a set of positions.
+struct AnyGoal<FP> {
+    goals: HashSet<Pos>,
+    passable: FP,
+}
+
+impl<FP: Fn(Pos) -> bool> JPSearchable for AnyGoal<FP> {
+    fn is_goal(&self, pos: Pos) -> bool {
+        self.goals.contains(&pos)
+    }
+
+    fn passable(&self, pos: Pos) -> bool {
+        (self.passable)(pos)
+    }
+
+    fn heuristic(&self, pos: Pos) -> u32 {
+        self.goals
+            .iter()
+            .map(|&goal| pos.distance(goal))
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// A path that is kept up to date as its ends move and tiles change, using D* Lite.
+///
+/// The search runs from the goal back to the start, so moving the start
+/// along the path costs almost nothing. Moving the goal or changing tiles
+/// only repairs the part of the search they affect, rather than starting
+/// over. Every step costs the same, and the goal can always be entered.
+///
+/// `passable` must answer the same for a tile from one update to the next,
+/// unless the tile was reported to `tiles_changed` in between.
+pub struct IncrementalPath {
+    start: Pos,
+    goal: Pos,
+    /// Where the start was when the keys in `open` were last made consistent.
+    last_start: Pos,
+    /// How far the start has moved in total, which keeps old keys valid.
+    km: u32,
+    g: HashMap<Pos, u32>,
+    rhs: HashMap<Pos, u32>,
+    open: MinHeap<Pos, (u32, u32)>,
+    /// The current key of every position in `open`. Other entries are stale.
+    open_keys: HashMap<Pos, (u32, u32)>,
+    /// Tiles whose neighbors must be updated before the next search.
+    changed: Vec<Pos>,
+}
+
+impl IncrementalPath {
+    pub fn new(start: Pos, goal: Pos) -> Self {
+        let mut path = IncrementalPath {
+            start,
+            goal,
+            last_start: start,
+            km: 0,
+            g: HashMap::new(),
+            rhs: HashMap::new(),
+            open: MinHeap::new(),
+            open_keys: HashMap::new(),
+            changed: Vec::new(),
+        };
+        path.rhs.insert(goal, 0);
+        let key = path.key(goal);
+        path.push(goal, key);
+        path
+    }
+
+    /// Move the start, e.g. after taking a step along the path.
+    pub fn move_start(&mut self, start: Pos) {
+        self.km = self.km.saturating_add(self.last_start.distance(start));
+        self.last_start = start;
+        self.start = start;
+    }
+
+    /// Move the goal, e.g. when the target being chased moves.
+    pub fn move_goal(&mut self, goal: Pos) {
+        let old = replace(&mut self.goal, goal);
+        self.changed.push(old);
+        self.changed.push(goal);
+    }
+
+    /// Note tiles that might have changed whether they are passable.
+    pub fn tiles_changed(&mut self, positions: &[Pos]) {
+        self.changed.extend_from_slice(positions);
+    }
+
+    /// The first step from the start along a shortest path to the goal.
+    ///
+    /// `None` if the start is the goal or the goal can't be reached.
+    pub fn next_step<FP: Fn(Pos) -> bool>(&mut self, passable: FP) -> Option<Pos> {
+        self.update(&passable);
+        if self.start == self.goal || self.g(self.start) == UNREACHABLE {
+            return None;
+        }
+        self.best_neighbor(self.start, &passable)
+    }
+
+    /// A shortest path from the start to the goal.
+    pub fn path<FP: Fn(Pos) -> bool>(&mut self, passable: FP) -> Option<Path> {
+        self.update(&passable);
+        if self.g(self.start) == UNREACHABLE {
+            return None;
+        }
+        let mut positions = vec![self.start];
+        let mut pos = self.start;
+        while pos != self.goal {
+            pos = self.best_neighbor(pos, &passable)?;
+            positions.push(pos);
+        }
+        Some(Path::walked(positions))
+    }
+
+    /// Apply the changes noted since the last search, then repair the search.
+    fn update<FP: Fn(Pos) -> bool>(&mut self, passable: &FP) {
+        for pos in take(&mut self.changed) {
+            self.update_vertex(pos, passable);
+            for neighbor in pos.neighbors() {
+                self.update_vertex(neighbor, passable);
+            }
+        }
+        while let Some(&top) = self.open.peek_priority() {
+            let start_key = self.key(self.start);
+            if top >= start_key && self.rhs(self.start) == self.g(self.start) {
+                break;
+            }
+            let pos = match self.open.pop() {
+                Some(pos) => pos,
+                None => break,
+            };
+            if self.open_keys.get(&pos) != Some(&top) {
+                continue;
+            }
+            self.open_keys.remove(&pos);
+            let new_key = self.key(pos);
+            if top < new_key {
+                self.push(pos, new_key);
+            } else if self.g(pos) > self.rhs(pos) {
+                let rhs = self.rhs(pos);
+                self.g.insert(pos, rhs);
+                for neighbor in pos.neighbors() {
+                    self.update_vertex(neighbor, passable);
+                }
+            } else {
+                self.g.insert(pos, UNREACHABLE);
+                self.update_vertex(pos, passable);
+                for neighbor in pos.neighbors() {
+                    self.update_vertex(neighbor, passable);
+                }
+            }
+        }
+    }
+
+    fn update_vertex<FP: Fn(Pos) -> bool>(&mut self, pos: Pos, passable: &FP) {
+        if !grid::contains(pos) {
+            return;
+        }
+        if pos != self.goal {
+            let rhs = pos
+                .neighbors()
+                .filter(|&neighbor| self.enterable(neighbor, passable))
+                .map(|neighbor| self.g(neighbor).saturating_add(1))
+                .min()
+                .unwrap_or(UNREACHABLE);
+            self.rhs.insert(pos, rhs);
+        } else {
+            self.rhs.insert(pos, 0);
+        }
+        self.open_keys.remove(&pos);
+        if self.g(pos) != self.rhs(pos) {
+            let key = self.key(pos);
+            self.push(pos, key);
+        }
+    }
+
+    /// The enterable neighbor of a position closest to the goal.
+    fn best_neighbor<FP: Fn(Pos) -> bool>(&self, pos: Pos, passable: &FP) -> Option<Pos> {
+        pos.neighbors()
+            .filter(|&neighbor| self.enterable(neighbor, passable))
+            .filter(|&neighbor| self.g(neighbor) < self.g(pos))
+            .min_by_key(|&neighbor| self.g(neighbor))
+    }
+
+    fn enterable<FP: Fn(Pos) -> bool>(&self, pos: Pos, passable: &FP) -> bool {
+        grid::contains(pos) && (pos == self.goal || passable(pos))
+    }
+
+    fn key(&self, pos: Pos) -> (u32, u32) {
+        let best = self.g(pos).min(self.rhs(pos));
+        let estimate = best
+            .saturating_add(self.start.distance(pos))
+            .saturating_add(self.km);
+        (estimate, best)
+    }
+
+    fn push(&mut self, pos: Pos, key: (u32, u32)) {
+        self.open_keys.insert(pos, key);
+        self.open.push(pos, key);
+    }
+
+    fn g(&self, pos: Pos) -> u32 {
+        self.g.get(&pos).cloned().unwrap_or(UNREACHABLE)
+    }
+
+    fn rhs(&self, pos: Pos) -> u32 {
+        self.rhs.get(&pos).cloned().unwrap_or(UNREACHABLE)
+    }
+}
+
+/// How many steps every tile of the level is from the nearest source.
+///
+/// Steps are only taken onto passable tiles inside the level, though the
+/// sources themselves needn't be passable. Tiles that can't be reached are
+/// `UNREACHABLE`.
+pub fn dijkstra_map<FP>(sources: &[Pos], passable: FP) -> Grid<u32>
+where
+    FP: Fn(Pos) -> bool,
+{
+    let start = if is_recording() {
+        Some(Instant::now())
+    } else {
+        None
+    };
+    let mut stats = SearchStats::default();
+    let mut distances = Grid::new(|_| UNREACHABLE);
+    let mut queue = VecDeque::new();
+    for &source in sources.iter().filter(|&&pos| grid::contains(pos)) {
+        if distances[source] != 0 {
+            distances[source] = 0;
+            queue.push_back(source);
+        }
+    }
+    stats.pushed = queue.len() as u32;
+    // Every step costs the same, so a breadth-first flood finds the shortest distances.
+    while let Some(pos) = queue.pop_front() {
+        stats.popped += 1;
+        let distance = distances[pos] + 1;
+        for next in pos.neighbors() {
+            stats.scanned += 1;
+            if grid::contains(next) && distances[next] == UNREACHABLE && passable(next) {
+                distances[next] = distance;
+                queue.push_back(next);
+                stats.pushed += 1;
+            }
+        }
+        stats.max_open = stats.max_open.max(queue.len() as u32);
+    }
+    if let Some(start) = start {
+        stats.elapsed = start.elapsed();
+        record(&stats);
+    }
+    distances
+}
+
+/// Recently found paths, kept until the map they were found on changes.
+///
+/// Callers number each state of the map with a revision, bumped on every
+/// change to anything the paths depend on, such as the terrain or what the
+/// searcher knows of it. The cache forgets everything when it sees a new one.
+/// Paths run from their origin to their goal.
+#[derive(Default)]
+pub struct PathCache {
+    revision: u64,
+    paths: VecDeque<Vec<Pos>>,
+}
+
+impl PathCache {
+    pub fn new() -> Self {
+        PathCache::default()
+    }
+
+    /// The rest of a cached path to a goal, starting from a position along it.
+    pub fn get(&mut self, revision: u64, from: Pos, goal: Pos) -> Option<&[Pos]> {
+        self.check_revision(revision);
+        self.paths
+            .iter()
+            .filter(|path| path.last() == Some(&goal))
+            .find_map(|path| {
+                let index = path.iter().position(|&pos| pos == from)?;
+                Some(&path[index..])
+            })
+    }
+
+    /// Keep a path found on a revision of the map, forgetting the oldest if full.
+    pub fn insert(&mut self, revision: u64, path: Vec<Pos>) {
+        self.check_revision(revision);
+        if self.paths.len() == PATH_CACHE_SIZE {
+            self.paths.pop_front();
+        }
+        self.paths.push_back(path);
+    }
+
+    fn check_revision(&mut self, revision: u64) {
+        if revision != self.revision {
+            self.revision = revision;
+            self.paths.clear();
+        }
+    }
+}
+
+/// The way to the nearest of some targets from every tile of the level.
+///
+/// Built once, e.g. each turn from where the players stand, it tells any
+/// number of mobs their next step without each searching for a path.
+pub struct FlowField {
+    targets: Vec<Pos>,
+    distances: Grid<u32>,
+}
+
+impl FlowField {
+    pub fn new<FP>(targets: &[Pos], passable: FP) -> Self
+    where
+        FP: Fn(Pos) -> bool,
+    {
+        FlowField {
+            targets: targets.to_vec(),
+            distances: dijkstra_map(targets, passable),
+        }
+    }
+
+    pub fn targets(&self) -> &[Pos] {
+        &self.targets
+    }
+
+    /// How many steps a tile is from the nearest target, or `UNREACHABLE`.
+    pub fn distance(&self, pos: Pos) -> u32 {
+        if grid::contains(pos) {
+            self.distances[pos]
+        } else {
+            UNREACHABLE
+        }
+    }
+
+    /// The neighbor of a tile that is the fewest steps from a target.
+    ///
+    /// `None` at a target, or where no target can be reached.
+    pub fn next_step(&self, pos: Pos) -> Option<Pos> {
+        pos.neighbors()
+            .filter(|&next| self.distance(next) < self.distance(pos))
+            .min_by_key(|&next| self.distance(next))
+    }
+}
+
+/// How safe every tile is from a set of threats. Lower is safer.
+///
+/// Distances from the threats are negated and weighted, then smoothed so
+/// that no tile is more than a step worse than its safest neighbor. A mob
+/// that keeps stepping to safer tiles then heads for the place furthest
+/// from every threat, and escapes around threats rather than into corners.
+/// Tiles that can't be reached from any threat, or at all, are `i32::MAX`.
+pub fn safety_map<FP>(threats: &[Pos], passable: FP) -> Grid<i32>
+where
+    FP: Fn(Pos) -> bool,
+{
+    let distances = dijkstra_map(threats, &passable);
+    let (numerator, denominator) = FLEE_WEIGHT;
+    let mut safety = Grid::new(|pos| {
+        if distances[pos] == UNREACHABLE {
+            i32::MAX
+        } else {
+            -(distances[pos] as i32) * numerator / denominator
+        }
+    });
+    let mut open = MinHeap::new();
+    for pos in grid::positions().filter(|&pos| safety[pos] != i32::MAX) {
+        open.push(pos, safety[pos]);
+    }
+    while let Some(pos) = open.pop() {
+        let next = safety[pos] + 1;
+        for neighbor in pos.neighbors() {
+            if grid::contains(neighbor) && safety[neighbor] != i32::MAX && safety[neighbor] > next {
+                safety[neighbor] = next;
+                open.push(neighbor, next);
+            }
+        }
+    }
+    safety
+}
+
+/// The way a mob should flee through a safety map.
+///
+/// Steps to the safest neighbor until no neighbor is safer, so the path
+/// is just the origin if it is already as safe as it gets. `passable`
+/// can rule out tiles that are taken right now.
+pub fn flee_path<FP>(safety: &Grid<i32>, origin: Pos, passable: FP) -> Path
+where
+    FP: Fn(Pos) -> bool,
+{
+    let mut positions = vec![origin];
+    let mut pos = origin;
+    loop {
+        let safer = pos
+            .neighbors()
+            .filter(|&neighbor| grid::contains(neighbor) && passable(neighbor))
+            .filter(|&neighbor| safety[neighbor] < safety[pos])
+            .min_by_key(|&neighbor| safety[neighbor]);
+        match safer {
+            Some(next) => {
+                positions.push(next);
+                pos = next;
+            }
+            None => return Path::walked(positions),
+        }
+    }
+}
+
+/// Plan the next `window` turns of several movers headed for the same goals,
+/// so they neither share a tile nor swap places.
+///
+/// This is windowed cooperative A*. Movers are planned one at a time in
+/// order, each searching through space and time around the tiles those
+/// before it have reserved, and waiting where it must. Every mover starts
+/// out holding its own tile, and no mover steps onto a tile still held by
+/// one planned after it, since movers take their steps in the same order.
+///
+/// `distance` is how many steps a tile is from the nearest goal, ignoring
+/// the movers, or `UNREACHABLE`. It guides the search, and judges how
+/// close each mover gets by the end of the window. Goals are where it is 0.
+///
+/// Each plan holds a mover's position on every turn of the window, starting
+/// with where it is now. Only the first step of a plan is meant to be taken
+/// before planning again. A mover that can't get out of the way of those
+/// planned before it just stays put.
+pub fn plan_group<FD, FP>(starts: &[Pos], distance: FD, passable: FP, window: u32) -> Vec<Vec<Pos>>
+where
+    FD: Fn(Pos) -> u32,
+    FP: Fn(Pos) -> bool,
+{
+    let mut reserved: HashMap<(Pos, u32), usize> = HashMap::new();
+    for (mover, &start) in starts.iter().enumerate() {
+        reserved.insert((start, 0), mover);
+    }
+    let mut plans = Vec::new();
+    for (mover, &start) in starts.iter().enumerate() {
+        let plan = plan_mover(mover, start, &reserved, &distance, &passable, window)
+            .unwrap_or_else(|| vec![start; window as usize + 1]);
+        for (turn, &pos) in plan.iter().enumerate() {
+            reserved.insert((pos, turn as u32), mover);
+        }
+        plans.push(plan);
+    }
+    plans
+}
+
+/// Search through space and time for one mover's plan around the reservations.
+fn plan_mover<FD, FP>(
+    mover: usize,
+    start: Pos,
+    reserved: &HashMap<(Pos, u32), usize>,
+    distance: &FD,
+    passable: &FP,
+    window: u32,
+) -> Option<Vec<Pos>>
+where
+    FD: Fn(Pos) -> u32,
+    FP: Fn(Pos) -> bool,
+{
+    let free = |pos: Pos, turn: u32| {
+        reserved
+            .get(&(pos, turn))
+            .map_or(true, |&other| other == mover)
+    };
+    let mut open = MinHeap::new();
+    let mut costs: HashMap<(Pos, u32), u32> = HashMap::new();
+    let mut parents: HashMap<(Pos, u32), Pos> = HashMap::new();
+    open.push((start, 0), distance(start));
+    costs.insert((start, 0), 0);
+    while let Some((pos, turn)) = open.pop() {
+        let at_goal = distance(pos) == 0 && (turn..=window).all(|later| free(pos, later));
+        if turn == window || at_goal {
+            let mut plan = vec![pos; (window - turn) as usize + 1];
+            let mut node = (pos, turn);
+            while let Some(&parent) = parents.get(&node) {
+                node = (parent, node.1 - 1);
+                plan.push(parent);
+            }
+            plan.reverse();
+            return Some(plan);
+        }
+        let cost = costs[&(pos, turn)];
+        for next in Some(pos).into_iter().chain(pos.neighbors()) {
+            if distance(next) == UNREACHABLE || (next != pos && !passable(next)) {
+                continue;
+            }
+            let swapped = match reserved.get(&(next, turn)) {
+                // A mover planned after this one hasn't left yet.
+                Some(&other) if other > mover => next != pos,
+                Some(&other) if other != mover => reserved.get(&(pos, turn + 1)) == Some(&other),
+                _ => false,
+            };
+            if swapped || !free(next, turn + 1) {
+                continue;
+            }
+            // Waiting at a goal costs nothing, so movers that got there stay.
+            let step_cost = if next == pos && distance(pos) == 0 {
+                0
+            } else {
+                1
+            };
+            let new_cost = cost + step_cost;
+            let node = (next, turn + 1);
+            if costs.get(&node).is_some_and(|&old| old <= new_cost) {
+                continue;
+            }
+            costs.insert(node, new_cost);
+            parents.insert(node, pos);
+            open.push(node, new_cost + distance(next));
+        }
+    }
+    None
+}
+
+/// A square of a large map, by its row and column among the others.
+type Chunk = (usize, usize);
+
+/// Shortcuts worked out up front for finding long paths over a large map.
+///
+/// The map is laid out in rows and columns like a `Grid`, but can be any
+/// size. It is cut into chunks `CHUNK_SIZE` tiles across, and a portal is
+/// placed wherever neighboring chunks meet along a stretch of open tiles.
+/// The steps between the portals of each chunk are counted once, when the
+/// hierarchy is built. A long path is then found over the portals and
+/// filled in one chunk at a time, so no search spreads over more than a
+/// chunk of the map. Paths found this way are nearly, but not always, the
+/// shortest.
+///
+/// The hierarchy must be built again whenever the map changes.
+pub struct PathHierarchy {
+    width: usize,
+    height: usize,
+    /// The portals each portal leads to, and how many steps away they are.
+    edges: HashMap<Pos, Vec<(Pos, u32)>>,
+}
+
+impl PathHierarchy {
+    /// Work out the portals of a map `width` tiles across and `height` rows down.
+    pub fn new<FP>(width: usize, height: usize, passable: FP) -> Self
+    where
+        FP: Fn(Pos) -> bool,
+    {
+        let mut hierarchy = PathHierarchy {
+            width,
+            height,
+            edges: HashMap::new(),
+        };
+        let open = |pos| hierarchy.contains(pos) && passable(pos);
+        // The open tiles along each side of each chunk, by the chunk on the other side.
+        let mut sides: BTreeMap<(Chunk, Chunk), Vec<Pos>> = BTreeMap::new();
+        for row in 0..height {
+            for col in 0..width {
+                let pos = grid::offset_to_pos(row, col);
+                if !open(pos) {
+                    continue;
+                }
+                let chunk = hierarchy.chunk(pos).unwrap();
+                for neighbor in pos.neighbors().filter(|&neighbor| open(neighbor)) {
+                    let other = hierarchy.chunk(neighbor).unwrap();
+                    if other != chunk {
+                        let side = sides.entry((chunk, other)).or_default();
+                        if !side.contains(&pos) {
+                            side.push(pos);
+                        }
+                    }
+                }
+            }
+        }
+        let mut links = Vec::new();
+        let mut portals: BTreeMap<Chunk, Vec<Pos>> = BTreeMap::new();
+        for (&(chunk, other), side) in sides.iter().filter(|&(&(a, b), _)| a < b) {
+            for stretch in stretches(side) {
+                let portal = stretch[stretch.len() / 2];
+                let across = portal
+                    .neighbors()
+                    .find(|&neighbor| open(neighbor) && hierarchy.chunk(neighbor) == Some(other))
+                    .unwrap();
+                links.push((portal, across));
+                for &(pos, chunk) in &[(portal, chunk), (across, other)] {
+                    let chunk_portals = portals.entry(chunk).or_default();
+                    if !chunk_portals.contains(&pos) {
+                        chunk_portals.push(pos);
+                    }
+                }
+            }
+        }
+        let mut edges: HashMap<Pos, Vec<(Pos, u32)>> = HashMap::new();
+        for (portal, across) in links {
+            edges.entry(portal).or_default().push((across, 1));
+            edges.entry(across).or_default().push((portal, 1));
+        }
+        for chunk_portals in portals.values() {
+            for &portal in chunk_portals {
+                let distances = hierarchy.chunk_distances(portal, &passable);
+                for &other in chunk_portals.iter().filter(|&&other| other != portal) {
+                    if let Some(&distance) = distances.get(&other) {
+                        edges.entry(portal).or_default().push((other, distance));
+                    }
+                }
+            }
+        }
+        hierarchy.edges = edges;
+        hierarchy
+    }
+
+    /// Whether a position is on the map.
+    pub fn contains(&self, pos: Pos) -> bool {
+        self.chunk(pos).is_some()
+    }
+
+    /// Find a path from an origin to a goal, both of which must be passable.
+    pub fn find_path<FP>(&self, origin: Pos, goal: Pos, passable: FP) -> Option<Path>
+    where
+        FP: Fn(Pos) -> bool,
+    {
+        let open = |pos| self.contains(pos) && passable(pos);
+        if !open(origin) || !open(goal) {
+            return None;
+        }
+        if self.chunk(origin) == self.chunk(goal) {
+            if let Some(path) = self.local_path(origin, goal, open) {
+                return Some(path);
+            }
+        }
+        let waypoints = self.abstract_path(origin, goal, open)?;
+        let mut positions = vec![origin];
+        let mut expanded = 0;
+        for pair in waypoints.windows(2) {
+            if self.chunk(pair[0]) == self.chunk(pair[1]) {
+                let leg = self.local_path(pair[0], pair[1], open)?;
+                expanded += leg.expanded;
+                positions.extend_from_slice(&leg.positions[1..]);
+            } else {
+                positions.push(pair[1]);
+            }
+        }
+        Some(Path {
+            expanded,
+            ..Path::walked(positions)
+        })
+    }
+
+    /// The portals to pass through on the way from an origin to a goal,
+    /// with the origin first and the goal last.
+    fn abstract_path<FP>(&self, origin: Pos, goal: Pos, open: FP) -> Option<Vec<Pos>>
+    where
+        FP: Fn(Pos) -> bool,
+    {
+        let from_origin = self.portal_distances(origin, &open);
+        let to_goal = self.portal_distances(goal, &open);
+        let mut costs = HashMap::new();
+        let mut parents = HashMap::new();
+        let mut open_set = MinHeap::new();
+        costs.insert(origin, 0);
+        open_set.push(origin, origin.distance(goal));
+        while let Some(node) = open_set.pop() {
+            if node == goal {
+                let mut waypoints = vec![goal];
+                let mut pos = goal;
+                while let Some(&parent) = parents.get(&pos) {
+                    waypoints.push(parent);
+                    pos = parent;
+                }
+                waypoints.reverse();
+                return Some(waypoints);
+            }
+            let cost = costs[&node];
+            let mut edges = self.edges.get(&node).cloned().unwrap_or_default();
+            if node == origin {
+                edges.extend_from_slice(&from_origin);
+            }
+            edges.extend(
+                to_goal
+                    .iter()
+                    .filter(|&&(portal, _)| portal == node)
+                    .map(|&(_, distance)| (goal, distance)),
+            );
+            for (next, distance) in edges {
+                let next_cost = cost + distance;
+                if costs.get(&next).map_or(true, |&known| next_cost < known) {
+                    costs.insert(next, next_cost);
+                    parents.insert(next, node);
+                    open_set.push(next, next_cost + next.distance(goal));
+                }
+            }
+        }
+        None
+    }
+
+    /// The portals of a position's chunk it can reach within the chunk,
+    /// and how many steps away they are.
+    fn portal_distances<FP>(&self, pos: Pos, open: FP) -> Vec<(Pos, u32)>
+    where
+        FP: Fn(Pos) -> bool,
+    {
+        let mut distances: Vec<(Pos, u32)> = self
+            .chunk_distances(pos, open)
+            .into_iter()
+            .filter(|&(portal, distance)| distance > 0 && self.edges.contains_key(&portal))
+            .collect();
+        // Sorted so that ties in the search break the same way every time.
+        distances.sort_by_key(|&(portal, distance)| (distance, grid::pos_to_offset(portal)));
+        distances
+    }
+
+    /// How many steps every tile of a position's chunk is from it, staying within the chunk.
+    fn chunk_distances<FP>(&self, from: Pos, passable: FP) -> HashMap<Pos, u32>
+    where
+        FP: Fn(Pos) -> bool,
+    {
+        let chunk = self.chunk(from);
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(from, 0);
+        queue.push_back(from);
+        while let Some(pos) = queue.pop_front() {
+            let distance = distances[&pos] + 1;
+            for next in pos.neighbors() {
+                if self.chunk(next) == chunk && !distances.contains_key(&next) && passable(next) {
+                    distances.insert(next, distance);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    /// A shortest path between two positions of a chunk that stays within it.
+    fn local_path<FP>(&self, origin: Pos, goal: Pos, open: FP) -> Option<Path>
+    where
+        FP: Fn(Pos) -> bool,
+    {
+        let chunk = self.chunk(origin);
+        jps(
+            origin,
+            |pos| pos == goal,
+            |pos| self.chunk(pos) == chunk && open(pos),
+            |pos| pos.distance(goal),
+            None,
+        )
+    }
+
+    fn chunk(&self, pos: Pos) -> Option<Chunk> {
+        let (row, col) = grid::pos_to_offset(pos)?;
+        if row < self.height && col < self.width {
+            Some((row / CHUNK_SIZE, col / CHUNK_SIZE))
+        } else {
+            None
+        }
+    }
+}
+
+/// Split tiles into stretches of neighboring tiles, keeping their order within each.
+fn stretches(tiles: &[Pos]) -> Vec<Vec<Pos>> {
+    let mut stretches: Vec<Vec<Pos>> = Vec::new();
+    let mut seen = HashSet::new();
+    for &start in tiles {
+        if !seen.insert(start) {
+            continue;
+        }
+        let mut stretch = vec![start];
+        let mut index = 0;
+        while index < stretch.len() {
+            let pos = stretch[index];
+            for &next in tiles {
+                if next.distance(pos) == 1 && seen.insert(next) {
+                    stretch.push(next);
+                }
+            }
+            index += 1;
+        }
+        stretches.push(stretch);
+    }
+    stretches
+}
+
+/// Jump point search from an origin to the nearest goal.
+///
+/// With a `max_cost`, the search gives up on any path that would take more
+/// steps than that, so the heuristic must never guess too high. Its open
+/// set is then a `BucketQueue`, which is faster for such small costs.
 pub(super) fn jps<FG, FP, FH>(
     origin: Pos,
     is_goal: FG,
     passable: FP,
     heuristic: FH,
-) -> Option<Vec<Pos>>
+    max_cost: Option<u32>,
+) -> Option<Path>
+where
+    FG: Fn(Pos) -> bool,
+    FP: Fn(Pos) -> bool,
+    FH: Fn(Pos) -> u32,
+{
+    let map = ClosureMap {
+        is_goal,
+        passable,
+        heuristic,
+    };
+    let mut search = SearchState::bounded(map, origin, max_cost);
+    match search.step(u32::MAX) {
+        SearchStatus::Found(path) => Some(path),
+        _ => None,
+    }
+}
+
+/// Jump point search from an origin to a goal.
+pub(super) fn jps_to<FP>(
+    origin: Pos,
+    goal: Goal,
+    passable: FP,
+    max_cost: Option<u32>,
+) -> Option<Path>
+where
+    FP: Fn(Pos) -> bool,
+{
+    jps(
+        origin,
+        |pos| goal.contains(pos),
+        passable,
+        |pos| goal.heuristic(pos),
+        max_cost,
+    )
+}
+
+/// A map made of a closure for each method of `JPSearchable`.
+struct ClosureMap<FG, FP, FH> {
+    is_goal: FG,
+    passable: FP,
+    heuristic: FH,
+}
+
+impl<FG, FP, FH> JPSearchable for ClosureMap<FG, FP, FH>
 where
     FG: Fn(Pos) -> bool,
     FP: Fn(Pos) -> bool,
     FH: Fn(Pos) -> u32,
 {
-    if is_goal(origin) {
-        return Some(vec![origin]);
+    fn is_goal(&self, pos: Pos) -> bool {
+        (self.is_goal)(pos)
+    }
+
+    fn passable(&self, pos: Pos) -> bool {
+        (self.passable)(pos)
+    }
+
+    fn heuristic(&self, pos: Pos) -> u32 {
+        (self.heuristic)(pos)
+    }
+}
+
+/// How far a `SearchState` has got.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum SearchStatus {
+    /// The search needs more steps.
+    Searching,
+    Found(Path),
+    /// Every route has been tried, and none reaches a goal.
+    Unreachable,
+}
+
+/// A jump point search that can be paused and resumed.
+///
+/// Each call to `step` expands at most so many nodes, so a search across a
+/// huge map can be spread over several turns or frames instead of stalling
+/// one. The map must not change while the search is under way.
+pub struct SearchState<M> {
+    map: M,
+    /// A bucket queue when the cost is bounded, and a heap otherwise.
+    open: Box<dyn PriorityQueue<OpenNode>>,
+    costs: HashMap<Pos, u32>,
+    parents: HashMap<Pos, JumpPoint>,
+    max_cost: Option<u32>,
+    expanded: u32,
+    truncated: bool,
+    status: SearchStatus,
+    stats: SearchStats,
+    /// Whether to time the search and record what it does.
+    timed: bool,
+}
+
+impl<M: JPSearchable> SearchState<M> {
+    /// Start a search from an origin to the nearest goal of a map.
+    pub fn new(map: M, origin: Pos) -> Self {
+        SearchState::bounded(map, origin, None)
+    }
+
+    /// Start a search that gives up on paths costing more than `max_cost`, if given.
+    ///
+    /// Every path cost is then small enough to keep the open set in buckets.
+    fn bounded(map: M, origin: Pos, max_cost: Option<u32>) -> Self {
+        let open: Box<dyn PriorityQueue<OpenNode>> = match max_cost {
+            Some(max_cost) => Box::new(BucketQueue::new(max_cost)),
+            None => Box::new(IndexedMinHeap::new()),
+        };
+        let mut search = SearchState {
+            map,
+            open,
+            costs: HashMap::new(),
+            parents: HashMap::new(),
+            max_cost,
+            expanded: 0,
+            truncated: false,
+            status: SearchStatus::Searching,
+            stats: SearchStats::default(),
+            timed: is_recording(),
+        };
+        if search.map.is_goal(origin) {
+            search.status = SearchStatus::Found(Path::walked(vec![origin]));
+            return search;
+        }
+        let initial_priority = search.map.heuristic(origin);
+        for &direction in &DIRECTIONS {
+            search
+                .open
+                .push(OpenNode::initial(origin, direction), initial_priority);
+        }
+        search.stats.pushed = DIRECTIONS.len() as u32;
+        search.stats.max_open = search.open.len() as u32;
+        if search.timed {
+            record(&search.stats);
+        }
+        search.costs.insert(origin, 0);
+        search
+    }
+
+    /// How many nodes have been expanded so far.
+    pub fn expanded(&self) -> u32 {
+        self.expanded
+    }
+
+    /// What the search has done so far.
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Expand up to `n_nodes` more nodes, stopping early if the search ends.
+    ///
+    /// Once the search has ended, every call returns how it ended.
+    pub fn step(&mut self, n_nodes: u32) -> SearchStatus {
+        if self.status == SearchStatus::Searching {
+            if let Some(goal) = self.advance(n_nodes) {
+                self.status = SearchStatus::Found(Path {
+                    positions: PathIter::new(&self.parents, goal).collect(),
+                    cost: self.costs[&goal],
+                    expanded: self.expanded,
+                    truncated: self.truncated,
+                });
+            }
+        }
+        self.status.clone()
+    }
+
+    /// Expand up to `n_nodes` more nodes, returning the goal if one is reached.
+    fn advance(&mut self, n_nodes: u32) -> Option<Pos> {
+        let start = if self.timed {
+            Some(Instant::now())
+        } else {
+            None
+        };
+        let before = self.stats;
+        let mut budget = n_nodes;
+        let mut goal = None;
+        while budget > 0 {
+            let popped = self.open.pop();
+            if popped.is_some() {
+                self.stats.popped += 1;
+            }
+            let curr = match popped {
+                None => {
+                    self.status = SearchStatus::Unreachable;
+                    break;
+                }
+                Some(OpenNode::Goal(pos)) => {
+                    goal = Some(pos);
+                    break;
+                }
+                Some(OpenNode::JumpPoint(curr)) => curr,
+            };
+            budget -= 1;
+            self.expanded += 1;
+            self.expand(curr);
+            self.stats.max_open = self.stats.max_open.max(self.open.len() as u32);
+        }
+        if let Some(start) = start {
+            self.stats.elapsed += start.elapsed();
+            record(&self.stats.since(&before));
+        }
+        goal
     }
+
+    fn expand(&mut self, curr: JumpPoint) {
+        let map = &self.map;
+        let open = &mut self.open;
+        let costs = &mut self.costs;
+        let parents = &mut self.parents;
+        let truncated = &mut self.truncated;
+        let pushed = &mut self.stats.pushed;
+        let max_cost = self.max_cost;
+        self.stats.scanned += curr.for_each_neighbor(
+            |neighbor| {
+                let neighbor_pos = neighbor.pos();
+                let new_cost = costs[&curr.pos] + neighbor.pos().distance(curr.pos);
+                if let Some(&cost) = costs.get(&neighbor_pos) {
+                    // several jump points can share a position, heading different ways,
+                    // so one reached at the same cost as another is still worth expanding.
+                    // The same jump point reached again is merged with the one already open.
+                    if new_cost > cost {
+                        return;
+                    }
+                }
+                let estimate = new_cost + map.heuristic(neighbor_pos);
+                if estimate > max_cost.unwrap_or(u32::MAX) {
+                    *truncated = true;
+                    return;
+                }
+                open.push(neighbor, estimate);
+                *pushed += 1;
+                parents.insert(neighbor_pos, curr.clone());
+                costs.insert(neighbor_pos, new_cost);
+            },
+            &|pos| map.is_goal(pos),
+            &|pos| map.passable(pos),
+        );
+    }
+}
+
+/// The cheapest path to a goal when tiles cost different amounts to enter.
+///
+/// `cost` is what entering a tile costs, or `None` if it can't be entered.
+/// The heuristic must never guess more than the real remaining cost.
+pub(super) fn cheapest_path<FG, FC, FH>(
+    origin: Pos,
+    is_goal: FG,
+    cost: FC,
+    heuristic: FH,
+) -> Option<Path>
+where
+    FG: Fn(Pos) -> bool,
+    FC: Fn(Pos) -> Option<u32>,
+    FH: Fn(Pos) -> u32,
+{
+    bounded_cheapest_path(origin, is_goal, cost, heuristic, None)
+}
+
+/// The cheapest path to a goal, giving up on any costing more than
+/// `max_cost`, if given.
+fn bounded_cheapest_path<FG, FC, FH>(
+    origin: Pos,
+    is_goal: FG,
+    cost: FC,
+    heuristic: FH,
+    max_cost: Option<u32>,
+) -> Option<Path>
+where
+    FG: Fn(Pos) -> bool,
+    FC: Fn(Pos) -> Option<u32>,
+    FH: Fn(Pos) -> u32,
+{
     let mut open = MinHeap::new();
     let mut costs: HashMap<Pos, u32> = HashMap::new();
-    let mut parents: HashMap<Pos, JumpPoint> = HashMap::new();
-    let initial_priority = heuristic(origin);
-    for &direction in &DIRECTIONS {
-        open.push(OpenNode::initial(origin, direction), initial_priority);
-    }
+    let mut parents: HashMap<Pos, Pos> = HashMap::new();
+    open.push(origin, heuristic(origin));
     costs.insert(origin, 0);
-    while let Some(node) = open.pop() {
-        match node {
-            OpenNode::Goal(pos) => {
-                return Some(construct_path(&parents, pos, costs[&pos]));
-            }
-            OpenNode::JumpPoint(curr) => {
-                curr.clone().for_each_neighbor(
-                    |neighbor| {
-                        let neighbor_pos = neighbor.pos();
-                        let new_cost = costs[&curr.pos] + neighbor.pos().distance(curr.pos);
-                        if let Some(&cost) = costs.get(&neighbor_pos) {
-                            // normally we would skip a neighbor if its cost was equal to the cost found already
-                            // here we don't because in this implementation of jps,
-                            // multiple neighbors can be created for a single position
-                            if new_cost > cost {
-                                return;
-                            }
-                        }
-                        open.push(neighbor, new_cost + heuristic(neighbor_pos));
-                        parents.insert(neighbor_pos, curr.clone());
-                        costs.insert(neighbor_pos, new_cost);
-                    },
-                    &is_goal,
-                    &passable,
-                );
+    let mut expanded = 0;
+    let mut truncated = false;
+    while let Some(pos) = open.pop() {
+        if is_goal(pos) {
+            let mut positions = vec![pos];
+            let mut step = pos;
+            while let Some(&parent) = parents.get(&step) {
+                positions.push(parent);
+                step = parent;
             }
+            positions.reverse();
+            return Some(Path {
+                positions,
+                cost: costs[&pos],
+                expanded,
+                truncated,
+            });
+        }
+        expanded += 1;
+        for &direction in &DIRECTIONS {
+            let neighbor = pos + direction;
+            let new_cost = match cost(neighbor) {
+                Some(step) => costs[&pos] + step,
+                None => continue,
+            };
+            if costs.get(&neighbor).is_some_and(|&cost| cost <= new_cost) {
+                continue;
+            }
+            let estimate = new_cost + heuristic(neighbor);
+            if estimate > max_cost.unwrap_or(u32::MAX) {
+                truncated = true;
+                continue;
+            }
+            costs.insert(neighbor, new_cost);
+            parents.insert(neighbor, pos);
+            open.push(neighbor, estimate);
         }
     }
     None
 }
 
-#[derive(Eq, PartialEq)]
+/// The cheapest path to a goal when tiles cost different amounts to enter,
+/// where every tile costs at least one.
+pub(super) fn cheapest_path_to<FC>(origin: Pos, goal: Goal, cost: FC) -> Option<Path>
+where
+    FC: Fn(Pos) -> Option<u32>,
+{
+    cheapest_path(
+        origin,
+        |pos| goal.contains(pos),
+        cost,
+        |pos| goal.heuristic(pos),
+    )
+}
+
+#[derive(Eq, PartialEq, Hash, Clone)]
 enum OpenNode {
     Goal(Pos),
     JumpPoint(JumpPoint),
 }
 
-#[derive(Eq, PartialEq, Clone)]
+#[derive(Eq, PartialEq, Hash, Clone)]
 struct JumpPoint {
     pos: Pos,
     direction: Direction,
     chirality: Chirality,
 }
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
 enum Chirality {
     Clockwise,
     Counterclockwise,
@@ -100,15 +1947,19 @@ impl JumpPoint {
         }
     }
 
-    fn for_each_neighbor<FC, FG, FP>(&self, mut callback: FC, is_goal: &FG, passable: &FP)
+    /// Jump from this jump point, calling back with each node found.
+    /// Returns how many tiles were scanned.
+    fn for_each_neighbor<FC, FG, FP>(&self, mut callback: FC, is_goal: &FG, passable: &FP) -> u32
     where
         FC: FnMut(OpenNode),
         FG: Fn(Pos) -> bool,
         FP: Fn(Pos) -> bool,
     {
         let leaf_direction = self.chirality.rotate(self.direction, 1);
+        let mut scanned = 0;
         for len in 1.. {
             let pos = self.pos + self.direction * len;
+            scanned += 1;
             if !passable(pos) {
                 break;
             }
@@ -120,8 +1971,10 @@ impl JumpPoint {
             if neighbor.is_forced(&passable) {
                 callback(OpenNode::JumpPoint(neighbor));
             }
-            for_each_leaf_neighbor(pos, leaf_direction, &mut callback, is_goal, passable);
+            scanned +=
+                for_each_leaf_neighbor(pos, leaf_direction, &mut callback, is_goal, passable);
         }
+        scanned
     }
 
     fn is_forced<FP>(&self, passable: &FP) -> bool
@@ -139,13 +1992,16 @@ fn for_each_leaf_neighbor<FC, FG, FP>(
     callback: &mut FC,
     is_goal: &FG,
     passable: &FP,
-) where
+) -> u32
+where
     FC: FnMut(OpenNode),
     FG: Fn(Pos) -> bool,
     FP: Fn(Pos) -> bool,
 {
+    let mut scanned = 0;
     for len in 1.. {
         let pos = root + direction * len;
+        scanned += 1;
         if !passable(pos) {
             break;
         }
@@ -162,6 +2018,7 @@ fn for_each_leaf_neighbor<FC, FG, FP>(
             callback(OpenNode::JumpPoint(neighbor2));
         }
     }
+    scanned
 }
 
 impl Chirality {
@@ -173,26 +2030,710 @@ impl Chirality {
     }
 }
 
-fn construct_path(parents: &HashMap<Pos, JumpPoint>, goal: Pos, total_cost: u32) -> Vec<Pos> {
-    let mut path = VecDeque::with_capacity(1 + total_cost as usize);
-    path.push_back(goal);
-    let mut pos = goal;
-    while let Some(&JumpPoint {
-        pos: parent_pos,
-        direction: stem_direction,
-        chirality,
-    }) = parents.get(&pos)
-    {
-        let leaf_direction = chirality.rotate(stem_direction, 1);
-        let (stem_cost, leaf_cost) = decompose(pos - parent_pos, stem_direction, leaf_direction);
-        let stem_tip = parent_pos + stem_direction * stem_cost;
-        for x in (0..leaf_cost).rev() {
-            path.push_back(stem_tip + leaf_direction * x);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid;
+
+    /// A map of the whole grid with a wall in it.
+    struct Walled {
+        wall: Vec<Pos>,
+        goal: Pos,
+    }
+
+    impl JPSearchable for Walled {
+        fn is_goal(&self, pos: Pos) -> bool {
+            pos == self.goal
+        }
+
+        fn passable(&self, pos: Pos) -> bool {
+            grid::contains(pos) && !self.wall.contains(&pos)
+        }
+
+        fn heuristic(&self, pos: Pos) -> u32 {
+            pos.distance(self.goal)
+        }
+    }
+
+    #[test]
+    fn test_dijkstra_map_from_nearest_source() {
+        let west = grid::center() + Direction::West * 3;
+        let east = grid::center() + Direction::East * 3;
+        let wall = grid::center() + Direction::Northeast;
+        let distances = dijkstra_map(&[west, east], |pos| pos != wall);
+        assert_eq!(distances[west], 0);
+        assert_eq!(distances[east], 0);
+        assert_eq!(distances[grid::center()], 3);
+        assert_eq!(distances[wall], UNREACHABLE);
+        let beyond: Pos = east + Direction::East * 2;
+        assert_eq!(distances[beyond], 2);
+    }
+
+    #[test]
+    fn test_path_cache_forgets_old_revisions() {
+        let origin = grid::center();
+        let path: Vec<Pos> = (0..5).map(|i| origin + Direction::East * i).collect();
+        let goal = path[4];
+        let mut cache = PathCache::new();
+        cache.insert(1, path.clone());
+        assert_eq!(cache.get(1, path[2], goal), Some(&path[2..]));
+        assert_eq!(cache.get(1, path[2], path[3]), None);
+        assert_eq!(cache.get(1, origin + Direction::West, goal), None);
+        assert_eq!(cache.get(2, path[2], goal), None);
+        cache.insert(2, path.clone());
+        assert_eq!(cache.get(1, origin, goal), None);
+
+        for i in 0..PATH_CACHE_SIZE as i32 {
+            cache.insert(3, vec![origin, origin + Direction::West * (i + 1)]);
+        }
+        cache.insert(3, path.clone());
+        assert_eq!(cache.get(3, origin, origin + Direction::West), None);
+        assert_eq!(cache.get(3, origin, goal), Some(&path[..]));
+    }
+
+    #[test]
+    fn test_flow_field_leads_around_walls() {
+        let target = grid::center();
+        let wall: Vec<Pos> = (-3..4)
+            .map(|i| target + Direction::West * 2 + Direction::Southeast * i)
+            .collect();
+        let passable = |pos: Pos| grid::contains(pos) && !wall.contains(&pos);
+        let field = FlowField::new(&[target], passable);
+        assert_eq!(field.targets(), &[target]);
+        assert_eq!(field.next_step(target), None);
+        assert_eq!(field.distance(wall[0]), UNREACHABLE);
+
+        let origin = target + Direction::West * 4;
+        let map = Walled {
+            wall: wall.clone(),
+            goal: target,
+        };
+        let steps = find_path(&map, origin).unwrap().steps();
+        assert_eq!(field.distance(origin), steps);
+        let mut pos = origin;
+        for _ in 0..steps {
+            pos = field.next_step(pos).unwrap();
+            assert!(passable(pos));
+        }
+        assert_eq!(pos, target);
+    }
+
+    #[test]
+    fn test_flee_path_reaches_safest_tiles() {
+        let threat = grid::center();
+        let passable = |pos: Pos| pos.distance(threat) <= 6;
+        let walled: Pos = threat + Direction::West * 7;
+        let safety = safety_map(&[threat], passable);
+        assert_eq!(safety[walled], i32::MAX);
+        for pos in grid::positions().filter(|&pos| passable(pos)) {
+            let safest = pos.neighbors().map(|pos| safety[pos]).min().unwrap();
+            assert!(safety[pos] <= safest.saturating_add(1));
+        }
+
+        let origin = threat + Direction::East;
+        let path = flee_path(&safety, origin, passable);
+        assert_eq!(path.origin(), origin);
+        assert_eq!(path.goal().distance(threat), 6);
+        for pair in path.positions().windows(2) {
+            assert_eq!(pair[0].distance(pair[1]), 1);
+            assert!(safety[pair[1]] < safety[pair[0]]);
+        }
+        assert_eq!(flee_path(&safety, path.goal(), passable).steps(), 0);
+    }
+
+    #[test]
+    fn test_group_files_through_a_corridor() {
+        let corridor: Vec<Pos> = (-6..7)
+            .map(|i| grid::center() + Direction::East * i)
+            .collect();
+        let goal = corridor[12];
+        let passable = |pos: Pos| corridor.contains(&pos);
+        let distance = |pos: Pos| {
+            if passable(pos) {
+                pos.distance(goal)
+            } else {
+                UNREACHABLE
+            }
+        };
+        let starts = [corridor[1], corridor[3], corridor[2]];
+        let plans = plan_group(&starts, distance, passable, 8);
+        for turn in 0..=8 {
+            let mut here: Vec<Pos> = plans.iter().map(|plan| plan[turn]).collect();
+            here.sort_by_key(|&pos| distance(pos));
+            here.dedup();
+            assert_eq!(here.len(), starts.len());
+        }
+        for (plan, &start) in plans.iter().zip(&starts) {
+            assert_eq!(plan[0], start);
+            for pair in plan.windows(2) {
+                assert!(pair[0].distance(pair[1]) <= 1);
+                assert!(passable(pair[1]));
+            }
+            assert!(distance(plan[8]) < distance(start));
+        }
+        // The one in front moves off first, and the others follow on its heels.
+        assert_eq!(plans[1][1], corridor[4]);
+        assert_eq!(plans[2][1], corridor[3]);
+        assert_eq!(plans[0][1], corridor[1]);
+    }
+
+    #[test]
+    fn test_incremental_path_follows_changes() {
+        let origin = grid::center() + Direction::West * 6;
+        let mut target = grid::center() + Direction::East * 6;
+        let mut wall: Vec<Pos> = (-3..4)
+            .map(|i| grid::center() + Direction::Southeast * i)
+            .collect();
+        let mut planner = IncrementalPath::new(origin, target);
+        let mut pos = origin;
+        for turn in 0..8 {
+            if turn == 3 {
+                let extra = grid::center() + Direction::Southeast * 4;
+                wall.push(extra);
+                planner.tiles_changed(&[extra]);
+            }
+            if turn % 2 == 1 {
+                target += Direction::Northeast;
+                planner.move_goal(target);
+            }
+            let passable = |pos: Pos| grid::contains(pos) && !wall.contains(&pos);
+            let map = Walled {
+                wall: wall.clone(),
+                goal: target,
+            };
+            let fresh = find_path(&map, pos).unwrap();
+            let path = planner.path(passable).unwrap();
+            assert_eq!(path.origin(), pos);
+            assert_eq!(path.goal(), target);
+            assert_eq!(path.steps(), fresh.steps(), "turn {}", turn);
+            for pair in path.positions().windows(2) {
+                assert_eq!(pair[0].distance(pair[1]), 1);
+                assert!(passable(pair[1]));
+            }
+            pos = planner.next_step(passable).unwrap();
+            planner.move_start(pos);
+        }
+    }
+
+    #[test]
+    fn test_incremental_path_unreachable() {
+        let origin = grid::center();
+        let goal = origin + Direction::East * 3;
+        let mut planner = IncrementalPath::new(origin, goal);
+        let ring: Vec<Pos> = goal.neighbors().collect();
+        assert!(planner.next_step(grid::contains).is_some());
+        planner.tiles_changed(&ring);
+        let walled = |pos: Pos| grid::contains(pos) && !ring.contains(&pos);
+        assert_eq!(planner.next_step(walled), None);
+        assert_eq!(planner.path(walled), None);
+    }
+
+    #[test]
+    fn test_find_path_to_nearest_goal() {
+        let origin = grid::center();
+        let near = origin + Direction::West * 3;
+        let far = origin + Direction::East * 5;
+        let passable = |pos: Pos| grid::contains(pos);
+        let path = find_path_to_any(origin, &[far, near], passable).unwrap();
+        assert_eq!(path.goal(), near);
+        assert_eq!(path.steps(), 3);
+
+        let walled = |pos: Pos| passable(pos) && pos.distance(near) > 1;
+        let path = find_path_to_any(origin, &[far, near], walled).unwrap();
+        assert_eq!(path.goal(), far);
+        assert_eq!(find_path_to_any(origin, &[], passable), None);
+    }
+
+    #[test]
+    fn test_find_path_around_wall() {
+        let origin = grid::center();
+        let goal = origin + Direction::East * 4;
+        let map = Walled {
+            wall: (-2..3)
+                .map(|i| origin + Direction::East * 2 + Direction::Southeast * i)
+                .collect(),
+            goal,
+        };
+        let path = find_path(&map, origin).unwrap();
+        assert_eq!(path.origin(), origin);
+        assert_eq!(path.goal(), goal);
+        assert!(path.steps() > 4);
+        assert_eq!(path.steps() as usize + 1, path.positions().len());
+        for pair in path.positions().windows(2) {
+            assert_eq!(pair[0].distance(pair[1]), 1);
+            assert!(map.passable(pair[1]));
+        }
+        assert_eq!(path.next_step(), Some(path.positions()[1]));
+
+        let map = Walled {
+            wall: origin.neighbors().collect(),
+            goal,
+        };
+        assert_eq!(find_path(&map, origin), None);
+    }
+
+    #[test]
+    fn test_goal_modes() {
+        let origin = grid::center();
+        let target = origin + Direction::East * 5;
+        let passable = |pos: Pos| grid::contains(pos) && pos != target;
+        assert_eq!(find_path_to(origin, Goal::Tile(target), passable), None);
+        let path = find_path_to(origin, Goal::Adjacent(target), passable).unwrap();
+        assert_eq!(path.goal(), origin + Direction::East * 4);
+        assert_eq!(path.steps(), 4);
+        let path = find_path_to(origin, Goal::Within(target, 3), passable).unwrap();
+        assert_eq!(path.steps(), 2);
+        assert_eq!(Goal::Adjacent(target).heuristic(target), 0);
+        assert!(!Goal::Adjacent(target).contains(target));
+        assert_eq!(
+            find_path_to(origin, Goal::Tile(origin), passable)
+                .unwrap()
+                .steps(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_bidirectional_matches_jump_point() {
+        let origin = grid::center();
+        let passable = |pos: Pos| {
+            grid::contains(pos) && {
+                let location = grid::pos_to_location(pos);
+                (location.x * 7 + location.y * 13) % 5 != 0
+            }
+        };
+        for goal in grid::positions().step_by(37) {
+            for &goal in &[
+                Goal::Tile(goal),
+                Goal::Adjacent(goal),
+                Goal::Within(goal, 2),
+            ] {
+                let jump_point = find_path_with(origin, goal, passable, Strategy::JumpPoint);
+                let path = find_path_with(origin, goal, passable, Strategy::Bidirectional);
+                assert_eq!(
+                    path.as_ref().map(Path::steps),
+                    jump_point.as_ref().map(Path::steps),
+                    "{:?}",
+                    goal
+                );
+                let path = match path {
+                    Some(path) => path,
+                    None => continue,
+                };
+                assert_eq!(path.origin(), origin);
+                assert!(goal.contains(path.goal()));
+                assert_eq!(path.cost(), path.steps());
+                for pair in path.positions().windows(2) {
+                    assert_eq!(pair[0].distance(pair[1]), 1);
+                    assert!(passable(pair[1]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_expands_less_in_the_open() {
+        let origin = grid::center() + Direction::West * 5;
+        let goal = grid::center() + Direction::East * 5;
+        let path = find_path_with(
+            origin,
+            Goal::Tile(goal),
+            grid::contains,
+            Strategy::Bidirectional,
+        )
+        .unwrap();
+        assert_eq!(path.steps(), 10);
+        let one_way = cheapest_path(
+            origin,
+            |pos| pos == goal,
+            |pos| if grid::contains(pos) { Some(1) } else { None },
+            |_| 0,
+        )
+        .unwrap();
+        assert!(path.expanded() * 2 < one_way.expanded());
+    }
+
+    #[test]
+    fn test_penalized_tiles_avoided_unless_too_costly() {
+        let origin = grid::center();
+        let goal = origin + Direction::East * 4;
+        let strip: Vec<Pos> = (-1..2)
+            .map(|i| origin + Direction::East * 2 + Direction::Southeast * i)
+            .collect();
+        let strip = &strip;
+        let penalty = |cost| move |pos| if strip.contains(&pos) { cost } else { 0 };
+        let path =
+            find_path_avoiding(origin, Goal::Tile(goal), grid::contains, penalty(10)).unwrap();
+        assert!(path.positions().iter().all(|pos| !strip.contains(pos)));
+        assert_eq!(path.cost(), path.steps());
+        assert!(path.steps() > 4);
+        let path =
+            find_path_avoiding(origin, Goal::Tile(goal), grid::contains, penalty(1)).unwrap();
+        assert_eq!(path.steps(), 4);
+        assert_eq!(path.cost(), 5);
+    }
+
+    #[test]
+    fn test_occupied_tiles_blocked_or_avoided() {
+        let origin = grid::center();
+        let goal = origin + Direction::East * 4;
+        let wall_at = |i| origin + Direction::East * 2 + Direction::Southeast * i;
+        let occupied = wall_at(0);
+        // A wall across the way, with gaps where `gaps` says.
+        let search = |gaps: &[i32], occupancy| {
+            let wall: Vec<Pos> = (-30..31)
+                .filter(|i| !gaps.contains(i))
+                .map(wall_at)
+                .collect();
+            find_path_among(
+                origin,
+                Goal::Tile(goal),
+                |pos| grid::contains(pos) && !wall.contains(&pos),
+                |pos| pos == occupied || pos == goal,
+                occupancy,
+                None,
+            )
+        };
+        let ignored = search(&[0, 1], Occupancy::Ignore).unwrap();
+        assert!(ignored.positions().contains(&occupied));
+        let blocked = search(&[0, 1], Occupancy::Block).unwrap();
+        assert!(!blocked.positions().contains(&occupied));
+        assert_eq!(blocked.steps(), 5);
+        let avoided = search(&[0, 1], Occupancy::Penalize(3)).unwrap();
+        assert_eq!(avoided.positions(), blocked.positions());
+
+        // With no way around, the occupied tile is crossed at a cost, and
+        // the goal itself is never counted as occupied.
+        assert_eq!(search(&[0], Occupancy::Block), None);
+        let crossed = search(&[0], Occupancy::Penalize(3)).unwrap();
+        assert!(crossed.positions().contains(&occupied));
+        assert_eq!(crossed.cost(), 7);
+    }
+
+    #[test]
+    fn test_path_through_portals() {
+        let origin = grid::center() + Direction::West * 8;
+        let goal = grid::center() + Direction::East * 8;
+        let entry: Pos = origin + Direction::East;
+        let exit: Pos = goal + Direction::West * 2;
+        let portals: HashMap<Pos, Pos> = vec![(entry, exit), (exit, entry)].into_iter().collect();
+        let path =
+            find_path_through_portals(origin, Goal::Tile(goal), grid::contains, &portals).unwrap();
+        assert_eq!(
+            path.positions(),
+            &[origin, entry, exit, exit + Direction::East, goal]
+        );
+        assert_eq!(path.cost(), 3);
+        assert_eq!(path.steps(), 4);
+
+        // Without portals, it is an ordinary shortest path.
+        let path =
+            find_path_through_portals(origin, Goal::Tile(goal), grid::contains, &HashMap::new())
+                .unwrap();
+        assert_eq!(path.cost(), origin.distance(goal));
+        assert_eq!(path.cost(), path.steps());
+
+        // A portal leading the wrong way is walked around.
+        let portals: HashMap<Pos, Pos> = vec![(entry, origin + Direction::West * 3)]
+            .into_iter()
+            .collect();
+        let path =
+            find_path_through_portals(origin, Goal::Tile(goal), grid::contains, &portals).unwrap();
+        assert_eq!(path.cost(), path.steps());
+        assert!(!path.positions().contains(&entry));
+    }
+
+    #[test]
+    fn test_jps_gives_up_beyond_max_cost() {
+        let origin = grid::center();
+        let goal = origin + Direction::East * 4;
+        let wall: Vec<Pos> = (-2..3)
+            .map(|i| origin + Direction::East * 2 + Direction::Southeast * i)
+            .collect();
+        let search = |max_cost| {
+            jps(
+                origin,
+                |pos| pos == goal,
+                |pos| grid::contains(pos) && !wall.contains(&pos),
+                |pos| pos.distance(goal),
+                max_cost,
+            )
+        };
+        let path = search(None).unwrap();
+        let steps = path.steps();
+        assert!(steps > 4);
+        assert_eq!(path.cost(), steps);
+        assert!(path.expanded() > 0);
+        assert!(!path.truncated());
+        assert_eq!(search(Some(steps)).map(|path| path.steps()), Some(steps));
+        // The bucket queue of a bounded search breaks ties like the heap.
+        assert_eq!(
+            search(Some(steps * 2)).map(Path::into_positions),
+            Some(path.positions().to_vec())
+        );
+        assert_eq!(search(Some(steps - 1)), None);
+
+        // A pillar behind the origin makes jump points off the way to the goal.
+        let pillar = origin + Direction::West * 2;
+        let search = |max_cost| {
+            jps(
+                origin,
+                |pos| pos == goal,
+                |pos| grid::contains(pos) && pos != pillar,
+                |pos| pos.distance(goal),
+                max_cost,
+            )
+            .unwrap()
+        };
+        assert!(!search(None).truncated());
+        let limited = search(Some(4));
+        assert_eq!(limited.cost(), 4);
+        assert!(limited.truncated());
+    }
+
+    #[test]
+    fn test_cheapest_path_goes_around_costly_tiles() {
+        let origin = grid::center();
+        let goal = origin + Direction::East * 2;
+        let costly = origin + Direction::East;
+        let cost = |pos: Pos| {
+            if !grid::contains(pos) {
+                None
+            } else if pos == costly {
+                Some(10)
+            } else {
+                Some(1)
+            }
+        };
+        let path =
+            cheapest_path(origin, |pos| pos == goal, cost, |pos| pos.distance(goal)).unwrap();
+        assert_eq!(path.origin(), origin);
+        assert_eq!(path.goal(), goal);
+        assert_eq!(path.steps(), 3);
+        assert_eq!(path.cost(), 3);
+        assert!(path.expanded() > 0);
+        assert!(!path.positions().contains(&costly));
+    }
+
+    /// Whether a tile of a winding cave of walls `SIZE` tiles square is open.
+    ///
+    /// Every so many columns a wall runs down the cave, with a gap at the
+    /// top or the bottom, alternately. Without gaps the two sides are cut off.
+    fn winding(gaps: bool) -> impl Fn(Pos) -> bool {
+        const SIZE: usize = 160;
+        move |pos| match grid::pos_to_offset(pos) {
+            Some((row, col)) if row < SIZE && col < SIZE => {
+                let gap = if (col / 20) % 2 == 0 {
+                    row < 3
+                } else {
+                    row >= SIZE - 3
+                };
+                col % 20 != 10 || (gaps && gap)
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_hierarchy_finds_long_paths() {
+        let open = winding(true);
+        let hierarchy = PathHierarchy::new(160, 160, &open);
+        let origin = grid::offset_to_pos(80, 0);
+        let goal = grid::offset_to_pos(80, 159);
+        let path = hierarchy.find_path(origin, goal, &open).unwrap();
+        assert_eq!(path.origin(), origin);
+        assert_eq!(path.goal(), goal);
+        for pair in path.positions().windows(2) {
+            assert_eq!(pair[0].distance(pair[1]), 1);
+            assert!(open(pair[1]));
         }
-        for y in (0..stem_cost).rev() {
-            path.push_back(parent_pos + stem_direction * y);
+        let shortest = find_path_to_any(origin, &[goal], &open).unwrap().steps();
+        assert!(path.steps() >= shortest);
+        assert!(path.steps() <= shortest * 5 / 4);
+
+        let near = origin + Direction::Southeast * 3;
+        let path = hierarchy.find_path(origin, near, &open).unwrap();
+        assert_eq!(path.steps(), 3);
+    }
+
+    #[test]
+    fn test_hierarchy_unreachable() {
+        let open = winding(false);
+        let hierarchy = PathHierarchy::new(160, 160, &open);
+        let origin = grid::offset_to_pos(80, 0);
+        assert_eq!(
+            hierarchy.find_path(origin, grid::offset_to_pos(80, 159), &open),
+            None
+        );
+        let wall = grid::offset_to_pos(80, 10);
+        assert_eq!(hierarchy.find_path(origin, wall, &open), None);
+    }
+
+    #[test]
+    fn test_search_resumes_where_it_left_off() {
+        let origin = grid::offset_to_pos(80, 0);
+        let goal = grid::offset_to_pos(80, 159);
+        let map = |gaps| AnyGoal {
+            goals: vec![goal].into_iter().collect(),
+            passable: winding(gaps),
+        };
+        let mut search = SearchState::new(map(true), origin);
+        let mut steps = 0;
+        let path = loop {
+            steps += 1;
+            match search.step(10) {
+                SearchStatus::Searching => assert_eq!(search.expanded(), steps * 10),
+                SearchStatus::Found(path) => break path,
+                SearchStatus::Unreachable => panic!("the goal can be reached"),
+            }
+        };
+        assert!(steps > 1);
+        assert_eq!(Some(&path), find_path(&map(true), origin).as_ref());
+        assert_eq!(search.step(10), SearchStatus::Found(path));
+
+        let mut search = SearchState::new(map(false), origin);
+        while search.step(10) == SearchStatus::Searching {}
+        assert_eq!(search.step(10), SearchStatus::Unreachable);
+    }
+
+    #[test]
+    fn test_search_stats_add_up() {
+        let origin = grid::offset_to_pos(80, 0);
+        let map = |goal| AnyGoal {
+            goals: vec![goal].into_iter().collect(),
+            passable: winding(true),
+        };
+        start_recording();
+        let (path, stats) = find_path_with_stats(map(grid::offset_to_pos(80, 159)), origin);
+        let path = path.unwrap();
+        assert_eq!(stats.popped, path.expanded() + 1);
+        assert!(stats.pushed >= stats.popped);
+        assert!(stats.scanned >= stats.popped);
+        assert!(stats.max_open > 0);
+
+        let (none, missed) = find_path_with_stats(map(grid::offset_to_pos(80, 10)), origin);
+        assert_eq!(none, None);
+        assert!(missed.popped > 0);
+        let mut total = stats;
+        total.add(&missed);
+        assert_eq!(stop_recording(), Some(total));
+        assert_eq!(stop_recording(), None);
+
+        let mut search = SearchState::new(map(grid::offset_to_pos(80, 159)), origin);
+        search.step(u32::MAX);
+        assert!(search.stats().popped > 0);
+        assert_eq!(search.stats().elapsed, Duration::default());
+    }
+
+    #[test]
+    fn test_batches_and_floods_recorded() {
+        let center = grid::center();
+        let requests = vec![(center, Goal::Tile(center + Direction::East * 5)); 4];
+        start_recording();
+        let paths = find_paths_batch(&requests, grid::contains);
+        let batch = stop_recording().unwrap();
+        assert!(paths.iter().all(Option::is_some));
+        assert!(batch.popped >= 4);
+
+        start_recording();
+        dijkstra_map(&[center], grid::contains);
+        let flood = stop_recording().unwrap();
+        assert_eq!(flood.popped as usize, grid::positions().count());
+    }
+
+    #[test]
+    fn test_batch_matches_one_at_a_time() {
+        let center = grid::center();
+        let open = Grid::new(|pos| pos.distance(center + Direction::East * 3) != 2);
+        let passable = |pos| grid::contains(pos) && open[pos];
+        let requests: Vec<(Pos, Goal)> = (0..64)
+            .map(|i| {
+                let origin = center + Direction::West * (i % 8) + Direction::Southwest * (i / 8);
+                let goal = match i % 3 {
+                    0 => Goal::Tile(center + Direction::East * 3),
+                    1 => Goal::Adjacent(center + Direction::East * 10),
+                    _ => Goal::Within(center + Direction::Northeast * 12, 2),
+                };
+                (origin, goal)
+            })
+            .collect();
+        let paths = find_paths_batch(&requests, passable);
+        assert_eq!(paths.len(), requests.len());
+        for (&(origin, goal), path) in requests.iter().zip(&paths) {
+            assert_eq!(*path, find_path_to(origin, goal, passable));
         }
-        pos = parent_pos;
+        assert_eq!(paths[0], None);
+        assert!(paths[1].is_some());
+    }
+
+    #[test]
+    fn test_path_within_points() {
+        let origin = grid::center();
+        let mud = origin + Direction::East * 2;
+        let cost = |pos: Pos| {
+            if !grid::contains(pos) {
+                None
+            } else if pos == mud {
+                Some(3)
+            } else {
+                Some(1)
+            }
+        };
+        let goal = Goal::Tile(origin + Direction::East * 6);
+        let path = find_path_within(origin, goal, cost, 4);
+        assert_eq!(path.origin(), origin);
+        assert_eq!(path.steps(), 4);
+        assert_eq!(path.cost(), 4);
+        assert_eq!(path.goal().distance(goal.target()), 3);
+
+        let stuck = find_path_within(origin, goal, cost, 0);
+        assert_eq!(stuck.positions(), &[origin]);
+
+        let reachable = reachable_within(origin, cost, 2);
+        assert_eq!(reachable.len(), 1 + 6 + 12 - 1);
+        assert_eq!(reachable[&origin], 0);
+        assert!(!reachable.contains_key(&mud));
+    }
+
+    #[test]
+    fn test_path_within_points_to_unreachable_goal() {
+        let origin = grid::center();
+        let target = origin + Direction::East * 6;
+        let walled = |pos: Pos| {
+            if grid::contains(pos) && pos.distance(target) != 1 {
+                Some(1)
+            } else {
+                None
+            }
+        };
+        let path = find_path_within(origin, Goal::Tile(target), walled, 3);
+        assert_eq!(path.cost(), 3);
+        assert_eq!(path.goal(), origin + Direction::East * 3);
+        let far = find_path_within(origin, Goal::Tile(target), walled, 20);
+        assert_eq!(far.goal().distance(target), 2);
+        assert!(far.cost() <= 4);
+    }
+
+    #[test]
+    fn test_path_iter_walks_forward() {
+        let origin = grid::offset_to_pos(80, 0);
+        let map = |goal| AnyGoal {
+            goals: vec![goal].into_iter().collect(),
+            passable: winding(true),
+        };
+        let goal = grid::offset_to_pos(80, 159);
+        let path = find_path(&map(goal), origin).unwrap();
+        let mut steps = find_path_iter(map(goal), origin).unwrap();
+        assert_eq!(steps.len(), path.positions().len());
+        assert_eq!(steps.next(), Some(origin));
+        assert_eq!(steps.len(), path.positions().len() - 1);
+        let rest: Vec<Pos> = steps.collect();
+        assert_eq!(&rest[..], &path.positions()[1..]);
+        assert_eq!(rest.last(), Some(&goal));
+
+        let here: Vec<Pos> = find_path_iter(map(origin), origin).unwrap().collect();
+        assert_eq!(here, vec![origin]);
+        assert!(find_path_iter(map(grid::offset_to_pos(80, 10)), origin).is_none());
     }
-    Vec::from(path)
 }
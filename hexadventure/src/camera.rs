@@ -0,0 +1,147 @@
+//! Deciding which part of the map a frontend should show.
+//!
+//! The world keeps a `Focus` saying what deserves attention,
+//! and a `Camera` turns it into a viewport center,
+//! so every frontend follows the player the same way.
+
+use grid::{pos_to_location, Location, HEIGHT, WIDTH};
+use prelude::*;
+
+/// What the camera should be looking at.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Focus {
+    /// Follow the active player.
+    Player,
+    /// Pan ahead to where the player is travelling.
+    Travel { destination: Pos },
+    /// Jump to something that just happened.
+    Event(Pos),
+}
+
+/// A viewport over the map, measured in screen locations.
+pub struct Camera {
+    center: Location,
+    width: i32,
+    height: i32,
+}
+
+/// How close the player may get to the edge of the viewport before it scrolls.
+const MARGIN: i32 = 4;
+/// How many rows the camera moves per update while panning.
+const PAN_SPEED: i32 = 2;
+
+impl Camera {
+    /// Create a camera over the middle of the map.
+    pub fn new(width: i32, height: i32) -> Self {
+        let mut camera = Camera {
+            center: pos_to_location(grid::center()),
+            width,
+            height,
+        };
+        camera.clamp();
+        camera
+    }
+
+    /// Move the camera for the current state of a world.
+    pub fn update(&mut self, world: &World) {
+        match world.focus() {
+            Focus::Player => self.follow(pos_to_location(world.player().pos)),
+            Focus::Travel { destination } => self.pan(pos_to_location(destination)),
+            Focus::Event(pos) => self.center = pos_to_location(pos),
+        }
+        self.clamp();
+    }
+
+    pub fn center(&self) -> Location {
+        self.center
+    }
+
+    /// The width and height of the viewport.
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// The location shown in the top left corner of the viewport.
+    pub fn top_left(&self) -> Location {
+        Location {
+            x: self.center.x - self.width / 2,
+            y: self.center.y - self.height / 2,
+        }
+    }
+
+    /// Whether a position is inside the viewport.
+    pub fn shows(&self, pos: Pos) -> bool {
+        let Location { x, y } = pos_to_location(pos);
+        let top_left = self.top_left();
+        x >= top_left.x
+            && x < top_left.x + self.width
+            && y >= top_left.y
+            && y < top_left.y + self.height
+    }
+
+    /// Scroll only as far as needed to keep a location away from the edges.
+    fn follow(&mut self, target: Location) {
+        // Locations are twice as dense horizontally, so the margin is too.
+        let half_width = (self.width / 2 - MARGIN * 2).max(0);
+        let half_height = (self.height / 2 - MARGIN).max(0);
+        self.center.x = self
+            .center
+            .x
+            .clamp(target.x - half_width, target.x + half_width);
+        self.center.y = self
+            .center
+            .y
+            .clamp(target.y - half_height, target.y + half_height);
+    }
+
+    /// Move towards a location at a limited speed.
+    fn pan(&mut self, target: Location) {
+        let step = |from: i32, to: i32, speed: i32| from + (to - from).clamp(-speed, speed);
+        self.center.x = step(self.center.x, target.x, PAN_SPEED * 2);
+        self.center.y = step(self.center.y, target.y, PAN_SPEED);
+    }
+
+    /// Keep the viewport over the map, or centered on it if the map is smaller.
+    fn clamp(&mut self) {
+        let map_width = 2 * WIDTH as i32;
+        let map_height = HEIGHT as i32;
+        self.center.x = clamp_axis(self.center.x, self.width, map_width);
+        self.center.y = clamp_axis(self.center.y, self.height, map_height);
+    }
+}
+
+fn clamp_axis(center: i32, size: i32, map_size: i32) -> i32 {
+    if size >= map_size {
+        map_size / 2
+    } else {
+        center.clamp(size / 2, map_size - (size - size / 2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stays_on_map() {
+        let mut camera = Camera::new(20, 10);
+        camera.follow(pos_to_location(grid::corner()));
+        camera.clamp();
+        let top_left = camera.top_left();
+        assert_eq!((top_left.x, top_left.y), (0, 0));
+        assert!(camera.shows(grid::corner()));
+    }
+
+    #[test]
+    fn test_follow_margin() {
+        let mut camera = Camera::new(40, 20);
+        let start = camera.center();
+        let mut target = start;
+        target.x += 2;
+        camera.follow(target);
+        assert_eq!(camera.center(), start);
+        target.y += 10;
+        camera.follow(target);
+        assert_eq!(camera.center().y, target.y - (20 / 2 - MARGIN));
+    }
+}
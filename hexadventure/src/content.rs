@@ -0,0 +1,487 @@
+//! Definitions of the mobs that can appear in the game, and checks that they make sense.
+//!
+//! Content names species by the names in the `Species` registry. The
+//! default content is built from `Species` variants, so it can't name a
+//! species the game doesn't have, and `lint` checks content read from data
+//! against the registry.
+
+use level::tile::Terrain;
+use loot::Tier;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use util::StableHasher;
+use world::mob::Species;
+
+/// Everything that can be spawned.
+#[derive(Hash, Clone, Serialize, Deserialize)]
+pub struct Content {
+    pub species: Vec<SpeciesDef>,
+    pub spawns: Vec<SpawnEntry>,
+    /// What each kind of terrain looks like.
+    #[serde(default)]
+    pub terrain: Vec<TerrainDef>,
+    /// What items of each tier look like.
+    #[serde(default)]
+    pub items: Vec<ItemDef>,
+    /// Lines added to a mob's description when its state matches.
+    #[serde(default)]
+    pub mob_lines: Vec<ConditionalLine>,
+}
+
+/// The base stats of a species.
+#[derive(Hash, Clone, Serialize, Deserialize)]
+pub struct SpeciesDef {
+    pub name: String,
+    pub max_health: u32,
+    pub max_guard: u32,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// The description of a kind of terrain.
+#[derive(Hash, Clone, Serialize, Deserialize)]
+pub struct TerrainDef {
+    pub terrain: Terrain,
+    pub description: String,
+}
+
+/// The description of items of a tier.
+#[derive(Hash, Clone, Serialize, Deserialize)]
+pub struct ItemDef {
+    pub tier: Tier,
+    pub description: String,
+}
+
+/// Something about a mob's current state worth mentioning.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Condition {
+    /// It has lost some health.
+    Wounded,
+    /// It has lost more than half its health.
+    BadlyWounded,
+    /// It has no guard left.
+    GuardBroken,
+    /// It is facing away from the player examining it.
+    FacingAway,
+}
+
+/// A line of description shown only when a condition holds.
+#[derive(Hash, Clone, Serialize, Deserialize)]
+pub struct ConditionalLine {
+    pub condition: Condition,
+    pub text: String,
+}
+
+/// What the bestiary says about a species.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BestiaryEntry {
+    pub name: String,
+    pub max_health: u32,
+    pub max_guard: u32,
+    pub description: String,
+    /// The shallowest depth it spawns at, if it spawns at all.
+    pub min_depth: Option<u32>,
+}
+
+/// An entry in the table of mobs spawned with a level.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpawnEntry {
+    /// The species spawned, which goes by its name in content.
+    #[serde(with = "species_name")]
+    pub species: Species,
+    /// The shallowest depth this entry spawns at.
+    pub min_depth: u32,
+    /// How likely this entry is compared to the others.
+    pub weight: u32,
+}
+
+/// Hashes the species by name, as content names it, so the fingerprint
+/// doesn't depend on the order of the registry.
+impl Hash for SpawnEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.species.name().hash(state);
+        self.min_depth.hash(state);
+        self.weight.hash(state);
+    }
+}
+
+/// Reads and writes a species by its name in the registry, rejecting names
+/// the game has no species for.
+mod species_name {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use world::mob::Species;
+
+    pub fn serialize<S: Serializer>(species: &Species, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(species.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Species, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Species::from_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown species {}", name)))
+    }
+}
+
+/// A problem found in the content.
+#[derive(PartialEq, Eq, Debug)]
+pub enum ContentError {
+    /// Two species have the same name.
+    DuplicateSpecies(String),
+    /// A species would die as soon as it spawned.
+    ZeroHealth(String),
+    /// A spawn entry refers to a species that isn't defined.
+    UndefinedSpecies(String),
+    /// A spawn entry can never be chosen.
+    ZeroWeight(String),
+    /// Nothing can spawn at this depth.
+    NoSpawns { depth: u32 },
+    /// A species has no description.
+    Undescribed(String),
+    /// A species is defined that the game has no mobs for.
+    UnknownSpecies(String),
+    /// A species the game has mobs for isn't defined.
+    MissingSpecies(String),
+}
+
+impl Content {
+    /// Check the content for problems.
+    pub fn lint(&self) -> Result<(), Vec<ContentError>> {
+        let mut errors = Vec::new();
+        let mut names = HashSet::new();
+        for species in &self.species {
+            if !names.insert(species.name.as_str()) {
+                errors.push(ContentError::DuplicateSpecies(species.name.clone()));
+            }
+            if species.max_health == 0 {
+                errors.push(ContentError::ZeroHealth(species.name.clone()));
+            }
+            if species.description.is_empty() {
+                errors.push(ContentError::Undescribed(species.name.clone()));
+            }
+            if Species::from_name(&species.name).is_none() {
+                errors.push(ContentError::UnknownSpecies(species.name.clone()));
+            }
+        }
+        for species in Species::ALL {
+            if !names.contains(species.name()) {
+                errors.push(ContentError::MissingSpecies(species.name().to_owned()));
+            }
+        }
+        for entry in &self.spawns {
+            let name = entry.species.name();
+            if !names.contains(name) {
+                errors.push(ContentError::UndefinedSpecies(name.to_owned()));
+            }
+            if entry.weight == 0 {
+                errors.push(ContentError::ZeroWeight(name.to_owned()));
+            }
+        }
+        let spawns_at_surface = self
+            .spawns
+            .iter()
+            .any(|entry| entry.weight > 0 && entry.min_depth <= 1);
+        if !spawns_at_surface {
+            errors.push(ContentError::NoSpawns { depth: 1 });
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// A hash of the content that stays the same across runs, for telling
+    /// whether a save was made with the same content.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The definition of a species.
+    pub fn species_def(&self, species: Species) -> Option<&SpeciesDef> {
+        self.species.iter().find(|def| def.name == species.name())
+    }
+
+    /// What a kind of terrain looks like, if it is described.
+    pub fn terrain_description(&self, terrain: Terrain) -> Option<&str> {
+        self.terrain
+            .iter()
+            .find(|def| def.terrain == terrain)
+            .map(|def| def.description.as_str())
+    }
+
+    /// What an item of a tier looks like, if it is described.
+    pub fn item_description(&self, tier: Tier) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|def| def.tier == tier)
+            .map(|def| def.description.as_str())
+    }
+
+    /// Every line to add to a mob's description given the conditions that hold.
+    pub fn mob_lines<'a>(&'a self, conditions: &'a [Condition]) -> impl Iterator<Item = &'a str> {
+        self.mob_lines
+            .iter()
+            .filter(move |line| conditions.contains(&line.condition))
+            .map(|line| line.text.as_str())
+    }
+
+    /// An entry for every species that can spawn, shallowest first.
+    pub fn bestiary(&self) -> Vec<BestiaryEntry> {
+        let mut entries: Vec<_> = self
+            .species
+            .iter()
+            .map(|species| BestiaryEntry {
+                name: species.name.clone(),
+                max_health: species.max_health,
+                max_guard: species.max_guard,
+                description: species.description.clone(),
+                min_depth: self
+                    .spawns
+                    .iter()
+                    .filter(|entry| entry.species.name() == species.name && entry.weight > 0)
+                    .map(|entry| entry.min_depth)
+                    .min(),
+            })
+            .filter(|entry| entry.min_depth.is_some())
+            .collect();
+        entries.sort_by_key(|entry| entry.min_depth);
+        entries
+    }
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Content {
+            species: vec![
+                species(
+                    Species::Hero,
+                    100,
+                    100,
+                    "A wanderer come to the caves in search of fortune.",
+                ),
+                species(
+                    Species::Skeleton,
+                    100,
+                    100,
+                    "The restless bones of an earlier adventurer.",
+                ),
+                species(
+                    Species::Spider,
+                    100,
+                    100,
+                    "A pale, long-legged spider that strings webs across the tunnels.",
+                ),
+                species(
+                    Species::Archer,
+                    100,
+                    100,
+                    "A skeleton with a bow, keeping its distance.",
+                ),
+                species(
+                    Species::Thief,
+                    100,
+                    100,
+                    "A quick-fingered shadow that grabs what it can and runs.",
+                ),
+                species(
+                    Species::Necromancer,
+                    100,
+                    100,
+                    "A robed figure who calls the fallen back to their feet.",
+                ),
+                species(
+                    Species::Behemoth,
+                    100,
+                    100,
+                    "A hulking thing that fills the tunnels it crawls through.",
+                ),
+                species(
+                    Species::Serpent,
+                    100,
+                    100,
+                    "A scaled coil as long as three men, dragging itself head first.",
+                ),
+            ],
+            spawns: vec![
+                spawn(Species::Skeleton, 1, 4),
+                spawn(Species::Archer, 2, 2),
+                spawn(Species::Thief, 2, 1),
+                spawn(Species::Spider, 3, 2),
+                spawn(Species::Necromancer, 5, 1),
+            ],
+            terrain: vec![
+                terrain(Terrain::Wall, "Solid rock."),
+                terrain(Terrain::Floor, "Bare cave floor."),
+                terrain(Terrain::ShortGrass, "Pale grass growing in the dark."),
+                terrain(Terrain::TallGrass, "Grass tall enough to hide in."),
+                terrain(Terrain::Brownberry, "A bush heavy with brown berries."),
+                terrain(Terrain::Exit, "Stairs leading further down."),
+                terrain(Terrain::Entrance, "Stairs leading back up."),
+                terrain(Terrain::Water, "Still, black water."),
+                terrain(Terrain::ShallowWater, "Cold water, ankle deep."),
+                terrain(Terrain::Web, "Sticky strands strung between the rocks."),
+                terrain(Terrain::Door, "A heavy wooden door, shut."),
+                terrain(Terrain::OpenDoor, "A heavy wooden door, standing open."),
+                terrain(Terrain::BrokenDoor, "Splintered planks on bent hinges."),
+                terrain(Terrain::Portal, "A ring of shimmering air."),
+            ],
+            items: vec![
+                item(Tier::Common, "Something plain and serviceable."),
+                item(Tier::Uncommon, "Something of fine make."),
+                item(Tier::Rare, "Something that hums faintly with power."),
+                item(Tier::Artifact, "Something ancient and glowing."),
+            ],
+            mob_lines: vec![
+                line(Condition::Wounded, "It looks wounded."),
+                line(Condition::BadlyWounded, "It is barely standing."),
+                line(Condition::GuardBroken, "Its guard is down."),
+                line(Condition::FacingAway, "It hasn't noticed you."),
+            ],
+        }
+    }
+}
+
+fn species(species: Species, max_health: u32, max_guard: u32, description: &str) -> SpeciesDef {
+    SpeciesDef {
+        name: species.name().to_owned(),
+        max_health,
+        max_guard,
+        description: description.to_owned(),
+    }
+}
+
+fn spawn(species: Species, min_depth: u32, weight: u32) -> SpawnEntry {
+    SpawnEntry {
+        species,
+        min_depth,
+        weight,
+    }
+}
+
+fn terrain(terrain: Terrain, description: &str) -> TerrainDef {
+    TerrainDef {
+        terrain,
+        description: description.to_owned(),
+    }
+}
+
+fn item(tier: Tier, description: &str) -> ItemDef {
+    ItemDef {
+        tier,
+        description: description.to_owned(),
+    }
+}
+
+fn line(condition: Condition, text: &str) -> ConditionalLine {
+    ConditionalLine {
+        condition,
+        text: text.to_owned(),
+    }
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContentError::DuplicateSpecies(name) => write!(f, "species {} is defined twice", name),
+            ContentError::ZeroHealth(name) => write!(f, "species {} has no health", name),
+            ContentError::UndefinedSpecies(name) => {
+                write!(f, "spawn table refers to undefined species {}", name)
+            }
+            ContentError::ZeroWeight(name) => write!(f, "spawn entry for {} has no weight", name),
+            ContentError::NoSpawns { depth } => write!(f, "nothing spawns at depth {}", depth),
+            ContentError::Undescribed(name) => write!(f, "species {} has no description", name),
+            ContentError::UnknownSpecies(name) => write!(f, "species {} is not in the game", name),
+            ContentError::MissingSpecies(name) => write!(f, "species {} is not defined", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bincode;
+
+    #[test]
+    fn test_default_content_valid() {
+        assert_eq!(Content::default().lint(), Ok(()));
+    }
+
+    #[test]
+    fn test_lint_errors() {
+        let mut content = Content::default();
+        content.species[1].max_health = 0;
+        content.species.retain(|def| def.name != "behemoth");
+        content.spawns.push(SpawnEntry {
+            species: Species::Behemoth,
+            min_depth: 3,
+            weight: 0,
+        });
+        let errors = content.lint().unwrap_err();
+        assert!(errors.contains(&ContentError::ZeroHealth("skeleton".to_owned())));
+        assert!(errors.contains(&ContentError::UndefinedSpecies("behemoth".to_owned())));
+        assert!(errors.contains(&ContentError::ZeroWeight("behemoth".to_owned())));
+    }
+
+    #[test]
+    fn test_spawn_entries_name_species() {
+        let entry = spawn(Species::Spider, 3, 1);
+        let bytes = bincode::serialize(&entry).unwrap();
+        let named: (String, u32, u32) = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(named, ("spider".to_owned(), 3, 1));
+        let read: SpawnEntry = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(read.species, Species::Spider);
+
+        let goblin = bincode::serialize(&("goblin", 3, 1)).unwrap();
+        assert!(bincode::deserialize::<SpawnEntry>(&goblin).is_err());
+    }
+
+    #[test]
+    fn test_lint_checks_species_against_registry() {
+        let mut content = Content::default();
+        content.species.retain(|def| def.name != "behemoth");
+        content.species.push(SpeciesDef {
+            name: "goblin".to_owned(),
+            max_health: 100,
+            max_guard: 100,
+            description: "A goblin.".to_owned(),
+        });
+        let errors = content.lint().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ContentError::UnknownSpecies("goblin".to_owned()),
+                ContentError::MissingSpecies("behemoth".to_owned()),
+            ]
+        );
+        assert!(content.species_def(Species::Behemoth).is_none());
+        assert_eq!(content.species_def(Species::Spider).unwrap().name, "spider");
+    }
+
+    #[test]
+    fn test_bestiary_lists_spawning_species() {
+        let bestiary = Content::default().bestiary();
+        assert_eq!(bestiary.len(), 5);
+        assert_eq!(bestiary[0].name, "skeleton");
+        assert_eq!(bestiary[0].min_depth, Some(1));
+        assert!(!bestiary[0].description.is_empty());
+        assert_eq!(bestiary[1].name, "archer");
+        assert_eq!(bestiary[1].min_depth, Some(2));
+        assert_eq!(bestiary[2].name, "thief");
+        assert_eq!(bestiary[3].name, "spider");
+        assert_eq!(bestiary[3].min_depth, Some(3));
+        assert_eq!(bestiary[4].name, "necromancer");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let mut content = Content::default();
+        assert_eq!(content.fingerprint(), Content::default().fingerprint());
+        content.species[1].max_guard += 1;
+        assert_ne!(content.fingerprint(), Content::default().fingerprint());
+    }
+}
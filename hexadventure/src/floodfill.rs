@@ -1,8 +1,80 @@
 //! Function for performing an efficient floodfill.
+//!
+//! `Regions` floods a whole grid at once, so whether two positions are
+//! connected can be asked again and again without searching each time.
 
-use grid::{Direction, Pos, DIRECTIONS};
+use grid::{self, Direction, Grid, Pos, DIRECTIONS};
 use std::collections::HashSet;
 
+/// Identifies one of the connected regions of a grid.
+pub type RegionId = u32;
+
+/// Every passable position of the grid, labeled by the region it is connected to.
+pub struct Regions {
+    labels: Grid<Option<RegionId>>,
+    sizes: Vec<usize>,
+}
+
+impl Regions {
+    /// Label the grid, flooding each region in turn.
+    pub fn new<F>(passable: F) -> Self
+    where
+        F: Fn(Pos) -> bool,
+    {
+        let mut labels = Grid::new(|_| None);
+        let mut sizes = Vec::new();
+        for pos in grid::positions() {
+            if labels[pos].is_some() || !passable(pos) {
+                continue;
+            }
+            let region = sizes.len() as RegionId;
+            let flooded = reachable_from(pos, &passable);
+            for &pos in &flooded {
+                labels[pos] = Some(region);
+            }
+            sizes.push(flooded.len());
+        }
+        Regions { labels, sizes }
+    }
+
+    /// The region a position is in, or `None` if it isn't passable.
+    pub fn region(&self, pos: Pos) -> Option<RegionId> {
+        if grid::contains(pos) {
+            self.labels[pos]
+        } else {
+            None
+        }
+    }
+
+    /// Whether one position can be reached from another.
+    pub fn connected(&self, a: Pos, b: Pos) -> bool {
+        self.region(a).is_some() && self.region(a) == self.region(b)
+    }
+
+    /// How many regions there are.
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// How many positions a region has.
+    pub fn size(&self, region: RegionId) -> usize {
+        self.sizes[region as usize]
+    }
+
+    /// Every position of a region.
+    pub fn positions(&self, region: RegionId) -> impl Iterator<Item = Pos> + '_ {
+        grid::positions().filter(move |&pos| self.labels[pos] == Some(region))
+    }
+}
+
+/// Every passable position of the grid connected to the origin.
+pub fn reachable_from<F>(origin: Pos, passable: F) -> HashSet<Pos>
+where
+    F: Fn(Pos) -> bool,
+{
+    flood(origin, |pos| grid::contains(pos) && passable(pos))
+}
+
 /// Performs a floodfill starting at origin.
 ///
 /// Positions are flooded if they are connected to the origin and floodable(pos) returns true.
@@ -73,7 +145,6 @@ fn recur<F>(
 mod tests {
     use super::*;
 
-    use grid::{self, Grid};
     use rand::{thread_rng, Rng};
 
     /// Naive recursive floodfill used to compare against the scanline floodfill.
@@ -115,4 +186,24 @@ mod tests {
             assert!(set_equiv(&normal_set, &basic_set));
         }
     }
+
+    #[test]
+    fn test_regions_split_by_wall() {
+        let center = grid::center();
+        let wall = |pos: Pos| pos.distance(center) == 3;
+        let regions = Regions::new(|pos| !wall(pos));
+        assert_eq!(regions.count(), 2);
+        let inside = regions.region(center).unwrap();
+        assert_eq!(regions.size(inside), 1 + 6 + 12);
+        assert!(regions
+            .positions(inside)
+            .all(|pos| pos.distance(center) < 3));
+        assert!(regions.connected(center, center + Direction::East * 2));
+        assert!(!regions.connected(center, center + Direction::East * 4));
+        assert_eq!(regions.region(center + Direction::East * 3), None);
+        assert_eq!(
+            reachable_from(center, |pos| !wall(pos)).len(),
+            regions.size(inside)
+        );
+    }
 }
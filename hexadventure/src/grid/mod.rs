@@ -5,6 +5,31 @@
 //! The x-axis (of the hex grid, not of the 3d space) points in `Direction::Southeast`,
 //! which is the vector {1, 0, -1} in 3d space.
 //! The y-axis points in `Direction::Southwest`, which is the vector {0, 1, -1}.
+//!
+//! Positions are stepped between with `Direction`s and `Displacement`s, and
+//! a `Grid` holds a value for every position of the level:
+//!
+//! ```
+//! use hexadventure::grid::{self, Direction, Grid};
+//!
+//! let center = grid::center();
+//! let east = center + Direction::East * 2;
+//! assert_eq!(center.distance(east), 2);
+//! assert_eq!((east - center).direction(), Direction::East);
+//!
+//! let mut visits: Grid<u32> = Grid::new(|_| 0);
+//! visits[east] += 1;
+//! assert_eq!(grid::positions().filter(|&pos| visits[pos] > 0).count(), 1);
+//! ```
+//!
+//! # Stability
+//!
+//! Frontends, mods and bots build on this module, so everything public in
+//! it stays source compatible until the version of the crate changes
+//! incompatibly. Only the size of the grid may change between versions, so
+//! code should go by `WIDTH`, `HEIGHT` and `contains` rather than numbers.
+//! `Direction` will always have exactly its six variants, so it can be
+//! matched on exhaustively.
 
 // This isn't generic over size yet because associated constants aren't stable
 
@@ -12,7 +37,16 @@ use line::Line;
 use std::iter::FromIterator;
 
 mod ops;
+pub mod rle;
 
+/// Every direction, clockwise from northeast.
+///
+/// ```
+/// use hexadventure::grid::{Direction, DIRECTIONS};
+///
+/// assert_eq!(DIRECTIONS[0], Direction::Northeast);
+/// assert_eq!(DIRECTIONS[1], DIRECTIONS[0].rotate(1));
+/// ```
 pub const DIRECTIONS: [Direction; 6] = [
     Direction::Northeast,
     Direction::East,
@@ -22,10 +56,29 @@ pub const DIRECTIONS: [Direction; 6] = [
     Direction::Northwest,
 ];
 
+/// How many positions each row of the grid has.
 pub const WIDTH: usize = 34;
+/// How many rows the grid has.
 pub const HEIGHT: usize = 26;
 
-#[derive(Serialize, Deserialize)]
+/// A value for every position of the grid, indexed by `Pos`.
+///
+/// ```
+/// use hexadventure::grid::{self, Grid, HEIGHT, WIDTH};
+///
+/// let mut seen: Grid<bool> = Grid::new(|pos| pos == grid::center());
+/// assert!(seen[grid::center()]);
+/// seen[grid::corner()] = true;
+/// assert_eq!(seen.iter().filter(|&&seen| seen).count(), 2);
+///
+/// let copied: Grid<bool> = seen.iter().cloned().collect();
+/// assert!(copied == seen);
+/// assert_eq!(copied.iter().count(), WIDTH * HEIGHT);
+/// ```
+///
+/// Indexing with a position outside the grid panics, so check `contains`
+/// first for positions that may be off the edge.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Grid<T>(Box<[T]>);
 
 /// A 2d index of a hexagonal grid.
@@ -38,12 +91,38 @@ struct Index2d {
 }
 
 /// A position on a hexagonal grid in axial coordinates.
+///
+/// Positions come from the functions of this module, such as `center` and
+/// `offset_to_pos`, and from stepping from other positions. They can lie
+/// outside the grid.
+///
+/// ```
+/// use hexadventure::grid::{self, Direction};
+///
+/// let pos = grid::offset_to_pos(3, 4);
+/// assert_eq!(grid::pos_to_offset(pos), Some((3, 4)));
+/// assert_eq!(pos + Direction::East - Direction::East, pos);
+/// assert!(!grid::contains(grid::corner() + Direction::West));
+/// ```
 #[derive(PartialEq, Eq, Debug, Copy, Clone, Hash, Serialize, Deserialize)]
 pub struct Pos<T = i32> {
     x: T,
     y: T,
 }
 
+/// The difference between two positions.
+///
+/// ```
+/// use hexadventure::grid::{self, Direction, Displacement};
+///
+/// let a = grid::center();
+/// let b = a + Direction::East * 2 + Direction::Southeast;
+/// let displacement: Displacement = b - a;
+/// assert_eq!(a + displacement, b);
+/// assert_eq!(displacement.distance(), 3);
+/// assert_eq!(displacement * 2, Direction::East * 4 + Direction::Southeast * 2);
+/// assert_eq!(-displacement + displacement, Direction::East * 0);
+/// ```
 #[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Displacement<T = i32> {
     x: T,
@@ -51,11 +130,20 @@ pub struct Displacement<T = i32> {
 }
 
 /// The location of a position as shown on screen.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub struct Location {
     pub x: i32,
     pub y: i32,
 }
 
+/// One of the six ways from a position to its neighbors.
+///
+/// ```
+/// use hexadventure::grid::Direction;
+///
+/// assert_eq!(-Direction::East, Direction::West);
+/// assert_eq!(Direction::East.rotate(2), Direction::Southwest);
+/// ```
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum Direction {
     Southeast,
@@ -67,16 +155,40 @@ pub enum Direction {
 }
 
 impl Pos {
+    /// The six positions next to this one, clockwise from northeast.
+    ///
+    /// Some of them may be outside the grid.
+    ///
+    /// ```
+    /// use hexadventure::grid::{self, Direction};
+    ///
+    /// let center = grid::center();
+    /// let neighbors: Vec<_> = center.neighbors().collect();
+    /// assert_eq!(neighbors.len(), 6);
+    /// assert_eq!(neighbors[1], center + Direction::East);
+    /// assert!(neighbors.iter().all(|&pos| pos.distance(center) == 1));
+    /// ```
     pub fn neighbors(self) -> impl Iterator<Item = Pos> {
         DIRECTIONS
             .into_iter()
             .map(move |&direction| self + direction)
     }
 
+    /// How many steps apart two positions are.
+    ///
+    /// ```
+    /// use hexadventure::grid::{self, Direction};
+    ///
+    /// let a = grid::center();
+    /// assert_eq!(a.distance(a + Direction::East * 2 + Direction::Southeast), 3);
+    /// assert_eq!(a.distance(a + Direction::East + Direction::West), 0);
+    /// ```
     pub fn distance(self, other: Pos) -> u32 {
         (other - self).distance()
     }
 
+    /// The straight line from this position to another, which can be
+    /// iterated over, as `line` does.
     pub fn to(self, target: Pos) -> Line {
         Line {
             start: self,
@@ -84,6 +196,17 @@ impl Pos {
         }
     }
 
+    /// The positions exactly `size` steps away, going clockwise, of which
+    /// there are six times `size`.
+    ///
+    /// ```
+    /// use hexadventure::grid;
+    ///
+    /// let center = grid::center();
+    /// assert_eq!(center.ring(3).count(), 18);
+    /// assert!(center.ring(3).all(|pos| pos.distance(center) == 3));
+    /// assert_eq!(center.ring(0).count(), 0);
+    /// ```
     pub fn ring(self, size: u32) -> impl Iterator<Item = Pos> {
         (0..size * 6).map(move |i| {
             let major = i / size;
@@ -96,10 +219,19 @@ impl Pos {
 }
 
 impl Displacement {
+    /// How many steps the displacement takes.
     pub fn distance(self) -> u32 {
         (self.x.abs() + self.y.abs() + (self.x + self.y).abs()) as u32 / 2u32
     }
 
+    /// The direction closest to the way the displacement points.
+    ///
+    /// ```
+    /// use hexadventure::grid::{Direction, Displacement};
+    ///
+    /// let displacement: Displacement = Direction::East * 3 + Direction::Southeast;
+    /// assert_eq!(displacement.direction(), Direction::East);
+    /// ```
     pub fn direction(self) -> Direction {
         let a = (self.x - self.y).abs();
         let b = (2 * self.y + self.x).abs();
@@ -126,34 +258,51 @@ impl Displacement {
     }
 }
 
-impl Displacement<f32> {
-    pub fn round(self) -> Displacement {
-        let x_int = self.x.round();
-        let y_int = self.y.round();
-        let xy_int = (self.x + self.y).round();
-        let dx = (self.x - x_int).abs();
-        let dy = (self.y - y_int).abs();
-        let dxy = self.x + self.y - xy_int.abs();
-        if dx > dy && dx > dxy {
-            Displacement {
-                x: (xy_int - y_int) as i32,
-                y: y_int as i32,
-            }
+impl Displacement {
+    /// This displacement scaled by `numerator / denominator`, rounded to the nearest hex.
+    ///
+    /// Uses only integer math, so it rounds the same way on every platform.
+    pub fn scale(self, numerator: u32, denominator: u32) -> Displacement {
+        if denominator == 0 {
+            panic!("attempt to divide by zero");
+        }
+        let (numerator, denominator) = (i64::from(numerator), i64::from(denominator));
+        let x = i64::from(self.x) * numerator;
+        let y = i64::from(self.y) * numerator;
+        let xy = x + y;
+        let x_int = round_div(x, denominator);
+        let y_int = round_div(y, denominator);
+        let xy_int = round_div(xy, denominator);
+        // How far each coordinate was rounded, in units of 1 / denominator.
+        let dx = (x - x_int * denominator).abs();
+        let dy = (y - y_int * denominator).abs();
+        let dxy = (xy - xy_int * denominator).abs();
+        let (x, y) = if dx > dy && dx > dxy {
+            (xy_int - y_int, y_int)
         } else if dy > dxy {
-            Displacement {
-                x: x_int as i32,
-                y: (xy_int - x_int) as i32,
-            }
+            (x_int, xy_int - x_int)
         } else {
-            Displacement {
-                x: x_int as i32,
-                y: y_int as i32,
-            }
+            (x_int, y_int)
+        };
+        Displacement {
+            x: x as i32,
+            y: y as i32,
         }
     }
 }
 
+/// Divide, rounding to the nearest whole number and halves away from zero.
+fn round_div(numerator: i64, denominator: i64) -> i64 {
+    let rounded = (2 * numerator.abs() + denominator) / (2 * denominator);
+    if numerator < 0 {
+        -rounded
+    } else {
+        rounded
+    }
+}
+
 impl Direction {
+    /// One step this way.
     pub fn to_displacement(self) -> Displacement {
         match self {
             Direction::Southeast => Displacement { x: 1, y: 0 },
@@ -165,6 +314,17 @@ impl Direction {
         }
     }
 
+    /// The direction `n` sixths of a turn clockwise from this one, or
+    /// counterclockwise if `n` is negative.
+    ///
+    /// ```
+    /// use hexadventure::grid::Direction;
+    ///
+    /// assert_eq!(Direction::East.rotate(1), Direction::Southeast);
+    /// assert_eq!(Direction::East.rotate(-1), Direction::Northeast);
+    /// assert_eq!(Direction::East.rotate(3), Direction::West);
+    /// assert_eq!(Direction::East.rotate(6), Direction::East);
+    /// ```
     pub fn rotate(self, n: i32) -> Direction {
         let index = n + match self {
             Direction::Northeast => 0,
@@ -178,17 +338,28 @@ impl Direction {
         DIRECTIONS[corrected_index as usize]
     }
 
+    /// How far one step this way goes along the x-axis.
     pub fn x(self) -> i32 {
         self.to_displacement().x
     }
 
+    /// How far one step this way goes along the y-axis.
     pub fn y(self) -> i32 {
         self.to_displacement().y
     }
 }
 
+/// How many steps in each of two directions add up to a displacement.
+///
 /// This will give the wrong answer if dir1 + dir2 = 0,
 /// since it doesn't calculate the determinant.
+///
+/// ```
+/// use hexadventure::grid::{decompose, Direction};
+///
+/// let displacement = Direction::East * 3 + Direction::Southeast * 2;
+/// assert_eq!(decompose(displacement, Direction::East, Direction::Southeast), (3, 2));
+/// ```
 pub fn decompose(displacement: Displacement, dir1: Direction, dir2: Direction) -> (i32, i32) {
     let a = dir2.y();
     let b = -dir2.x();
@@ -220,10 +391,12 @@ impl<T> Grid<T> {
         Grid(Box::from(grid))
     }
 
+    /// Every value, in the order of `positions`.
     pub fn iter(&self) -> ::std::slice::Iter<T> {
         self.0.iter()
     }
 
+    /// Every value, in the order of `positions`.
     pub fn iter_mut(&mut self) -> ::std::slice::IterMut<T> {
         self.0.iter_mut()
     }
@@ -243,6 +416,14 @@ pub fn corner() -> Pos {
 }
 
 /// Turn a position in a grid into a location.
+///
+/// ```
+/// use hexadventure::grid::{self, Direction};
+///
+/// let center = grid::pos_to_location(grid::center());
+/// let east = grid::pos_to_location(grid::center() + Direction::East);
+/// assert_eq!((east.x - center.x, east.y - center.y), (2, 0));
+/// ```
 pub fn pos_to_location(pos: Pos) -> Location {
     let Index2d { row, col } = pos_to_index(pos);
     Location {
@@ -257,6 +438,27 @@ pub fn contains(pos: Pos) -> bool {
     col < WIDTH && row < HEIGHT
 }
 
+/// The position at a row and column of a grid laid out like this one, but of any size.
+pub fn offset_to_pos(row: usize, col: usize) -> Pos {
+    index_to_pos(Index2d { row, col })
+}
+
+/// The row and column of a position in a grid laid out like this one, but of any size.
+///
+/// `None` if the position is above the first row or left of the first column.
+pub fn pos_to_offset(pos: Pos) -> Option<(usize, usize)> {
+    let row = pos.x + pos.y;
+    if row < 0 {
+        return None;
+    }
+    let col = pos.x - row_first_x(row as usize);
+    if col < 0 {
+        return None;
+    }
+    Some((row as usize, col as usize))
+}
+
+/// Every position of the grid that isn't on its edge, row by row.
 pub fn inner_positions() -> impl Iterator<Item = Pos> {
     let inner_width = WIDTH - 2;
     let inner_height = HEIGHT - 2;
@@ -270,6 +472,54 @@ pub fn inner_positions() -> impl Iterator<Item = Pos> {
     })
 }
 
+/// The positions along a straight line from `a` to `b`, both included.
+///
+/// Each is the hex nearest to the point as far along the line in cube
+/// coordinates, so every step is to a neighbor.
+///
+/// ```
+/// use hexadventure::grid::{self, Direction};
+///
+/// let a = grid::center();
+/// let b = a + Direction::East * 4 + Direction::Southeast * 2;
+/// let line: Vec<_> = grid::line(a, b).collect();
+/// assert_eq!(line.len(), 7);
+/// assert_eq!((line[0], line[6]), (a, b));
+/// assert!(line.windows(2).all(|step| step[0].distance(step[1]) == 1));
+/// ```
+pub fn line(a: Pos, b: Pos) -> impl Iterator<Item = Pos> {
+    a.to(b).into_iter()
+}
+
+/// Whether `b` is in sight of `a`, with every position between them transparent.
+///
+/// Neither end is checked, so a mob in a wall can still be seen.
+///
+/// ```
+/// use hexadventure::grid::{self, Direction};
+///
+/// let a = grid::center();
+/// let wall = a + Direction::East * 2;
+/// assert!(!grid::los(a, a + Direction::East * 4, |pos| pos != wall));
+/// assert!(grid::los(a, wall, |pos| pos != wall));
+/// ```
+pub fn los<FT: Fn(Pos) -> bool>(a: Pos, b: Pos, transparent: FT) -> bool {
+    line(a, b)
+        .skip(1)
+        .take_while(|&pos| pos != b)
+        .all(transparent)
+}
+
+/// Every position of the grid, row by row, in the order `Grid` keeps its values.
+///
+/// ```
+/// use hexadventure::grid::{self, Grid};
+///
+/// let offsets: Grid<Option<(usize, usize)>> = Grid::new(grid::pos_to_offset);
+/// for (pos, &offset) in grid::positions().zip(offsets.iter()) {
+///     assert_eq!(grid::pos_to_offset(pos), offset);
+/// }
+/// ```
 pub fn positions() -> impl Iterator<Item = Pos> {
     (0..HEIGHT).flat_map(move |row| (0..WIDTH).map(move |col| index_to_pos(Index2d { row, col })))
 }
@@ -350,6 +600,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_offsets_beyond_grid() {
+        assert_eq!(pos_to_offset(offset_to_pos(300, 500)), Some((300, 500)));
+        assert_eq!(pos_to_offset(corner() + Direction::West), None);
+        assert_eq!(pos_to_offset(corner() + Direction::Northeast), None);
+    }
+
     #[test]
     fn test_index_order() {
         let g = Grid::new(|pos| pos_to_index(pos));
@@ -363,6 +620,27 @@ mod tests {
         contains(pos) && pos.neighbors().any(|pos| !contains(pos))
     }
 
+    #[test]
+    fn test_line_steps_between_neighbors() {
+        let a = center();
+        let b = a + Direction::East * 3 + Direction::Southeast * 2;
+        let steps: Vec<Pos> = line(a, b).collect();
+        assert_eq!(steps.len(), a.distance(b) as usize + 1);
+        assert_eq!((steps[0], steps[steps.len() - 1]), (a, b));
+        assert!(steps.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+        assert_eq!(line(a, a).collect::<Vec<_>>(), vec![a]);
+    }
+
+    #[test]
+    fn test_los_ignores_the_ends() {
+        let a = center();
+        let b = a + Direction::West * 4;
+        let wall = a + Direction::West * 2;
+        assert!(los(a, b, |pos| pos != a && pos != b));
+        assert!(!los(a, b, |pos| pos != wall));
+        assert!(los(a, a + Direction::West, |_| false));
+    }
+
     #[test]
     fn test_inner_positions() {
         let mut positions = inner_positions();
@@ -123,20 +123,6 @@ impl ops::Mul<u32> for Displacement {
     }
 }
 
-impl ops::Div<u32> for Displacement {
-    type Output = Displacement<f32>;
-
-    fn div(self, rhs: u32) -> Displacement<f32> {
-        if rhs == 0 {
-            panic!("attempt to divide by zero");
-        }
-        Displacement {
-            x: self.x as f32 / rhs as f32,
-            y: self.y as f32 / rhs as f32,
-        }
-    }
-}
-
 impl ops::Neg for Displacement {
     type Output = Displacement;
 
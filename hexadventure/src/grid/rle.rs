@@ -0,0 +1,88 @@
+//! A compact serde representation of grids as runs of equal values.
+//!
+//! Levels are mostly long stretches of wall and floor, so a grid is stored
+//! as a list of `(length, value)` runs in row order. A whole grid has fewer
+//! than 65536 tiles, so lengths fit in a `u16`. Use it on a field with
+//! `#[serde(with = "grid::rle")]`.
+//!
+//! Saves and the protocol both store grids this way, so changing it means
+//! bumping both `save::SAVE_VERSION` and `protocol::PROTOCOL_VERSION`.
+
+use super::{Grid, HEIGHT, WIDTH};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<T, S>(grid: &Grid<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + PartialEq,
+    S: Serializer,
+{
+    let mut runs: Vec<(u16, &T)> = Vec::new();
+    for value in grid.iter() {
+        match runs.last_mut() {
+            Some(&mut (ref mut length, last)) if last == value => *length += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs.serialize(serializer)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Grid<T>, D::Error>
+where
+    T: Deserialize<'de> + Clone,
+    D: Deserializer<'de>,
+{
+    let runs: Vec<(u16, T)> = Vec::deserialize(deserializer)?;
+    let total: u64 = runs.iter().map(|&(length, _)| u64::from(length)).sum();
+    if total != (WIDTH * HEIGHT) as u64 {
+        return Err(D::Error::custom(format!(
+            "runs cover {} tiles instead of {}",
+            total,
+            WIDTH * HEIGHT
+        )));
+    }
+    let mut values = Vec::with_capacity(WIDTH * HEIGHT);
+    for (length, value) in runs {
+        values.extend((0..length).map(|_| value.clone()));
+    }
+    Ok(values.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bincode;
+    use grid;
+    use level::tile::Terrain;
+    #[cfg(feature = "game")]
+    use level::GenParams;
+    #[cfg(feature = "game")]
+    use world::World;
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "grid::rle")] Grid<Terrain>);
+
+    #[cfg(feature = "game")]
+    #[test]
+    fn test_round_trip_is_compact() {
+        let world = World::from_seed(28, GenParams::default());
+        let terrain = Grid::new(|pos| world.level[pos].terrain);
+        let plain = bincode::serialize(&terrain).unwrap();
+        let encoded = bincode::serialize(&Wrapper(terrain.clone())).unwrap();
+        assert!(encoded.len() * 2 < plain.len());
+        let decoded: Wrapper = bincode::deserialize(&encoded).unwrap();
+        assert!(decoded.0 == terrain);
+
+        let saved = bincode::serialize(&world).unwrap();
+        let loaded: World = bincode::deserialize(&saved).unwrap();
+        assert_eq!(loaded.state_hash(), world.state_hash());
+    }
+
+    #[test]
+    fn test_wrong_length_rejected() {
+        let runs: Vec<(u16, Terrain)> = vec![(10, Terrain::Wall), (20, Terrain::Floor)];
+        let bytes = bincode::serialize(&runs).unwrap();
+        assert!(bincode::deserialize::<Wrapper>(&bytes).is_err());
+    }
+}
@@ -0,0 +1,560 @@
+//! Generating the levels of a run.
+
+use super::basic;
+use super::fallback;
+use super::params::GenParams;
+use super::pass::{self, GenError, GenPass, GenProgress, LevelPlan, Stage};
+use super::populate::{populate, SPAWN_COUNT};
+use super::portal::add_portals;
+use super::tile::{Terrain, Tile};
+use content::{Content, SpawnEntry};
+use noise::Noise;
+use prelude::*;
+use rand::{IsaacRng, Rng};
+use std::mem::{replace, take};
+use std::sync::mpsc::Sender;
+use util::mix_seed;
+use world::mob::Npcs;
+
+/// Responsible for generating levels.
+///
+/// Each level is generated with its own seed derived from the master seed,
+/// so that how much randomness one level uses doesn't change the next.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Architect {
+    seed: u64,
+    params: GenParams,
+    /// What spawns, and with what stats.
+    content: Content,
+    depth: u32,
+    #[serde(with = "grid::rle")]
+    next_level: Grid<Terrain>,
+    /// The depths of the levels that fell back to a simple layout.
+    fallback_depths: Vec<u32>,
+    /// How many more or fewer mobs each level spawned with, starting at depth 1.
+    spawn_adjustments: Vec<i32>,
+    /// The adjustment to use for the next level.
+    next_spawn_adjustment: i32,
+    /// The name of each pass registered on top of the builtin ones, and the
+    /// first depth it was run on, in the order they were registered.
+    registered_passes: Vec<(String, u32)>,
+    /// The passes registered on top of the builtin ones, each with the first
+    /// depth it is run on.
+    ///
+    /// These aren't saved, so they must be registered again after loading.
+    #[serde(skip)]
+    custom_passes: Vec<(u32, Box<dyn GenPass>)>,
+    /// Where to report progress, if anywhere. Not saved either.
+    #[serde(skip)]
+    progress: Option<Sender<GenProgress>>,
+}
+
+impl Architect {
+    /// An architect that spawns mobs from the given content.
+    pub fn new(seed: u64, params: GenParams, content: Content) -> Self {
+        let mut rng = IsaacRng::new_from_u64(level_seed(seed, 0));
+        let next_level = basic::generate_with(&params.smoothing, &mut rng)
+            .iter()
+            .map(|&t| Terrain::from(t))
+            .collect();
+        Architect {
+            seed,
+            params,
+            content,
+            depth: 0,
+            next_level,
+            fallback_depths: Vec::new(),
+            spawn_adjustments: Vec::new(),
+            next_spawn_adjustment: 0,
+            registered_passes: Vec::new(),
+            custom_passes: Vec::new(),
+            progress: None,
+        }
+    }
+
+    /// The content levels are populated from.
+    pub fn content(&self) -> &Content {
+        &self.content
+    }
+
+    /// The depth of the most recently generated level.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// The seed used to generate the level at a given depth.
+    pub fn level_seed(&self, depth: u32) -> u64 {
+        level_seed(self.seed, depth)
+    }
+
+    /// The depths of the levels that couldn't be generated within the budget.
+    pub fn fallback_depths(&self) -> &[u32] {
+        &self.fallback_depths
+    }
+
+    /// Spawn more or fewer mobs than usual on the next level generated.
+    pub fn adjust_next_spawns(&mut self, adjustment: i32) {
+        self.next_spawn_adjustment = adjustment;
+    }
+
+    /// Add a pass to the pipeline of every level generated from now on.
+    ///
+    /// A pass registered again after loading, under the name of one
+    /// registered before saving, takes its place, so it is run on the same
+    /// depths when levels are regenerated.
+    pub fn register_pass(&mut self, pass: Box<dyn GenPass>) {
+        let name = pass.name().to_owned();
+        let live = self
+            .custom_passes
+            .iter()
+            .filter(|(_, live)| live.name() == name)
+            .count();
+        let earlier = self
+            .registered_passes
+            .iter()
+            .filter(|(registered, _)| *registered == name)
+            .nth(live);
+        let first_depth = match earlier {
+            Some(&(_, first_depth)) => first_depth,
+            None => {
+                self.registered_passes.push((name, self.depth + 1));
+                self.depth + 1
+            }
+        };
+        self.custom_passes.push((first_depth, pass));
+    }
+
+    /// Report the progress of every level generated from now on.
+    pub fn report_progress(&mut self, progress: Sender<GenProgress>) {
+        self.progress = Some(progress);
+    }
+
+    pub fn generate(&mut self) -> (Grid<Tile>, Npcs) {
+        let custom_passes = take(&mut self.custom_passes);
+        let level = self.generate_with(&custom_passes);
+        self.custom_passes = custom_passes;
+        level
+    }
+
+    /// Generate the level at a given depth exactly as it was generated in this run.
+    ///
+    /// The stairs of each level depend on the level above,
+    /// so the levels above are regenerated as well, each with the custom
+    /// passes it was generated with.
+    /// `None` for depth 0, for depths this run hasn't generated yet, and
+    /// when a pass registered before saving hasn't been registered again.
+    pub fn regenerate_level(&self, depth: u32) -> Option<(Grid<Tile>, Npcs)> {
+        if self.custom_passes.len() < self.registered_passes.len() {
+            return None;
+        }
+        let index = (depth as usize).checked_sub(1)?;
+        let (&adjustment, above) = self.spawn_adjustments.get(..=index)?.split_last()?;
+        let mut architect = Architect::new(self.seed, self.params.clone(), self.content.clone());
+        for &adjustment in above {
+            architect.adjust_next_spawns(adjustment);
+            architect.generate_with(&self.custom_passes);
+        }
+        architect.adjust_next_spawns(adjustment);
+        Some(architect.generate_with(&self.custom_passes))
+    }
+
+    /// Generate the next level, with the custom passes registered by its depth.
+    ///
+    /// Each attempt at running the pipeline uses a seed derived from the level seed.
+    /// If every attempt fails, or the budget runs out,
+    /// the level falls back to a simple layout.
+    fn generate_with(&mut self, custom_passes: &[(u32, Box<dyn GenPass>)]) -> (Grid<Tile>, Npcs) {
+        self.depth += 1;
+        let level_seed = self.level_seed(self.depth);
+        let adjustment = replace(&mut self.next_spawn_adjustment, 0);
+        self.spawn_adjustments.push(adjustment);
+        let spawn_count = (SPAWN_COUNT as i32 + adjustment).max(0) as usize;
+        let terrain = replace(&mut self.next_level, Grid::new(|_| Terrain::Wall));
+        let builtin_passes = builtin_passes(&self.params, &self.content);
+        let passes: Vec<&dyn GenPass> = builtin_passes
+            .iter()
+            .chain(
+                custom_passes
+                    .iter()
+                    .filter(|&&(first_depth, _)| first_depth <= self.depth)
+                    .map(|(_, pass)| pass),
+            )
+            .map(|pass| &**pass)
+            .collect();
+        let budget = &self.params.budget;
+        let mut passes_left = budget.max_passes;
+        for attempt in 0..budget.max_attempts {
+            let mut rng = IsaacRng::new_from_u64(attempt_seed(level_seed, attempt));
+            let elevation = Noise::new(rng.gen(), self.params.variation.scale);
+            let mut plan = LevelPlan::new(self.depth, terrain.clone(), elevation);
+            plan.spawn_count = spawn_count;
+            let depth = self.depth;
+            let progress = &self.progress;
+            let report_stage = |stage| {
+                report(
+                    progress,
+                    GenProgress::Stage {
+                        depth,
+                        attempt,
+                        stage,
+                    },
+                )
+            };
+            let result =
+                pass::run_passes(&mut plan, &passes, &mut rng, &mut passes_left, report_stage);
+            match result {
+                Ok(()) => return self.finish(plan),
+                Err(GenError::OverBudget) => break,
+                Err(error) => report(
+                    progress,
+                    GenProgress::AttemptFailed {
+                        depth,
+                        attempt,
+                        error,
+                    },
+                ),
+            }
+        }
+        self.fallback_depths.push(self.depth);
+        report(
+            &self.progress,
+            GenProgress::FallingBack { depth: self.depth },
+        );
+        let mut rng = IsaacRng::new_from_u64(level_seed);
+        let elevation = Noise::new(rng.gen(), self.params.variation.scale);
+        let mut plan = LevelPlan::new(self.depth, terrain, elevation);
+        plan.spawn_count = spawn_count;
+        fallback::fallback(&mut plan, &self.content.spawns, &mut rng);
+        self.finish(plan)
+    }
+
+    fn finish(&mut self, mut plan: LevelPlan) -> (Grid<Tile>, Npcs) {
+        self.next_level = plan
+            .next_terrain
+            .take()
+            .expect("No pass carved the next level.");
+        report(&self.progress, GenProgress::Done { depth: self.depth });
+        plan.build(&self.content)
+    }
+}
+
+/// Send progress if anyone is listening.
+///
+/// A frontend that stopped listening doesn't stop generation.
+fn report(progress: &Option<Sender<GenProgress>>, update: GenProgress) {
+    if let Some(progress) = progress {
+        let _ = progress.send(update);
+    }
+}
+
+/// Derive the seed of a level from the master seed.
+///
+/// Depth 0 seeds the carving of the first level.
+fn level_seed(seed: u64, depth: u32) -> u64 {
+    mix_seed(seed, u64::from(depth))
+}
+
+/// Derive the seed of an attempt at generating a level.
+///
+/// The first attempt uses the level seed itself.
+fn attempt_seed(level_seed: u64, attempt: u32) -> u64 {
+    if attempt == 0 {
+        level_seed
+    } else {
+        mix_seed(level_seed, u64::from(attempt))
+    }
+}
+
+/// The passes every level of a run is generated with.
+fn builtin_passes(params: &GenParams, content: &Content) -> Vec<Box<dyn GenPass>> {
+    let mut passes = pass::terrain_passes(params);
+    passes.push(Box::new(SpawnPass(content.spawns.clone())));
+    passes.push(Box::new(PortalPass));
+    passes
+}
+
+/// Chooses where mobs start, and what they are, from a spawn table.
+struct SpawnPass(Vec<SpawnEntry>);
+
+/// Now and then opens a pair of portals.
+///
+/// It runs after the spawns, so that levels without portals come out the
+/// same as they would without it.
+struct PortalPass;
+
+impl GenPass for SpawnPass {
+    fn name(&self) -> &str {
+        "spawns"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Spawns
+    }
+
+    fn apply(&self, plan: &mut LevelPlan, rng: &mut IsaacRng) -> Result<(), GenError> {
+        let spawns = populate(plan, &self.0, rng);
+        plan.spawns.extend(spawns);
+        Ok(())
+    }
+}
+
+impl GenPass for PortalPass {
+    fn name(&self) -> &str {
+        "portals"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Spawns
+    }
+
+    fn apply(&self, plan: &mut LevelPlan, rng: &mut IsaacRng) -> Result<(), GenError> {
+        add_portals(plan, rng);
+        Ok(())
+    }
+}
+
+impl LevelPlan {
+    /// Turn the plan into a playable level, giving each mob the stats
+    /// content gives its species.
+    pub fn build(self, content: &Content) -> (Grid<Tile>, Npcs) {
+        let terrain = self.terrain;
+        let mut level = Grid::new(|pos| Tile {
+            terrain: terrain[pos],
+            mob_id: None,
+            loot: None,
+            decal: None,
+            battered: 0,
+            corpse: None,
+            space: None,
+            rune: None,
+        });
+        let mut npcs = Npcs::new();
+        for (pos, species) in self.spawns {
+            let mob_id = npcs.insert(Mob::spawned(pos, species, content));
+            level[pos].mob_id = Some(mob_id);
+        }
+        (level, npcs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::params::Theme;
+    use super::*;
+
+    use bincode;
+    use floodfill::Regions;
+    use rand::thread_rng;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use world::mob::{self, Species};
+    use world::Party;
+
+    #[test]
+    fn test_regenerate_level() {
+        let mut architect =
+            Architect::new(thread_rng().gen(), GenParams::default(), Content::default());
+        let levels: Vec<Grid<Tile>> = (0..3).map(|_| architect.generate().0).collect();
+        for (i, level) in levels.iter().enumerate() {
+            let (regenerated, _) = architect.regenerate_level(i as u32 + 1).unwrap();
+            assert!(grid::positions().all(|pos| level[pos].terrain == regenerated[pos].terrain));
+        }
+        assert!(architect.regenerate_level(0).is_none());
+        assert!(architect.regenerate_level(4).is_none());
+    }
+
+    #[test]
+    fn test_spawns_follow_content() {
+        let mut content = Content::default();
+        content
+            .spawns
+            .retain(|entry| entry.species == Species::Archer);
+        content.spawns[0].min_depth = 1;
+        for def in &mut content.species {
+            def.max_health = 40;
+        }
+        let world = World::with_content(8, GenParams::default(), Party::solo(), content);
+        assert_eq!(world[MobId::player(0)].max_health, 40);
+        let mut spawned = 0;
+        mob::for_each(&world, |mob_id| {
+            assert_eq!(world[mob_id].species, Species::Archer);
+            assert_eq!(world[mob_id].health, 40);
+            spawned += 1;
+        });
+        assert!(spawned > 0);
+    }
+
+    #[test]
+    fn test_fallback() {
+        let mut params = GenParams::default();
+        params.budget.max_attempts = 0;
+        let mut architect = Architect::new(thread_rng().gen(), params, Content::default());
+        for _ in 0..3 {
+            architect.generate();
+        }
+        assert_eq!(architect.fallback_depths(), &[1, 2, 3]);
+    }
+
+    /// A pass that fails every attempt.
+    struct Refuse;
+
+    impl GenPass for Refuse {
+        fn name(&self) -> &str {
+            "refuse"
+        }
+
+        fn stage(&self) -> Stage {
+            Stage::Validation
+        }
+
+        fn apply(&self, _plan: &mut LevelPlan, _rng: &mut IsaacRng) -> Result<(), GenError> {
+            Err(GenError::Failed {
+                pass: self.name().to_owned(),
+                reason: "always".to_owned(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_failed_attempts_reported() {
+        let (sender, receiver) = channel();
+        let mut params = GenParams::default();
+        params.budget.max_attempts = 3;
+        let mut architect = Architect::new(5, params, Content::default());
+        architect.register_pass(Box::new(Refuse));
+        architect.report_progress(sender);
+        architect.generate();
+        let failed = receiver
+            .try_iter()
+            .filter(|update| matches!(update, GenProgress::AttemptFailed { depth: 1, .. }))
+            .count();
+        assert_eq!(failed, 3);
+        assert_eq!(architect.fallback_depths(), &[1]);
+    }
+
+    #[test]
+    fn test_budget_counts_passes() {
+        let mut params = GenParams::default();
+        params.budget.max_passes = 3;
+        let mut architect = Architect::new(5, params, Content::default());
+        architect.generate();
+        assert_eq!(architect.fallback_depths(), &[1]);
+    }
+
+    #[test]
+    fn test_progress_reported() {
+        let (sender, receiver) = channel();
+        let mut architect = Architect::new(39, GenParams::default(), Content::default());
+        architect.report_progress(sender);
+        architect.generate();
+        let updates: Vec<_> = receiver.try_iter().collect();
+        let stages: Vec<_> = updates
+            .iter()
+            .filter_map(|update| match update {
+                GenProgress::Stage {
+                    depth: 1,
+                    attempt: 0,
+                    stage,
+                } => Some(*stage),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stages[0], Stage::Carve);
+        assert_eq!(updates.last(), Some(&GenProgress::Done { depth: 1 }));
+
+        drop(receiver);
+        architect.generate();
+    }
+
+    #[test]
+    fn test_world_generated_on_thread() {
+        let (sender, receiver) = channel();
+        let loading = thread::spawn(move || {
+            World::with_progress(3, GenParams::default(), Party::solo(), sender)
+        });
+        let done = receiver
+            .iter()
+            .any(|update| update == GenProgress::Done { depth: 1 });
+        assert!(done);
+        assert_eq!(loading.join().unwrap().depth(), 1);
+    }
+
+    struct Flatten;
+
+    impl GenPass for Flatten {
+        fn name(&self) -> &str {
+            "flatten"
+        }
+
+        fn stage(&self) -> Stage {
+            Stage::Decorations
+        }
+
+        fn apply(&self, plan: &mut LevelPlan, _rng: &mut IsaacRng) -> Result<(), GenError> {
+            for pos in grid::positions() {
+                if plan.terrain[pos] == Terrain::ShortGrass {
+                    plan.terrain[pos] = Terrain::Floor;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_levels_valid() {
+        for (seed, &theme) in [Theme::Crystal, Theme::Tunnels].iter().enumerate() {
+            let mut architect =
+                Architect::new(seed as u64, GenParams::new(theme), Content::default());
+            for _ in 0..10 {
+                let (level, _) = architect.generate();
+                let walkable = |pos| {
+                    grid::contains(pos)
+                        && match level[pos].terrain {
+                            Terrain::Exit | Terrain::Entrance => true,
+                            terrain => terrain.passable_once_opened(),
+                        }
+                };
+                let exit = grid::positions()
+                    .find(|&pos| level[pos].terrain == Terrain::Exit)
+                    .unwrap();
+                let regions = Regions::new(walkable);
+                assert!(grid::positions()
+                    .filter(|&pos| walkable(pos))
+                    .all(|pos| regions.connected(pos, exit)));
+            }
+            assert!(architect.fallback_depths().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_custom_pass() {
+        let mut architect = Architect::new(
+            thread_rng().gen(),
+            GenParams::new(Theme::Tunnels),
+            Content::default(),
+        );
+        architect.register_pass(Box::new(Flatten));
+        for _ in 0..3 {
+            let (level, _) = architect.generate();
+            assert!(grid::positions().all(|pos| level[pos].terrain != Terrain::ShortGrass));
+        }
+    }
+
+    #[test]
+    fn test_regenerate_with_pass_registered_midway() {
+        let mut architect = Architect::new(12, GenParams::new(Theme::Tunnels), Content::default());
+        let first = architect.generate().0;
+        assert!(grid::positions().any(|pos| first[pos].terrain == Terrain::ShortGrass));
+        architect.register_pass(Box::new(Flatten));
+        let second = architect.generate().0;
+        assert!(architect.regenerate_level(1).unwrap().0 == first);
+        assert!(architect.regenerate_level(2).unwrap().0 == second);
+
+        let saved = bincode::serialize(&architect).unwrap();
+        let mut loaded: Architect = bincode::deserialize(&saved).unwrap();
+        assert!(loaded.regenerate_level(1).is_none());
+        loaded.register_pass(Box::new(Flatten));
+        assert!(loaded.regenerate_level(1).unwrap().0 == first);
+        assert!(loaded.regenerate_level(2).unwrap().0 == second);
+    }
+}
@@ -2,6 +2,9 @@
 
 use rand::Rng;
 
+use super::params::Smoothing;
+use super::smooth::smooth;
+use super::tile;
 use floodfill::flood;
 use grid::{self, Grid, Pos};
 
@@ -18,9 +21,15 @@ pub enum Terrain {
 }
 
 pub(super) fn generate<R: Rng>(rng: &mut R) -> Grid<Terrain> {
+    generate_with(&Smoothing::none(), rng)
+}
+
+/// Generate a level, smoothing it after the caves are carved.
+pub(super) fn generate_with<R: Rng>(smoothing: &Smoothing, rng: &mut R) -> Grid<Terrain> {
     let mut grid = Grid::new(|_pos| Terrain::Wall);
     let positions = calc_shuffled_positions(rng);
     carve_caves(&positions, &mut grid);
+    smooth(&mut grid, smoothing);
     remove_isolated_walls(&mut grid);
     remove_isolated_floors(&mut grid);
     remove_small_caves(&mut grid);
@@ -145,11 +154,11 @@ pub(super) fn is_cave(pos: Pos, grid: &Grid<Terrain>) -> bool {
     grid[pos] == Terrain::Floor && count_floor_groups(pos, grid) == 1
 }
 
-impl From<Terrain> for super::Terrain {
+impl From<Terrain> for tile::Terrain {
     fn from(terrain: Terrain) -> Self {
         match terrain {
-            Terrain::Floor => super::Terrain::Floor,
-            Terrain::Wall => super::Terrain::Wall,
+            Terrain::Floor => tile::Terrain::Floor,
+            Terrain::Wall => tile::Terrain::Wall,
         }
     }
 }
@@ -0,0 +1,82 @@
+//! Cave generation on its own, for maps outside of a game.
+
+use super::basic;
+use super::params::GenParams;
+use super::pass::{self, GenPass, LevelPlan};
+use super::tile::Terrain;
+use grid::Grid;
+use noise::Noise;
+use rand::{IsaacRng, Rng};
+use std::mem::replace;
+
+/// Generates the terrain of level after level, each linked to the next by
+/// stairs, without the mobs, loot or portals of a game.
+///
+/// Each level runs through the same terrain passes as the levels of a game,
+/// but without its retries or budget, so the levels run out early if one
+/// can't be carved or fails validation.
+pub struct Caves {
+    rng: IsaacRng,
+    params: GenParams,
+    passes: Vec<Box<dyn GenPass>>,
+    depth: u32,
+    next_level: Grid<Terrain>,
+}
+
+impl Caves {
+    /// Start carving from a seed.
+    ///
+    /// The same seed and parameters always carve the same levels.
+    pub fn new(seed: u64, params: GenParams) -> Self {
+        let mut rng = IsaacRng::new_from_u64(seed);
+        let next_level = basic::generate_with(&params.smoothing, &mut rng)
+            .iter()
+            .map(|&t| Terrain::from(t))
+            .collect();
+        let passes = pass::terrain_passes(&params);
+        Caves {
+            rng,
+            params,
+            passes,
+            depth: 0,
+            next_level,
+        }
+    }
+}
+
+impl Iterator for Caves {
+    type Item = Grid<Terrain>;
+
+    fn next(&mut self) -> Option<Grid<Terrain>> {
+        self.depth += 1;
+        let terrain = replace(&mut self.next_level, Grid::new(|_| Terrain::Wall));
+        let elevation = Noise::new(self.rng.gen(), self.params.variation.scale);
+        let mut plan = LevelPlan::new(self.depth, terrain, elevation);
+        let passes: Vec<&dyn GenPass> = self.passes.iter().map(|pass| &**pass).collect();
+        let mut passes_left = u32::MAX;
+        pass::run_passes(&mut plan, &passes, &mut self.rng, &mut passes_left, |_| ()).ok()?;
+        self.next_level = plan.next_terrain?;
+        Some(plan.terrain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid;
+
+    #[test]
+    fn test_levels_linked_by_stairs() {
+        let levels: Vec<Grid<Terrain>> = Caves::new(12, GenParams::default()).take(3).collect();
+        assert_eq!(levels.len(), 3);
+        for pair in levels.windows(2) {
+            let exit = grid::positions()
+                .find(|&pos| pair[0][pos] == Terrain::Exit)
+                .unwrap();
+            assert_eq!(pair[1][exit], Terrain::Entrance);
+        }
+        let again: Vec<Grid<Terrain>> = Caves::new(12, GenParams::default()).take(3).collect();
+        assert!(again == levels);
+    }
+}
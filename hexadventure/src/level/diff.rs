@@ -0,0 +1,102 @@
+//! The changes made to a level since it was generated.
+//!
+//! Levels can be regenerated from the seed, so a level only needs to be
+//! saved as the tiles that differ from what generation made. Which kind of
+//! space a tile is part of is worked out from the terrain, so it is left out.
+
+use super::tile::Tile;
+use prelude::*;
+
+/// The tiles of a level that differ from how it was generated.
+///
+/// Mob ids on the tiles refer to the npcs of the level, which are saved
+/// separately.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelDiff {
+    /// The depth of the level, which picks the generated level to apply to.
+    pub depth: u32,
+    changes: Vec<(Pos, Tile)>,
+}
+
+impl LevelDiff {
+    /// The tiles of `level` that differ from `generated`.
+    pub fn between(depth: u32, generated: &Grid<Tile>, level: &Grid<Tile>) -> Self {
+        let changes = grid::positions()
+            .filter(|&pos| without_space(generated[pos]) != without_space(level[pos]))
+            .map(|pos| (pos, without_space(level[pos])))
+            .collect();
+        LevelDiff { depth, changes }
+    }
+
+    /// Turn the generated level back into the changed one.
+    pub fn apply(&self, generated: &mut Grid<Tile>) {
+        for &(pos, tile) in &self.changes {
+            generated[pos] = Tile {
+                space: generated[pos].space,
+                ..tile
+            };
+        }
+    }
+
+    /// The changed tiles and what they are now.
+    pub fn changes(&self) -> &[(Pos, Tile)] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn without_space(tile: Tile) -> Tile {
+    Tile {
+        space: None,
+        ..tile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::Terrain;
+    use loot::Loot;
+
+    fn tile(terrain: Terrain) -> Tile {
+        Tile {
+            terrain,
+            mob_id: None,
+            loot: None,
+            decal: None,
+            battered: 0,
+            corpse: None,
+            space: None,
+            rune: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_applies_changes() {
+        let generated = Grid::new(|pos: Pos| {
+            if pos.distance(grid::center()) <= 3 {
+                tile(Terrain::Floor)
+            } else {
+                tile(Terrain::Wall)
+            }
+        });
+        let mut level = generated.clone();
+        let dug: Pos = grid::center() + Direction::East * 4;
+        level[dug].terrain = Terrain::Floor;
+        level[grid::center()].loot = Some(Loot {
+            gold: 3,
+            item: None,
+        });
+        let diff = LevelDiff::between(2, &generated, &level);
+        assert_eq!(diff.changes().len(), 2);
+
+        let mut rebuilt = generated.clone();
+        diff.apply(&mut rebuilt);
+        assert!(rebuilt == level);
+        assert!(LevelDiff::between(2, &level, &rebuilt).is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+//! Hang doors in the narrowest tunnels.
+
+use super::tile::Terrain;
+use grid::{self, Grid, Pos, DIRECTIONS};
+use rand::Rng;
+
+/// The most doors a level has.
+const MAX_DOORS: usize = 3;
+
+/// Hang up to `MAX_DOORS` doors in tunnels one tile wide, no two side by side.
+pub(super) fn add_doors<R: Rng>(level: &mut Grid<Terrain>, rng: &mut R) {
+    let mut candidates: Vec<Pos> = grid::inner_positions()
+        .filter(|&pos| is_doorway(level, pos))
+        .collect();
+    rng.shuffle(&mut candidates);
+    let mut doors: Vec<Pos> = Vec::new();
+    for pos in candidates {
+        if doors.len() == MAX_DOORS {
+            break;
+        }
+        if doors.iter().all(|&door| door.distance(pos) > 1) {
+            doors.push(pos);
+        }
+    }
+    for pos in doors {
+        level[pos] = Terrain::Door;
+    }
+}
+
+/// Whether a floor tile has open floor on two opposite sides and wall on the other four.
+fn is_doorway(level: &Grid<Terrain>, pos: Pos) -> bool {
+    level[pos] == Terrain::Floor
+        && DIRECTIONS[..3].iter().any(|&direction| {
+            DIRECTIONS.iter().all(|&side| {
+                let neighbor = level[pos + side];
+                if side == direction || side == direction.rotate(3) {
+                    neighbor == Terrain::Floor
+                } else {
+                    neighbor == Terrain::Wall
+                }
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid::Direction;
+    use rand::IsaacRng;
+
+    #[test]
+    fn test_doors_hung_in_tunnel() {
+        let center = grid::inner_positions().nth(500).unwrap();
+        let tunnel: Vec<Pos> = (1..4)
+            .flat_map(|i| vec![center + Direction::East * i, center + Direction::West * i])
+            .chain(Some(center))
+            .collect();
+        let mut level = Grid::new(|pos| {
+            if tunnel.contains(&pos) {
+                Terrain::Floor
+            } else {
+                Terrain::Wall
+            }
+        });
+        add_doors(&mut level, &mut IsaacRng::new_from_u64(1));
+        let doors: Vec<Pos> = grid::positions()
+            .filter(|&pos| level[pos] == Terrain::Door)
+            .collect();
+        assert!(!doors.is_empty() && doors.len() <= MAX_DOORS);
+        for &door in &doors {
+            assert!(tunnel.contains(&door));
+            assert!(doors
+                .iter()
+                .all(|&other| other == door || other.distance(door) > 1));
+        }
+    }
+}
@@ -1,24 +1,34 @@
 use super::basic;
+use super::params::Smoothing;
 use super::tile::Terrain;
 use grid::{self, Grid, Pos};
 use rand::Rng;
 
-pub(super) fn add_exit<R: Rng>(level: &mut Grid<Terrain>, rng: &mut R) -> Grid<Terrain> {
+/// How many next levels to try before giving up on placing an exit.
+const MAX_TRIES: u32 = 100;
+
+/// Place an exit in the level, returning the next level with a matching entrance.
+pub(super) fn add_exit<R: Rng>(
+    level: &mut Grid<Terrain>,
+    smoothing: &Smoothing,
+    rng: &mut R,
+) -> Option<Grid<Terrain>> {
     let mut positions: Vec<Pos> = grid::inner_positions().collect();
     rng.shuffle(&mut positions);
-    loop {
-        let next_level = basic::generate(rng);
+    for _ in 0..MAX_TRIES {
+        let next_level = basic::generate_with(smoothing, rng);
         if let Some(exit_pos) = find_exit(level, &next_level, &positions) {
             level[exit_pos] = Terrain::Exit;
-            break Grid::new(|pos| {
+            return Some(Grid::new(|pos| {
                 if pos == exit_pos {
                     Terrain::Entrance
                 } else {
                     Terrain::from(next_level[pos])
                 }
-            });
+            }));
         }
     }
+    None
 }
 
 fn find_exit(
@@ -34,7 +44,7 @@ fn find_exit(
     None
 }
 
-fn is_valid_exit(pos: Pos, level: &Grid<Terrain>) -> bool {
+pub(super) fn is_valid_exit(pos: Pos, level: &Grid<Terrain>) -> bool {
     level[pos] == Terrain::Wall
         && basic::count_neighbor_groups(pos, level, |t| t != Terrain::Wall) == 1
         && count_neighbors(pos, level, |t| t == Terrain::Wall) == 4
@@ -47,7 +57,7 @@ fn is_valid_entrance(pos: Pos, level: &Grid<basic::Terrain>) -> bool {
         && count_neighbors(pos, level, |t| t == basic::Terrain::Wall) == 4
 }
 
-fn count_neighbors<T: Copy, F>(pos: Pos, level: &Grid<T>, predicate: F) -> usize
+pub(super) fn count_neighbors<T: Copy, F>(pos: Pos, level: &Grid<T>, predicate: F) -> usize
 where
     F: Fn(T) -> bool,
 {
@@ -0,0 +1,80 @@
+//! A simple level layout that can always be generated.
+//!
+//! Used when the regular pipeline fails or runs out of time.
+
+use super::exit::is_valid_exit;
+use super::pass::LevelPlan;
+use super::populate::populate;
+use super::tile::Terrain;
+use content::SpawnEntry;
+use prelude::*;
+use rand::Rng;
+
+/// Turn a plan into one large open cave.
+///
+/// Keeps the entrance where it is, so the level still matches the one above.
+pub(super) fn fallback<R: Rng>(plan: &mut LevelPlan, spawns: &[SpawnEntry], rng: &mut R) {
+    let entrance = grid::positions().find(|&pos| plan.terrain[pos] == Terrain::Entrance);
+    plan.terrain = open_cave();
+    if let Some(entrance) = entrance {
+        plan.terrain[entrance] = Terrain::Entrance;
+    }
+    let mut positions: Vec<Pos> = grid::inner_positions().collect();
+    rng.shuffle(&mut positions);
+    let exit_pos = positions
+        .into_iter()
+        .find(|&pos| is_valid_exit(pos, &plan.terrain))
+        .expect("An open cave always has room for an exit.");
+    plan.terrain[exit_pos] = Terrain::Exit;
+    let mut next_terrain = open_cave();
+    next_terrain[exit_pos] = Terrain::Entrance;
+    plan.next_terrain = Some(next_terrain);
+    plan.spawns = populate(plan, spawns, rng);
+}
+
+/// A level that is floor everywhere except for two layers of wall around the edge.
+fn open_cave() -> Grid<Terrain> {
+    let mut inner = Grid::new(|_pos| false);
+    for pos in grid::inner_positions() {
+        inner[pos] = true;
+    }
+    Grid::new(|pos| {
+        if inner[pos] && pos.neighbors().all(|pos| inner[pos]) {
+            Terrain::Floor
+        } else {
+            Terrain::Wall
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::basic;
+    use super::super::exit::count_neighbors;
+    use super::super::pass::{GenPass, ValidationPass};
+    use super::*;
+
+    use content::Content;
+    use noise::Noise;
+    use rand::{thread_rng, IsaacRng};
+
+    #[test]
+    fn test_fallback_valid() {
+        let mut rng = IsaacRng::new_from_u64(thread_rng().gen());
+        let terrain = basic::generate(&mut rng)
+            .iter()
+            .map(|&t| Terrain::from(t))
+            .collect();
+        let mut plan = LevelPlan::new(1, terrain, Noise::new(0, 1));
+        for depth in 1..5 {
+            fallback(&mut plan, &Content::default().spawns, &mut rng);
+            ValidationPass.apply(&mut plan, &mut rng).unwrap();
+            let next_terrain = plan.next_terrain.take().unwrap();
+            let entrance = grid::positions()
+                .find(|&pos| next_terrain[pos] == Terrain::Entrance)
+                .unwrap();
+            assert!(count_neighbors(entrance, &next_terrain, |t| t == Terrain::Floor) > 0);
+            plan = LevelPlan::new(depth + 1, next_terrain, Noise::new(0, 1));
+        }
+    }
+}
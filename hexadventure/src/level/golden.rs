@@ -0,0 +1,93 @@
+//! Golden-master tests of level generation.
+//!
+//! The first few levels of a fixed set of seeds are drawn as text and
+//! compared with the snapshots checked in under `golden/levels`, so a
+//! change to the architect or its passes shows up as a change to the maps.
+//! When a change to the levels is intended, run the tests again with
+//! `BLESS=1` to write the new maps over the snapshots, and check them in
+//! along with the change.
+
+use super::params::{GenParams, Theme};
+use super::Architect;
+use ascii::{self, GlyphTheme};
+use content::Content;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+/// The seeds of the levels kept as snapshots.
+const SEEDS: [u64; 4] = [1, 7, 42, 1234];
+/// How many levels of each seed are kept.
+const DEPTHS: u32 = 3;
+/// Set to rewrite the snapshots instead of checking against them.
+const BLESS_VAR: &str = "BLESS";
+
+/// Draw the first levels generated from a seed, one after another.
+fn render_levels(seed: u64, theme: Theme) -> String {
+    let mut architect = Architect::new(seed, GenParams::new(theme), Content::default());
+    let mut text = String::new();
+    for depth in 1..=DEPTHS {
+        let (level, npcs) = architect.generate();
+        writeln!(text, "depth {}", depth).unwrap();
+        text.push_str(&ascii::render_level(&level, &npcs, &GlyphTheme::classic()));
+    }
+    text
+}
+
+fn snapshot_path(seed: u64, theme: Theme) -> PathBuf {
+    let name = format!("{:?}-{}.txt", theme, seed).to_lowercase();
+    [env!("CARGO_MANIFEST_DIR"), "golden", "levels", &name]
+        .iter()
+        .collect()
+}
+
+/// Compare the levels of a seed with their snapshot, or bless them.
+fn check(seed: u64, theme: Theme) {
+    let rendered = render_levels(seed, theme);
+    let path = snapshot_path(seed, theme);
+    if env::var_os(BLESS_VAR).is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &rendered).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "No snapshot at {}. Run with {}=1 to write it.",
+            path.display(),
+            BLESS_VAR
+        )
+    });
+    if rendered != expected {
+        let line = rendered
+            .lines()
+            .zip(expected.lines())
+            .position(|(got, want)| got != want)
+            .unwrap_or_else(|| rendered.lines().count().min(expected.lines().count()));
+        panic!(
+            "Levels of seed {} ({:?}) differ from {} from line {}. \
+             Run with {}=1 to accept the change.\n--- expected\n{}\n--- generated\n{}",
+            seed,
+            theme,
+            path.display(),
+            line + 1,
+            BLESS_VAR,
+            expected,
+            rendered
+        );
+    }
+}
+
+#[test]
+fn test_crystal_levels_match_snapshots() {
+    for &seed in &SEEDS {
+        check(seed, Theme::Crystal);
+    }
+}
+
+#[test]
+fn test_tunnel_levels_match_snapshots() {
+    for &seed in &SEEDS {
+        check(seed, Theme::Tunnels);
+    }
+}
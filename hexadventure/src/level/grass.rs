@@ -1,22 +1,30 @@
-use super::basic::calc_shuffled_positions;
+use super::params::Variation;
 use super::tile::Terrain;
 use fov::calc_fov;
-use grid::{Grid, Pos};
-use rand::Rng;
+use grid::{self, Grid, Pos};
+use noise::{Noise, ONE};
 
-pub(super) fn add_grass<R: Rng>(level: &mut Grid<Terrain>, rng: &mut R) {
-    let positions = calc_shuffled_positions(rng);
-    for &pos in &positions {
-        if level[pos] == Terrain::Floor {
-            let fov_size = calc_fov_size(level, pos);
-            if fov_size > 60 {
-                level[pos] = Terrain::ShortGrass;
-            }
+/// Grow moss on open floor in low-lying areas.
+///
+/// Moss covers about `moss_density` percent of the open floor,
+/// favoring the valleys of the elevation field.
+pub(super) fn add_grass(level: &mut Grid<Terrain>, elevation: &Noise, variation: &Variation) {
+    let mut moss = Vec::new();
+    for pos in grid::inner_positions() {
+        if level[pos] == Terrain::Floor
+            && i64::from(elevation.sample_unit(pos)) * 100
+                < i64::from(variation.moss_density) * i64::from(ONE)
+            && calc_fov_size(level, pos) > 60
+        {
+            moss.push(pos);
         }
     }
+    for pos in moss {
+        level[pos] = Terrain::ShortGrass;
+    }
 }
 
-fn calc_fov_size(level: &mut Grid<Terrain>, pos: Pos) -> u32 {
+fn calc_fov_size(level: &Grid<Terrain>, pos: Pos) -> u32 {
     let transparent = |pos| match level[pos] {
         Terrain::Floor | Terrain::ShortGrass | Terrain::TallGrass => true,
         _ => false,
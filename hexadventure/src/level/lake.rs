@@ -1,13 +1,21 @@
 use super::basic;
+use super::params::Variation;
 use super::tile::Terrain;
 use floodfill::flood;
 use grid::{self, Grid, Pos};
+use noise::{Noise, ONE};
 use rand::Rng;
+use std::collections::HashSet;
 
 const MIN_LAKE_SIZE: usize = 10;
 const MIN_WALL_SIZE: usize = 4;
 
-pub(super) fn add_lakes<R: Rng>(level: &mut Grid<Terrain>, rng: &mut R) {
+pub(super) fn add_lakes<R: Rng>(
+    level: &mut Grid<Terrain>,
+    elevation: &Noise,
+    variation: &Variation,
+    rng: &mut R,
+) {
     let exit_pos = grid::positions()
         .find(|&pos| level[pos] == Terrain::Exit)
         .expect("Exit not found.");
@@ -30,14 +38,16 @@ pub(super) fn add_lakes<R: Rng>(level: &mut Grid<Terrain>, rng: &mut R) {
         let mut lake_level = basic::generate(rng);
         for pos in grid::inner_positions() {
             let lake = flood(pos, |pos| basic::is_cave(pos, &lake_level));
-            let mut lake_floor_size = 0;
             for &pos in &lake {
                 lake_level[pos] = basic::Terrain::Wall;
-                if level[pos] == Terrain::Floor {
-                    lake_floor_size += 1;
-                }
             }
-            if lake_floor_size == 0 {
+            // Tiles by the stairs stay dry, so they must count as part of the level.
+            let lake: HashSet<Pos> = lake.into_iter().filter(|&pos| !near_stairs[pos]).collect();
+            let lake_floor_size = lake
+                .iter()
+                .filter(|&&pos| level[pos] == Terrain::Floor)
+                .count();
+            if lake_floor_size == 0 || too_high(&lake, elevation, variation.max_lake_elevation) {
                 continue;
             }
             let level_size_with_lake = flood(exit_pos, |pos| {
@@ -47,9 +57,7 @@ pub(super) fn add_lakes<R: Rng>(level: &mut Grid<Terrain>, rng: &mut R) {
                 lake_count += 1;
                 level_size -= lake_floor_size;
                 for pos in lake {
-                    if !near_stairs[pos] {
-                        level[pos] = Terrain::Water;
-                    }
+                    level[pos] = Terrain::Water;
                 }
             }
         }
@@ -57,6 +65,17 @@ pub(super) fn add_lakes<R: Rng>(level: &mut Grid<Terrain>, rng: &mut R) {
     remove_isolated_walls(level);
 }
 
+/// Whether the mean elevation of a lake is above a percentage.
+///
+/// Sums whole numbers, so the result doesn't depend on the order the set is visited in.
+fn too_high(lake: &HashSet<Pos>, elevation: &Noise, max_percent: u32) -> bool {
+    let total: i64 = lake
+        .iter()
+        .map(|&pos| i64::from(elevation.sample_unit(pos)))
+        .sum();
+    total * 100 > i64::from(max_percent) * i64::from(ONE) * lake.len() as i64
+}
+
 fn floodable(pos: Pos, level: &Grid<Terrain>) -> bool {
     match level[pos] {
         Terrain::Floor | Terrain::Entrance | Terrain::Exit => true,
@@ -64,6 +83,7 @@ fn floodable(pos: Pos, level: &Grid<Terrain>) -> bool {
     }
 }
 
+/// Remove groups of 3 walls or less.
 fn remove_isolated_walls(grid: &mut Grid<Terrain>) {
     let outer_wall = flood(grid::corner(), |pos| {
         grid::contains(pos) && grid[pos] == Terrain::Wall
@@ -78,8 +98,18 @@ fn remove_isolated_walls(grid: &mut Grid<Terrain>) {
             visited[pos] = true;
         }
         if wall_positions.len() < MIN_WALL_SIZE {
+            // Walls surrounded by a lake become part of the lake,
+            // so that they don't turn into unreachable islands.
+            let in_lake = wall_positions
+                .iter()
+                .any(|&pos| pos.neighbors().any(|pos| grid[pos] == Terrain::Water));
+            let terrain = if in_lake {
+                Terrain::Water
+            } else {
+                Terrain::Floor
+            };
             for pos in wall_positions {
-                grid[pos] = Terrain::Floor;
+                grid[pos] = terrain;
             }
         }
     }
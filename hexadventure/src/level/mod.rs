@@ -1,41 +1,39 @@
 //! Level generation.
+//!
+//! `Caves` carves the terrain of level after level on its own. With the
+//! `game` feature, the `Architect` generates each level of a run as a
+//! pipeline of passes, which also choose where mobs start and open portals.
 
+#[cfg(feature = "game")]
+mod architect;
 mod basic;
+mod caves;
+#[cfg(feature = "game")]
+pub mod diff;
+mod door;
 mod exit;
+#[cfg(feature = "game")]
+mod fallback;
+#[cfg(all(test, feature = "game"))]
+mod golden;
 mod grass;
 mod lake;
+mod params;
+mod pass;
+#[cfg(feature = "game")]
+mod placement;
+#[cfg(feature = "game")]
 mod populate;
+#[cfg(feature = "game")]
+mod portal;
+mod smooth;
+pub mod space;
 pub mod tile;
 
+#[cfg(feature = "game")]
+pub(crate) use self::architect::Architect;
+pub use self::caves::Caves;
+pub use self::params::{Budget, GenParams, Smoothing, Theme, Variation};
+pub use self::pass::{GenError, GenPass, GenProgress, LevelPlan, Stage};
+#[cfg(feature = "game")]
 pub use self::populate::place_mob;
-use self::populate::populate;
-use self::tile::{Terrain, Tile};
-use prelude::*;
-use rand::IsaacRng;
-use std::mem::replace;
-use world::mob::Npcs;
-
-/// Responsible for generating levels.
-#[derive(Serialize, Deserialize)]
-pub(super) struct Architect {
-    rng: IsaacRng,
-    next_level: Grid<Terrain>,
-}
-
-impl Architect {
-    pub fn new(seed: u64) -> Self {
-        let mut rng = IsaacRng::new_from_u64(seed);
-        let next_level = basic::generate(&mut rng)
-            .iter()
-            .map(|&t| Terrain::from(t))
-            .collect();
-        Architect { rng, next_level }
-    }
-
-    pub fn generate(&mut self) -> (Grid<Tile>, Npcs) {
-        let new_next_level = exit::add_exit(&mut self.next_level, &mut self.rng);
-        lake::add_lakes(&mut self.next_level, &mut self.rng);
-        // grass::add_grass(next_level, &mut self.rng);
-        populate(replace(&mut self.next_level, new_next_level), &mut self.rng)
-    }
-}
@@ -0,0 +1,112 @@
+//! Tunable parameters for level generation.
+
+/// Parameters that control how a level is generated.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GenParams {
+    pub smoothing: Smoothing,
+    pub variation: Variation,
+    pub budget: Budget,
+}
+
+/// Parameters for the cellular smoothing pass run after the caves are carved.
+///
+/// Each pass visits every inner position once.
+/// A wall with at least `fill_threshold` floor neighbors becomes floor,
+/// and a floor with at least `erode_threshold` wall neighbors becomes wall.
+/// A threshold above 6 disables that half of the pass.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Smoothing {
+    pub passes: u32,
+    pub fill_threshold: usize,
+    pub erode_threshold: usize,
+}
+
+/// Parameters for the large-scale variation driven by noise.
+///
+/// Elevation is a noise field ranging from 0 to 1. Everything here is a
+/// whole number, so levels come out the same on every platform.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Variation {
+    /// The rough width in hexes of hills and valleys.
+    pub scale: u32,
+    /// Lakes are only placed where their average elevation, in percent, is at most this.
+    pub max_lake_elevation: u32,
+    /// The percentage of open floor that is covered in moss.
+    pub moss_density: u32,
+}
+
+/// How hard to try generating a level before falling back to a simple layout.
+///
+/// The budget counts work rather than time, so a level comes out the same on
+/// every machine, and can always be generated again exactly.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Budget {
+    /// How many times to run the pipeline, each time with a different seed.
+    pub max_attempts: u32,
+    /// How many passes all attempts together may run.
+    pub max_passes: u32,
+}
+
+/// A visual style for a level.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Theme {
+    /// Jagged caves straight from the carver.
+    Crystal,
+    /// Smooth, water-worn tunnels.
+    Tunnels,
+}
+
+impl GenParams {
+    pub fn new(theme: Theme) -> Self {
+        match theme {
+            Theme::Crystal => GenParams {
+                smoothing: Smoothing::none(),
+                variation: Variation {
+                    scale: 6,
+                    max_lake_elevation: 60,
+                    moss_density: 0,
+                },
+                budget: Budget::default(),
+            },
+            Theme::Tunnels => GenParams {
+                smoothing: Smoothing {
+                    passes: 2,
+                    fill_threshold: 4,
+                    erode_threshold: 5,
+                },
+                variation: Variation {
+                    scale: 12,
+                    max_lake_elevation: 50,
+                    moss_density: 40,
+                },
+                budget: Budget::default(),
+            },
+        }
+    }
+}
+
+impl Default for GenParams {
+    fn default() -> Self {
+        GenParams::new(Theme::Crystal)
+    }
+}
+
+impl Smoothing {
+    /// Smoothing that leaves the level untouched.
+    pub fn none() -> Self {
+        Smoothing {
+            passes: 0,
+            fill_threshold: 7,
+            erode_threshold: 7,
+        }
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            max_attempts: 10,
+            max_passes: 100,
+        }
+    }
+}
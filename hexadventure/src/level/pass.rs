@@ -0,0 +1,284 @@
+//! Composable level generation passes.
+//!
+//! The architect and `Caves` build each level by running a pipeline of passes
+//! over a `LevelPlan`. Passes run in order of their stage, and passes with the
+//! same stage run in the order they were added.
+
+use super::door::add_doors;
+use super::exit::add_exit;
+use super::grass::add_grass;
+use super::lake::add_lakes;
+use super::params::{GenParams, Smoothing, Variation};
+#[cfg(feature = "game")]
+use super::populate::SPAWN_COUNT;
+use super::tile::Terrain;
+use floodfill::Regions;
+use grid::{self, Grid, Pos};
+use noise::Noise;
+use rand::IsaacRng;
+#[cfg(feature = "game")]
+use world::mob::Species;
+
+/// When a pass runs relative to the others.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+pub enum Stage {
+    Carve,
+    Lakes,
+    Chasms,
+    Decorations,
+    Vaults,
+    Spawns,
+    Validation,
+}
+
+/// A step in generating a level.
+///
+/// Passes are `Send` so a world can be generated on a loading thread.
+pub trait GenPass: Send {
+    /// A short name used when reporting errors.
+    fn name(&self) -> &str;
+
+    fn stage(&self) -> Stage;
+
+    fn apply(&self, plan: &mut LevelPlan, rng: &mut IsaacRng) -> Result<(), GenError>;
+}
+
+/// A level that is still being generated.
+pub struct LevelPlan {
+    /// How deep the level is. The first level has a depth of 1.
+    pub depth: u32,
+    pub terrain: Grid<Terrain>,
+    /// The terrain of the level below, once a pass has carved it.
+    pub next_terrain: Option<Grid<Terrain>>,
+    /// Large-scale elevation ranging from 0 to 1.
+    pub elevation: Noise,
+    /// How many mobs the spawn pass should place.
+    #[cfg(feature = "game")]
+    pub spawn_count: usize,
+    /// The mobs to place once generation is done.
+    #[cfg(feature = "game")]
+    pub spawns: Vec<(Pos, Species)>,
+    /// Tiles set aside by vault passes, which other passes keep clear of.
+    pub vaults: Vec<Pos>,
+}
+
+/// How far generating a level has got, for showing on a loading screen.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum GenProgress {
+    /// An attempt at the level started running the passes of a stage.
+    Stage {
+        depth: u32,
+        attempt: u32,
+        stage: Stage,
+    },
+    /// An attempt at the level failed, and the next one will be tried if the
+    /// budget allows.
+    AttemptFailed {
+        depth: u32,
+        attempt: u32,
+        error: GenError,
+    },
+    /// Every attempt failed, and the level is falling back to a simple layout.
+    FallingBack { depth: u32 },
+    /// The level is ready.
+    Done { depth: u32 },
+}
+
+/// Why a level could not be generated.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum GenError {
+    /// A pass found the level in a state it can't work with.
+    Failed { pass: String, reason: String },
+    /// Some passable tiles can't be reached from the stairs.
+    Disconnected,
+    /// A mob would be spawned on a tile it can't stand on.
+    BadSpawn(Pos),
+    /// Generation ran more passes than the budget allows.
+    OverBudget,
+}
+
+impl LevelPlan {
+    pub fn new(depth: u32, terrain: Grid<Terrain>, elevation: Noise) -> Self {
+        LevelPlan {
+            depth,
+            terrain,
+            next_terrain: None,
+            elevation,
+            #[cfg(feature = "game")]
+            spawn_count: SPAWN_COUNT,
+            #[cfg(feature = "game")]
+            spawns: Vec::new(),
+            vaults: Vec::new(),
+        }
+    }
+}
+
+/// Run passes over a plan in order of their stage.
+///
+/// Each pass run takes one from `passes_left`, and generation gives up once
+/// none are left. Calls `report` whenever a new stage starts.
+pub(super) fn run_passes<F: FnMut(Stage)>(
+    plan: &mut LevelPlan,
+    passes: &[&dyn GenPass],
+    rng: &mut IsaacRng,
+    passes_left: &mut u32,
+    mut report: F,
+) -> Result<(), GenError> {
+    let mut passes = passes.to_vec();
+    passes.sort_by_key(|pass| pass.stage());
+    let mut stage = None;
+    for pass in passes {
+        if *passes_left == 0 {
+            return Err(GenError::OverBudget);
+        }
+        *passes_left -= 1;
+        if stage != Some(pass.stage()) {
+            stage = Some(pass.stage());
+            report(pass.stage());
+        }
+        pass.apply(plan, rng)?;
+    }
+    if plan.next_terrain.is_none() {
+        return Err(GenError::Failed {
+            pass: "pipeline".to_owned(),
+            reason: "no pass carved the next level".to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// The passes that shape the terrain of every level, and check it.
+pub(super) fn terrain_passes(params: &GenParams) -> Vec<Box<dyn GenPass>> {
+    vec![
+        Box::new(CarvePass(params.smoothing.clone())),
+        Box::new(LakePass(params.variation.clone())),
+        Box::new(MossPass(params.variation.clone())),
+        Box::new(DoorPass),
+        Box::new(ValidationPass),
+    ]
+}
+
+/// Carves the level below and links it to this one with stairs.
+struct CarvePass(Smoothing);
+
+/// Floods low-lying caves.
+struct LakePass(Variation);
+
+/// Grows moss on open floor.
+struct MossPass(Variation);
+
+/// Hangs doors in narrow tunnels.
+struct DoorPass;
+
+/// Checks that the level is playable.
+pub(super) struct ValidationPass;
+
+impl GenPass for CarvePass {
+    fn name(&self) -> &str {
+        "carve"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Carve
+    }
+
+    fn apply(&self, plan: &mut LevelPlan, rng: &mut IsaacRng) -> Result<(), GenError> {
+        match add_exit(&mut plan.terrain, &self.0, rng) {
+            Some(next_terrain) => {
+                plan.next_terrain = Some(next_terrain);
+                Ok(())
+            }
+            None => Err(GenError::Failed {
+                pass: self.name().to_owned(),
+                reason: "no place for an exit".to_owned(),
+            }),
+        }
+    }
+}
+
+impl GenPass for LakePass {
+    fn name(&self) -> &str {
+        "lakes"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Lakes
+    }
+
+    fn apply(&self, plan: &mut LevelPlan, rng: &mut IsaacRng) -> Result<(), GenError> {
+        add_lakes(&mut plan.terrain, &plan.elevation, &self.0, rng);
+        Ok(())
+    }
+}
+
+impl GenPass for MossPass {
+    fn name(&self) -> &str {
+        "moss"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Decorations
+    }
+
+    fn apply(&self, plan: &mut LevelPlan, _rng: &mut IsaacRng) -> Result<(), GenError> {
+        add_grass(&mut plan.terrain, &plan.elevation, &self.0);
+        Ok(())
+    }
+}
+
+impl GenPass for DoorPass {
+    fn name(&self) -> &str {
+        "doors"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Decorations
+    }
+
+    fn apply(&self, plan: &mut LevelPlan, rng: &mut IsaacRng) -> Result<(), GenError> {
+        add_doors(&mut plan.terrain, rng);
+        Ok(())
+    }
+}
+
+impl GenPass for ValidationPass {
+    fn name(&self) -> &str {
+        "validation"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Validation
+    }
+
+    fn apply(&self, plan: &mut LevelPlan, _rng: &mut IsaacRng) -> Result<(), GenError> {
+        let terrain = &plan.terrain;
+        let walkable = |pos| {
+            grid::contains(pos)
+                && match terrain[pos] {
+                    Terrain::Exit | Terrain::Entrance => true,
+                    terrain => terrain.passable_once_opened(),
+                }
+        };
+        let start = grid::positions().find(|&pos| terrain[pos] == Terrain::Exit);
+        let start = match start {
+            Some(pos) => pos,
+            None => {
+                return Err(GenError::Failed {
+                    pass: self.name().to_owned(),
+                    reason: "no exit".to_owned(),
+                })
+            }
+        };
+        let regions = Regions::new(walkable);
+        if grid::positions().any(|pos| walkable(pos) && !regions.connected(pos, start)) {
+            return Err(GenError::Disconnected);
+        }
+        #[cfg(feature = "game")]
+        for &(pos, _) in &plan.spawns {
+            if !terrain[pos].passable() {
+                return Err(GenError::BadSpawn(pos));
+            }
+        }
+        Ok(())
+    }
+}
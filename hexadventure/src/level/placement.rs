@@ -0,0 +1,98 @@
+//! Choosing where generation passes put things.
+
+use super::pass::LevelPlan;
+use super::tile::Terrain;
+use astar::{dijkstra_map, UNREACHABLE};
+use grid::{self, Grid, Pos};
+use rand::Rng;
+
+impl LevelPlan {
+    /// Pick up to `count` tiles for a pass to put things on.
+    ///
+    /// Only tiles that can be walked to from the entrance, at least
+    /// `min_distance` steps from it, outside any vault, and that `fits`
+    /// accepts are picked. Which ones depends only on the plan and `rng`.
+    /// Closed doors count as walkable.
+    pub fn pick_tiles<R: Rng, F>(
+        &self,
+        count: usize,
+        min_distance: u32,
+        fits: F,
+        rng: &mut R,
+    ) -> Vec<Pos>
+    where
+        F: Fn(Pos) -> bool,
+    {
+        let distances = distances_from_entrance(&self.terrain);
+        let mut positions: Vec<Pos> = grid::inner_positions().collect();
+        rng.shuffle(&mut positions);
+        positions
+            .into_iter()
+            .filter(|&pos| {
+                distances[pos] != UNREACHABLE
+                    && distances[pos] >= min_distance
+                    && !self.vaults.contains(&pos)
+                    && fits(pos)
+            })
+            .take(count)
+            .collect()
+    }
+}
+
+/// Where players arrive on a level.
+///
+/// That is the stairs up, or the first level's open tile closest to the center.
+fn entrance(terrain: &Grid<Terrain>) -> Option<Pos> {
+    grid::positions()
+        .find(|&pos| terrain[pos] == Terrain::Entrance)
+        .or_else(|| {
+            let center = grid::center();
+            (0..grid::WIDTH.max(grid::HEIGHT) as u32)
+                .flat_map(|r| center.ring(r))
+                .find(|&pos| grid::contains(pos) && terrain[pos].passable())
+        })
+}
+
+/// How many steps each tile is from the entrance.
+fn distances_from_entrance(terrain: &Grid<Terrain>) -> Grid<u32> {
+    let sources: Vec<Pos> = entrance(terrain).into_iter().collect();
+    dijkstra_map(&sources, |pos| terrain[pos].passable_once_opened())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid::Direction;
+    use noise::Noise;
+    use rand::IsaacRng;
+
+    #[test]
+    fn test_picks_reachable_tiles_far_enough() {
+        let entrance: Pos = grid::center();
+        let cut_off: Pos = entrance + Direction::East * 8;
+        let terrain = Grid::new(|pos: Pos| {
+            if pos == entrance {
+                Terrain::Entrance
+            } else if pos.distance(entrance) <= 5 || pos.distance(cut_off) <= 1 {
+                Terrain::Floor
+            } else {
+                Terrain::Wall
+            }
+        });
+        let mut plan = LevelPlan::new(1, terrain, Noise::new(0, 1));
+        plan.vaults.push(entrance + Direction::West * 4);
+        let picked = plan.pick_tiles(200, 3, |_| true, &mut IsaacRng::new_from_u64(1));
+        let expected = grid::positions()
+            .filter(|&pos| (3..=5).contains(&pos.distance(entrance)))
+            .count();
+        assert_eq!(picked.len(), expected - 1);
+        assert!(picked
+            .iter()
+            .all(|&pos| pos.distance(entrance) >= 3 && pos.distance(entrance) <= 5));
+        assert!(!picked.contains(&(entrance + Direction::West * 4)));
+
+        let again = plan.pick_tiles(200, 3, |_| true, &mut IsaacRng::new_from_u64(1));
+        assert_eq!(picked, again);
+    }
+}
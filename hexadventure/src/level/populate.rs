@@ -1,45 +1,70 @@
 //! Populate a level with mobs
 
-use super::basic::calc_shuffled_positions;
-use super::tile::{Terrain, Tile};
+use super::pass::LevelPlan;
+use super::tile::Tile;
+use content::SpawnEntry;
 use prelude::*;
 use rand::Rng;
-use world::mob::{Npcs, Species};
+use world::mob::Species;
 
-pub(super) fn populate<R: Rng>(level: Grid<Terrain>, rng: &mut R) -> (Grid<Tile>, Npcs) {
-    fn near_entrance(pos: Pos, level: &Grid<Tile>) -> bool {
-        pos.neighbors()
-            .any(|pos| level[pos].terrain == Terrain::Entrance)
-    }
+/// How many mobs a level starts with unless pacing says otherwise.
+pub(super) const SPAWN_COUNT: usize = 2;
+/// The fewest steps between the entrance and where a mob starts.
+pub(super) const SPAWN_DISTANCE: u32 = 2;
 
-    let positions = calc_shuffled_positions(rng);
-    let mut level = Grid::new(|pos| Tile {
-        terrain: level[pos],
-        mob_id: None,
-    });
-    let mut npcs = Npcs::new();
-    let mut npc_count = 0;
-    for pos in positions {
-        if level[pos].terrain.passable() && !near_entrance(pos, &level) {
-            let mob = Mob::new(pos, Species::Skeleton);
-            let mob_id = npcs.insert(mob);
-            level[pos].mob_id = Some(mob_id);
-            npc_count += 1;
-            if npc_count > 1 {
-                break;
-            }
+/// Choose where the mobs of a level start, and what they are.
+///
+/// Each mob is drawn from the entries of the spawn table that spawn at the
+/// level's depth, in proportion to their weights. No mobs spawn where no
+/// entry does.
+pub(super) fn populate<R: Rng>(
+    plan: &LevelPlan,
+    table: &[SpawnEntry],
+    rng: &mut R,
+) -> Vec<(Pos, Species)> {
+    let terrain = &plan.terrain;
+    let tiles = plan.pick_tiles(
+        plan.spawn_count,
+        SPAWN_DISTANCE,
+        |pos| terrain[pos].passable(),
+        rng,
+    );
+    tiles
+        .into_iter()
+        .filter_map(|pos| Some((pos, species(table, plan.depth, rng)?)))
+        .collect()
+}
+
+/// Draw a species from the entries of the spawn table that spawn at a depth.
+fn species<R: Rng>(table: &[SpawnEntry], depth: u32, rng: &mut R) -> Option<Species> {
+    let entries: Vec<&SpawnEntry> = table
+        .iter()
+        .filter(|entry| entry.min_depth <= depth && entry.weight > 0)
+        .collect();
+    let total: u64 = entries.iter().map(|entry| u64::from(entry.weight)).sum();
+    if total == 0 {
+        return None;
+    }
+    // Rolled as a `u64`, like the shuffles around it, so the generator keeps
+    // drawing whole words.
+    let mut roll = rng.gen_range(0, total);
+    for entry in entries {
+        let weight = u64::from(entry.weight);
+        if roll < weight {
+            return Some(entry.species);
         }
+        roll -= weight;
     }
-    (level, npcs)
+    unreachable!()
 }
 
-/// Place a mob as close as possible to a position in the level
+/// Place a mob on the free tile closest to a position in the level
 pub fn place_mob<R: Rng>(level: &mut Grid<Tile>, center: Pos, mob_id: MobId, rng: &mut R) -> Pos {
     let flip = rng.gen();
     for r in 0.. {
         for pos in center.ring(r) {
             let pos = if flip { center - (pos - center) } else { pos };
-            if level[pos].terrain.passable() {
+            if level[pos].terrain.passable() && level[pos].mob_id.is_none() {
                 level[pos].mob_id = Some(mob_id);
                 return pos;
             }
@@ -47,3 +72,39 @@ pub fn place_mob<R: Rng>(level: &mut Grid<Tile>, center: Pos, mob_id: MobId, rng
     }
     unreachable!()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use content::Content;
+    use rand::{thread_rng, IsaacRng};
+
+    #[test]
+    fn test_species_spawn_from_their_depth() {
+        let table = Content::default().spawns;
+        let mut rng = IsaacRng::new_from_u64(thread_rng().gen());
+        for depth in 1..8 {
+            let spawned: Vec<Species> = (0..200)
+                .map(|_| species(&table, depth, &mut rng).unwrap())
+                .collect();
+            for entry in &table {
+                assert_eq!(
+                    spawned.contains(&entry.species),
+                    entry.min_depth <= depth,
+                    "{} at depth {}",
+                    entry.species.name(),
+                    depth
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_nothing_spawns_without_entries() {
+        let mut table = Content::default().spawns;
+        table.retain(|entry| entry.min_depth > 1);
+        assert_eq!(species(&table, 1, &mut thread_rng()), None);
+        assert!(species(&table, 2, &mut thread_rng()).is_some());
+    }
+}
@@ -0,0 +1,78 @@
+//! Now and then, open a pair of portals far apart.
+
+use super::pass::LevelPlan;
+use super::tile::Terrain;
+use grid::Pos;
+use rand::Rng;
+
+/// The percent chance of a level having a pair of portals.
+///
+/// Rolled as a `u64`, like the shuffles around it, so the generator keeps
+/// drawing whole words.
+pub(super) const PORTAL_CHANCE: u64 = 10;
+/// The fewest steps either portal is from the entrance.
+const ENTRANCE_DISTANCE: u32 = 4;
+/// The fewest steps the two portals of a pair are apart, as the crow flies.
+pub(super) const PORTAL_SPREAD: u32 = 15;
+
+/// Maybe turn two floor tiles far apart into portals, returning where they are.
+///
+/// The world links the two portals of a level to each other when the level is entered.
+pub(super) fn add_portals<R: Rng>(plan: &mut LevelPlan, rng: &mut R) -> Option<(Pos, Pos)> {
+    if rng.gen_range(0, 100) >= PORTAL_CHANCE {
+        return None;
+    }
+    let open = |plan: &LevelPlan, pos: Pos| {
+        plan.terrain[pos] == Terrain::Floor && plan.spawns.iter().all(|&(spawn, _)| spawn != pos)
+    };
+    let first = *plan
+        .pick_tiles(1, ENTRANCE_DISTANCE, |pos| open(plan, pos), rng)
+        .first()?;
+    let second = *plan
+        .pick_tiles(
+            1,
+            ENTRANCE_DISTANCE,
+            |pos| open(plan, pos) && pos.distance(first) >= PORTAL_SPREAD,
+            rng,
+        )
+        .first()?;
+    plan.terrain[first] = Terrain::Portal;
+    plan.terrain[second] = Terrain::Portal;
+    Some((first, second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid::{self, Grid};
+    use noise::Noise;
+    use rand::IsaacRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_portals_placed_far_apart() {
+        let inner: HashSet<Pos> = grid::inner_positions().collect();
+        let terrain = Grid::new(|pos| {
+            if inner.contains(&pos) {
+                Terrain::Floor
+            } else {
+                Terrain::Wall
+            }
+        });
+        let mut placed = 0;
+        for seed in 0..100 {
+            let mut rng = IsaacRng::new_from_u64(seed);
+            let mut plan = LevelPlan::new(1, terrain.clone(), Noise::new(0, 8));
+            if let Some((first, second)) = add_portals(&mut plan, &mut rng) {
+                placed += 1;
+                assert!(first.distance(second) >= PORTAL_SPREAD);
+                let portals = grid::positions()
+                    .filter(|&pos| plan.terrain[pos] == Terrain::Portal)
+                    .count();
+                assert_eq!(portals, 2);
+            }
+        }
+        assert!(placed > 0 && placed < 50);
+    }
+}
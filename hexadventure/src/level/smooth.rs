@@ -0,0 +1,72 @@
+//! Cellular smoothing of a freshly carved level.
+
+use super::basic::{count_floor_groups, Terrain};
+use super::params::Smoothing;
+use grid::{self, Grid, Pos};
+
+/// Smooth out single-tile nubs and pits.
+///
+/// Positions are updated in place one at a time, so that a floor is only
+/// eroded if its floor neighbors stay connected without it.
+pub(super) fn smooth(grid: &mut Grid<Terrain>, smoothing: &Smoothing) {
+    for _ in 0..smoothing.passes {
+        for pos in grid::inner_positions() {
+            match grid[pos] {
+                Terrain::Wall => {
+                    if count_neighbors(pos, grid, Terrain::Floor) >= smoothing.fill_threshold {
+                        grid[pos] = Terrain::Floor;
+                    }
+                }
+                Terrain::Floor => {
+                    if count_neighbors(pos, grid, Terrain::Wall) >= smoothing.erode_threshold
+                        && count_floor_groups(pos, grid) == 1
+                    {
+                        grid[pos] = Terrain::Wall;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn count_neighbors(pos: Pos, grid: &Grid<Terrain>, terrain: Terrain) -> usize {
+    pos.neighbors().filter(|&pos| grid[pos] == terrain).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::basic;
+    use super::super::params::{GenParams, Theme};
+    use super::*;
+
+    use floodfill::flood;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_smoothed_connected() {
+        let params = GenParams::new(Theme::Tunnels);
+        let grid = basic::generate_with(&params.smoothing, &mut thread_rng());
+        let floor_pos = grid::positions()
+            .find(|&pos| grid[pos] == Terrain::Floor)
+            .unwrap();
+        let cave = flood(floor_pos, |pos| grid[pos] == Terrain::Floor);
+        assert!(grid::positions().all(|pos| grid[pos] == Terrain::Wall || cave.contains(&pos)));
+    }
+
+    #[test]
+    fn test_no_nubs() {
+        let smoothing = Smoothing {
+            passes: 1,
+            fill_threshold: 5,
+            erode_threshold: 7,
+        };
+        let mut grid = Grid::new(|_pos| Terrain::Wall);
+        for pos in grid::inner_positions() {
+            grid[pos] = Terrain::Floor;
+        }
+        let nub = grid::center();
+        grid[nub] = Terrain::Wall;
+        smooth(&mut grid, &smoothing);
+        assert!(grid[nub] == Terrain::Floor);
+    }
+}
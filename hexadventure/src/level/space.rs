@@ -0,0 +1,99 @@
+//! Telling corridors, rooms and caverns apart.
+
+use super::tile::Terrain;
+use astar::dijkstra_map;
+use floodfill::flood;
+use grid::{self, Grid, Pos};
+
+/// The furthest a corridor tile or any of its neighbors is from a wall.
+pub const CORRIDOR_CLEARANCE: u32 = 1;
+/// The fewest tiles an open region needs to be a cavern rather than a room.
+pub const CAVERN_SIZE: usize = 80;
+
+/// What kind of space an open tile is part of.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Space {
+    Corridor,
+    Room,
+    Cavern,
+}
+
+/// Classify every open tile of a level, leaving the rest `None`.
+///
+/// A tile is corridor if neither it nor any neighbor is more than
+/// `CORRIDOR_CLEARANCE` steps from a wall. The corridors split the other
+/// open tiles into regions, and each region is a room or, from
+/// `CAVERN_SIZE` tiles up, a cavern. Closed doors count as open.
+pub fn classify(terrain: &Grid<Terrain>) -> Grid<Option<Space>> {
+    let open = |pos: Pos| grid::contains(pos) && terrain[pos].passable_once_opened();
+    let clearance = clearance(terrain);
+    let mut spaces = Grid::new(|pos| {
+        if !open(pos) {
+            None
+        } else if pos
+            .neighbors()
+            .chain(Some(pos))
+            .all(|pos| !open(pos) || clearance[pos] <= CORRIDOR_CLEARANCE)
+        {
+            Some(Space::Corridor)
+        } else {
+            Some(Space::Room)
+        }
+    });
+    let mut sized = Grid::new(|_| false);
+    for pos in grid::positions() {
+        if spaces[pos] != Some(Space::Room) || sized[pos] {
+            continue;
+        }
+        let region = flood(pos, |pos| {
+            grid::contains(pos) && spaces[pos] == Some(Space::Room)
+        });
+        let space = if region.len() >= CAVERN_SIZE {
+            Space::Cavern
+        } else {
+            Space::Room
+        };
+        for &pos in &region {
+            sized[pos] = true;
+            spaces[pos] = Some(space);
+        }
+    }
+    spaces
+}
+
+/// How many steps each tile is from the nearest wall or edge of the level.
+fn clearance(terrain: &Grid<Terrain>) -> Grid<u32> {
+    let walls: Vec<Pos> = grid::positions()
+        .filter(|&pos| {
+            !terrain[pos].passable_once_opened() || pos.neighbors().any(|pos| !grid::contains(pos))
+        })
+        .collect();
+    dijkstra_map(&walls, |_| true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid::Direction;
+
+    #[test]
+    fn test_corridor_between_room_and_cavern() {
+        let center = grid::center();
+        let room: Pos = center + Direction::West * 8;
+        let cavern: Pos = center + Direction::East * 7;
+        let terrain = Grid::new(|pos: Pos| {
+            let in_corridor = (0..16).any(|i| pos == room + Direction::East * i);
+            if in_corridor || pos.distance(room) <= 2 || pos.distance(cavern) <= 6 {
+                Terrain::Floor
+            } else {
+                Terrain::Wall
+            }
+        });
+        let spaces = classify(&terrain);
+        assert_eq!(spaces[room], Some(Space::Room));
+        assert_eq!(spaces[center], Some(Space::Corridor));
+        assert_eq!(spaces[cavern], Some(Space::Cavern));
+        assert_eq!(spaces[room + Direction::West * 4], None);
+    }
+}
@@ -1,12 +1,47 @@
+#[cfg(feature = "game")]
+use level::space::Space;
+#[cfg(feature = "game")]
+use loot::Loot;
+#[cfg(feature = "game")]
 use prelude::*;
+#[cfg(feature = "game")]
+use world::mob::Species;
+#[cfg(feature = "game")]
+use world::seal::Rune;
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[cfg(feature = "game")]
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 pub struct Tile {
     pub terrain: Terrain,
     pub mob_id: Option<MobId>,
+    pub loot: Option<Loot>,
+    pub decal: Option<Decal>,
+    /// How many blows a closed door has taken.
+    pub battered: u32,
+    /// The species of whatever died here, if its remains are still around.
+    pub corpse: Option<Species>,
+    /// The kind of space this tile is part of, if it is open.
+    ///
+    /// Worked out from the terrain of the whole level, and kept up to date by the world.
+    pub space: Option<Space>,
+    /// A rune dropped by the guardian of sealed stairs.
+    pub rune: Option<Rune>,
 }
 
-#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+/// A mark left on a tile that fades over time.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Decal {
+    pub kind: DecalKind,
+    /// How many more turns the mark lasts.
+    pub turns_left: u32,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum DecalKind {
+    Blood,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum Terrain {
     Wall,
     Floor,
@@ -16,6 +51,17 @@ pub enum Terrain {
     Exit,
     Entrance,
     Water,
+    /// Sticky strands that catch mobs walking into them.
+    Web,
+    /// A closed door. Depending on their kind, mobs open it, smash it, or go around.
+    Door,
+    OpenDoor,
+    /// What's left of a door smashed off its hinges.
+    BrokenDoor,
+    /// A shimmering gateway that carries whatever steps on it to its twin.
+    Portal,
+    /// Water shallow enough to wade through, noisily.
+    ShallowWater,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq)]
@@ -23,6 +69,8 @@ pub enum TileView {
     Visible,
     Remembered(Terrain),
     None,
+    /// Known by magic, but never seen.
+    Mapped(Terrain),
 }
 
 // pub enum FullTileView<'a> {
@@ -38,24 +86,68 @@ impl Terrain {
     pub fn passable(&self) -> bool {
         use self::Terrain::*;
         match *self {
-            Wall | Entrance | Exit | Water => false,
+            Wall | Entrance | Exit | Water | Door => false,
             _ => true,
         }
     }
 
+    /// Whether the terrain is passable, or a closed door that can be opened to make it so.
+    pub fn passable_once_opened(&self) -> bool {
+        self.passable() || *self == Terrain::Door
+    }
+
     pub fn transparent(&self) -> bool {
         use self::Terrain::*;
         match *self {
-            Wall | TallGrass => false,
+            Wall | TallGrass | Door => false,
             _ => true,
         }
     }
 
+    /// How brightly the terrain glows. Light fades by one for each tile it spreads.
+    pub fn glow(&self) -> u32 {
+        match *self {
+            // The pale moss.
+            Terrain::ShortGrass => 2,
+            Terrain::Portal => 3,
+            _ => 0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        use self::Terrain::*;
+        match *self {
+            Wall => "wall",
+            Floor => "floor",
+            ShortGrass => "short grass",
+            TallGrass => "tall grass",
+            Brownberry => "brownberry",
+            Exit => "stairs down",
+            Entrance => "stairs up",
+            Water => "water",
+            ShallowWater => "shallow water",
+            Web => "web",
+            Door => "door",
+            OpenDoor => "open door",
+            BrokenDoor => "broken door",
+            Portal => "portal",
+        }
+    }
+
     // pub fn solid(&self) -> bool {
     //     !self.passable() && !self.transparent()
     // }
 }
 
+impl DecalKind {
+    /// How many turns a fresh mark of this kind lasts.
+    pub fn lifetime(self) -> u32 {
+        match self {
+            DecalKind::Blood => 300,
+        }
+    }
+}
+
 impl TileView {
     pub fn is_visible(&self) -> bool {
         match self {
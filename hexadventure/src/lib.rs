@@ -1,19 +1,60 @@
+//! Game logic for Hex Adventure.
+//!
+//! Without the default `game` feature, only the parts that are useful on
+//! their own are built: the hex `grid`, `line`, `astar` pathfinding, `fov`,
+//! `floodfill`, `noise`, `rng`, and the cave generation of `level`, through
+//! `level::Caves`.
+
 extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate num;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "game")]
+extern crate serde_json;
+#[cfg(test)]
+extern crate bincode;
+
+#[cfg(feature = "game")]
+#[macro_use]
+mod registry;
 
-mod astar;
+#[cfg(feature = "game")]
+pub mod ascii;
+pub mod astar;
+#[cfg(feature = "game")]
+pub mod camera;
+#[cfg(feature = "game")]
+pub mod content;
 pub mod floodfill;
 pub mod fov;
 pub mod grid;
 pub mod level;
 pub mod line;
+#[cfg(feature = "game")]
+pub mod loot;
 mod minheap;
+#[cfg(feature = "game")]
+pub mod names;
+pub mod noise;
+#[cfg(feature = "game")]
+pub mod profile;
+#[cfg(feature = "game")]
+pub mod protocol;
+pub mod rng;
+#[cfg(feature = "game")]
+pub mod save;
+#[cfg(feature = "game")]
+pub mod storage;
+#[cfg(feature = "stress")]
+pub mod stress;
 mod util;
+#[cfg(feature = "game")]
 pub mod world;
 
+#[cfg(feature = "game")]
 pub mod prelude {
     pub use grid::{self, Direction, Grid, Pos};
     pub use world::mob::{Mob, MobId};
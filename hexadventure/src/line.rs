@@ -34,15 +34,18 @@ impl Iterator for LineIterator {
     fn next(&mut self) -> Option<Self::Item> {
         if self.progress > self.length {
             None
+        } else if self.length == 0 {
+            self.progress += 1;
+            Some(self.start)
         } else {
-            let pos = self.start + ((self.end - self.start) * self.progress / self.length).round();
+            let pos = self.start + (self.end - self.start).scale(self.progress, self.length);
             self.progress += 1;
             Some(pos)
         }
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let size = self.length - self.progress;
+        let size = self.length + 1 - self.progress;
         (size as usize, Some(size as usize))
     }
 }
@@ -0,0 +1,230 @@
+//! Rolling loot: how good it is, and what mobs drop when they die.
+//!
+//! Every source of loot rolls its quality here, so treasure on the floor,
+//! in vaults and dropped by mobs all follow the same tunable odds.
+
+use rand::Rng;
+use world::mob::Species;
+
+/// How good a piece of loot is.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Tier {
+    Common,
+    Uncommon,
+    Rare,
+    Artifact,
+}
+
+/// Every tier, from common to artifact.
+pub const TIERS: [Tier; 4] = [Tier::Common, Tier::Uncommon, Tier::Rare, Tier::Artifact];
+
+/// Where loot is found.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum LootContext {
+    /// Lying on the floor of a level.
+    Floor,
+    /// In a vault's chest.
+    Vault,
+    /// Dropped by a mob when it dies.
+    Drop,
+}
+
+/// What a kind of mob can drop when it dies.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct MobLoot {
+    /// The least and most gold dropped.
+    pub gold: (u32, u32),
+    /// The percent chance of dropping the equipment it wields.
+    pub equipment_chance: u32,
+    /// The percent chance of dropping something special, rolled as if from a vault.
+    pub special_chance: u32,
+}
+
+/// Loot lying on a tile.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Loot {
+    pub gold: u32,
+    /// The tier of the best item here, if there is one.
+    pub item: Option<Tier>,
+}
+
+/// The odds of each tier, indexed in order from common to artifact.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LootWeights {
+    /// The weight of each tier on the first level.
+    pub base: [u32; 4],
+    /// How much the weight of each tier grows with every level deeper.
+    pub per_depth: [u32; 4],
+    /// How many levels deeper vault chests roll as.
+    pub vault_bonus_depth: u32,
+}
+
+impl Tier {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tier::Common => "common",
+            Tier::Uncommon => "uncommon",
+            Tier::Rare => "rare",
+            Tier::Artifact => "artifact",
+        }
+    }
+}
+
+impl LootWeights {
+    /// The weight of each tier for loot found at a depth.
+    pub fn weights(&self, depth: u32, context: LootContext) -> [u32; 4] {
+        let depth = match context {
+            LootContext::Vault => depth + self.vault_bonus_depth,
+            LootContext::Floor | LootContext::Drop => depth,
+        };
+        let levels = depth.saturating_sub(1);
+        let mut weights = self.base;
+        for (weight, growth) in weights.iter_mut().zip(&self.per_depth) {
+            *weight += growth * levels;
+        }
+        weights
+    }
+
+    /// Roll the tier of a piece of loot.
+    pub fn roll<R: Rng>(&self, depth: u32, context: LootContext, rng: &mut R) -> Tier {
+        let weights = self.weights(depth, context);
+        let total: u32 = weights.iter().sum();
+        assert!(total > 0, "At least one loot tier must have a weight.");
+        let mut roll = rng.gen_range(0, total);
+        for (&tier, &weight) in TIERS.iter().zip(&weights) {
+            if roll < weight {
+                return tier;
+            }
+            roll -= weight;
+        }
+        unreachable!("The roll is less than the total weight.")
+    }
+}
+
+impl MobLoot {
+    /// The loot table of a species.
+    pub fn of(species: Species) -> Self {
+        match species {
+            Species::Hero => MobLoot {
+                gold: (0, 0),
+                equipment_chance: 0,
+                special_chance: 0,
+            },
+            Species::Skeleton => MobLoot {
+                gold: (1, 10),
+                equipment_chance: 20,
+                special_chance: 2,
+            },
+            Species::Spider => MobLoot {
+                gold: (0, 5),
+                equipment_chance: 10,
+                special_chance: 1,
+            },
+            Species::Archer => MobLoot {
+                gold: (2, 12),
+                equipment_chance: 20,
+                special_chance: 2,
+            },
+            Species::Thief => MobLoot {
+                gold: (5, 20),
+                equipment_chance: 10,
+                special_chance: 2,
+            },
+            Species::Necromancer => MobLoot {
+                gold: (20, 50),
+                equipment_chance: 40,
+                special_chance: 20,
+            },
+            Species::Behemoth => MobLoot {
+                gold: (20, 60),
+                equipment_chance: 60,
+                special_chance: 25,
+            },
+            Species::Serpent => MobLoot {
+                gold: (15, 45),
+                equipment_chance: 40,
+                special_chance: 20,
+            },
+        }
+    }
+
+    /// Roll what a mob drops at a depth, if anything.
+    pub fn roll<R: Rng>(&self, weights: &LootWeights, depth: u32, rng: &mut R) -> Option<Loot> {
+        let (min, max) = self.gold;
+        let gold = rng.gen_range(min, max + 1);
+        let item = if rng.gen_range(0, 100) < self.special_chance {
+            Some(weights.roll(depth, LootContext::Vault, rng))
+        } else if rng.gen_range(0, 100) < self.equipment_chance {
+            Some(weights.roll(depth, LootContext::Drop, rng))
+        } else {
+            None
+        };
+        if gold == 0 && item.is_none() {
+            None
+        } else {
+            Some(Loot { gold, item })
+        }
+    }
+}
+
+impl Loot {
+    /// Combine two piles of loot on the same tile.
+    ///
+    /// `None` if both piles hold an item, since a pile only has room for one.
+    pub fn merge(self, other: Loot) -> Option<Loot> {
+        match (self.item, other.item) {
+            (Some(_), Some(_)) => None,
+            (item, other_item) => Some(Loot {
+                gold: self.gold + other.gold,
+                item: item.or(other_item),
+            }),
+        }
+    }
+}
+
+impl Default for LootWeights {
+    fn default() -> Self {
+        LootWeights {
+            base: [70, 22, 7, 1],
+            per_depth: [0, 2, 1, 0],
+            vault_bonus_depth: 5,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::IsaacRng;
+
+    #[test]
+    fn test_vaults_roll_higher() {
+        let weights = LootWeights::default();
+        let mut rng = IsaacRng::new_from_u64(0);
+        let mut count_rare = |context| {
+            (0..1000)
+                .filter(|_| weights.roll(3, context, &mut rng) >= Tier::Rare)
+                .count()
+        };
+        let floor = count_rare(LootContext::Floor);
+        let vault = count_rare(LootContext::Vault);
+        assert!(vault > floor);
+        assert_eq!(
+            weights.weights(1, LootContext::Drop),
+            weights.weights(1, LootContext::Floor)
+        );
+    }
+
+    #[test]
+    fn test_mob_loot() {
+        let weights = LootWeights::default();
+        let mut rng = IsaacRng::new_from_u64(0);
+        assert_eq!(MobLoot::of(Species::Hero).roll(&weights, 1, &mut rng), None);
+        let skeleton = MobLoot::of(Species::Skeleton);
+        for _ in 0..100 {
+            let loot = skeleton.roll(&weights, 1, &mut rng).unwrap();
+            assert!(loot.gold >= 1 && loot.gold <= 10);
+        }
+    }
+}
@@ -1,41 +1,289 @@
 //! A minheap that separates values from priorities.
 //! Intended for use in astar.
+//!
+//! Values with the same priority come out in the order they were pushed,
+//! so searches that use the heap break ties the same way on every run.
+//! `IndexedMinHeap` holds each value at most once, and lowers the priority
+//! of a value pushed again rather than keeping both. `BucketQueue` does the
+//! same for small whole-number priorities, with a bucket for each, so that
+//! pushing and popping don't have to keep a heap in order.
 
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::Hash;
 
-pub struct MinHeap<V: Eq, P: Ord>(BinaryHeap<MinHeapItem<V, P>>);
+/// A queue that gives back the value with the lowest whole-number priority first.
+///
+/// Each value is queued at most once. Both `IndexedMinHeap` and
+/// `BucketQueue` are, and give values back in the same order.
+pub trait PriorityQueue<V> {
+    /// Add a value, or lower its priority if it is already queued.
+    fn push(&mut self, value: V, priority: u32);
+
+    /// The value with the lowest priority, or of those, the one pushed or lowered first.
+    fn pop(&mut self) -> Option<V>;
+
+    /// How many values are queued.
+    fn len(&self) -> usize;
+}
+
+pub struct MinHeap<V: Eq, P: Ord> {
+    heap: BinaryHeap<MinHeapItem<V, P>>,
+    /// How many values have been pushed, which numbers the next one.
+    pushed: u64,
+}
 
 #[derive(Eq, PartialEq)]
 struct MinHeapItem<V: Eq, P: Ord> {
     value: V,
     priority: P,
+    sequence: u64,
 }
 
 impl<V: Eq, P: Ord> MinHeap<V, P> {
     pub fn new() -> Self {
-        MinHeap(BinaryHeap::new())
+        MinHeap {
+            heap: BinaryHeap::new(),
+            pushed: 0,
+        }
     }
 
     pub fn push(&mut self, value: V, priority: P) {
-        self.0.push(MinHeapItem { value, priority })
+        let sequence = self.pushed;
+        self.pushed += 1;
+        self.heap.push(MinHeapItem {
+            value,
+            priority,
+            sequence,
+        })
+    }
+
+    /// The priority of the value `pop` would return next.
+    pub fn peek_priority(&self) -> Option<&P> {
+        self.heap.peek().map(|item| &item.priority)
     }
 
+    /// The value with the lowest priority, or of those, the one pushed first.
     pub fn pop(&mut self) -> Option<V> {
-        match self.0.pop() {
+        match self.heap.pop() {
             Some(item) => Some(item.value),
             None => None,
         }
     }
 }
 
+/// A minheap that knows where each of its values is, so it can lower the
+/// priority of one in place.
+pub struct IndexedMinHeap<V: Hash + Eq + Clone, P: Ord> {
+    /// A binary heap, smallest first.
+    items: Vec<MinHeapItem<V, P>>,
+    /// The index in `items` of each value.
+    slots: HashMap<V, usize>,
+    /// How many values have been pushed, which numbers the next one.
+    pushed: u64,
+}
+
+impl<V: Hash + Eq + Clone, P: Ord> IndexedMinHeap<V, P> {
+    pub fn new() -> Self {
+        IndexedMinHeap {
+            items: Vec::new(),
+            slots: HashMap::new(),
+            pushed: 0,
+        }
+    }
+
+    pub fn contains(&self, value: &V) -> bool {
+        self.slots.contains_key(value)
+    }
+
+    /// Add a value, or lower its priority if it is already in the heap.
+    ///
+    /// A value already in the heap with the same or a lower priority is left as it is.
+    pub fn push(&mut self, value: V, priority: P) {
+        if self.contains(&value) {
+            self.decrease_key(&value, priority);
+            return;
+        }
+        let sequence = self.next_sequence();
+        let index = self.items.len();
+        self.slots.insert(value.clone(), index);
+        self.items.push(MinHeapItem {
+            value,
+            priority,
+            sequence,
+        });
+        self.sift_up(index);
+    }
+
+    /// Lower the priority of a value in the heap.
+    ///
+    /// Returns whether it was lowered. A value lowered to the same priority
+    /// as others comes out after them, as if it had just been pushed.
+    pub fn decrease_key(&mut self, value: &V, priority: P) -> bool {
+        let index = match self.slots.get(value) {
+            Some(&index) => index,
+            None => return false,
+        };
+        if priority >= self.items[index].priority {
+            return false;
+        }
+        let sequence = self.next_sequence();
+        self.items[index].priority = priority;
+        self.items[index].sequence = sequence;
+        self.sift_up(index);
+        true
+    }
+
+    /// The value with the lowest priority, or of those, the one pushed or lowered first.
+    pub fn pop(&mut self) -> Option<V> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let item = self.items.swap_remove(0);
+        self.slots.remove(&item.value);
+        if !self.items.is_empty() {
+            self.slots.insert(self.items[0].value.clone(), 0);
+            self.sift_down(0);
+        }
+        Some(item.value)
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        let sequence = self.pushed;
+        self.pushed += 1;
+        sequence
+    }
+
+    /// Whether the item at one index should come out before the item at another.
+    fn precedes(&self, a: usize, b: usize) -> bool {
+        self.items[a] > self.items[b]
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.items.swap(a, b);
+        self.slots.insert(self.items[a].value.clone(), a);
+        self.slots.insert(self.items[b].value.clone(), b);
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if !self.precedes(index, parent) {
+                break;
+            }
+            self.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let mut first = index;
+            for child in [2 * index + 1, 2 * index + 2] {
+                if child < self.items.len() && self.precedes(child, first) {
+                    first = child;
+                }
+            }
+            if first == index {
+                break;
+            }
+            self.swap(index, first);
+            index = first;
+        }
+    }
+}
+
+impl<V: Hash + Eq + Clone> PriorityQueue<V> for IndexedMinHeap<V, u32> {
+    fn push(&mut self, value: V, priority: u32) {
+        IndexedMinHeap::push(self, value, priority)
+    }
+
+    fn pop(&mut self) -> Option<V> {
+        IndexedMinHeap::pop(self)
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+/// A priority queue with a bucket of values for each priority.
+///
+/// Pushing is constant time, and popping only ever moves forward through
+/// the buckets, so it is fastest when priorities are small and the lowest
+/// rises steadily, as in a search. A value whose priority is lowered is
+/// queued again in its new bucket, and its old entry is skipped when
+/// reached.
+pub struct BucketQueue<V: Hash + Eq + Clone> {
+    /// The values queued at each priority, with the push that queued them.
+    buckets: Vec<VecDeque<(V, u64)>>,
+    /// The priority of each value still queued, and the push that queued it there.
+    queued: HashMap<V, (u32, u64)>,
+    /// No bucket before this one holds a value still queued.
+    lowest: usize,
+    /// How many values have been pushed, which numbers the next one.
+    pushed: u64,
+}
+
+impl<V: Hash + Eq + Clone> BucketQueue<V> {
+    /// A queue with buckets ready for priorities up to `max_priority`.
+    ///
+    /// Higher priorities can still be pushed, but cost more the first time.
+    pub fn new(max_priority: u32) -> Self {
+        BucketQueue {
+            buckets: (0..=max_priority).map(|_| VecDeque::new()).collect(),
+            queued: HashMap::new(),
+            lowest: 0,
+            pushed: 0,
+        }
+    }
+}
+
+impl<V: Hash + Eq + Clone> PriorityQueue<V> for BucketQueue<V> {
+    fn push(&mut self, value: V, priority: u32) {
+        if self
+            .queued
+            .get(&value)
+            .is_some_and(|&(queued, _)| queued <= priority)
+        {
+            return;
+        }
+        let sequence = self.pushed;
+        self.pushed += 1;
+        let index = priority as usize;
+        if index >= self.buckets.len() {
+            self.buckets.resize_with(index + 1, VecDeque::new);
+        }
+        self.queued.insert(value.clone(), (priority, sequence));
+        self.buckets[index].push_back((value, sequence));
+        self.lowest = self.lowest.min(index);
+    }
+
+    fn pop(&mut self) -> Option<V> {
+        while self.lowest < self.buckets.len() {
+            while let Some((value, sequence)) = self.buckets[self.lowest].pop_front() {
+                if self.queued.get(&value) == Some(&(self.lowest as u32, sequence)) {
+                    self.queued.remove(&value);
+                    return Some(value);
+                }
+            }
+            self.lowest += 1;
+        }
+        None
+    }
+
+    fn len(&self) -> usize {
+        self.queued.len()
+    }
+}
+
 impl<V: Eq, P: Ord> Ord for MinHeapItem<V, P> {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.priority.cmp(&other.priority) {
-            Ordering::Equal => Ordering::Equal,
-            Ordering::Greater => Ordering::Less,
-            Ordering::Less => Ordering::Greater,
-        }
+        // Reversed, since `BinaryHeap` pops the greatest item first.
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
     }
 }
 
@@ -44,3 +292,67 @@ impl<V: Eq, P: Ord> PartialOrd for MinHeapItem<V, P> {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ties_pop_in_push_order() {
+        let mut heap = MinHeap::new();
+        for (value, priority) in [(0, 2), (1, 1), (2, 2), (3, 1), (4, 0), (5, 2)].iter() {
+            heap.push(*value, *priority);
+        }
+        let mut popped = Vec::new();
+        while let Some(value) = heap.pop() {
+            popped.push(value);
+        }
+        assert_eq!(popped, vec![4, 1, 3, 0, 2, 5]);
+    }
+
+    #[test]
+    fn test_indexed_heap_lowers_in_place() {
+        let mut heap = IndexedMinHeap::new();
+        for (value, priority) in [(0, 5), (1, 3), (2, 4), (3, 3)].iter() {
+            heap.push(*value, *priority);
+        }
+        heap.push(0, 6);
+        assert!(heap.decrease_key(&2, 3));
+        assert!(!heap.decrease_key(&1, 3));
+        assert!(!heap.decrease_key(&7, 0));
+        heap.push(0, 1);
+        let mut popped = Vec::new();
+        while let Some(value) = heap.pop() {
+            assert!(!heap.contains(&value));
+            popped.push(value);
+        }
+        assert_eq!(popped, vec![0, 1, 3, 2]);
+    }
+
+    #[test]
+    fn test_bucket_queue_matches_indexed_heap() {
+        let mut heap = IndexedMinHeap::new();
+        let mut buckets = BucketQueue::new(10);
+        let mut popped = (Vec::new(), Vec::new());
+        // Values and priorities that collide often, with pops mixed in.
+        for i in 0..500u32 {
+            let value = (i * 7) % 23;
+            let priority = (i * 13) % 17 + i / 50;
+            if i % 5 == 4 {
+                popped.0.extend(PriorityQueue::pop(&mut heap));
+                popped.1.extend(buckets.pop());
+            } else {
+                PriorityQueue::push(&mut heap, value, priority);
+                buckets.push(value, priority);
+            }
+        }
+        while let Some(value) = PriorityQueue::pop(&mut heap) {
+            popped.0.push(value);
+        }
+        while let Some(value) = buckets.pop() {
+            popped.1.push(value);
+        }
+        assert_eq!(popped.0, popped.1);
+        assert!(buckets.pop().is_none());
+    }
+}
@@ -0,0 +1,215 @@
+//! Names for the places and things of a run.
+//!
+//! A name is strung together from syllables drawn from the tables of a
+//! `Theme`, so the depths sound different from the beasts in them. Each
+//! syllable is picked knowing how the last one ended, so a name never
+//! jams two clusters of consonants together. A name comes from its seed
+//! alone, so a run gives everything the same name each time it is played.
+
+use util::mix_seed;
+use world::mob::capitalize;
+
+/// What is being named.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Theme {
+    /// Levels and regions of the caves.
+    Place,
+    /// Unique mobs.
+    Beast,
+    /// Artifacts.
+    Relic,
+}
+
+/// The sounds a theme makes its words from.
+struct Syllables {
+    /// Consonants that can follow a vowel, the first few of them single letters.
+    onsets: &'static [&'static str],
+    /// How many of the onsets are single letters.
+    single_onsets: usize,
+    vowels: &'static [&'static str],
+    /// Endings of syllables, where an empty one leaves the vowel open.
+    codas: &'static [&'static str],
+    /// The fewest and most syllables in a word.
+    lengths: (u64, u64),
+}
+
+const PLACE_SYLLABLES: Syllables = Syllables {
+    onsets: &[
+        "b", "d", "g", "h", "k", "m", "n", "r", "s", "v", "z", "dr", "gr", "kh", "sk", "th",
+    ],
+    single_onsets: 11,
+    vowels: &["a", "e", "i", "o", "u", "ae", "au", "ei"],
+    codas: &["", "", "", "l", "m", "n", "r", "rn", "sk", "th"],
+    lengths: (2, 3),
+};
+
+const BEAST_SYLLABLES: Syllables = Syllables {
+    onsets: &[
+        "g", "k", "m", "n", "r", "s", "t", "v", "z", "gr", "kr", "sh", "sk", "th", "vr", "zh",
+    ],
+    single_onsets: 9,
+    vowels: &["a", "o", "u", "i", "aa", "uu"],
+    codas: &["", "", "g", "k", "x", "z", "sh", "rk", "th"],
+    lengths: (1, 3),
+};
+
+const RELIC_SYLLABLES: Syllables = Syllables {
+    onsets: &["c", "f", "l", "m", "n", "s", "t", "v", "ph", "sel", "th"],
+    single_onsets: 8,
+    vowels: &["a", "e", "i", "y", "ae", "ia", "io"],
+    codas: &["", "", "", "l", "n", "s", "th", "ra"],
+    lengths: (2, 3),
+};
+
+const PLACE_NOUNS: [&str; 10] = [
+    "Hollow", "Deeps", "Warrens", "Reach", "Vaults", "Halls", "Grotto", "Maw", "Throat", "Sink",
+];
+const PLACE_ADJECTIVES: [&str; 8] = [
+    "Drowned", "Silent", "Crooked", "Ashen", "Weeping", "Hungry", "Sunken", "Pale",
+];
+const EPITHETS: [&str; 8] = [
+    "the Gnawer",
+    "the Hungry",
+    "the Old",
+    "the Blind",
+    "the Patient",
+    "the Pale",
+    "Bonebreaker",
+    "the Unseen",
+];
+const RELICS: [&str; 8] = [
+    "Lantern", "Blade", "Crown", "Ring", "Mantle", "Horn", "Staff", "Shard",
+];
+
+/// Rolls drawn one after another from a seed.
+struct Rolls {
+    seed: u64,
+    drawn: u64,
+}
+
+impl Rolls {
+    fn new(seed: u64, theme: Theme) -> Self {
+        let salt = match theme {
+            Theme::Place => 1,
+            Theme::Beast => 2,
+            Theme::Relic => 3,
+        };
+        Rolls {
+            seed: mix_seed(seed, salt),
+            drawn: 0,
+        }
+    }
+
+    /// A number from `low` up to and including `high`.
+    fn between(&mut self, low: u64, high: u64) -> u64 {
+        let roll = mix_seed(self.seed, self.drawn);
+        self.drawn += 1;
+        low + roll % (high - low + 1)
+    }
+
+    fn pick<'a>(&mut self, words: &[&'a str]) -> &'a str {
+        words[self.between(0, words.len() as u64 - 1) as usize]
+    }
+}
+
+impl Theme {
+    fn syllables(self) -> &'static Syllables {
+        match self {
+            Theme::Place => &PLACE_SYLLABLES,
+            Theme::Beast => &BEAST_SYLLABLES,
+            Theme::Relic => &RELIC_SYLLABLES,
+        }
+    }
+}
+
+/// A made-up word in the sound of a theme, capitalized.
+pub fn word(seed: u64, theme: Theme) -> String {
+    make_word(&mut Rolls::new(seed, theme), theme.syllables())
+}
+
+fn make_word(rolls: &mut Rolls, syllables: &Syllables) -> String {
+    let (fewest, most) = syllables.lengths;
+    let mut word = String::new();
+    let mut closed = false;
+    for _ in 0..rolls.between(fewest, most) {
+        // After a consonant, only a single one follows, so none pile up.
+        let onsets = if closed {
+            &syllables.onsets[..syllables.single_onsets]
+        } else {
+            syllables.onsets
+        };
+        word.push_str(rolls.pick(onsets));
+        word.push_str(rolls.pick(syllables.vowels));
+        let coda = rolls.pick(syllables.codas);
+        word.push_str(coda);
+        closed = !coda.is_empty();
+    }
+    capitalize(&word)
+}
+
+/// The name of a level or region of the caves.
+pub fn place_name(seed: u64) -> String {
+    let mut rolls = Rolls::new(seed, Theme::Place);
+    let noun = rolls.pick(&PLACE_NOUNS);
+    match rolls.between(0, 2) {
+        0 => format!("The {} {}", rolls.pick(&PLACE_ADJECTIVES), noun),
+        1 => format!(
+            "The {} of {}",
+            noun,
+            make_word(&mut rolls, &PLACE_SYLLABLES)
+        ),
+        _ => format!("{} {}", make_word(&mut rolls, &PLACE_SYLLABLES), noun),
+    }
+}
+
+/// The name of a unique mob.
+pub fn beast_name(seed: u64) -> String {
+    let mut rolls = Rolls::new(seed, Theme::Beast);
+    let name = make_word(&mut rolls, &BEAST_SYLLABLES);
+    format!("{} {}", name, rolls.pick(&EPITHETS))
+}
+
+/// The name of an artifact.
+pub fn relic_name(seed: u64) -> String {
+    let mut rolls = Rolls::new(seed, Theme::Relic);
+    let relic = rolls.pick(&RELICS);
+    format!(
+        "The {} of {}",
+        relic,
+        make_word(&mut rolls, &RELIC_SYLLABLES)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_names_follow_the_seed() {
+        for seed in 0..50 {
+            assert_eq!(place_name(seed), place_name(seed));
+            assert_eq!(beast_name(seed), beast_name(seed));
+            assert_eq!(relic_name(seed), relic_name(seed));
+        }
+        let places: HashSet<String> = (0..50).map(place_name).collect();
+        assert!(places.len() > 40);
+        assert_ne!(word(7, Theme::Place), word(7, Theme::Beast));
+    }
+
+    #[test]
+    fn test_words_are_pronounceable() {
+        for seed in 0..200 {
+            for &theme in &[Theme::Place, Theme::Beast, Theme::Relic] {
+                let word = word(seed, theme).to_lowercase();
+                assert!(word.chars().all(|c| c.is_ascii_lowercase()));
+                let mut run = 0;
+                for c in word.chars() {
+                    run = if "aeiouy".contains(c) { 0 } else { run + 1 };
+                    assert!(run <= 4, "{} has too many consonants in a row", word);
+                }
+            }
+        }
+    }
+}
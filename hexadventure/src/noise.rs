@@ -0,0 +1,162 @@
+//! Seedable gradient noise sampled on the hex grid.
+//!
+//! Positions are projected onto the plane using their on-screen location,
+//! scaled so that neighboring hexes are one unit apart,
+//! and then sampled with two dimensional Perlin noise.
+//!
+//! Samples are fixed-point numbers with `FRACTION_BITS` bits after the
+//! point, and only integer math is used, so the same seed gives the same
+//! noise on every platform.
+
+use grid::{pos_to_location, Location, Pos};
+use rand::{IsaacRng, Rng};
+
+const TABLE_SIZE: usize = 256;
+
+/// How many bits of a sample come after the point.
+pub const FRACTION_BITS: u32 = 16;
+/// A sample of one.
+pub const ONE: i32 = 1 << FRACTION_BITS;
+
+/// Horizontal distance between the centers of adjacent hexes in the same row.
+const HEX_WIDTH: i64 = ONE as i64;
+/// Vertical distance between the centers of hexes in adjacent rows, sqrt(3)/2.
+const ROW_HEIGHT: i64 = 56_756;
+/// The square root of two.
+const SQRT_2: i64 = 92_682;
+
+/// A deterministic noise field.
+///
+/// Two noise fields created from the same seed always produce the same samples.
+pub struct Noise {
+    permutation: Vec<u8>,
+    scale: u32,
+}
+
+impl Noise {
+    /// Create a noise field whose features are roughly `scale` hexes wide.
+    pub fn new(seed: u64, scale: u32) -> Self {
+        assert!(scale > 0, "Noise needs a scale of at least one hex.");
+        let mut rng = IsaacRng::new_from_u64(seed);
+        let mut permutation: Vec<u8> = (0..TABLE_SIZE).map(|i| i as u8).collect();
+        rng.shuffle(&mut permutation);
+        Noise { permutation, scale }
+    }
+
+    /// Sample the noise at a position. The result ranges from `-ONE` to `ONE`.
+    pub fn sample(&self, pos: Pos) -> i32 {
+        let Location { x, y } = pos_to_location(pos);
+        let scale = i64::from(self.scale);
+        let x = i64::from(x) * HEX_WIDTH / 2 / scale;
+        let y = i64::from(y) * ROW_HEIGHT / scale;
+        self.perlin(x, y)
+    }
+
+    /// Sample the noise at a position, rescaled to range from 0 to `ONE`.
+    pub fn sample_unit(&self, pos: Pos) -> i32 {
+        (self.sample(pos) + ONE) / 2
+    }
+
+    fn perlin(&self, x: i64, y: i64) -> i32 {
+        // Shifting right rounds toward negative infinity, like floor.
+        let (ix, iy) = ((x >> FRACTION_BITS) as i32, (y >> FRACTION_BITS) as i32);
+        let one = i64::from(ONE);
+        let (fx, fy) = (x & (one - 1), y & (one - 1));
+        let n00 = self.gradient(ix, iy, fx, fy);
+        let n10 = self.gradient(ix + 1, iy, fx - one, fy);
+        let n01 = self.gradient(ix, iy + 1, fx, fy - one);
+        let n11 = self.gradient(ix + 1, iy + 1, fx - one, fy - one);
+        let (u, v) = (fade(fx), fade(fy));
+        let value = lerp(v, lerp(u, n00, n10), lerp(u, n01, n11));
+        // The largest possible value of two dimensional perlin noise is sqrt(1/2).
+        let value = (value * SQRT_2) >> FRACTION_BITS;
+        value.max(-one).min(one) as i32
+    }
+
+    /// Dot product of the lattice point's gradient with the offset from it.
+    fn gradient(&self, ix: i32, iy: i32, dx: i64, dy: i64) -> i64 {
+        let hash = self.hash(ix, iy);
+        match hash % 8 {
+            0 => dx + dy,
+            1 => dx - dy,
+            2 => -dx + dy,
+            3 => -dx - dy,
+            4 => dx,
+            5 => -dx,
+            6 => dy,
+            _ => -dy,
+        }
+    }
+
+    fn hash(&self, ix: i32, iy: i32) -> u8 {
+        let wrap = |i: i32| (i & (TABLE_SIZE as i32 - 1)) as usize;
+        let a = self.permutation[wrap(ix)] as i32;
+        self.permutation[wrap(a + iy)]
+    }
+}
+
+/// Multiply two fixed-point numbers.
+fn mul(a: i64, b: i64) -> i64 {
+    (a * b) >> FRACTION_BITS
+}
+
+/// Perlin's smootherstep curve.
+fn fade(t: i64) -> i64 {
+    let one = i64::from(ONE);
+    let cubed = mul(mul(t, t), t);
+    mul(cubed, mul(t, mul(t, 6 * one) - 15 * one) + 10 * one)
+}
+
+fn lerp(t: i64, a: i64, b: i64) -> i64 {
+    a + mul(t, b - a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid;
+
+    #[test]
+    fn test_deterministic() {
+        let a = Noise::new(42, 8);
+        let b = Noise::new(42, 8);
+        assert!(grid::positions().all(|pos| a.sample(pos) == b.sample(pos)));
+    }
+
+    #[test]
+    fn test_range() {
+        let noise = Noise::new(7, 5);
+        for pos in grid::positions() {
+            let value = noise.sample(pos);
+            assert!((-ONE..=ONE).contains(&value));
+            assert!((0..=ONE).contains(&noise.sample_unit(pos)));
+        }
+    }
+
+    /// Samples depend only on the seed, never on the platform.
+    #[test]
+    fn test_samples_pinned() {
+        let noise = Noise::new(11, 6);
+        let total: i64 = grid::positions()
+            .map(|pos| i64::from(noise.sample(pos)))
+            .sum();
+        assert_eq!(total, -2_010_439);
+        let samples: Vec<i32> = grid::positions()
+            .step_by(97)
+            .take(4)
+            .map(|pos| noise.sample(pos))
+            .collect();
+        assert_eq!(samples, vec![0, -19_527, 32_627, -11_313]);
+    }
+
+    #[test]
+    fn test_coherent() {
+        let noise = Noise::new(3, 10);
+        for pos in grid::inner_positions() {
+            for neighbor in pos.neighbors() {
+                assert!((noise.sample(pos) - noise.sample(neighbor)).abs() < ONE / 2);
+            }
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! What a player carries from one run to the next.
+//!
+//! A profile is kept apart from saves, so it survives when a run ends or
+//! a save is lost. New games start with the options of the profile, and
+//! finished runs are added to its statistics.
+
+use std::fmt;
+use world::auto::{AutoStop, GameOptions};
+use world::World;
+
+/// A player's settings and progress across every run.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// The options new games start with.
+    pub options: GameOptions,
+    pub stats: LifetimeStats,
+    /// Achievements earned so far, in the order they were earned.
+    pub achievements: Vec<Achievement>,
+    /// The names of the backgrounds and challenges unlocked so far.
+    pub unlocked: Vec<String>,
+    /// The id of the last run recorded, so a run isn't counted twice.
+    ///
+    /// Profiles stored before runs had ids hold a seed here instead, which
+    /// matches no run.
+    last_run: Option<u64>,
+}
+
+/// Totals over every finished run.
+#[derive(PartialEq, Eq, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LifetimeStats {
+    pub runs: u32,
+    pub deaths: u32,
+    /// The deepest level any run reached.
+    pub deepest: u32,
+    pub turns: u64,
+}
+
+/// A milestone reached in some run.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Achievement {
+    /// Reached a level at least this deep.
+    ReachedDepth(u32),
+}
+
+/// A background or challenge a profile can unlock.
+pub struct Unlock {
+    pub name: &'static str,
+    pub kind: UnlockKind,
+    /// What must be achieved first, if anything.
+    pub requires: Option<Achievement>,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum UnlockKind {
+    /// Who the character was before they came to the caves.
+    Background,
+    /// A harder way to play.
+    Challenge,
+    /// Something learned in earlier runs that every new character knows.
+    Knowledge,
+}
+
+/// An option offered when creating a character.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct Choice {
+    pub name: &'static str,
+    pub kind: UnlockKind,
+    pub unlocked: bool,
+    /// What must be achieved to unlock it, if anything.
+    pub requires: Option<Achievement>,
+}
+
+/// Every background and challenge, in the order they are offered.
+pub const UNLOCKS: [Unlock; 5] = [
+    Unlock {
+        name: "wanderer",
+        kind: UnlockKind::Background,
+        requires: None,
+    },
+    Unlock {
+        name: "delver",
+        kind: UnlockKind::Background,
+        requires: Some(Achievement::ReachedDepth(5)),
+    },
+    Unlock {
+        name: "spelunker",
+        kind: UnlockKind::Background,
+        requires: Some(Achievement::ReachedDepth(10)),
+    },
+    Unlock {
+        name: "no rest",
+        kind: UnlockKind::Challenge,
+        requires: Some(Achievement::ReachedDepth(15)),
+    },
+    Unlock {
+        name: "warding",
+        kind: UnlockKind::Knowledge,
+        requires: Some(Achievement::ReachedDepth(20)),
+    },
+];
+
+/// Every version of the stored profile format.
+///
+/// Profiles are stored as this enum, so an old profile still reads as the
+/// variant it was written as. Add a variant to change the format, and
+/// teach `migrate` to bring the old one up to date.
+#[derive(Serialize, Deserialize)]
+pub enum StoredProfile {
+    V1(ProfileV1),
+    V2(ProfileV2),
+    V3(Profile),
+}
+
+/// A profile as first stored, before the options knew about wards.
+///
+/// Frozen: never change it, since stored profiles are read by position.
+#[derive(Serialize, Deserialize)]
+pub struct ProfileV1 {
+    name: String,
+    options: OptionsV1,
+    stats: LifetimeStats,
+    achievements: Vec<Achievement>,
+    unlocked: Vec<String>,
+    last_run: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OptionsV1 {
+    auto_stop: AutoStop,
+    adaptive_pacing: bool,
+}
+
+/// A profile as stored once the options knew about wards, but not yet about
+/// the detailed combat log.
+///
+/// Frozen: never change it, since stored profiles are read by position.
+#[derive(Serialize, Deserialize)]
+pub struct ProfileV2 {
+    name: String,
+    options: OptionsV2,
+    stats: LifetimeStats,
+    achievements: Vec<Achievement>,
+    unlocked: Vec<String>,
+    last_run: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OptionsV2 {
+    auto_stop: AutoStop,
+    adaptive_pacing: bool,
+    wards: bool,
+}
+
+/// Depths that earn an achievement when reached.
+const DEPTH_MILESTONES: [u32; 4] = [5, 10, 15, 20];
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Profile {
+            name: name.to_owned(),
+            options: GameOptions::default(),
+            stats: LifetimeStats::default(),
+            achievements: Vec::new(),
+            unlocked: Vec::new(),
+            last_run: None,
+        }
+    }
+
+    /// Start a new game with the options of this profile, and what it has unlocked.
+    pub fn apply(&self, world: &mut World) {
+        *world.options_mut() = self.options.clone();
+        world.options_mut().wards = self.unlocked.iter().any(|name| name == "warding");
+    }
+
+    /// Add a run to the statistics and achievements.
+    ///
+    /// Returns the achievements it earned. A run already recorded is ignored.
+    pub fn record_run(&mut self, world: &World) -> Vec<Achievement> {
+        if self.last_run == Some(world.run_id()) {
+            return Vec::new();
+        }
+        self.last_run = Some(world.run_id());
+        self.stats.runs += 1;
+        if world.is_over() {
+            self.stats.deaths += 1;
+        }
+        self.stats.deepest = self.stats.deepest.max(world.depth());
+        self.stats.turns += world.turn();
+        let mut earned = Vec::new();
+        for &depth in &DEPTH_MILESTONES {
+            let achievement = Achievement::ReachedDepth(depth);
+            if world.depth() >= depth && !self.achievements.contains(&achievement) {
+                self.achievements.push(achievement);
+                earned.push(achievement);
+            }
+        }
+        for unlock in &UNLOCKS {
+            let met = match unlock.requires {
+                Some(achievement) => self.achievements.contains(&achievement),
+                None => true,
+            };
+            if met && !self.unlocked.iter().any(|name| name == unlock.name) {
+                self.unlocked.push(unlock.name.to_owned());
+            }
+        }
+        earned
+    }
+
+    /// Every background and challenge, and whether this profile has unlocked it.
+    pub fn choices(&self) -> Vec<Choice> {
+        UNLOCKS
+            .iter()
+            .map(|unlock| Choice {
+                name: unlock.name,
+                kind: unlock.kind,
+                unlocked: unlock.requires.is_none()
+                    || self.unlocked.iter().any(|name| name == unlock.name),
+                requires: unlock.requires,
+            })
+            .collect()
+    }
+
+    /// Wrap the profile in the current version of the stored format.
+    pub fn store(&self) -> StoredProfile {
+        StoredProfile::V3(self.clone())
+    }
+}
+
+impl StoredProfile {
+    /// Bring a stored profile up to date.
+    pub fn migrate(self) -> Profile {
+        match self {
+            StoredProfile::V1(profile) => Profile {
+                name: profile.name,
+                options: GameOptions {
+                    auto_stop: profile.options.auto_stop,
+                    adaptive_pacing: profile.options.adaptive_pacing,
+                    ..GameOptions::default()
+                },
+                stats: profile.stats,
+                achievements: profile.achievements,
+                unlocked: profile.unlocked,
+                last_run: profile.last_run,
+            },
+            StoredProfile::V2(profile) => Profile {
+                name: profile.name,
+                options: GameOptions {
+                    auto_stop: profile.options.auto_stop,
+                    adaptive_pacing: profile.options.adaptive_pacing,
+                    wards: profile.options.wards,
+                    ..GameOptions::default()
+                },
+                stats: profile.stats,
+                achievements: profile.achievements,
+                unlocked: profile.unlocked,
+                last_run: profile.last_run,
+            },
+            StoredProfile::V3(profile) => profile,
+        }
+    }
+}
+
+impl fmt::Display for Achievement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Achievement::ReachedDepth(depth) => write!(f, "reach level {}", depth),
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::new("Adventurer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bincode;
+    use level::GenParams;
+
+    #[test]
+    fn test_run_recorded_once() {
+        let mut profile = Profile::default();
+        let mut world = World::from_seed(4, GenParams::default());
+        world.rest_for(3);
+        profile.record_run(&world);
+        profile.record_run(&world);
+        assert_eq!(
+            profile.stats,
+            LifetimeStats {
+                runs: 1,
+                deaths: 0,
+                deepest: 1,
+                turns: world.turn(),
+            }
+        );
+        assert!(profile.achievements.is_empty());
+        let replay = World::from_seed(4, GenParams::default());
+        profile.record_run(&replay);
+        assert_eq!(profile.stats.runs, 2);
+    }
+
+    #[test]
+    fn test_depth_unlocks() {
+        let mut profile = Profile::default();
+        assert_eq!(
+            profile
+                .choices()
+                .iter()
+                .filter(|choice| choice.unlocked)
+                .count(),
+            1
+        );
+        profile.achievements.push(Achievement::ReachedDepth(5));
+        let world = World::from_seed(4, GenParams::default());
+        profile.record_run(&world);
+        let choices = profile.choices();
+        let delver = choices
+            .iter()
+            .find(|choice| choice.name == "delver")
+            .unwrap();
+        assert!(delver.unlocked);
+        let spelunker = choices
+            .iter()
+            .find(|choice| choice.name == "spelunker")
+            .unwrap();
+        assert!(!spelunker.unlocked);
+        assert_eq!(spelunker.requires, Some(Achievement::ReachedDepth(10)));
+    }
+
+    #[test]
+    fn test_options_applied() {
+        let mut profile = Profile::default();
+        profile.options.adaptive_pacing = true;
+        let mut world = World::from_seed(4, GenParams::default());
+        profile.store().migrate().apply(&mut world);
+        assert!(world.options().adaptive_pacing);
+    }
+
+    #[test]
+    fn test_v1_profile_migrated() {
+        let v1 = StoredProfile::V1(ProfileV1 {
+            name: "Old Hand".to_owned(),
+            options: OptionsV1 {
+                auto_stop: AutoStop::default(),
+                adaptive_pacing: true,
+            },
+            stats: LifetimeStats {
+                runs: 12,
+                deaths: 11,
+                deepest: 21,
+                turns: 9000,
+            },
+            achievements: vec![Achievement::ReachedDepth(20)],
+            unlocked: vec!["warding".to_owned()],
+            last_run: None,
+        });
+        let bytes = bincode::serialize(&v1).unwrap();
+        let stored: StoredProfile = bincode::deserialize(&bytes).unwrap();
+        let profile = stored.migrate();
+        assert_eq!(profile.name, "Old Hand");
+        assert_eq!(profile.stats.runs, 12);
+        assert_eq!(profile.unlocked, vec!["warding".to_owned()]);
+        assert!(profile.options.adaptive_pacing);
+        let mut world = World::from_seed(4, GenParams::default());
+        profile.apply(&mut world);
+        assert!(world.options().wards);
+    }
+
+    #[test]
+    fn test_v2_profile_migrated() {
+        let v2 = StoredProfile::V2(ProfileV2 {
+            name: "Old Hand".to_owned(),
+            options: OptionsV2 {
+                auto_stop: AutoStop::default(),
+                adaptive_pacing: false,
+                wards: true,
+            },
+            stats: LifetimeStats::default(),
+            achievements: Vec::new(),
+            unlocked: Vec::new(),
+            last_run: Some(4),
+        });
+        let bytes = bincode::serialize(&v2).unwrap();
+        let stored: StoredProfile = bincode::deserialize(&bytes).unwrap();
+        let profile = stored.migrate();
+        assert_eq!(profile.name, "Old Hand");
+        assert!(profile.options.wards);
+        assert!(!profile.options.combat_detail);
+    }
+}
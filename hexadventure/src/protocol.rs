@@ -0,0 +1,270 @@
+//! Encoding what a remote client may see as a stream of frames.
+//!
+//! A `Snapshot` holds only what the player can observe.
+//! The `Encoder` turns a sequence of snapshots into keyframes,
+//! which hold a whole snapshot, and deltas, which hold only what changed.
+//! The `Decoder` on the other end rebuilds the snapshots from the frames.
+//!
+//! Every keyframe starts with the version of the protocol, so a client can
+//! tell a stream it doesn't understand from a damaged one. Keyframes must
+//! stay the first variant of `Frame`, with the version as their first field.
+
+use camera::Focus;
+use prelude::*;
+pub use world::ambience::{Ambience, Space};
+pub use world::observation::{MobView, SeenLoot, SeenTile};
+pub use world::senses::Sensed;
+
+/// The version of the frames written by this build.
+///
+/// Bump it whenever a frame changes shape, e.g. when the encoding of grids or
+/// of anything in a snapshot changes.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Everything the player can observe on one turn.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub turn: u64,
+    pub depth: u32,
+    #[serde(with = "grid::rle")]
+    pub tiles: Grid<SeenTile>,
+    /// The active player followed by every other visible mob.
+    pub mobs: Vec<MobView>,
+    /// Mobs the player senses through walls.
+    pub sensed: Vec<Sensed>,
+    /// The loot on every tile the player can see.
+    pub loot: Vec<SeenLoot>,
+    /// Whether the mobs, sensed mobs and loot are as the player
+    /// hallucinates them.
+    pub hallucinating: bool,
+    /// What the camera should be looking at.
+    pub focus: Focus,
+    /// What the player's surroundings are like, for ambient sound and color.
+    pub ambience: Ambience,
+}
+
+/// A unit of the stream sent to a client.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Frame {
+    Keyframe { version: u32, snapshot: Snapshot },
+    Delta(Delta),
+}
+
+/// The changes from one snapshot to the next.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Delta {
+    /// The turn of the snapshot this delta applies to.
+    pub base_turn: u64,
+    pub turn: u64,
+    pub tiles: Vec<(Pos, SeenTile)>,
+    pub mobs: Vec<MobView>,
+    pub sensed: Vec<Sensed>,
+    pub loot: Vec<SeenLoot>,
+    pub hallucinating: bool,
+    pub focus: Focus,
+    pub ambience: Ambience,
+}
+
+/// Turns snapshots into frames.
+pub struct Encoder {
+    last: Option<Snapshot>,
+    keyframe_interval: u32,
+    since_keyframe: u32,
+}
+
+/// Rebuilds snapshots from frames.
+pub struct Decoder {
+    snapshot: Option<Snapshot>,
+}
+
+/// Why a frame couldn't be decoded.
+#[derive(PartialEq, Eq, Debug)]
+pub enum ProtocolError {
+    /// A delta arrived before any keyframe.
+    NoKeyframe,
+    /// A delta was made against a different snapshot than the one held.
+    WrongBase { expected: u64, found: u64 },
+    /// The frames were encoded by a build speaking another version of the protocol.
+    WrongVersion { expected: u32, found: u32 },
+}
+
+impl Snapshot {
+    /// Capture what the active player of a world can observe.
+    pub fn of(world: &World) -> Self {
+        let observation = world.observation();
+        let mut mobs = vec![observation.me];
+        mobs.extend(observation.mobs);
+        Snapshot {
+            turn: observation.turn,
+            depth: observation.depth,
+            tiles: observation.tiles,
+            mobs,
+            sensed: observation.sensed,
+            loot: observation.loot,
+            hallucinating: observation.hallucinating,
+            focus: world.focus(),
+            ambience: observation.ambience,
+        }
+    }
+}
+
+impl Encoder {
+    /// Create an encoder that sends a keyframe at least every `keyframe_interval` frames.
+    pub fn new(keyframe_interval: u32) -> Self {
+        Encoder {
+            last: None,
+            keyframe_interval,
+            since_keyframe: 0,
+        }
+    }
+
+    /// Encode the next snapshot.
+    ///
+    /// Sends a keyframe if this is the first snapshot, if the level changed,
+    /// or if enough frames have passed since the last keyframe.
+    pub fn encode(&mut self, snapshot: Snapshot) -> Frame {
+        let frame = match self.last {
+            Some(ref last)
+                if last.depth == snapshot.depth
+                    && self.since_keyframe + 1 < self.keyframe_interval =>
+            {
+                self.since_keyframe += 1;
+                Frame::Delta(Delta::between(last, &snapshot))
+            }
+            _ => {
+                self.since_keyframe = 0;
+                Frame::Keyframe {
+                    version: PROTOCOL_VERSION,
+                    snapshot: snapshot.clone(),
+                }
+            }
+        };
+        self.last = Some(snapshot);
+        frame
+    }
+
+    /// Send a keyframe next, e.g. when a client reconnects.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+impl Delta {
+    fn between(old: &Snapshot, new: &Snapshot) -> Self {
+        let tiles = grid::positions()
+            .filter(|&pos| old.tiles[pos] != new.tiles[pos])
+            .map(|pos| (pos, new.tiles[pos]))
+            .collect();
+        Delta {
+            base_turn: old.turn,
+            turn: new.turn,
+            tiles,
+            mobs: new.mobs.clone(),
+            sensed: new.sensed.clone(),
+            loot: new.loot.clone(),
+            hallucinating: new.hallucinating,
+            focus: new.focus,
+            ambience: new.ambience,
+        }
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder { snapshot: None }
+    }
+
+    /// Apply a frame, returning the snapshot it describes.
+    pub fn apply(&mut self, frame: Frame) -> Result<&Snapshot, ProtocolError> {
+        match frame {
+            Frame::Keyframe { version, snapshot } => {
+                if version != PROTOCOL_VERSION {
+                    return Err(ProtocolError::WrongVersion {
+                        expected: PROTOCOL_VERSION,
+                        found: version,
+                    });
+                }
+                self.snapshot = Some(snapshot);
+            }
+            Frame::Delta(delta) => {
+                let snapshot = self.snapshot.as_mut().ok_or(ProtocolError::NoKeyframe)?;
+                if snapshot.turn != delta.base_turn {
+                    return Err(ProtocolError::WrongBase {
+                        expected: snapshot.turn,
+                        found: delta.base_turn,
+                    });
+                }
+                snapshot.turn = delta.turn;
+                for (pos, tile) in delta.tiles {
+                    snapshot.tiles[pos] = tile;
+                }
+                snapshot.mobs = delta.mobs;
+                snapshot.sensed = delta.sensed;
+                snapshot.loot = delta.loot;
+                snapshot.hallucinating = delta.hallucinating;
+                snapshot.focus = delta.focus;
+                snapshot.ambience = delta.ambience;
+            }
+        }
+        Ok(self.snapshot.as_ref().expect("A snapshot was just stored."))
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+    use world::action;
+
+    #[test]
+    fn test_round_trip() {
+        let mut world = World::from_seed(1, GenParams::default());
+        let mut encoder = Encoder::new(4);
+        let mut decoder = Decoder::new();
+        for turn in 0..10 {
+            let direction = grid::DIRECTIONS[turn % 6];
+            if action::walk(world.active_player(), direction, &mut world).is_ok() {
+                world.tick();
+            }
+            let snapshot = Snapshot::of(&world);
+            let frame = encoder.encode(snapshot.clone());
+            assert!(*decoder.apply(frame).unwrap() == snapshot);
+        }
+    }
+
+    #[test]
+    fn test_delta_needs_keyframe() {
+        let mut world = World::from_seed(2, GenParams::default());
+        let mut encoder = Encoder::new(10);
+        encoder.encode(Snapshot::of(&world));
+        world.tick();
+        let frame = encoder.encode(Snapshot::of(&world));
+        assert_eq!(
+            Decoder::new().apply(frame).err(),
+            Some(ProtocolError::NoKeyframe)
+        );
+    }
+
+    #[test]
+    fn test_keyframe_from_another_version_refused() {
+        let world = World::from_seed(2, GenParams::default());
+        let frame = Frame::Keyframe {
+            version: PROTOCOL_VERSION + 1,
+            snapshot: Snapshot::of(&world),
+        };
+        assert_eq!(
+            Decoder::new().apply(frame).err(),
+            Some(ProtocolError::WrongVersion {
+                expected: PROTOCOL_VERSION,
+                found: PROTOCOL_VERSION + 1
+            })
+        );
+    }
+}
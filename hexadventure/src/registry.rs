@@ -0,0 +1,96 @@
+//! Typed ids for the things content refers to by name.
+//!
+//! `registry!` declares an enum along with the name each variant goes by in
+//! content, so code that refers to a kind of thing names a variant, and a
+//! misspelled or missing one fails to build. Content read from data can
+//! still name anything, so `from_name` is how it is checked against the
+//! registry when linted.
+
+/// Declare an enum whose variants each have a name in content.
+///
+/// Besides the enum itself, this gives it `ALL`, every variant in the order
+/// declared, `name`, and `from_name`. Two variants with the same name fail
+/// to build, since `from_name` could only ever find the first.
+macro_rules! registry {
+    (
+        $(#[$attr:meta])*
+        pub enum $registry:ident {
+            $($(#[$variant_attr:meta])* $variant:ident => $name:expr,)*
+        }
+    ) => {
+        $(#[$attr])*
+        pub enum $registry {
+            $($(#[$variant_attr])* $variant,)*
+        }
+
+        const _: () = assert!(
+            !$crate::registry::has_duplicates(&[$($name),*]),
+            concat!("two variants of ", stringify!($registry), " have the same name")
+        );
+
+        impl $registry {
+            /// Every variant, in the order declared.
+            pub const ALL: &'static [$registry] = &[$($registry::$variant),*];
+
+            /// The name this goes by in content.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($registry::$variant => $name,)*
+                }
+            }
+
+            /// The variant that goes by a name in content, if there is one.
+            pub fn from_name(name: &str) -> Option<Self> {
+                $registry::ALL
+                    .iter()
+                    .cloned()
+                    .find(|variant| variant.name() == name)
+            }
+        }
+    };
+}
+
+/// Whether any name appears twice, checked when a registry is built.
+pub(crate) const fn has_duplicates(names: &[&str]) -> bool {
+    let mut i = 0;
+    while i < names.len() {
+        let mut j = i + 1;
+        while j < names.len() {
+            if same_name(names[i], names[j]) {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// `str` equality, which isn't usable in constants.
+const fn same_name(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_names_found() {
+        assert!(!has_duplicates(&["hero", "skeleton", "spider"]));
+        assert!(has_duplicates(&["hero", "spider", "hero"]));
+        assert!(!has_duplicates(&["spider", "spiders"]));
+        assert!(!has_duplicates(&[]));
+    }
+}
@@ -0,0 +1,121 @@
+//! Named random number streams derived from a single seed.
+//!
+//! Each subsystem draws from its own stream, so adding a random roll to one
+//! subsystem doesn't shift the rolls of any other.
+
+use rand::IsaacRng;
+use std::hash::Hasher;
+use util::{mix_seed, StableHasher};
+
+/// A subsystem that needs randomness.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Stream {
+    Architect,
+    Spawns,
+    Combat,
+    Ai,
+    Items,
+    Ambient,
+}
+
+const STREAMS: [Stream; 6] = [
+    Stream::Architect,
+    Stream::Spawns,
+    Stream::Combat,
+    Stream::Ai,
+    Stream::Items,
+    Stream::Ambient,
+];
+
+/// Owns one random number generator per stream.
+#[derive(Serialize, Deserialize)]
+pub struct RngRegistry {
+    seed: u64,
+    streams: Vec<IsaacRng>,
+}
+
+impl Stream {
+    /// The name the stream's seed is derived from.
+    ///
+    /// Renaming a stream changes every roll made from it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stream::Architect => "architect",
+            Stream::Spawns => "spawns",
+            Stream::Combat => "combat",
+            Stream::Ai => "ai",
+            Stream::Items => "items",
+            Stream::Ambient => "ambient",
+        }
+    }
+
+    fn index(self) -> usize {
+        STREAMS
+            .iter()
+            .position(|&stream| stream == self)
+            .expect("Every stream is listed in STREAMS.")
+    }
+}
+
+impl RngRegistry {
+    pub fn new(seed: u64) -> Self {
+        let streams = STREAMS
+            .iter()
+            .map(|&stream| IsaacRng::new_from_u64(stream_seed(seed, stream)))
+            .collect();
+        RngRegistry { seed, streams }
+    }
+
+    /// The seed every stream is derived from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The seed a stream started from.
+    pub fn stream_seed(&self, stream: Stream) -> u64 {
+        stream_seed(self.seed, stream)
+    }
+
+    pub fn get(&mut self, stream: Stream) -> &mut IsaacRng {
+        &mut self.streams[stream.index()]
+    }
+}
+
+fn stream_seed(seed: u64, stream: Stream) -> u64 {
+    mix_seed(seed, name_hash(stream.name()))
+}
+
+fn name_hash(name: &str) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write(name.as_bytes());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::Rng;
+
+    #[test]
+    fn test_streams_independent() {
+        let mut a = RngRegistry::new(9);
+        let mut b = RngRegistry::new(9);
+        for _ in 0..10 {
+            let _: u32 = a.get(Stream::Combat).gen();
+        }
+        let x: u64 = a.get(Stream::Ai).gen();
+        let y: u64 = b.get(Stream::Ai).gen();
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn test_streams_distinct() {
+        let registry = RngRegistry::new(9);
+        for &a in &STREAMS {
+            for &b in &STREAMS {
+                assert_eq!(a == b, registry.stream_seed(a) == registry.stream_seed(b));
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! The envelope wrapped around a saved world.
+//!
+//! The world itself is serialized by the frontend in whatever format it
+//! likes. The envelope records which version of the save format and which
+//! content the save was made with, and a checksum of the serialized world,
+//! so a bad save is reported as what went wrong rather than as a
+//! deserialization error.
+//!
+//! Every number in the header is little-endian:
+//!
+//! ```text
+//! magic    4 bytes  "HEXA"
+//! version  4 bytes
+//! content  8 bytes  fingerprint of the content
+//! checksum 8 bytes  of the payload
+//! length   8 bytes  of the payload
+//! payload
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::hash::Hasher;
+use util::StableHasher;
+
+/// The version of the save format written by this build.
+///
+/// Bump it whenever the serialized world changes shape. The world is
+/// serialized positionally, so appending a field or reordering enum variants
+/// breaks every older save.
+pub const SAVE_VERSION: u32 = 17;
+
+/// The oldest save format this build can still read.
+pub const OLDEST_SUPPORTED: u32 = 17;
+
+const MAGIC: &[u8; 4] = b"HEXA";
+const HEADER_LEN: usize = 32;
+
+/// Why a save couldn't be opened.
+#[derive(PartialEq, Eq, Debug)]
+pub enum SaveError {
+    /// The file isn't a save at all.
+    NotASave,
+    /// The file is damaged or cut short.
+    Corrupt,
+    /// The save was made by a newer version of the game.
+    VersionTooNew { found: u32, supported: u32 },
+    /// The save was made by an older version of the game, in a format this one can't read.
+    VersionTooOld { found: u32, oldest: u32 },
+    /// The save was made with different content, e.g. other mods.
+    IncompatibleContent { expected: u64, found: u64 },
+}
+
+/// Wrap a serialized world in an envelope.
+///
+/// `content` is the fingerprint of the content the world was played with.
+pub fn seal(payload: &[u8], content: u64) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&SAVE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&content.to_le_bytes());
+    bytes.extend_from_slice(&checksum(payload).to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Check an envelope and return the serialized world inside it.
+pub fn open(bytes: &[u8], content: u64) -> Result<&[u8], SaveError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(SaveError::NotASave);
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(SaveError::Corrupt);
+    }
+    let version = read_u32(&bytes[4..8]);
+    if version > SAVE_VERSION {
+        return Err(SaveError::VersionTooNew {
+            found: version,
+            supported: SAVE_VERSION,
+        });
+    }
+    if version < OLDEST_SUPPORTED {
+        return Err(SaveError::VersionTooOld {
+            found: version,
+            oldest: OLDEST_SUPPORTED,
+        });
+    }
+    let found = read_u64(&bytes[8..16]);
+    if found != content {
+        return Err(SaveError::IncompatibleContent {
+            expected: content,
+            found,
+        });
+    }
+    let sum = read_u64(&bytes[16..24]);
+    let len = read_u64(&bytes[24..32]);
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() as u64 != len || checksum(payload) != sum {
+        return Err(SaveError::Corrupt);
+    }
+    Ok(payload)
+}
+
+fn checksum(payload: &[u8]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write(payload);
+    hasher.finish()
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::NotASave => write!(f, "the file is not a save"),
+            SaveError::Corrupt => write!(f, "the save is corrupt"),
+            SaveError::VersionTooNew { found, supported } => write!(
+                f,
+                "the save is from a newer version (format {}, this game reads up to {})",
+                found, supported
+            ),
+            SaveError::VersionTooOld { found, oldest } => write!(
+                f,
+                "the save is from an older version (format {}, this game reads from {})",
+                found, oldest
+            ),
+            SaveError::IncompatibleContent { .. } => {
+                write!(f, "the save was made with different content")
+            }
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let sealed = seal(b"world", 7);
+        assert_eq!(open(&sealed, 7), Ok(&b"world"[..]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let sealed = seal(b"world", 7);
+        assert_eq!(open(b"nope", 7), Err(SaveError::NotASave));
+        assert_eq!(
+            open(&sealed[..sealed.len() - 1], 7),
+            Err(SaveError::Corrupt)
+        );
+        let mut flipped = sealed.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert_eq!(open(&flipped, 7), Err(SaveError::Corrupt));
+        assert_eq!(
+            open(&sealed, 8),
+            Err(SaveError::IncompatibleContent {
+                expected: 8,
+                found: 7
+            })
+        );
+        let mut newer = sealed.clone();
+        newer[4] = SAVE_VERSION as u8 + 1;
+        assert_eq!(
+            open(&newer, 7),
+            Err(SaveError::VersionTooNew {
+                found: SAVE_VERSION + 1,
+                supported: SAVE_VERSION
+            })
+        );
+        let mut older = sealed.clone();
+        older[4] = OLDEST_SUPPORTED as u8 - 1;
+        assert_eq!(
+            open(&older, 7),
+            Err(SaveError::VersionTooOld {
+                found: OLDEST_SUPPORTED - 1,
+                oldest: OLDEST_SUPPORTED
+            })
+        );
+    }
+}
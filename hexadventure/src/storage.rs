@@ -0,0 +1,183 @@
+//! Where saves and other files are kept between runs.
+//!
+//! Frontends read and write named files through a `Storage`, so the same
+//! code works with a directory on disk, with memory in tests, and with
+//! whatever a platform without a filesystem provides.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Setting this environment variable keeps files in the directory it names,
+/// e.g. next to the executable for a portable install.
+pub const DATA_DIR_VAR: &str = "HEXADVENTURE_DATA_DIR";
+
+/// A place to keep named files.
+pub trait Storage {
+    /// The contents of a file, or `None` if there is no such file.
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Replace the contents of a file, creating it if needed.
+    fn write(&mut self, name: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// Move a file to a new name, replacing any file already there.
+    /// Does nothing if there is no such file.
+    fn rename(&mut self, from: &str, to: &str) -> io::Result<()>;
+}
+
+/// Files in a directory on disk, which is created on the first write.
+pub struct DirStorage {
+    dir: PathBuf,
+}
+
+/// Files held in memory and lost when dropped.
+#[derive(Default)]
+pub struct MemoryStorage {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl DirStorage {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        DirStorage { dir: dir.into() }
+    }
+
+    /// Keep files in the directory named by `DATA_DIR_VAR` if it is set,
+    /// or else where the platform keeps application data.
+    ///
+    /// `None` if the platform has no such place.
+    pub fn open_default() -> Option<Self> {
+        let dir = match env::var_os(DATA_DIR_VAR) {
+            Some(dir) => PathBuf::from(dir),
+            None => default_data_dir()?,
+        };
+        Some(DirStorage::new(dir))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Storage for DirStorage {
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.dir.join(name)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(name), bytes)
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> io::Result<()> {
+        match fs::rename(self.dir.join(from), self.dir.join(to)) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.files.get(name).cloned())
+    }
+
+    fn write(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        self.files.insert(name.to_owned(), bytes.to_vec());
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> io::Result<()> {
+        if let Some(bytes) = self.files.remove(from) {
+            self.files.insert(to.to_owned(), bytes);
+        }
+        Ok(())
+    }
+}
+
+/// Where this platform keeps application data for the game.
+///
+/// This is the roaming profile, where saves were kept before there was a
+/// `Storage`, so existing saves are still found.
+#[cfg(target_os = "windows")]
+fn default_data_dir() -> Option<PathBuf> {
+    let mut dir = PathBuf::from(env::var_os("APPDATA")?);
+    dir.push("as-f");
+    dir.push("hex-adventure");
+    Some(dir)
+}
+
+/// Where this platform keeps application data for the game.
+#[cfg(target_os = "macos")]
+fn default_data_dir() -> Option<PathBuf> {
+    let mut dir = PathBuf::from(env::var_os("HOME")?);
+    dir.push("Library");
+    dir.push("Application Support");
+    dir.push("hex-adventure");
+    Some(dir)
+}
+
+/// Where this platform keeps application data for the game.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn default_data_dir() -> Option<PathBuf> {
+    let mut dir = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let mut dir = PathBuf::from(env::var_os("HOME")?);
+            dir.push(".local");
+            dir.push("share");
+            dir
+        }
+    };
+    dir.push("hex-adventure");
+    Some(dir)
+}
+
+/// Platforms without a filesystem, like the web, must provide their own `Storage`.
+#[cfg(not(any(unix, target_os = "windows")))]
+fn default_data_dir() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_storage<S: Storage>(storage: &mut S) {
+        assert_eq!(storage.read("save").unwrap(), None);
+        storage.write("save", b"one").unwrap();
+        storage.rename("save", "backup").unwrap();
+        storage.write("save", b"two").unwrap();
+        assert_eq!(storage.read("save").unwrap(), Some(b"two".to_vec()));
+        assert_eq!(storage.read("backup").unwrap(), Some(b"one".to_vec()));
+        storage.rename("missing", "save").unwrap();
+        assert_eq!(storage.read("save").unwrap(), Some(b"two".to_vec()));
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        check_storage(&mut MemoryStorage::new());
+    }
+
+    #[test]
+    fn test_dir_storage_created_on_write() {
+        let dir = env::temp_dir()
+            .join(format!("hexadventure-test-{}", ::std::process::id()))
+            .join("data");
+        let mut storage = DirStorage::new(&dir);
+        check_storage(&mut storage);
+        assert!(dir.is_dir());
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+//! Worst-case maps and timings for measuring performance.
+//!
+//! Only built with the `stress` feature. `cargo bench --features stress`
+//! times pathfinding, field of view, whole turns and each phase of a turn
+//! on each map. Timings depend on the machine, so they are only reported;
+//! what fails the bench is an operation doing more work than its budget,
+//! counted in search nodes and tiles, which is the same everywhere.
+
+use astar::{self, find_path_with, Goal, Path, Strategy};
+use fov::calc_fov;
+use grid::{pos_to_location, HEIGHT, WIDTH};
+use level::tile::{Terrain, Tile};
+use prelude::*;
+use std::time::{Duration, Instant};
+use world::action::Action;
+use world::mob::{Npcs, Species};
+use world::schedule::PHASES;
+
+/// A map made to be slow to search or see across.
+pub struct StressMap {
+    pub name: &'static str,
+    pub terrain: Grid<Terrain>,
+    /// The player is placed as close to this as possible.
+    pub start: Pos,
+    /// The stairs down, as far from the start as the map allows.
+    pub exit: Pos,
+}
+
+/// How much work one run of a measured operation does, and how long it
+/// took on average.
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name: String,
+    /// Search nodes expanded, or tiles scanned or seen, in one run.
+    pub work: u64,
+    /// The most work the operation may do before it counts as a regression.
+    pub budget: u64,
+    pub time: Duration,
+}
+
+/// The most nodes and tiles a path across a map may pop and scan.
+pub const JPS_BUDGET: u64 = 1_250;
+/// The most nodes a path across a map may expand searching from both ends.
+pub const BIDIRECTIONAL_BUDGET: u64 = 1_000;
+/// The most tiles a field of view over a map may see.
+pub const FOV_BUDGET: u64 = 1_400;
+/// The most nodes and tiles the searches in a turn with `STRESS_MOBS` mobs
+/// may pop and scan.
+pub const TURN_BUDGET: u64 = 8_000;
+/// How many mobs crowd the map when timing whole turns.
+pub const STRESS_MOBS: usize = 100;
+
+impl StressMap {
+    /// One open cavern, walled only at the edges.
+    pub fn open_cavern() -> Self {
+        let start = at(1, 1);
+        let exit = at(HEIGHT - 2, WIDTH - 2);
+        let terrain = Grid::new(|pos| {
+            let (row, col) = row_col(pos);
+            if pos == exit {
+                Terrain::Exit
+            } else if row == 0 || row == HEIGHT - 1 || col == 0 || col == WIDTH - 1 {
+                Terrain::Wall
+            } else {
+                Terrain::Floor
+            }
+        });
+        StressMap {
+            name: "open cavern",
+            terrain,
+            start,
+            exit,
+        }
+    }
+
+    /// A single corridor winding back and forth across every row.
+    pub fn maze() -> Self {
+        let start = at(1, 1);
+        let exit = at(HEIGHT - 2, WIDTH - 2);
+        let terrain = Grid::new(|pos| {
+            let (row, col) = row_col(pos);
+            let edge = row == 0 || row == HEIGHT - 1 || col == 0 || col == WIDTH - 1;
+            let gap = if row % 4 == 2 { WIDTH - 2 } else { 1 };
+            let divider = row % 2 == 0 && row < HEIGHT - 2 && col != gap;
+            if pos == exit {
+                Terrain::Exit
+            } else if edge || divider {
+                Terrain::Wall
+            } else {
+                Terrain::Floor
+            }
+        });
+        StressMap {
+            name: "maze",
+            terrain,
+            start,
+            exit,
+        }
+    }
+
+    /// Every stress map.
+    pub fn all() -> Vec<Self> {
+        vec![StressMap::open_cavern(), StressMap::maze()]
+    }
+
+    /// The path from the start to the tile next to the exit.
+    pub fn path(&self, strategy: Strategy) -> Option<Path> {
+        let terrain = &self.terrain;
+        let passable = |pos| grid::contains(pos) && terrain[pos].passable();
+        find_path_with(self.start, Goal::Adjacent(self.exit), passable, strategy)
+    }
+
+    /// How many tiles can be seen from the start.
+    pub fn fov(&self) -> usize {
+        let mut seen = 0;
+        calc_fov(
+            self.start,
+            |pos| self.terrain[pos].transparent(),
+            |_| seen += 1,
+        );
+        seen
+    }
+
+    /// A world on this map, crowded with mobs.
+    pub fn world(&self, mobs: usize) -> World {
+        let mut world = World::from_seed(0, Default::default());
+        let mut level = Grid::new(|pos| Tile {
+            terrain: self.terrain[pos],
+            mob_id: None,
+            loot: None,
+            decal: None,
+            battered: 0,
+            corpse: None,
+            space: None,
+            rune: None,
+        });
+        let mut npcs = Npcs::new();
+        let open = grid::positions()
+            .filter(|&pos| self.terrain[pos].passable() && pos.distance(self.start) > 2)
+            .take(mobs);
+        for pos in open {
+            let mob_id = npcs.insert(Mob::new(pos, Species::Skeleton));
+            level[pos].mob_id = Some(mob_id);
+        }
+        let player = world.active_player();
+        world.enter_level(level, npcs, player, self.start);
+        world
+    }
+}
+
+/// Average how long `f` takes over a number of runs.
+pub fn time<F: FnMut()>(runs: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        f();
+    }
+    start.elapsed() / runs.max(1)
+}
+
+/// How many nodes and tiles the searches `f` runs pop and scan.
+pub fn searched<F: FnOnce()>(f: F) -> u64 {
+    astar::start_recording();
+    f();
+    let stats = astar::stop_recording().unwrap_or_default();
+    u64::from(stats.popped) + u64::from(stats.scanned)
+}
+
+/// Measure everything on every map, timing each over a number of runs.
+///
+/// The work is counted on a fresh world, so it doesn't depend on `runs`.
+pub fn run(runs: u32) -> Vec<Measurement> {
+    let mut measurements = Vec::new();
+    for map in StressMap::all() {
+        measurements.push(Measurement {
+            name: format!("jps across {}", map.name),
+            work: searched(|| {
+                map.path(Strategy::JumpPoint);
+            }),
+            budget: JPS_BUDGET,
+            time: time(runs, || {
+                map.path(Strategy::JumpPoint);
+            }),
+        });
+        measurements.push(Measurement {
+            name: format!("bidirectional across {}", map.name),
+            // Searching from both ends isn't recorded, but counts what it expands.
+            work: map
+                .path(Strategy::Bidirectional)
+                .map_or(0, |path| u64::from(path.expanded())),
+            budget: BIDIRECTIONAL_BUDGET,
+            time: time(runs, || {
+                map.path(Strategy::Bidirectional);
+            }),
+        });
+        measurements.push(Measurement {
+            name: format!("fov in {}", map.name),
+            work: map.fov() as u64,
+            budget: FOV_BUDGET,
+            time: time(runs, || {
+                map.fov();
+            }),
+        });
+        let mut fresh = map.world(STRESS_MOBS);
+        let mut world = map.world(STRESS_MOBS);
+        measurements.push(Measurement {
+            name: format!("turn in {} with {} mobs", map.name, STRESS_MOBS),
+            work: searched(|| {
+                let _ = fresh.perform_confirmed(Action::Rest);
+            }),
+            budget: TURN_BUDGET,
+            time: time(runs, || {
+                let _ = world.perform_confirmed(Action::Rest);
+            }),
+        });
+        for &phase in &PHASES {
+            let mut fresh = map.world(STRESS_MOBS);
+            measurements.push(Measurement {
+                name: format!("{:?} phase in {}", phase, map.name),
+                work: searched(|| fresh.run_phase(phase)),
+                budget: TURN_BUDGET,
+                time: time(runs, || world.run_phase(phase)),
+            });
+        }
+    }
+    measurements
+}
+
+impl Measurement {
+    /// Whether the operation did more work than its budget allows.
+    pub fn regressed(&self) -> bool {
+        self.work > self.budget
+    }
+}
+
+/// The position at a row and column of the grid.
+fn at(row: usize, col: usize) -> Pos {
+    grid::positions()
+        .find(|&pos| row_col(pos) == (row, col))
+        .unwrap()
+}
+
+fn row_col(pos: Pos) -> (usize, usize) {
+    let location = pos_to_location(pos);
+    let row = location.y as usize;
+    (row, (location.x as usize - row % 2) / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stress_maps_connected() {
+        for map in StressMap::all() {
+            let path = map.path(Strategy::JumpPoint).unwrap();
+            assert_eq!(path.goal().distance(map.exit), 1, "{}", map.name);
+            let bidirectional = map.path(Strategy::Bidirectional).unwrap();
+            assert_eq!(bidirectional.steps(), path.steps(), "{}", map.name);
+        }
+        let maze = StressMap::maze().path(Strategy::JumpPoint).unwrap();
+        assert!(maze.positions().len() > WIDTH * HEIGHT / 3);
+    }
+
+    #[test]
+    fn test_stress_world_crowded() {
+        let world = StressMap::open_cavern().world(STRESS_MOBS);
+        let mut count = 0;
+        ::world::mob::for_each(&world, |_| count += 1);
+        assert_eq!(count, STRESS_MOBS);
+        assert!(world.player().pos.distance(StressMap::open_cavern().start) <= 1);
+    }
+
+    #[test]
+    fn test_stress_within_budget() {
+        for measurement in run(1) {
+            assert!(
+                !measurement.regressed(),
+                "{} did {} work, over its budget of {}",
+                measurement.name,
+                measurement.work,
+                measurement.budget
+            );
+        }
+    }
+}
@@ -1,5 +1,15 @@
 //! Miscellaneous functions.
 
+use std::hash::Hasher;
+
+/// A hasher that gives the same results across runs, compiler versions and
+/// platforms.
+///
+/// Uses 64-bit FNV-1a. Integers are hashed as their little-endian bytes,
+/// and `usize` and `isize` as 64 bits, so lengths and indices hash the same
+/// on 32-bit and 64-bit targets.
+pub struct StableHasher(u64);
+
 /// Zip a slice with itself but rotated forward by one.
 pub fn self_zip<T: Copy>(slice: &[T]) -> Vec<(T, T)> {
     let mut v = Vec::with_capacity(slice.len());
@@ -10,14 +20,95 @@ pub fn self_zip<T: Copy>(slice: &[T]) -> Vec<(T, T)> {
     v
 }
 
+/// Derive a new seed from a seed and a number.
+///
+/// Uses the splitmix64 finalizer, so that nearby numbers give unrelated seeds.
+pub fn mix_seed(seed: u64, n: u64) -> u64 {
+    let mut z = seed ^ n.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.write_i64(n as i64);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use std::hash::Hash;
+
     #[test]
     fn test_self_zip() {
         let array = [1, 2, 3];
         let result = vec![(1, 2), (2, 3), (3, 1)];
         assert_eq!(self_zip(&array), result);
     }
+
+    #[test]
+    fn test_mix_seed() {
+        assert_eq!(mix_seed(5, 3), mix_seed(5, 3));
+        assert_ne!(mix_seed(5, 3), mix_seed(5, 4));
+        assert_ne!(mix_seed(5, 3), mix_seed(6, 3));
+    }
+
+    #[test]
+    fn test_stable_hasher_ignores_pointer_width() {
+        let hash = |write: &dyn Fn(&mut StableHasher)| {
+            let mut hasher = StableHasher::new();
+            write(&mut hasher);
+            hasher.finish()
+        };
+        let bytes = hash(&|hasher| hasher.write(&[7, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(hash(&|hasher| hasher.write_usize(7)), bytes);
+        assert_eq!(hash(&|hasher| hasher.write_isize(7)), bytes);
+        assert_eq!(hash(&|hasher| hasher.write_u64(7)), bytes);
+        assert_eq!(hash(&|hasher| 7usize.hash(hasher)), bytes);
+    }
 }
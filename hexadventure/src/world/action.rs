@@ -1,8 +1,88 @@
-use level::place_mob;
-use level::tile::{Terrain, TileView};
+use camera::Focus;
+use level::tile::{DecalKind, Terrain};
 use prelude::*;
-use rand::{thread_rng, Rng};
-use world::mob::PLAYER_ID;
+use rand::Rng;
+use rng::Stream;
+use world::combat_log::AttackRecord;
+use world::danger::Danger;
+use world::engrave::Inscription;
+use world::mob::capitalize;
+use world::modifier::Stance;
+
+/// Something a player can choose to do on their turn.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Action {
+    Rest,
+    Walk(Direction),
+    /// Step quietly, taking two turns: one to creep towards the tile, one to reach it.
+    Sneak(Direction),
+    /// Refill a lantern from a flask of oil.
+    Refuel,
+    /// Engrave a message on the floor underfoot.
+    Engrave(Inscription),
+    /// Take up a stance.
+    Stance(Stance),
+}
+
+/// Why an action wasn't taken.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum ActionError {
+    /// The action isn't possible, or didn't use up the turn.
+    Invalid,
+    /// The action is dangerous, and must be confirmed with `perform_confirmed`.
+    NeedsConfirmation(Danger),
+}
+
+impl World {
+    /// Have the active player take an action, ending their turn if it succeeds.
+    ///
+    /// Dangerous actions aren't taken until they are confirmed.
+    pub fn perform(&mut self, action: Action) -> Result<(), ActionError> {
+        if let Some(danger) = self.danger(action) {
+            return Err(ActionError::NeedsConfirmation(danger));
+        }
+        self.perform_confirmed(action)
+    }
+
+    /// Take an action even if it is dangerous.
+    ///
+    /// A dead player can't act, so every action fails once the party is dead.
+    pub fn perform_confirmed(&mut self, action: Action) -> Result<(), ActionError> {
+        let player = self.active_player();
+        if !self[player].alive {
+            return Err(ActionError::Invalid);
+        }
+        self.focus = Focus::Player;
+        let taken = self.pace(action);
+        let result = match taken {
+            Action::Rest => rest(player, self),
+            Action::Walk(direction) => walk(player, direction, self),
+            Action::Sneak(direction) => sneak(player, direction, self),
+            Action::Refuel => self.refuel(player),
+            Action::Engrave(inscription) => self.engrave(player, inscription),
+            Action::Stance(stance) => self.take_stance(player, stance),
+        };
+        if !matches!(taken, Action::Sneak(_)) {
+            self[player].creeping = None;
+        }
+        if result.is_ok() {
+            self.tick();
+        }
+        self.record(action);
+        result.map_err(|()| ActionError::Invalid)
+    }
+
+    /// An action as the active player would take it: walking is sneaking
+    /// while their modifiers slow them.
+    pub(super) fn pace(&self, action: Action) -> Action {
+        match action {
+            Action::Walk(direction) if self.is_slowed(self.active_player()) => {
+                Action::Sneak(direction)
+            }
+            _ => action,
+        }
+    }
+}
 
 pub fn rest(_mob_id: MobId, _world: &mut World) -> Result<(), ()> {
     Ok(())
@@ -10,22 +90,24 @@ pub fn rest(_mob_id: MobId, _world: &mut World) -> Result<(), ()> {
 
 pub fn walk(mob_id: MobId, direction: Direction, world: &mut World) -> Result<(), ()> {
     let target_pos = world[mob_id].pos + direction;
-    if world.level[target_pos].mob_id.is_some() {
+    if world.blocker(mob_id, target_pos, direction).is_some() {
         attack_melee(mob_id, direction, world)
-    } else if world.level[target_pos].terrain.passable() {
-        if world[mob_id].guard_recovery > 0 && world[mob_id].facing == direction.rotate(3) {
-            retreat_unchecked(mob_id, direction, world)
-        } else {
-            world.level[target_pos - direction].mob_id = None;
-            world.level[target_pos].mob_id = Some(mob_id);
-            world[mob_id].pos = target_pos;
-            world[mob_id].facing = direction;
-            Ok(())
-        }
+    } else if world[mob_id].entangled {
+        world.struggle(mob_id);
+        Ok(())
+    } else if world.fits(mob_id, target_pos, direction) {
+        step(mob_id, direction, false, world)
+    } else if let Some(door) = world.door_in_way(mob_id, target_pos, direction) {
+        world.work_door(mob_id, door)
     } else if world.level[target_pos].terrain == Terrain::Exit {
-        if mob_id.is_player() {
+        if world.refuse_passage(mob_id, target_pos) {
+            Err(())
+        } else if mob_id.is_player() {
+            let approach = world[mob_id].pos;
+            world.gather_followers(mob_id);
             world[mob_id].pos = target_pos;
-            descend_unchecked(world);
+            descend_unchecked(mob_id, target_pos, world);
+            world.link_stairs(approach, target_pos, mob_id);
             Err(())
         } else {
             Err(())
@@ -35,28 +117,17 @@ pub fn walk(mob_id: MobId, direction: Direction, world: &mut World) -> Result<()
     }
 }
 
+/// Attack the first mob in the way of stepping in a direction.
+///
+/// A huge mob attacks with its whole front, and any tile of a huge target can be hit.
 fn attack_melee(mob_id: MobId, direction: Direction, world: &mut World) -> Result<(), ()> {
-    let target_pos = world[mob_id].pos + direction;
-    if let Some(target) = world.level[target_pos].mob_id {
-        if mob_id.is_player() || target.is_player() {
-            let damage = thread_rng().gen_range(1, 7) + thread_rng().gen_range(1, 7);
-            let guard = world[target].guard;
-            if damage <= guard {
-                world[target].guard -= damage;
-            } else {
-                let damage = damage - world[target].guard;
-                world[target].guard = 0;
-                if damage < world[target].health {
-                    world[target].health -= damage;
-                } else {
-                    world[target].health = 0;
-                    target.die(world);
-                }
-            }
-            if world[target].facing == direction.rotate(3) {
-                world[target].guard_recovery = damage / 2;
+    let center = world[mob_id].pos + direction;
+    if let Some((target, target_pos)) = world.blocker(mob_id, center, direction) {
+        if mob_id.is_player() != target.is_player() {
+            strike(mob_id, target, target_pos, "hits", world);
+            if world[mob_id].species.steals() && world[target].alive {
+                world.steal(mob_id, target);
             }
-            world[mob_id].facing = direction;
             Ok(())
         } else {
             Err(())
@@ -66,27 +137,158 @@ fn attack_melee(mob_id: MobId, direction: Direction, world: &mut World) -> Resul
     }
 }
 
+/// Step quietly, creeping towards the tile on the first turn and reaching it on the second.
+///
+/// Anything but a plain step, like an attack, happens as if walking.
+fn sneak(mob_id: MobId, direction: Direction, world: &mut World) -> Result<(), ()> {
+    let target_pos = world[mob_id].pos + direction;
+    if world.blocker(mob_id, target_pos, direction).is_some()
+        || world[mob_id].entangled
+        || !world.fits(mob_id, target_pos, direction)
+    {
+        world[mob_id].creeping = None;
+        walk(mob_id, direction, world)
+    } else if world[mob_id].creeping == Some(direction) {
+        world[mob_id].creeping = None;
+        step(mob_id, direction, true, world)
+    } else {
+        world[mob_id].creeping = Some(direction);
+        Ok(())
+    }
+}
+
+/// Move onto a free tile, backing away if recovering from a hit taken from the front.
+///
+/// A portal stepped onto carries the mob on through it.
+fn step(mob_id: MobId, direction: Direction, quiet: bool, world: &mut World) -> Result<(), ()> {
+    let approach = world[mob_id].pos;
+    if world[mob_id].backs_away(direction) {
+        retreat_unchecked(mob_id, direction, world)?;
+    } else {
+        let target_pos = world[mob_id].pos + direction;
+        world.move_mob_facing(mob_id, target_pos, direction);
+    }
+    world.pass_through_portal(mob_id, approach)?;
+    world.entangle(mob_id);
+    world.pick_up(mob_id);
+    world.footstep(mob_id, quiet);
+    Ok(())
+}
+
+/// Deal damage to a target at one of the tiles it takes up, turning to face it.
+///
+/// `verb` describes the attack in the message, e.g. "hits". Strikes always
+/// hit unless the striker's modifiers make them less accurate. With the
+/// detailed combat log on, the whole resolution is recorded.
+pub(crate) fn strike(mob_id: MobId, target: MobId, target_pos: Pos, verb: &str, world: &mut World) {
+    let direction = (target_pos - world[mob_id].pos).direction();
+    let hit_chance = world.hit_chance(mob_id);
+    let mut record = if world.options().combat_detail {
+        Some(AttackRecord {
+            turn: world.turn(),
+            attacker: world.describe(mob_id),
+            target: world.describe(target),
+            verb: verb.to_owned(),
+            hit_chance,
+            hit_roll: None,
+            rolled: None,
+            modifiers: world.attack_modifiers(mob_id, target),
+            damage: 0,
+            guard_lost: 0,
+            health_lost: 0,
+            killed: false,
+        })
+    } else {
+        None
+    };
+    if hit_chance < 100 {
+        let roll = world.rng(Stream::Combat).gen_range(0, 100);
+        if let Some(record) = &mut record {
+            record.hit_roll = Some(roll);
+        }
+        if roll >= hit_chance {
+            let message = format!(
+                "{} misses {}.",
+                capitalize(&world.describe(mob_id)),
+                world.describe(target)
+            );
+            world.report(target_pos, message);
+            world.turn_mob(mob_id, direction);
+            if let Some(record) = record {
+                world.log_attack(record);
+            }
+            return;
+        }
+    }
+    let rolled = roll_damage(world.rng(Stream::Combat));
+    let damage = world.modified_damage(mob_id, target, rolled);
+    let (guard_before, health_before) = (world[target].guard, world[target].health);
+    let message = format!(
+        "{} {} {} for {}.",
+        capitalize(&world.describe(mob_id)),
+        verb,
+        world.describe(target),
+        damage
+    );
+    world.report(target_pos, message);
+    world.record_blow(mob_id, target, verb, damage);
+    let guard = world[target].guard;
+    if damage <= guard {
+        world[target].guard -= damage;
+    } else {
+        let damage = damage - world[target].guard;
+        world[target].guard = 0;
+        world.stain(target_pos, DecalKind::Blood);
+        if damage < world[target].health {
+            world[target].health -= damage;
+        } else {
+            world[target].health = 0;
+            target.die(world);
+        }
+    }
+    if world[target].facing == direction.rotate(3) {
+        world[target].guard_recovery = damage / 2;
+    }
+    world.turn_mob(mob_id, direction);
+    if let Some(mut record) = record {
+        record.rolled = Some(rolled);
+        record.damage = damage;
+        record.guard_lost = guard_before - world[target].guard;
+        record.health_lost = health_before - world[target].health;
+        record.killed = !world[target].alive;
+        world.log_attack(record);
+    }
+}
+
+/// The number of dice rolled for melee damage.
+const DAMAGE_DICE: u32 = 2;
+/// The number of sides on each damage die.
+const DAMAGE_SIDES: u32 = 6;
+
+/// The least and most damage a melee attack can deal.
+pub fn damage_range() -> (u32, u32) {
+    (DAMAGE_DICE, DAMAGE_DICE * DAMAGE_SIDES)
+}
+
+fn roll_damage<R: Rng>(rng: &mut R) -> u32 {
+    (0..DAMAGE_DICE)
+        .map(|_| rng.gen_range(1, DAMAGE_SIDES + 1))
+        .sum()
+}
+
 fn retreat_unchecked(mob_id: MobId, direction: Direction, world: &mut World) -> Result<(), ()> {
     let target_pos = world[mob_id].pos + direction;
-    world.level[target_pos - direction].mob_id = None;
-    world.level[target_pos].mob_id = Some(mob_id);
-    world[mob_id].pos = target_pos;
+    world.move_mob(mob_id, target_pos);
     world[mob_id].guard += world[mob_id].guard_recovery;
     Ok(())
 }
 
-fn descend_unchecked(world: &mut World) {
+/// Move every living player to the next level, gathered around a position
+/// with the one who took the stairs closest to it.
+pub(super) fn descend_unchecked(mob_id: MobId, arrival: Pos, world: &mut World) {
+    let adjustment = world.pacing_adjustment();
+    world.architect.adjust_next_spawns(adjustment);
     let (level, npcs) = world.architect.generate();
-    world.level = level;
-    world.npcs = npcs;
-    let player_pos = place_mob(
-        &mut world.level,
-        world.player.pos,
-        PLAYER_ID,
-        &mut thread_rng(),
-    );
-    world.player.facing = (player_pos - world.player.pos).direction();
-    world.player.pos = player_pos;
-    world.fov = Grid::new(|_| TileView::None);
-    world.update_fov();
+    world.generated_level = Some(level.clone());
+    world.enter_level(level, npcs, mob_id, arrival);
 }
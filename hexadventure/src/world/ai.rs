@@ -1,29 +1,437 @@
+use astar::{
+    cheapest_path, cheapest_path_to, find_path_among, find_path_through_portals, plan_group, Goal,
+    Occupancy, UNREACHABLE,
+};
 use grid::DIRECTIONS;
+use level::space::Space;
+use level::tile::Terrain;
 use prelude::*;
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use rng::Stream;
+use std::collections::HashMap;
 use world::action;
+use world::light::LIGHT_PENALTY;
+use world::mob::{self, Size};
+use world::necromancy::EXPOSED_PENALTY;
+use world::ranged;
+
+/// How far a mob that shoots tries to get from a target that gets close.
+const KITE_DISTANCE: u32 = 3;
+/// The extra cost of a step next to the target, when backing away from it.
+const CLOSE_PENALTY: u32 = 5;
+/// The longest path a big mob plans when charging, so one far from its
+/// target doesn't search the whole level every turn.
+const MAX_CHASE_STEPS: u32 = 30;
+/// How many extra steps a charging mob takes to go around another npc
+/// rather than wait behind it.
+const CROWD_PENALTY: u32 = 6;
+/// How many turns ahead a pack plans its steps.
+const PACK_WINDOW: u32 = 8;
+
+/// What an npc sets out to do this round.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+enum Intent {
+    /// Run for the stairs with the loot it carries.
+    Escape,
+    /// Keep out of reach of a player standing on a ward.
+    Shun(Pos),
+    /// Keep its distance from a player while shooting at them.
+    Kite(Pos, u32),
+    /// Send its minions after a player.
+    Command(Pos),
+    /// Close in on a player it can see.
+    Chase(Pos),
+    /// Go to where it last heard something.
+    Investigate(Pos),
+    Rest,
+}
+
+/// How an npc closes in on what it chases.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+enum Approach {
+    /// Along a path wide enough for a big footprint.
+    Charge,
+    /// Around tiles lit more or less than it likes.
+    Stalk,
+    /// By the pursuit's flow field, with the rest of the pack.
+    Pack,
+}
+
+/// Decide what an npc sets out to do this round.
+fn intent(mob_id: MobId, world: &World) -> Intent {
+    let mob = &world[mob_id];
+    if !mob.carried.is_empty() {
+        return Intent::Escape;
+    }
+    let species = mob.species;
+    let target = world
+        .players()
+        .iter()
+        .filter(|player| player.alive)
+        .map(|player| player.pos)
+        .min_by_key(|&pos| pos.distance(mob.pos));
+    match target {
+        Some(target) if world.is_visible(mob.pos) => {
+            if species.fears_wards() && world.is_warded(target) {
+                Intent::Shun(target)
+            } else if let Some(range) = species.range() {
+                Intent::Kite(target, range)
+            } else if species.raises_dead() {
+                Intent::Command(target)
+            } else {
+                Intent::Chase(target)
+            }
+        }
+        _ => match mob.heard {
+            Some(heard) if !species.size().covers(mob.pos, mob.facing, heard) => {
+                Intent::Investigate(heard)
+            }
+            _ => Intent::Rest,
+        },
+    }
+}
+
+/// Decide how an npc closes in on what it chases.
+fn approach(mob_id: MobId, world: &World) -> Approach {
+    let species = world[mob_id].species;
+    if species.size() != Size::Small {
+        Approach::Charge
+    } else if species.light_preference().is_some() {
+        Approach::Stalk
+    } else {
+        Approach::Pack
+    }
+}
 
 pub fn act(mob_id: MobId, world: &mut World) -> Result<(), ()> {
+    let intent = intent(mob_id, world);
+    match intent {
+        Intent::Escape | Intent::Investigate(_) => {}
+        _ => world[mob_id].heard = None,
+    }
+    match intent {
+        Intent::Escape => run_for_stairs(mob_id, world),
+        Intent::Shun(target) => shun(mob_id, target, world),
+        Intent::Kite(target, range) => kite(mob_id, target, range, world),
+        Intent::Command(target) => command(mob_id, target, world),
+        Intent::Chase(target) | Intent::Investigate(target) => chase(mob_id, target, world),
+        Intent::Rest => action::rest(mob_id, world),
+    }
+}
+
+pub fn chase(mob_id: MobId, target: Pos, world: &mut World) -> Result<(), ()> {
+    match approach(mob_id, world) {
+        Approach::Charge => return charge(mob_id, target, world),
+        Approach::Stalk => return stalk(mob_id, target, world),
+        Approach::Pack => {}
+    }
+    let flip = world.rng(Stream::Ai).gen();
     let mob_pos = world[mob_id].pos;
-    if world.fov[mob_pos].is_visible() {
-        chase(mob_id, world.player.pos, world)
+    let caught_by_webs = world[mob_id].species.caught_by_webs();
+    // A pack's plan keeps its members out of each other's way, until they
+    // are close enough to attack.
+    let in_reach = world
+        .pursuit
+        .as_ref()
+        .is_some_and(|field| field.distance(mob_pos) <= 1);
+    match world.pack_steps.get(&mob_id) {
+        Some(&next) if next != mob_pos => {
+            let walked = action::walk(mob_id, (next - mob_pos).direction(), world);
+            if walked.is_ok() {
+                return walked;
+            }
+        }
+        Some(_) if !in_reach => return action::rest(mob_id, world),
+        _ => {}
+    }
+    if let Some(next) = portal_shortcut(mob_pos, target, world) {
+        let walked = action::walk(mob_id, (next - mob_pos).direction(), world);
+        if walked.is_ok() {
+            return walked;
+        }
+    }
+    // Chasing a player, a step that brings the mob fewer steps from them
+    // closes in, even if it doesn't look like it. Otherwise it has to look.
+    let field = world
+        .pursuit
+        .as_ref()
+        .filter(|field| field.targets().contains(&target))
+        .filter(|field| field.distance(mob_pos) != UNREACHABLE);
+    let closer = |pos: Pos| match field {
+        Some(field) => field.distance(pos) < field.distance(mob_pos),
+        None => pos.distance(target) < mob_pos.distance(target),
+    };
+    let mut steps: Vec<Direction> = DIRECTIONS
+        .iter()
+        .map(|&direction| if flip { direction.rotate(3) } else { direction })
+        .filter(|&direction| {
+            let pos = mob_pos + direction;
+            let tile = world.level[pos];
+            // Mobs that would be caught steer around empty webs.
+            let web = tile.terrain == Terrain::Web && tile.mob_id.is_none();
+            closer(pos) && !(web && caught_by_webs)
+        })
+        .collect();
+    // Packs spread out in caverns. Elsewhere the first step that closes in
+    // is taken, which lines them up single file in corridors.
+    if world.level[mob_pos].space == Some(Space::Cavern) {
+        steps.sort_by_key(|&direction| crowding(mob_id, mob_pos + direction, world));
+    }
+    for direction in steps {
+        if let Ok(x) = action::walk(mob_id, direction, world) {
+            return Ok(x);
+        }
+    }
+    action::rest(mob_id, world)
+}
+
+/// The next step toward a target, if the shortest way there goes through a
+/// portal on the level.
+fn portal_shortcut(mob_pos: Pos, target: Pos, world: &World) -> Option<Pos> {
+    let portals = world.local_portals();
+    if portals.is_empty() {
+        return None;
+    }
+    let passable = |pos| grid::contains(pos) && world.level[pos].terrain.passable();
+    let path = find_path_through_portals(mob_pos, Goal::Adjacent(target), passable, &portals)?;
+    if path.cost() < path.steps() {
+        path.next_step()
     } else {
-        action::rest(mob_id, world)
+        None
     }
 }
 
-pub fn chase(mob_id: MobId, target: Pos, world: &mut World) -> Result<(), ()> {
-    let mut rng = thread_rng();
-    let flip = rng.gen();
+/// Plan the next step of every npc that would chase the players by the
+/// pursuit's flow field this round, together, so packs file through
+/// corridors rather than jamming them.
+pub(super) fn plan_pack(world: &World) -> HashMap<MobId, Pos> {
+    let field = match world.pursuit {
+        Some(ref field) => field,
+        None => return HashMap::new(),
+    };
+    let mut pack = Vec::new();
+    mob::for_each(world, |mob_id| {
+        if joins_pack(mob_id, world) {
+            pack.push(mob_id);
+        }
+    });
+    let starts: Vec<Pos> = pack.iter().map(|&mob_id| world[mob_id].pos).collect();
+    // The goals are the tiles next to the players.
+    let distance = |pos| match field.distance(pos) {
+        UNREACHABLE => UNREACHABLE,
+        steps => steps.saturating_sub(1),
+    };
+    let passable = |pos: Pos| {
+        let tile = world.level[pos];
+        let web = tile.terrain == Terrain::Web && tile.mob_id.is_none();
+        // Pack mates step out of the way as the plan goes, so only other
+        // mobs are in it.
+        let occupied = tile.mob_id.is_some_and(|other| !pack.contains(&other));
+        let cost = Some(1).filter(|_| tile.terrain.passable_once_opened() && !web);
+        Occupancy::Block.cost(cost, occupied).is_some()
+    };
+    let plans = plan_group(&starts, distance, passable, PACK_WINDOW);
+    pack.into_iter()
+        .zip(plans)
+        .map(|(mob_id, plan)| (mob_id, plan[1]))
+        .collect()
+}
+
+/// Whether an npc will chase the players by the pursuit's flow field this
+/// round, as `act` and `chase` decide.
+fn joins_pack(mob_id: MobId, world: &World) -> bool {
+    world[mob_id].alive
+        && matches!(intent(mob_id, world), Intent::Chase(_))
+        && approach(mob_id, world) == Approach::Pack
+}
+
+/// Keep out of reach of a target standing on a ward.
+fn shun(mob_id: MobId, target: Pos, world: &mut World) -> Result<(), ()> {
     let mob_pos = world[mob_id].pos;
-    for &direction in &DIRECTIONS {
-        let direction = if flip { direction.rotate(3) } else { direction };
-        let pos = mob_pos + direction;
-        if pos.distance(target) < mob_pos.distance(target) {
-            if let Ok(x) = action::walk(mob_id, direction, world) {
-                return Ok(x);
+    if mob_pos.distance(target) <= 1 {
+        for &direction in &DIRECTIONS {
+            let pos = mob_pos + direction;
+            if pos.distance(target) > 1 && world.fits(mob_id, pos, direction) {
+                return action::walk(mob_id, direction, world);
             }
         }
     }
     action::rest(mob_id, world)
 }
+
+/// How many other npcs are next to a tile.
+fn crowding(mob_id: MobId, pos: Pos, world: &World) -> usize {
+    pos.neighbors()
+        .filter(|&pos| match world.level[pos].mob_id {
+            Some(other) => other != mob_id && !other.is_player(),
+            None => false,
+        })
+        .count()
+}
+
+/// What entering a tile costs a mob, with the tiles other mobs stand on
+/// treated as `occupancy` says.
+fn step_cost_among(mob_id: MobId, pos: Pos, occupancy: Occupancy, world: &World) -> Option<u32> {
+    let occupied = world.level[pos].mob_id.is_some_and(|other| other != mob_id);
+    occupancy.cost(world.step_cost(mob_id, pos), occupied)
+}
+
+/// Chase a target along a path wide enough for the whole footprint.
+///
+/// Stepping greedily would wedge a big mob in the first narrow tunnel.
+/// Other npcs in the way are gone around when there is room.
+fn charge(mob_id: MobId, target: Pos, world: &mut World) -> Result<(), ()> {
+    let mob_pos = world[mob_id].pos;
+    let reach = world[mob_id].species.size().reach() + 1;
+    let path = find_path_among(
+        mob_pos,
+        Goal::Within(target, reach),
+        |pos| world.step_cost(mob_id, pos).is_some(),
+        |pos| {
+            world
+                .blocker(mob_id, pos, world.facing_toward(mob_id, pos))
+                .is_some_and(|(other, _)| !other.is_player())
+        },
+        Occupancy::Penalize(CROWD_PENALTY),
+        Some(MAX_CHASE_STEPS),
+    );
+    let next = path.and_then(|path| path.next_step());
+    match next {
+        Some(next) => action::walk(mob_id, (next - mob_pos).direction(), world)
+            .or_else(|()| action::rest(mob_id, world)),
+        None if mob_pos.distance(target) <= reach => {
+            action::walk(mob_id, (target - mob_pos).direction(), world)
+                .or_else(|()| action::rest(mob_id, world))
+        }
+        None => action::rest(mob_id, world),
+    }
+}
+
+/// Chase a target along the path that strays least from the light a mob likes.
+fn stalk(mob_id: MobId, target: Pos, world: &mut World) -> Result<(), ()> {
+    let mob_pos = world[mob_id].pos;
+    if mob_pos.distance(target) <= 1 {
+        return action::walk(mob_id, (target - mob_pos).direction(), world)
+            .or_else(|()| action::rest(mob_id, world));
+    }
+    let cost = |pos: Pos| {
+        let cost = step_cost_among(mob_id, pos, Occupancy::Block, world)?;
+        Some(cost + LIGHT_PENALTY * world.light_discomfort(mob_id, pos))
+    };
+    let path = cheapest_path_to(mob_pos, Goal::Adjacent(target), cost);
+    match path.and_then(|path| path.next_step()) {
+        Some(next) => action::walk(mob_id, (next - mob_pos).direction(), world)
+            .or_else(|()| action::rest(mob_id, world)),
+        None => action::rest(mob_id, world),
+    }
+}
+
+/// Keep a target at a distance and shoot it.
+///
+/// Backs away from a target that gets close, fighting only when cornered,
+/// and moves to get a clear shot when walls or other npcs are in the way.
+fn kite(mob_id: MobId, target: Pos, range: u32, world: &mut World) -> Result<(), ()> {
+    let mob_pos = world[mob_id].pos;
+    let cost = |pos: Pos| step_cost_among(mob_id, pos, Occupancy::Block, world);
+    let path = if mob_pos.distance(target) <= 1 {
+        cheapest_path(
+            mob_pos,
+            |pos| pos.distance(target) >= KITE_DISTANCE,
+            |pos| {
+                let cost = cost(pos)?;
+                if pos.distance(target) <= 1 {
+                    Some(cost + CLOSE_PENALTY)
+                } else {
+                    Some(cost)
+                }
+            },
+            |_| 0,
+        )
+    } else if world.can_shoot(mob_id, target) {
+        return ranged::shoot(mob_id, target, world).or_else(|()| action::rest(mob_id, world));
+    } else {
+        cheapest_path(
+            mob_pos,
+            |pos| {
+                pos.distance(target) >= 2
+                    && pos.distance(target) <= range
+                    && world.projectile_blocker(pos, target).is_none()
+            },
+            cost,
+            |_| 0,
+        )
+    };
+    match path.and_then(|path| path.next_step()) {
+        Some(next) => action::walk(mob_id, (next - mob_pos).direction(), world)
+            .or_else(|()| action::rest(mob_id, world)),
+        None if mob_pos.distance(target) <= 1 => {
+            action::walk(mob_id, (target - mob_pos).direction(), world)
+                .or_else(|()| action::rest(mob_id, world))
+        }
+        None => action::rest(mob_id, world),
+    }
+}
+
+/// Run for the stairs down with stolen loot, and escape once there.
+fn run_for_stairs(mob_id: MobId, world: &mut World) -> Result<(), ()> {
+    let mob_pos = world[mob_id].pos;
+    let exit = match world.exit() {
+        Some(exit) => exit,
+        None => return action::rest(mob_id, world),
+    };
+    if mob_pos.distance(exit) <= 1 {
+        world.escape(mob_id);
+        return Ok(());
+    }
+    let path = cheapest_path_to(mob_pos, Goal::Adjacent(exit), |pos| {
+        step_cost_among(mob_id, pos, Occupancy::Block, world)
+    });
+    match path.and_then(|path| path.next_step()) {
+        Some(next) => action::walk(mob_id, (next - mob_pos).direction(), world)
+            .or_else(|()| action::rest(mob_id, world)),
+        None => action::rest(mob_id, world),
+    }
+}
+
+/// Raise the dead, and keep them between the mob and a target.
+///
+/// Raises a corpse beside the mob if there is one, and otherwise heads for
+/// the nearest, avoiding tiles no further from the target than its
+/// minions. With no corpse to raise, backs away behind its minions, or
+/// fights like anything else if it has none.
+fn command(mob_id: MobId, target: Pos, world: &mut World) -> Result<(), ()> {
+    let mob_pos = world[mob_id].pos;
+    if let Some(corpse) = mob_pos.neighbors().find(|&pos| world.raisable(pos)) {
+        return world.raise(mob_id, corpse);
+    }
+    let cover = world
+        .minions(mob_id)
+        .iter()
+        .map(|&minion| world[minion].pos.distance(target))
+        .min();
+    let exposed = |pos: Pos| cover.is_some_and(|cover| pos.distance(target) <= cover);
+    let cost = |pos: Pos| {
+        let cost = step_cost_among(mob_id, pos, Occupancy::Block, world)?;
+        let penalty = if exposed(pos) { EXPOSED_PENALTY } else { 0 };
+        Some(cost + penalty)
+    };
+    let path = if grid::positions().any(|pos| world.raisable(pos)) {
+        cheapest_path(
+            mob_pos,
+            |pos| pos.neighbors().any(|pos| world.raisable(pos)),
+            cost,
+            |_| 0,
+        )
+    } else if exposed(mob_pos) {
+        cheapest_path(mob_pos, |pos| !exposed(pos), cost, |_| 0)
+    } else if cover.is_some() {
+        return action::rest(mob_id, world);
+    } else {
+        return chase(mob_id, target, world);
+    };
+    match path.and_then(|path| path.next_step()) {
+        Some(next) => action::walk(mob_id, (next - mob_pos).direction(), world)
+            .or_else(|()| action::rest(mob_id, world)),
+        None => chase(mob_id, target, world),
+    }
+}
@@ -0,0 +1,123 @@
+//! The feel of the place the active player stands in, for ambient sound and color.
+//!
+//! Ambience is worked out afresh from what the player can see within
+//! `AMBIENCE_RADIUS`, or only next to them if they are blind, along with the
+//! kind of space they stand in. Frontends are free to ignore it.
+
+use fov::calc_fov;
+pub use level::space::Space;
+use level::tile::Terrain;
+use prelude::*;
+use std::collections::HashSet;
+use world::lamp::DARK_RADIUS;
+
+/// How far around the player the surroundings are taken into account.
+pub const AMBIENCE_RADIUS: u32 = 8;
+/// The fewest tiles of moss in view, per hundred open tiles, that feel overgrown.
+pub const OVERGROWN_PERCENT: usize = 30;
+
+/// What the surroundings of the active player are like.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Ambience {
+    /// The kind of space the player stands in.
+    pub space: Space,
+    /// Whether there is water in view.
+    pub near_water: bool,
+    /// Whether much of the open ground in view is covered in moss.
+    pub overgrown: bool,
+    /// Whether there are webs in view.
+    pub webbed: bool,
+    /// How brightly lit the player's tile is.
+    pub light: u32,
+}
+
+impl World {
+    /// Work out the surroundings of the active player from what they can see.
+    pub fn ambience(&self) -> Ambience {
+        let center = self.player().pos;
+        let radius = if self.is_blind(self.active_player()) {
+            DARK_RADIUS
+        } else {
+            AMBIENCE_RADIUS
+        };
+        // The fov can visit a tile more than once, so gather them first.
+        let mut in_view = HashSet::new();
+        calc_fov(
+            center,
+            |pos| self.level[pos].terrain.transparent(),
+            |pos| {
+                if pos.distance(center) <= radius {
+                    in_view.insert(pos);
+                }
+            },
+        );
+        let seen: Vec<_> = in_view
+            .into_iter()
+            .map(|pos| self.level[pos].terrain)
+            .collect();
+        let open = seen.iter().filter(|terrain| terrain.passable()).count();
+        let moss = seen
+            .iter()
+            .filter(|&&terrain| terrain == Terrain::ShortGrass)
+            .count();
+        Ambience {
+            space: self.level[center].space.unwrap_or(Space::Corridor),
+            near_water: seen.contains(&Terrain::Water),
+            overgrown: open > 0 && moss * 100 >= open * OVERGROWN_PERCENT,
+            webbed: seen.contains(&Terrain::Web),
+            light: self.light(center),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing;
+
+    /// A world with nothing but floor within the ambience radius of the player.
+    fn open_world() -> World {
+        let mut world = testing::open_world(26, AMBIENCE_RADIUS + 1);
+        world.reclassify();
+        world
+    }
+
+    #[test]
+    fn test_open_cavern_and_corridor() {
+        let mut world = open_world();
+        let ambience = world.ambience();
+        assert_eq!(ambience.space, Space::Cavern);
+        assert!(!ambience.near_water && !ambience.overgrown && !ambience.webbed);
+
+        let center = world.player().pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= AMBIENCE_RADIUS + 1) {
+            world.level[pos].terrain = Terrain::Wall;
+        }
+        for distance in 0..4 {
+            let (east, west): (Pos, Pos) = (
+                center + Direction::East * distance,
+                center + Direction::West * distance,
+            );
+            world.level[east].terrain = Terrain::Floor;
+            world.level[west].terrain = Terrain::Floor;
+        }
+        let water: Pos = center + Direction::East * 4;
+        world.level[water].terrain = Terrain::Water;
+        world.reclassify();
+        let ambience = world.ambience();
+        assert_eq!(ambience.space, Space::Corridor);
+        assert!(ambience.near_water);
+    }
+
+    #[test]
+    fn test_moss_overgrows() {
+        let mut world = open_world();
+        let center = world.player().pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 5) {
+            world.level[pos].terrain = Terrain::ShortGrass;
+        }
+        let ambience = world.ambience();
+        assert!(ambience.overgrown);
+        assert_eq!(ambience.light, 2);
+    }
+}
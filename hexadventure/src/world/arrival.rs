@@ -0,0 +1,103 @@
+//! Putting mobs on a level they arrive at from another.
+//!
+//! Every way onto a new level goes through `arrive`, so no mob ever lands
+//! in a wall, in water, or on top of another mob. A player whose landing
+//! spot is taken by an npc shoves it aside rather than being pushed away
+//! from where they meant to arrive.
+
+use prelude::*;
+use world::mob::Size;
+
+impl World {
+    /// Put a mob on the current level as close to a position as it can stand.
+    ///
+    /// The mob's old position is taken to be on another level.
+    /// Returns where it ended up.
+    pub(crate) fn arrive(&mut self, mob_id: MobId, center: Pos) -> Pos {
+        let facing = self[mob_id].facing;
+        if mob_id.is_player() {
+            if let Some(spot) = self.nearest(center, |pos| self.fits(mob_id, pos, facing)) {
+                self.shove_aside(mob_id, spot);
+            }
+        }
+        let pos = self
+            .nearest(center, |pos| {
+                self.fits(mob_id, pos, facing) && self.blocker(mob_id, pos, facing).is_none()
+            })
+            .expect("Every level has room for another mob.");
+        self[mob_id].pos = pos;
+        for pos in self[mob_id].footprint() {
+            self.level[pos].mob_id = Some(mob_id);
+        }
+        pos
+    }
+
+    /// The closest position to a center that is accepted, nearest rings first.
+    fn nearest<F: Fn(Pos) -> bool>(&self, center: Pos, accept: F) -> Option<Pos> {
+        let max_radius = grid::WIDTH.max(grid::HEIGHT) as u32;
+        // A ring of radius 0 is empty, so the center is tried on its own.
+        Some(center)
+            .into_iter()
+            .chain((1..max_radius).flat_map(|radius| center.ring(radius)))
+            .find(|&pos| accept(pos))
+    }
+
+    /// Move a small npc in the way of an arriving mob to a free tile next to it.
+    fn shove_aside(&mut self, mob_id: MobId, center: Pos) {
+        let facing = self[mob_id].facing;
+        let occupant = match self.blocker(mob_id, center, facing) {
+            Some((occupant, _)) => occupant,
+            None => return,
+        };
+        if occupant.is_player() || self[occupant].species.size() != Size::Small {
+            return;
+        }
+        let footprint = self[mob_id].species.size().footprint(center, facing);
+        let occupant_facing = self[occupant].facing;
+        let aside = self[occupant].pos.neighbors().find(|&pos| {
+            !footprint.contains(&pos)
+                && self.fits(occupant, pos, occupant_facing)
+                && self.blocker(occupant, pos, occupant_facing).is_none()
+        });
+        if let Some(aside) = aside {
+            self.move_mob(occupant, aside);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::mob::Species;
+
+    #[test]
+    fn test_arrival_avoids_walls_and_shoves_npcs() {
+        let mut world = World::from_seed(27, GenParams::default());
+        let center = world.player().pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+            if let Some(mob_id) = world.level[pos].mob_id {
+                world.lift(mob_id);
+            }
+        }
+        let water: Pos = center + Direction::East * 2;
+        world.level[water].terrain = Terrain::Water;
+        let skeleton = world.spawn(Species::Skeleton, center).unwrap();
+        let player = world.active_player();
+
+        assert_eq!(world.arrive(player, center), center);
+        assert_eq!(world.level[center].mob_id, Some(player));
+        assert_eq!(world[skeleton].pos.distance(center), 1);
+        assert_eq!(world.level[world[skeleton].pos].mob_id, Some(skeleton));
+
+        let spider = world.spawn(Species::Spider, center + Direction::West * 3);
+        world.lift(spider.unwrap());
+        let pos = world.arrive(spider.unwrap(), water);
+        assert_ne!(pos, water);
+        assert!(world.level[pos].terrain.passable());
+        assert!(pos.distance(water) <= 1);
+    }
+}
@@ -0,0 +1,506 @@
+//! Actions that repeat over many turns: resting, travelling and exploring.
+//!
+//! All three stop under the same conditions, decided in one place by
+//! `World::interruption`, so they can't drift apart.
+
+use astar::{find_path_avoiding, jps, Goal, Path};
+use camera::Focus;
+use level::tile::{Terrain, TileView};
+use prelude::*;
+use world::action::{Action, ActionError};
+use world::mob;
+
+/// How many extra steps travelling will take to keep off a known web.
+const WEB_PENALTY: u32 = 8;
+/// How many extra steps travelling will take to keep out of reach of a visible hostile.
+const HOSTILE_PENALTY: u32 = 8;
+
+/// Settings a player can change in the middle of a run.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct GameOptions {
+    pub auto_stop: AutoStop,
+    /// Spawn more mobs for players doing well and fewer for those struggling.
+    pub adaptive_pacing: bool,
+    /// Whether weak mobs keep away from players standing on a ward.
+    ///
+    /// Set from the profile when a game starts, once warding is unlocked.
+    pub wards: bool,
+    /// Whether every attack is recorded in detail in the combat log.
+    pub combat_detail: bool,
+}
+
+/// When repeated actions stop early.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AutoStop {
+    pub hostiles: HostileStop,
+    /// Stop once the player has lost health and has at most this percentage of it left.
+    pub low_health_percent: u32,
+}
+
+/// Which hostiles stop a repeated action.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum HostileStop {
+    /// Any hostile the player can see.
+    Any,
+    /// Only hostiles close enough to attack soon.
+    Dangerous,
+    /// Hostiles never stop a repeated action.
+    Never,
+}
+
+/// Why a repeated action stopped.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Stop {
+    /// A hostile came into view, or close enough to matter.
+    Hostile(Pos),
+    /// The player's health fell too low.
+    LowHealth,
+    /// The player is no longer the one taking a turn.
+    TurnOver,
+    /// The action was done, e.g. the destination was reached.
+    Done,
+    /// There is no way to carry on.
+    Blocked,
+    /// The action needs confirming before it is taken.
+    NeedsConfirmation,
+}
+
+/// A repeated action, saved with the world so it can be resumed after an interruption.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Plan {
+    Rest { turns_left: u32 },
+    Travel { destination: Pos },
+    Explore,
+}
+
+/// Hostiles at most this far away count as dangerous.
+const DANGER_DISTANCE: u32 = 2;
+
+impl World {
+    pub fn options(&self) -> &GameOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut GameOptions {
+        &mut self.options
+    }
+
+    /// Rest for up to a number of turns.
+    pub fn rest_for(&mut self, turns: u32) -> Stop {
+        self.repeat(Plan::Rest { turns_left: turns })
+    }
+
+    /// Walk to a known position along the shortest known path.
+    pub fn travel_to(&mut self, destination: Pos) -> Stop {
+        self.repeat(Plan::Travel { destination })
+    }
+
+    /// Walk towards the nearest unexplored part of the level until all of it is known.
+    pub fn explore(&mut self) -> Stop {
+        self.repeat(Plan::Explore)
+    }
+
+    /// The interrupted repeated action of the active player, if they have one.
+    pub fn plan(&self) -> Option<Plan> {
+        self.plans[self.active]
+    }
+
+    /// Forget the interrupted repeated action of the active player.
+    pub fn cancel_plan(&mut self) {
+        self.plans[self.active] = None;
+    }
+
+    /// Carry on with the interrupted repeated action of the active player.
+    ///
+    /// `None` if there is nothing to resume, or the plan no longer makes
+    /// sense, e.g. because it was loaded from a save of a different level.
+    pub fn resume(&mut self) -> Option<Stop> {
+        let plan = self.plans[self.active].take()?;
+        let valid = match plan {
+            Plan::Rest { turns_left } => turns_left > 0,
+            Plan::Travel { destination } => {
+                grid::contains(destination) && self.fov()[destination] != TileView::None
+            }
+            Plan::Explore => true,
+        };
+        if valid {
+            Some(self.repeat(plan))
+        } else {
+            None
+        }
+    }
+
+    /// Why the active player should stop repeating an action, if they should.
+    ///
+    /// `start_health` is the player's health when they started.
+    pub fn interruption(&self, start_health: u32) -> Option<Stop> {
+        let player = self.player();
+        if !player.alive {
+            return Some(Stop::TurnOver);
+        }
+        let options = &self.options.auto_stop;
+        let max_distance = match options.hostiles {
+            HostileStop::Any => Some(u32::MAX),
+            HostileStop::Dangerous => Some(DANGER_DISTANCE),
+            HostileStop::Never => None,
+        };
+        if let Some(max_distance) = max_distance {
+            let nearest = self
+                .visible_hostiles()
+                .into_iter()
+                .filter(|pos| pos.distance(player.pos) <= max_distance)
+                .min_by_key(|pos| pos.distance(player.pos));
+            if let Some(pos) = nearest {
+                return Some(Stop::Hostile(pos));
+            }
+        }
+        if player.health < start_health
+            && player.health * 100 <= player.max_health * options.low_health_percent
+        {
+            return Some(Stop::LowHealth);
+        }
+        None
+    }
+
+    /// Follow a plan until it is done or something interrupts it.
+    ///
+    /// An interrupted plan is kept so it can be resumed.
+    fn repeat(&mut self, mut plan: Plan) -> Stop {
+        let player = self.active_player();
+        let start_health = self.player().health;
+        let focus = match plan {
+            Plan::Travel { destination } => Focus::Travel { destination },
+            Plan::Rest { .. } | Plan::Explore => Focus::Player,
+        };
+        let stop = loop {
+            let action = match self.next_action(plan) {
+                Some(action) => action,
+                None => break Stop::Done,
+            };
+            if let Some(stop) = self.interruption(start_health) {
+                break stop;
+            }
+            match self.perform(action) {
+                Ok(()) => {
+                    if self.focus == Focus::Player {
+                        self.focus = focus;
+                    }
+                }
+                Err(ActionError::Invalid) => break Stop::Blocked,
+                Err(ActionError::NeedsConfirmation(_)) => break Stop::NeedsConfirmation,
+            }
+            if let Plan::Rest { turns_left } = plan {
+                plan = Plan::Rest {
+                    turns_left: turns_left - 1,
+                };
+            }
+            if self.active_player() != player {
+                break Stop::TurnOver;
+            }
+        };
+        let index = player.player_index().unwrap();
+        self.plans[index] = match stop {
+            Stop::Done | Stop::Blocked => None,
+            _ if !self[player].alive => None,
+            _ if plan == Plan::Rest { turns_left: 0 } => None,
+            _ => Some(plan),
+        };
+        stop
+    }
+
+    /// The next action a plan calls for, if it isn't done.
+    fn next_action(&mut self, plan: Plan) -> Option<Action> {
+        match plan {
+            Plan::Rest { turns_left } => {
+                if turns_left > 0 {
+                    Some(Action::Rest)
+                } else {
+                    None
+                }
+            }
+            Plan::Travel { destination } => self.travel_step(destination),
+            Plan::Explore => {
+                let fov = self.fov();
+                self.step_towards(|pos| {
+                    pos.neighbors()
+                        .any(|pos| matches!(fov[pos], TileView::None | TileView::Mapped(_)))
+                })
+            }
+        }
+    }
+
+    /// The next step towards a destination.
+    ///
+    /// The path found on an earlier step is followed for as long as neither
+    /// the terrain nor what the players know of it changes, and no mob the
+    /// player can see stands in the way. While hostiles are in sight the path is found afresh every step,
+    /// to keep away from them as they move.
+    fn travel_step(&mut self, destination: Pos) -> Option<Action> {
+        let origin = self.player().pos;
+        let revision = self.map_revision();
+        let cached = self
+            .path_cache
+            .get(revision, origin, destination)
+            .and_then(|rest| rest.get(1).cloned());
+        let cached = if self.visible_hostiles().is_empty() {
+            cached
+        } else {
+            None
+        };
+        let next = match cached {
+            Some(next) if !(self.fov()[next].is_visible() && self.level[next].mob_id.is_some()) => {
+                next
+            }
+            _ => {
+                let path = self.travel_path(destination)?;
+                let next = path.get(1).cloned();
+                self.path_cache.insert(revision, path);
+                next?
+            }
+        };
+        Some(Action::Walk((next - origin).direction()))
+    }
+
+    /// The first step along the shortest path the player knows to a goal.
+    ///
+    /// `None` if the player is already at a goal, or can't find a way to one.
+    fn step_towards<F>(&self, is_goal: F) -> Option<Action>
+    where
+        F: Fn(Pos) -> bool,
+    {
+        let path = self.known_path(is_goal)?;
+        let origin = path[0];
+        path.get(1)
+            .map(|&next| Action::Walk((next - origin).direction()))
+    }
+
+    /// The shortest path the player knows to a goal, starting where they stand.
+    ///
+    /// Only tiles the player has seen are used, and visible mobs are walked around.
+    pub(super) fn known_path<F>(&self, is_goal: F) -> Option<Vec<Pos>>
+    where
+        F: Fn(Pos) -> bool,
+    {
+        let origin = self.player().pos;
+        jps(origin, is_goal, |pos| self.known_passable(pos), |_| 0, None).map(Path::into_positions)
+    }
+
+    /// The path travelling to a destination takes, starting where the player stands.
+    ///
+    /// Like `known_path`, but known webs and the tiles next to visible
+    /// hostiles are kept clear of, unless going around takes many more steps.
+    pub(super) fn travel_path(&self, destination: Pos) -> Option<Vec<Pos>> {
+        let origin = self.player().pos;
+        let hostiles = self.visible_hostiles();
+        find_path_avoiding(
+            origin,
+            Goal::Tile(destination),
+            |pos| self.known_passable(pos),
+            |pos| self.travel_penalty(pos, &hostiles),
+        )
+        .map(Path::into_positions)
+    }
+
+    /// Where the npcs the active player can see stand.
+    pub(super) fn visible_hostiles(&self) -> Vec<Pos> {
+        let fov = self.fov();
+        let mut hostiles = Vec::new();
+        mob::for_each(self, |mob_id| {
+            let mob = &self[mob_id];
+            if mob.alive && fov[mob.pos].is_visible() {
+                hostiles.push(mob.pos);
+            }
+        });
+        hostiles
+    }
+
+    /// Whether the player knows they can walk through a tile, or open a door on it.
+    ///
+    /// Only tiles the player has seen count, and visible mobs are in the way.
+    fn known_passable(&self, pos: Pos) -> bool {
+        grid::contains(pos)
+            && match self.fov()[pos] {
+                TileView::Visible => {
+                    self.level[pos].terrain.passable_once_opened()
+                        && self.level[pos].mob_id.is_none()
+                }
+                TileView::Remembered(terrain) | TileView::Mapped(terrain) => {
+                    terrain.passable_once_opened()
+                }
+                TileView::None => false,
+            }
+    }
+
+    /// How many extra steps travelling through a tile is worth, for the
+    /// dangers the player knows of there.
+    fn travel_penalty(&self, pos: Pos, hostiles: &[Pos]) -> u32 {
+        let terrain = match self.fov()[pos] {
+            TileView::Visible => Some(self.level[pos].terrain),
+            TileView::Remembered(terrain) | TileView::Mapped(terrain) => Some(terrain),
+            TileView::None => None,
+        };
+        let mut penalty = 0;
+        if terrain == Some(Terrain::Web) {
+            penalty += WEB_PENALTY;
+        }
+        if hostiles.iter().any(|hostile| hostile.distance(pos) <= 1) {
+            penalty += HOSTILE_PENALTY;
+        }
+        penalty
+    }
+}
+
+impl Default for AutoStop {
+    fn default() -> Self {
+        AutoStop {
+            hostiles: HostileStop::Any,
+            low_health_percent: 50,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::Party;
+
+    #[test]
+    fn test_rest_stops_for_hostiles() {
+        let mut world = World::from_seed(3, GenParams::default());
+        remove_npcs(&mut world);
+        assert_eq!(world.rest_for(5), Stop::Done);
+        assert_eq!(world.turn(), 5);
+
+        let mut npc = None;
+        mob::for_each(&world, |mob_id| npc = Some(mob_id));
+        let npc = npc.unwrap();
+        let pos = world
+            .player()
+            .pos
+            .ring(3)
+            .find(|&pos| {
+                world.is_visible(pos)
+                    && world.level[pos].terrain.passable()
+                    && world.level[pos].mob_id.is_none()
+            })
+            .unwrap();
+        world[npc].alive = true;
+        world[npc].pos = pos;
+        world.level[pos].mob_id = Some(npc);
+        assert_eq!(world.rest_for(5), Stop::Hostile(pos));
+        assert_eq!(world.turn(), 5);
+
+        assert_eq!(world.plan(), Some(Plan::Rest { turns_left: 5 }));
+        world.cancel_plan();
+        assert_eq!(world.resume(), None);
+
+        world.options_mut().auto_stop.hostiles = HostileStop::Dangerous;
+        assert_eq!(world.rest_for(1), Stop::Done);
+    }
+
+    #[test]
+    fn test_plan_resumed_after_turn_over() {
+        let party = Party {
+            size: 2,
+            shared_fov: true,
+        };
+        let mut world = World::with_party(3, GenParams::default(), party);
+        remove_npcs(&mut world);
+        assert_eq!(world.rest_for(3), Stop::TurnOver);
+        assert_eq!(world.plan(), None);
+        world.tick();
+        assert_eq!(world.plan(), Some(Plan::Rest { turns_left: 2 }));
+        assert_eq!(world.resume(), Some(Stop::TurnOver));
+        world.tick();
+        assert_eq!(world.plan(), Some(Plan::Rest { turns_left: 1 }));
+        assert_eq!(world.resume(), Some(Stop::TurnOver));
+        world.tick();
+        assert_eq!(world.plan(), None);
+    }
+
+    #[test]
+    fn test_explore_reveals_level() {
+        let mut world = World::from_seed(5, GenParams::default());
+        remove_npcs(&mut world);
+        assert_eq!(world.explore(), Stop::Done);
+        let fov = world.fov();
+        let reachable = ::floodfill::flood(world.player().pos, |pos| {
+            world.level[pos].terrain.passable()
+        });
+        assert!(reachable.iter().all(|&pos| fov[pos] != TileView::None));
+    }
+
+    #[test]
+    fn test_travel_follows_path_until_map_changes() {
+        let mut world = World::from_seed(8, GenParams::default());
+        remove_npcs(&mut world);
+        let player = world.active_player();
+        let origin = world.player().pos;
+        for pos in grid::positions().filter(|pos| pos.distance(origin) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        // With the whole level mapped, walking around shows nothing new.
+        world.magic_map(player, origin, None);
+        world.update_fov();
+        let destination: Pos = origin + Direction::East * 2;
+        // A roundabout path, which travel keeps to since nothing changes.
+        let roundabout: Vec<Pos> = vec![
+            origin,
+            origin + Direction::Northeast,
+            origin + Direction::Northeast + Direction::East,
+            destination + Direction::Northeast,
+            destination,
+        ];
+        let revision = world.map_revision();
+        world.path_cache.insert(revision, roundabout.clone());
+        assert_eq!(world.travel_to(destination), Stop::Done);
+        assert_eq!(world.player().pos, destination);
+        assert_eq!(world.turn(), 4);
+        assert_eq!(world.map_revision(), revision);
+
+        let detour: Vec<Pos> = vec![
+            destination,
+            destination + Direction::Southwest,
+            origin + Direction::Southeast,
+            origin,
+        ];
+        world.path_cache.insert(revision, detour);
+        let wall: Pos = origin + Direction::East * 3;
+        world.set_terrain(wall, Terrain::Wall);
+        assert_ne!(world.map_revision(), revision);
+        assert_eq!(world.travel_to(origin), Stop::Done);
+        assert_eq!(world.turn(), 6);
+
+        // Forgetting the map changes what the player knows, so the
+        // roundabout path is found afresh too.
+        let revision = world.map_revision();
+        world.path_cache.insert(revision, roundabout);
+        world.forget_mapped(player);
+        assert_ne!(world.map_revision(), revision);
+        assert_eq!(world.travel_to(destination), Stop::Done);
+        assert_eq!(world.turn(), 8);
+    }
+
+    #[test]
+    fn test_travel_keeps_off_webs() {
+        let mut world = World::from_seed(8, GenParams::default());
+        remove_npcs(&mut world);
+        let origin = world.player().pos;
+        for pos in grid::positions().filter(|pos| pos.distance(origin) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let web: Pos = origin + Direction::East;
+        world.level[web].terrain = Terrain::Web;
+        world.update_fov();
+        let destination: Pos = origin + Direction::East * 2;
+        let path = world.travel_path(destination).unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(!path.contains(&web));
+        assert_eq!(world.travel_to(destination), Stop::Done);
+        assert!(!world.player().entangled);
+    }
+}
@@ -0,0 +1,101 @@
+//! The current level as the changes made to it since it was generated.
+//!
+//! The world keeps the level as it came out of generation, so working out
+//! what changed is a single comparison. The copy isn't saved, and after
+//! loading it is regenerated from the seed the first time it is needed.
+//! Generation is deterministic, so this gives back exactly the level the run
+//! started with, even one that fell back to a simple layout.
+
+use level::diff::LevelDiff;
+use level::space::classify;
+use level::tile::Tile;
+use prelude::*;
+
+impl World {
+    /// The changes made to the current level since it was generated.
+    pub fn level_diff(&mut self) -> Option<LevelDiff> {
+        let depth = self.depth();
+        if self.generated_level.is_none() {
+            self.generated_level = self.regenerate_level(depth).map(|(level, _)| level);
+        }
+        let generated = self.generated_level.as_ref()?;
+        Some(LevelDiff::between(depth, generated, &self.level))
+    }
+
+    /// Regenerate the level a diff was made on and apply the diff to it.
+    ///
+    /// `None` if the run hasn't generated that level yet, or it can't be
+    /// regenerated.
+    pub fn rebuild_level(&self, diff: &LevelDiff) -> Option<Grid<Tile>> {
+        let (mut level, _) = self.regenerate_level(diff.depth)?;
+        diff.apply(&mut level);
+        let terrain = Grid::new(|pos| level[pos].terrain);
+        let spaces = classify(&terrain);
+        for pos in grid::positions() {
+            level[pos].space = spaces[pos];
+        }
+        Some(level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bincode;
+    use level::tile::Terrain;
+    use level::GenParams;
+    use loot::Loot;
+    use world::action::Action;
+
+    #[test]
+    fn test_level_rebuilt_from_diff() {
+        let mut world = World::from_seed(29, GenParams::default());
+        let player = world.player().pos;
+        let dug = grid::inner_positions()
+            .find(|&pos| world.level[pos].terrain == Terrain::Wall)
+            .unwrap();
+        world.set_terrain(dug, Terrain::Floor);
+        let pile = player
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain.passable())
+            .unwrap();
+        world.level[pile].loot = Some(Loot {
+            gold: 5,
+            item: None,
+        });
+        let _ = world.perform_confirmed(Action::Rest);
+
+        let diff = world.level_diff().unwrap();
+        assert!(diff.changes().iter().any(|&(pos, _)| pos == dug));
+        assert!(diff.changes().iter().any(|&(pos, _)| pos == player));
+        assert!(diff.changes().len() < grid::WIDTH * grid::HEIGHT / 10);
+        assert!(world.rebuild_level(&diff).unwrap() == world.level);
+
+        let saved = bincode::serialize(&world).unwrap();
+        let mut loaded: World = bincode::deserialize(&saved).unwrap();
+        assert!(loaded.level_diff().unwrap() == diff);
+        let level_bytes = bincode::serialize(&Wrapper(world.level.clone())).unwrap();
+        assert!(bincode::serialize(&diff).unwrap().len() < level_bytes.len());
+    }
+
+    #[test]
+    fn test_fallback_level_rebuilt() {
+        let mut params = GenParams::default();
+        params.budget.max_attempts = 0;
+        let mut world = World::from_seed(29, params);
+        let diff = world.level_diff().unwrap();
+        assert!(world.rebuild_level(&diff).unwrap() == world.level);
+    }
+
+    #[test]
+    fn test_levels_not_reached_not_rebuilt() {
+        let mut world = World::from_seed(29, GenParams::default());
+        let mut diff = world.level_diff().unwrap();
+        diff.depth = world.depth() + 1;
+        assert!(world.rebuild_level(&diff).is_none());
+    }
+
+    #[derive(Serialize)]
+    struct Wrapper(#[serde(with = "grid::rle")] Grid<Tile>);
+}
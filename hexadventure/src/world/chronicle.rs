@@ -0,0 +1,282 @@
+//! The notable events of a run, and a summary of the run as JSON.
+//!
+//! The world notes each level entered, each npc killed, each pile of loot
+//! and each rune picked up, and each player death, in the order they
+//! happen. `run_summary` turns these into a JSON document for tools that
+//! track runs, alongside the morgue's plain text.
+//!
+//! The summary is an object with these fields:
+//!
+//! ```text
+//! version   SUMMARY_VERSION
+//! seed      the master seed, as a string
+//! status    "alive" or "dead"
+//! turns     how many turns have passed
+//! depth     the depth of the current level
+//! party     how many players there are
+//! options   every game option, by the name of its field
+//! levels    for each level reached, shallowest first:
+//!             depth, name, seed (a string), simple_layout, entered_turn
+//!             (null if never entered), kills, gold_found, items_found
+//! kills     species and count, in the order of their first kill
+//! timeline  every milestone: turn, depth, event, and by event
+//!             killed:      species
+//!             found:       gold, item (a tier name, or null)
+//!             player_died: player
+//! ```
+//!
+//! The layout of the summary only ever gains fields while
+//! `SUMMARY_VERSION` stays the same, so readers can skip fields they don't
+//! know. Seeds are written as strings, since they don't fit the numbers
+//! most JSON readers use.
+
+use loot::Loot;
+use prelude::*;
+use world::auto::GameOptions;
+use world::mob::Species;
+
+/// The version of the layout of run summaries.
+pub const SUMMARY_VERSION: u32 = 1;
+
+/// Something worth remembering about a run.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Milestone {
+    pub turn: u64,
+    /// The depth of the level it happened on.
+    pub depth: u32,
+    pub kind: MilestoneKind,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum MilestoneKind {
+    /// The party entered the level.
+    Entered,
+    /// An npc died.
+    Killed(Species),
+    /// A player picked up loot.
+    Found(Loot),
+    /// A player took the rune of the level.
+    TookRune,
+    /// A player died, given by their index.
+    PlayerDied(usize),
+}
+
+impl MilestoneKind {
+    /// The name of the kind in run summaries.
+    fn name(self) -> &'static str {
+        match self {
+            MilestoneKind::Entered => "entered",
+            MilestoneKind::Killed(_) => "killed",
+            MilestoneKind::Found(_) => "found",
+            MilestoneKind::TookRune => "took_rune",
+            MilestoneKind::PlayerDied(_) => "player_died",
+        }
+    }
+}
+
+impl World {
+    /// Every milestone of the run so far, in the order they happened.
+    pub fn milestones(&self) -> &[Milestone] {
+        &self.milestones
+    }
+
+    /// Note a milestone on the current level.
+    pub(crate) fn note(&mut self, kind: MilestoneKind) {
+        let milestone = Milestone {
+            turn: self.turn(),
+            depth: self.depth(),
+            kind,
+        };
+        self.milestones.push(milestone);
+    }
+
+    /// Summarize the run so far as a JSON object.
+    pub fn run_summary(&self) -> String {
+        let summary = RunSummary {
+            version: SUMMARY_VERSION,
+            seed: self.seed().to_string(),
+            status: if self.is_over() { "dead" } else { "alive" },
+            turns: self.turn(),
+            depth: self.depth(),
+            party: self.players().len(),
+            options: self.options(),
+            levels: (1..=self.depth())
+                .map(|depth| self.level_summary(depth))
+                .collect(),
+            kills: self.kill_counts(),
+            timeline: self.milestones.iter().map(TimelineEntry::new).collect(),
+        };
+        serde_json::to_string(&summary).expect("Run summaries always serialize.")
+    }
+
+    /// What happened on the level at a depth.
+    fn level_summary(&self, depth: u32) -> LevelSummary {
+        let mut summary = LevelSummary {
+            depth,
+            name: self.level_name(depth),
+            seed: self.level_seed(depth).to_string(),
+            simple_layout: self.architect.fallback_depths().contains(&depth),
+            entered_turn: None,
+            kills: 0,
+            gold_found: 0,
+            items_found: 0,
+        };
+        let on_level = self
+            .milestones
+            .iter()
+            .filter(|milestone| milestone.depth == depth);
+        for milestone in on_level {
+            match milestone.kind {
+                MilestoneKind::Entered => {
+                    summary.entered_turn = summary.entered_turn.or(Some(milestone.turn))
+                }
+                MilestoneKind::Killed(_) => summary.kills += 1,
+                MilestoneKind::Found(loot) => {
+                    summary.gold_found += loot.gold;
+                    summary.items_found += loot.item.map_or(0, |_| 1);
+                }
+                MilestoneKind::TookRune | MilestoneKind::PlayerDied(_) => {}
+            }
+        }
+        summary
+    }
+
+    /// How many of each species were killed, in the order of their first kill.
+    fn kill_counts(&self) -> Vec<KillCount> {
+        let mut counts: Vec<KillCount> = Vec::new();
+        for milestone in &self.milestones {
+            if let MilestoneKind::Killed(species) = milestone.kind {
+                let species = species.name();
+                match counts.iter_mut().find(|kill| kill.species == species) {
+                    Some(kill) => kill.count += 1,
+                    None => counts.push(KillCount { species, count: 1 }),
+                }
+            }
+        }
+        counts
+    }
+}
+
+#[derive(Serialize)]
+struct RunSummary<'a> {
+    version: u32,
+    seed: String,
+    status: &'static str,
+    turns: u64,
+    depth: u32,
+    party: usize,
+    options: &'a GameOptions,
+    levels: Vec<LevelSummary>,
+    kills: Vec<KillCount>,
+    timeline: Vec<TimelineEntry>,
+}
+
+#[derive(Serialize)]
+struct LevelSummary {
+    depth: u32,
+    name: String,
+    seed: String,
+    simple_layout: bool,
+    entered_turn: Option<u64>,
+    kills: u32,
+    gold_found: u32,
+    items_found: u32,
+}
+
+#[derive(Serialize)]
+struct KillCount {
+    species: &'static str,
+    count: u32,
+}
+
+/// A milestone, with only the fields its kind of event has.
+#[derive(Serialize)]
+struct TimelineEntry {
+    turn: u64,
+    depth: u32,
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    species: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gold: Option<u32>,
+    /// Present for found loot, where it is null if the pile had no item.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item: Option<Option<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player: Option<usize>,
+}
+
+impl TimelineEntry {
+    fn new(milestone: &Milestone) -> Self {
+        let mut entry = TimelineEntry {
+            turn: milestone.turn,
+            depth: milestone.depth,
+            event: milestone.kind.name(),
+            species: None,
+            gold: None,
+            item: None,
+            player: None,
+        };
+        match milestone.kind {
+            MilestoneKind::Killed(species) => entry.species = Some(species.name()),
+            MilestoneKind::Found(loot) => {
+                entry.gold = Some(loot.gold);
+                entry.item = Some(loot.item.map(|tier| tier.name()));
+            }
+            MilestoneKind::PlayerDied(index) => entry.player = Some(index),
+            MilestoneKind::Entered | MilestoneKind::TookRune => {}
+        }
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+    use world::action;
+    use world::mob;
+
+    #[test]
+    fn test_summary_counts_kills_and_loot() {
+        let mut world = World::from_seed(41, GenParams::default());
+        let player = world.active_player();
+        let mut victim = None;
+        mob::for_each(&world, |mob_id| {
+            if victim.is_none() && world[mob_id].alive {
+                victim = Some(mob_id);
+            }
+        });
+        let victim = victim.unwrap();
+        let species = world[victim].species;
+        victim.die(&mut world);
+        let pos = world[player].pos;
+        world.level[pos].loot = Some(Loot {
+            gold: 7,
+            item: None,
+        });
+        world.pick_up(player);
+        let exit = world.exit().unwrap();
+        action::descend_unchecked(player, exit, &mut world);
+
+        let kinds: Vec<MilestoneKind> = world.milestones().iter().map(|m| m.kind).collect();
+        assert_eq!(kinds[0], MilestoneKind::Entered);
+        assert!(kinds.contains(&MilestoneKind::Killed(species)));
+        assert_eq!(kinds[kinds.len() - 1], MilestoneKind::Entered);
+
+        let json = world.run_summary();
+        assert!(json.starts_with("{\"version\":1,\"seed\":\"41\","));
+        assert!(json.contains(&format!(
+            "{{\"species\":\"{}\",\"count\":1}}",
+            species.name()
+        )));
+        assert!(json.contains("\"gold_found\":7,"));
+        assert!(json.contains("\"event\":\"found\",\"gold\":7,\"item\":null}"));
+        assert!(json.contains("\"combat_detail\":false"));
+        assert!(json.contains("\"hostiles\":\"Any\""));
+        assert!(json.contains("{\"depth\":2,"));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+        assert_eq!(json.matches('[').count(), json.matches(']').count());
+    }
+}
@@ -0,0 +1,155 @@
+//! A detailed record of how every attack was resolved.
+//!
+//! With `GameOptions::combat_detail` on, each strike records its chance to
+//! hit and the roll against it, the damage rolled, every modifier that
+//! changed either, and how much guard and health the target lost. Unlike
+//! messages, attacks are recorded whether or not a player saw them, so the
+//! log explains deaths and helps balance the game. Only the latest
+//! `COMBAT_LOG_LEN` attacks are kept, and the morgue includes them.
+
+use prelude::*;
+use world::modifier::{Attribute, Effect, Modifier};
+
+/// How many of the latest attacks the combat log keeps.
+pub const COMBAT_LOG_LEN: usize = 100;
+
+/// How one attack was resolved.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct AttackRecord {
+    pub turn: u64,
+    pub attacker: String,
+    pub target: String,
+    /// How the attack was made, e.g. "hits".
+    pub verb: String,
+    /// The percent chance of hitting, after modifiers.
+    pub hit_chance: u32,
+    /// The percent roll against the chance to hit, if one was needed.
+    pub hit_roll: Option<u32>,
+    /// The damage dice rolled, or `None` if the attack missed.
+    pub rolled: Option<u32>,
+    /// The attacker's accuracy and damage modifiers, and the target's defense modifiers.
+    pub modifiers: Vec<Modifier>,
+    pub damage: u32,
+    pub guard_lost: u32,
+    pub health_lost: u32,
+    pub killed: bool,
+}
+
+impl AttackRecord {
+    /// A line breaking the attack down, for reading.
+    pub fn describe(&self) -> String {
+        let mut line = format!("Turn {}: {} ", self.turn, self.attacker);
+        match self.rolled {
+            None => line += &format!("misses {}", self.target),
+            Some(_) => line += &format!("{} {}", self.verb, self.target),
+        }
+        line += &format!("; {}% to hit", self.hit_chance);
+        if let Some(roll) = self.hit_roll {
+            line += &format!(", rolled {}", roll);
+        }
+        for modifier in &self.modifiers {
+            let effect = match modifier.effect {
+                Effect::Flat(amount) => format!("{:+}", amount),
+                Effect::Percent(amount) => format!("{:+}%", amount),
+                Effect::Cap(limit) => format!("at most {}", limit),
+            };
+            line += &format!(
+                "; {} {} ({})",
+                effect,
+                format!("{:?}", modifier.attribute).to_lowercase(),
+                format!("{:?}", modifier.source).to_lowercase()
+            );
+        }
+        if let Some(rolled) = self.rolled {
+            line += &format!(
+                "; rolled {} damage, dealt {}: {} guard, {} health",
+                rolled, self.damage, self.guard_lost, self.health_lost
+            );
+        }
+        if self.killed {
+            line += "; killed";
+        }
+        line
+    }
+}
+
+impl World {
+    /// The latest attacks, oldest first, if the detailed combat log is on.
+    pub fn combat_log(&self) -> &[AttackRecord] {
+        &self.combat_log
+    }
+
+    /// The modifiers that bear on an attack from one mob on another.
+    pub(crate) fn attack_modifiers(&self, mob_id: MobId, target: MobId) -> Vec<Modifier> {
+        let attacker = self.modifiers(mob_id);
+        let defender = self.modifiers(target);
+        attacker
+            .of(Attribute::Accuracy)
+            .chain(attacker.of(Attribute::Damage))
+            .chain(defender.of(Attribute::Defense))
+            .cloned()
+            .collect()
+    }
+
+    /// Record an attack, keeping only the latest.
+    pub(crate) fn log_attack(&mut self, record: AttackRecord) {
+        self.combat_log.push(record);
+        if self.combat_log.len() > COMBAT_LOG_LEN {
+            self.combat_log.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::action::Action;
+    use world::mob::{Mob, Species};
+    use world::modifier::{Source, Stance};
+
+    #[test]
+    fn test_attacks_broken_down() {
+        let mut world = World::from_seed(35, GenParams::default());
+        remove_npcs(&mut world);
+        let player = world.active_player();
+        let center = world[player].pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let skeleton_pos: Pos = center + Direction::East;
+        let skeleton = world.npcs.insert(Mob::new(skeleton_pos, Species::Skeleton));
+        world.level[skeleton_pos].mob_id = Some(skeleton);
+        world.update_fov();
+
+        world.perform(Action::Walk(Direction::East)).unwrap();
+        assert!(world.combat_log().is_empty());
+
+        world.options_mut().combat_detail = true;
+        world[player].stance = Stance::Defensive;
+        let guard = world[skeleton].guard;
+        world.perform(Action::Walk(Direction::East)).unwrap();
+        let record = world
+            .combat_log()
+            .iter()
+            .find(|record| record.attacker == world.describe(player))
+            .unwrap()
+            .clone();
+        assert_eq!(record.hit_chance, 75);
+        assert!(record.hit_roll.is_some());
+        assert!(record
+            .modifiers
+            .iter()
+            .any(|modifier| modifier.source == Source::Stance));
+        match record.rolled {
+            Some(_) => {
+                assert_eq!(record.guard_lost, guard - world[skeleton].guard);
+                assert!(record.describe().contains("guard"));
+            }
+            None => assert!(record.describe().contains("misses")),
+        }
+    }
+}
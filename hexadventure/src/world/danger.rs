@@ -0,0 +1,97 @@
+//! Detecting actions a player should confirm before taking.
+//!
+//! Every frontend gets the same protection by handling
+//! `ActionError::NeedsConfirmation` instead of checking for danger itself.
+
+use prelude::*;
+use std::fmt;
+use world::action::Action;
+use world::predict::Outcome;
+
+/// Why an action needs confirming.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Danger {
+    /// Taking the stairs with this many hostiles next to the player.
+    HostilesAdjacent(usize),
+}
+
+impl World {
+    /// Whether an action by the active player is dangerous.
+    pub fn danger(&self, action: Action) -> Option<Danger> {
+        match self.predict(action).outcome {
+            Outcome::Descend => {
+                let player = self.active_player();
+                let pos = self[player].pos;
+                let mut hostiles = Vec::new();
+                for mob_id in pos.neighbors().filter_map(|pos| self.level[pos].mob_id) {
+                    // A huge mob may be next to the player on several tiles.
+                    if mob_id.is_player() != player.is_player() && !hostiles.contains(&mob_id) {
+                        hostiles.push(mob_id);
+                    }
+                }
+                let hostiles = hostiles.len();
+                if hostiles > 0 {
+                    Some(Danger::HostilesAdjacent(hostiles))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Danger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Danger::HostilesAdjacent(1) => write!(f, "An enemy is next to you."),
+            Danger::HostilesAdjacent(count) => write!(f, "{} enemies are next to you.", count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::action::ActionError;
+    use world::mob;
+    use world::testing::remove_npcs_near;
+
+    fn move_mob(world: &mut World, mob_id: MobId, pos: Pos) {
+        let old_pos = world[mob_id].pos;
+        world.level[old_pos].mob_id = None;
+        world.level[pos].mob_id = Some(mob_id);
+        world[mob_id].pos = pos;
+    }
+
+    #[test]
+    fn test_descend_next_to_hostile() {
+        let mut world = World::from_seed(8, GenParams::default());
+        let exit = grid::positions()
+            .find(|&pos| world.level[pos].terrain == Terrain::Exit)
+            .unwrap();
+        let free = |world: &World, pos: Pos| {
+            world.level[pos].terrain.passable() && world.level[pos].mob_id.is_none()
+        };
+        let stand = exit.neighbors().find(|&pos| free(&world, pos)).unwrap();
+        // Generation may leave a mob by the stairs; the first check needs none.
+        remove_npcs_near(&mut world, stand, 2);
+        let player = world.active_player();
+        move_mob(&mut world, player, stand);
+        let action = Action::Walk((exit - stand).direction());
+        assert_eq!(world.danger(action), None);
+
+        let mut npc = None;
+        mob::for_each(&world, |mob_id| npc = Some(mob_id));
+        let beside = stand.neighbors().find(|&pos| free(&world, pos)).unwrap();
+        move_mob(&mut world, npc.unwrap(), beside);
+        assert_eq!(
+            world.perform(action),
+            Err(ActionError::NeedsConfirmation(Danger::HostilesAdjacent(1)))
+        );
+        assert_eq!(world.player().pos, stand);
+    }
+}
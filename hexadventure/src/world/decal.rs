@@ -0,0 +1,50 @@
+//! Marks left on the level by what happens there.
+
+use level::tile::{Decal, DecalKind};
+use prelude::*;
+
+impl World {
+    /// Leave a fresh mark on a tile, replacing any mark already there.
+    pub(crate) fn stain(&mut self, pos: Pos, kind: DecalKind) {
+        self.level[pos].decal = Some(Decal {
+            kind,
+            turns_left: kind.lifetime(),
+        });
+    }
+
+    /// Age every mark on the level by a turn, removing those that have faded.
+    pub(super) fn fade_decals(&mut self) {
+        for tile in self.level.iter_mut() {
+            let faded = match tile.decal.as_mut() {
+                Some(decal) => {
+                    decal.turns_left = decal.turns_left.saturating_sub(1);
+                    decal.turns_left == 0
+                }
+                None => false,
+            };
+            if faded {
+                tile.decal = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+
+    #[test]
+    fn test_decals_fade() {
+        let mut world = World::from_seed(2, GenParams::default());
+        let pos = world.player().pos;
+        world.stain(pos, DecalKind::Blood);
+        for _ in 1..DecalKind::Blood.lifetime() {
+            world.fade_decals();
+        }
+        assert!(world.level[pos].decal.is_some());
+        world.fade_decals();
+        assert_eq!(world.level[pos].decal, None);
+    }
+}
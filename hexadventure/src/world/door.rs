@@ -0,0 +1,147 @@
+//! Doors, and how each kind of mob gets through them.
+//!
+//! A closed door blocks movement and sight. Walking into one opens it,
+//! smashes at it, or does nothing, depending on the walker's species. It
+//! takes `DOOR_STRENGTH` blows to break a door, and each blow lands on a
+//! separate turn. Paths through a door cost the turns spent getting through.
+
+use level::tile::Terrain;
+use prelude::*;
+use world::mob::{capitalize, DoorSkill};
+
+/// How many blows it takes to smash a door.
+pub const DOOR_STRENGTH: u32 = 3;
+
+impl World {
+    /// Change the terrain of a tile, forgetting any damage done to a door there.
+    ///
+    /// The spaces of the level are worked out again if the tile opened or closed.
+    pub(crate) fn set_terrain(&mut self, pos: Pos, terrain: Terrain) {
+        let was_open = self.level[pos].terrain.passable_once_opened();
+        self.level[pos].terrain = terrain;
+        self.level[pos].battered = 0;
+        self.terrain_revision += 1;
+        if terrain.passable_once_opened() != was_open {
+            self.reclassify();
+        }
+    }
+
+    /// The closed door keeping a mob from standing centered on a position
+    /// facing a direction, if that's all that is.
+    pub fn door_in_way(&self, mob_id: MobId, center: Pos, facing: Direction) -> Option<Pos> {
+        let footprint = self[mob_id].species.size().footprint(center, facing);
+        let openable = footprint
+            .iter()
+            .all(|&pos| grid::contains(pos) && self.level[pos].terrain.passable_once_opened());
+        if !openable {
+            return None;
+        }
+        footprint
+            .into_iter()
+            .find(|&pos| self.level[pos].terrain == Terrain::Door)
+    }
+
+    /// How many turns it takes a mob to step to a position, counting doors in the way.
+    ///
+    /// `None` if the mob can't get there, even given time. Other mobs are ignored.
+    /// The mob is taken to face as `World::facing_toward` says it would.
+    pub fn step_cost(&self, mob_id: MobId, center: Pos) -> Option<u32> {
+        let facing = self.facing_toward(mob_id, center);
+        if self.fits(mob_id, center, facing) {
+            return Some(1);
+        }
+        let door = self.door_in_way(mob_id, center, facing)?;
+        match self[mob_id].species.door_skill() {
+            DoorSkill::Open => Some(2),
+            DoorSkill::Smash => Some(1 + DOOR_STRENGTH - self.level[door].battered),
+            DoorSkill::Blocked => None,
+        }
+    }
+
+    /// Spend a turn getting a closed door out of the way, if the mob can.
+    pub(crate) fn work_door(&mut self, mob_id: MobId, door: Pos) -> Result<(), ()> {
+        let name = capitalize(&self.describe(mob_id));
+        match self[mob_id].species.door_skill() {
+            DoorSkill::Open => {
+                self.set_terrain(door, Terrain::OpenDoor);
+                self.report(door, format!("{} opens the door.", name));
+                Ok(())
+            }
+            DoorSkill::Smash => {
+                self.level[door].battered += 1;
+                if self.level[door].battered >= DOOR_STRENGTH {
+                    self.set_terrain(door, Terrain::BrokenDoor);
+                    self.report(door, format!("{} smashes the door down.", name));
+                } else {
+                    self.report(door, format!("{} batters the door.", name));
+                }
+                Ok(())
+            }
+            DoorSkill::Blocked => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use world::action;
+    use world::mob::Species;
+
+    /// A world of open floor with a closed door east of the player and nobody else.
+    fn door_world() -> (World, Pos) {
+        let mut world = open_world(23, 8);
+        let center = world.player().pos;
+        let door: Pos = center + Direction::East * 2;
+        world.level[door].terrain = Terrain::Door;
+        (world, door)
+    }
+
+    #[test]
+    fn test_hero_opens_door() {
+        let (mut world, door) = door_world();
+        let player = world.active_player();
+        let start = world[player].pos;
+        action::walk(player, Direction::East, &mut world).unwrap();
+        assert_eq!(world.step_cost(player, door), Some(2));
+        action::walk(player, Direction::East, &mut world).unwrap();
+        assert_eq!(world.level[door].terrain, Terrain::OpenDoor);
+        assert_eq!(world[player].pos, start + Direction::East);
+        action::walk(player, Direction::East, &mut world).unwrap();
+        assert_eq!(world[player].pos, door);
+    }
+
+    #[test]
+    fn test_spider_blocked_by_door() {
+        let (mut world, door) = door_world();
+        let spider = world
+            .spawn(Species::Spider, door + Direction::West)
+            .unwrap();
+        assert_eq!(world.step_cost(spider, door), None);
+        assert!(action::walk(spider, Direction::East, &mut world).is_err());
+        assert_eq!(world.level[door].terrain, Terrain::Door);
+    }
+
+    #[test]
+    fn test_behemoth_smashes_door() {
+        let (mut world, door) = door_world();
+        let behemoth = world
+            .spawn(Species::Behemoth, door + Direction::East * 2)
+            .unwrap();
+        assert_eq!(
+            world.step_cost(behemoth, door + Direction::East),
+            Some(1 + DOOR_STRENGTH)
+        );
+        for blow in 1..DOOR_STRENGTH {
+            action::walk(behemoth, Direction::West, &mut world).unwrap();
+            assert_eq!(world.level[door].battered, blow);
+            assert_eq!(world.level[door].terrain, Terrain::Door);
+        }
+        action::walk(behemoth, Direction::West, &mut world).unwrap();
+        assert_eq!(world.level[door].terrain, Terrain::BrokenDoor);
+        assert_eq!(world.level[door].battered, 0);
+        assert_eq!(world[behemoth].pos, door + Direction::East * 2);
+    }
+}
@@ -0,0 +1,233 @@
+//! Messages players engrave on the floor.
+//!
+//! An engraving stays on its tile for as long as the run lasts, even after
+//! the players leave its level, and is remembered as a landmark so it can
+//! be travelled back to. Most engravings are just notes, but once a
+//! profile has unlocked warding, weak mobs keep away from a player who
+//! stands on the ward.
+
+use level::tile::Terrain;
+use prelude::*;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str;
+use world::mob::capitalize;
+
+/// The most bytes an inscription can hold.
+pub const INSCRIPTION_LEN: usize = 32;
+/// The word that wards off weak mobs, in any case.
+pub const WARD: &str = "Elbereth";
+
+/// A short message to engrave.
+///
+/// Kept to a fixed size so actions that carry one can still be copied.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Serialize)]
+pub struct Inscription {
+    len: u8,
+    bytes: [u8; INSCRIPTION_LEN],
+}
+
+/// An inscription and where it was engraved.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Engraving {
+    pub depth: u32,
+    pub pos: Pos,
+    pub inscription: Inscription,
+}
+
+impl Inscription {
+    /// An inscription of some text, with its whitespace collapsed to single spaces.
+    ///
+    /// `None` if the text is blank or longer than `INSCRIPTION_LEN` bytes.
+    pub fn new(text: &str) -> Option<Self> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() || text.len() > INSCRIPTION_LEN {
+            return None;
+        }
+        let mut bytes = [0; INSCRIPTION_LEN];
+        bytes[..text.len()].copy_from_slice(text.as_bytes());
+        Some(Inscription {
+            len: text.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.bytes
+            .get(..self.len as usize)
+            .and_then(|bytes| str::from_utf8(bytes).ok())
+            .unwrap_or("")
+    }
+
+    /// Whether the inscription is the ward against weak mobs.
+    pub fn is_ward(&self) -> bool {
+        self.as_str().eq_ignore_ascii_case(WARD)
+    }
+}
+
+impl<'de> Deserialize<'de> for Inscription {
+    /// Rejects a length past the end of the bytes, or bytes that aren't UTF-8,
+    /// so a damaged save or message can't make an inscription unprintable.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Raw {
+            len: u8,
+            bytes: [u8; INSCRIPTION_LEN],
+        }
+        let Raw { len, bytes } = Raw::deserialize(deserializer)?;
+        let text = bytes
+            .get(..len as usize)
+            .ok_or_else(|| D::Error::custom(format!("inscription of {} bytes", len)))?;
+        str::from_utf8(text).map_err(D::Error::custom)?;
+        Ok(Inscription { len, bytes })
+    }
+}
+
+impl fmt::Debug for Inscription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl World {
+    /// What is engraved on a tile of the current level, if anything.
+    pub fn engraving(&self, pos: Pos) -> Option<Inscription> {
+        let depth = self.depth();
+        self.engravings
+            .iter()
+            .find(|engraving| engraving.depth == depth && engraving.pos == pos)
+            .map(|engraving| engraving.inscription)
+    }
+
+    /// Every engraving the players have made, on every level.
+    pub fn engravings(&self) -> &[Engraving] {
+        &self.engravings
+    }
+
+    /// Whether weak mobs keep away from a tile.
+    pub fn is_warded(&self, pos: Pos) -> bool {
+        self.options.wards
+            && self
+                .engraving(pos)
+                .is_some_and(|inscription| inscription.is_ward())
+    }
+
+    /// Whether a mob can engrave on the tile it stands on.
+    pub fn can_engrave(&self, mob_id: MobId) -> bool {
+        self.level[self[mob_id].pos].terrain == Terrain::Floor
+    }
+
+    /// Engrave an inscription on the floor under a mob, replacing whatever was there.
+    pub(crate) fn engrave(&mut self, mob_id: MobId, inscription: Inscription) -> Result<(), ()> {
+        if !self.can_engrave(mob_id) {
+            return Err(());
+        }
+        let depth = self.depth();
+        let pos = self[mob_id].pos;
+        self.engravings
+            .retain(|engraving| engraving.depth != depth || engraving.pos != pos);
+        self.engravings.push(Engraving {
+            depth,
+            pos,
+            inscription,
+        });
+        let message = format!(
+            "{} engraves \"{}\" on the floor.",
+            capitalize(&self.describe(mob_id)),
+            inscription.as_str()
+        );
+        self.report(pos, message);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use bincode;
+    use content::Content;
+    use level::GenParams;
+    use world::action::Action;
+    use world::mob::{Mob, Species};
+    use world::recall::Landmark;
+
+    #[test]
+    fn test_inscriptions_are_short_and_tidy() {
+        let inscription = Inscription::new("  beware   the  pit ").unwrap();
+        assert_eq!(inscription.as_str(), "beware the pit");
+        assert_eq!(Inscription::new(" \t"), None);
+        assert_eq!(Inscription::new(&"x".repeat(INSCRIPTION_LEN + 1)), None);
+        assert!(Inscription::new("ELBERETH").unwrap().is_ward());
+    }
+
+    #[test]
+    fn test_deserialize_rejects_bad_inscriptions() {
+        let inscription = Inscription::new("beware the pit").unwrap();
+        let mut bytes = bincode::serialize(&inscription).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Inscription>(&bytes).unwrap(),
+            inscription
+        );
+        bytes[0] = INSCRIPTION_LEN as u8 + 1;
+        assert!(bincode::deserialize::<Inscription>(&bytes).is_err());
+        bytes[0] = 1;
+        bytes[1] = 0xff;
+        assert!(bincode::deserialize::<Inscription>(&bytes).is_err());
+    }
+
+    /// A world with nothing but floor around the player, and a skeleton next to them.
+    fn warded_world(wards: bool) -> (World, MobId) {
+        let mut world = World::from_seed(35, GenParams::default());
+        remove_npcs(&mut world);
+        let center = world.player().pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 4) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let pos: Pos = center + Direction::East;
+        let skeleton = world.npcs.insert(Mob::new(pos, Species::Skeleton));
+        world.level[pos].mob_id = Some(skeleton);
+        world.options_mut().wards = wards;
+        world.update_fov();
+        (world, skeleton)
+    }
+
+    #[test]
+    fn test_engraving_examined_and_remembered() {
+        let (mut world, _) = warded_world(false);
+        let pos = world.player().pos;
+        let inscription = Inscription::new("loot north").unwrap();
+        world.perform(Action::Engrave(inscription)).unwrap();
+        assert_eq!(world.engraving(pos), Some(inscription));
+        let lines = world.examine(pos, &Content::default());
+        assert!(lines.iter().any(|line| line.contains("loot north")));
+        let sightings = world.recall(|landmark| *landmark == Landmark::Engraving(inscription));
+        assert_eq!(sightings.len(), 1);
+        assert_eq!(sightings[0].pos, pos);
+    }
+
+    #[test]
+    fn test_weak_mobs_keep_off_wards_once_unlocked() {
+        let ward = Inscription::new(WARD).unwrap();
+        for &wards in &[false, true] {
+            let (mut world, skeleton) = warded_world(wards);
+            let player = world.active_player();
+            world.perform(Action::Engrave(ward)).unwrap();
+            for _ in 0..3 {
+                world.perform(Action::Rest).unwrap();
+            }
+            let hurt = world[player].guard < world[player].max_guard
+                || world[player].health < world[player].max_health;
+            assert_eq!(hurt, !wards);
+            assert_eq!(world.is_warded(world[player].pos), wards);
+            if wards {
+                assert!(world[skeleton].pos.distance(world[player].pos) > 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Events scheduled on the turn clock, which keeps running across levels.
+//!
+//! Events are saved with the world. An event tied to a level is dropped
+//! if the party has left that level by the time it is due.
+
+use level::place_mob;
+use level::tile::Terrain;
+use prelude::*;
+use rng::Stream;
+use world::level_event::LevelEvent;
+use world::mob::Species;
+
+/// Something that happens to the world at a set turn.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum WorldEvent {
+    /// Send a hunter after the party if it is still on the level at this depth.
+    Hunter { depth: u32 },
+    /// Open a tile toward the stairs if a player is sealed in on the level at this depth.
+    Collapse { depth: u32 },
+    /// Play a stage of a level event, if the party is still on the level at this depth.
+    Level {
+        depth: u32,
+        event: LevelEvent,
+        stage: u32,
+    },
+}
+
+/// An event and the turn it is due on.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct ScheduledEvent {
+    pub turn: u64,
+    pub event: WorldEvent,
+}
+
+/// How many turns the party can stay on a level before a hunter is sent after it.
+pub const LINGER_TURNS: u64 = 1500;
+
+impl World {
+    /// Schedule an event for a turn. Events due on the same turn happen in the order scheduled.
+    pub fn schedule(&mut self, turn: u64, event: WorldEvent) {
+        let index = self
+            .events
+            .iter()
+            .position(|scheduled| scheduled.turn > turn)
+            .unwrap_or(self.events.len());
+        self.events.insert(index, ScheduledEvent { turn, event });
+    }
+
+    /// Every event still to come, soonest first.
+    pub fn scheduled_events(&self) -> &[ScheduledEvent] {
+        &self.events
+    }
+
+    /// Schedule the events of a level the party has just entered.
+    pub(super) fn schedule_level_events(&mut self) {
+        let turn = self.turn() + LINGER_TURNS;
+        let depth = self.depth();
+        self.schedule(turn, WorldEvent::Hunter { depth });
+    }
+
+    /// Run every event that is due.
+    pub(super) fn run_due_events(&mut self) {
+        while self
+            .events
+            .first()
+            .is_some_and(|scheduled| scheduled.turn <= self.turn)
+        {
+            let scheduled = self.events.remove(0);
+            self.run_event(scheduled.event);
+        }
+    }
+
+    fn run_event(&mut self, event: WorldEvent) {
+        match event {
+            WorldEvent::Hunter { depth } => {
+                if depth != self.depth() {
+                    return;
+                }
+                self.send_down_stairs(Species::Skeleton);
+                self.hear("You hear footsteps on the stairs above.".to_owned());
+            }
+            WorldEvent::Collapse { depth } => {
+                if depth == self.depth() {
+                    self.collapse();
+                }
+            }
+            WorldEvent::Level {
+                depth,
+                event,
+                stage,
+            } => self.run_level_event(depth, event, stage),
+        }
+    }
+
+    /// Put a new mob by the stairs up, as if it came down them.
+    pub(super) fn send_down_stairs(&mut self, species: Species) {
+        let entrance = grid::positions()
+            .find(|&pos| self.level[pos].terrain == Terrain::Entrance)
+            .unwrap_or_else(grid::center);
+        let mob_id = self
+            .npcs
+            .insert(Mob::spawned(entrance, species, self.architect.content()));
+        let pos = place_mob(
+            &mut self.level,
+            entrance,
+            mob_id,
+            self.rngs.get(Stream::Spawns),
+        );
+        self[mob_id].pos = pos;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+    use world::mob;
+
+    fn count_npcs(world: &World) -> usize {
+        let mut count = 0;
+        mob::for_each(world, |_| count += 1);
+        count
+    }
+
+    #[test]
+    fn test_hunter_sent_after_lingering() {
+        let mut world = World::from_seed(6, GenParams::default());
+        let npcs = count_npcs(&world);
+        assert_eq!(
+            world.scheduled_events(),
+            &[ScheduledEvent {
+                turn: LINGER_TURNS,
+                event: WorldEvent::Hunter { depth: 1 },
+            }]
+        );
+        world.turn = LINGER_TURNS - 1;
+        world.tick();
+        assert_eq!(count_npcs(&world), npcs + 1);
+        assert!(world.scheduled_events().is_empty());
+    }
+
+    #[test]
+    fn test_hunter_dropped_after_leaving() {
+        let mut world = World::from_seed(6, GenParams::default());
+        world.schedule(LINGER_TURNS, WorldEvent::Hunter { depth: 3 });
+        world.turn = LINGER_TURNS;
+        let npcs = count_npcs(&world);
+        world.run_due_events();
+        assert_eq!(count_npcs(&world), npcs + 1);
+    }
+}
@@ -0,0 +1,113 @@
+//! Describing what a player sees on a tile.
+//!
+//! Descriptions come from the content, so the text can change without
+//! touching the code. Lines about a mob's state are picked from its live
+//! health, guard and facing.
+
+use content::{Condition, Content};
+use level::tile::TileView;
+use prelude::*;
+use world::mob::capitalize;
+
+impl World {
+    /// Describe a tile to the active player, one line per sentence.
+    ///
+    /// Only the terrain is described on remembered tiles, and nothing at all
+    /// on tiles the player has never seen.
+    pub fn examine(&self, pos: Pos, content: &Content) -> Vec<String> {
+        let mut lines = Vec::new();
+        let tile = &self.level[pos];
+        let terrain = match self.fov()[pos] {
+            TileView::Visible => tile.terrain,
+            TileView::Remembered(terrain) | TileView::Mapped(terrain) => terrain,
+            TileView::None => return lines,
+        };
+        if self.fov()[pos].is_visible() {
+            if let Some(mob_id) = tile.mob_id {
+                lines.push(format!("{}.", capitalize(&self.describe(mob_id))));
+                if let Some(def) = content.species_def(self.seen_species(mob_id)) {
+                    lines.push(def.description.clone());
+                }
+                if mob_id != self.active_player() {
+                    let conditions = self.conditions(mob_id);
+                    lines.extend(content.mob_lines(&conditions).map(str::to_owned));
+                }
+            }
+            if let Some(loot) = tile.loot {
+                let loot = self.apparent_loot(pos, loot);
+                if loot.gold > 0 {
+                    lines.push(format!("{} gold lies here.", loot.gold));
+                }
+                if let Some(description) = loot.item.and_then(|tier| content.item_description(tier))
+                {
+                    lines.push(description.to_owned());
+                }
+            }
+            if let Some(species) = tile.corpse {
+                lines.push(format!("The remains of a {} lie here.", species.name()));
+            }
+            if let Some(inscription) = self.engraving(pos) {
+                lines.push(format!("\"{}\" is engraved here.", inscription.as_str()));
+            }
+        }
+        if let Some(description) = content.terrain_description(terrain) {
+            lines.push(description.to_owned());
+        }
+        lines
+    }
+
+    /// Every condition that currently holds for a mob.
+    fn conditions(&self, mob_id: MobId) -> Vec<Condition> {
+        let mob = &self[mob_id];
+        let mut conditions = Vec::new();
+        if mob.health < mob.max_health {
+            conditions.push(Condition::Wounded);
+        }
+        if mob.health * 2 < mob.max_health {
+            conditions.push(Condition::BadlyWounded);
+        }
+        if mob.guard == 0 {
+            conditions.push(Condition::GuardBroken);
+        }
+        let toward = (self.player().pos - mob.pos).direction();
+        let facing_toward = [toward.rotate(-1), toward, toward.rotate(1)];
+        if !facing_toward.contains(&mob.facing) {
+            conditions.push(Condition::FacingAway);
+        }
+        conditions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::mob::Species;
+
+    #[test]
+    fn test_examine_wounded_mob() {
+        let mut world = World::from_seed(5, GenParams::default());
+        let content = Content::default();
+        let player = world.active_player();
+        let pos = world[player].pos + Direction::East;
+        world.level[pos].terrain = Terrain::Floor;
+        let mob_id = world.npcs.insert(Mob::new(pos, Species::Skeleton));
+        world.level[pos].mob_id = Some(mob_id);
+        world[mob_id].facing = Direction::West;
+        world.update_fov();
+        let lines = world.examine(pos, &content);
+        assert_eq!(lines[0], "The skeleton.");
+        assert!(!lines.contains(&"It looks wounded.".to_owned()));
+        assert!(!lines.contains(&"It hasn't noticed you.".to_owned()));
+
+        world[mob_id].health = 40;
+        world[mob_id].facing = Direction::East;
+        let lines = world.examine(pos, &content);
+        assert!(lines.contains(&"It looks wounded.".to_owned()));
+        assert!(lines.contains(&"It is barely standing.".to_owned()));
+        assert!(lines.contains(&"It hasn't noticed you.".to_owned()));
+        assert_eq!(lines.last().unwrap(), "Bare cave floor.");
+    }
+}
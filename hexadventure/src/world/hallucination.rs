@@ -0,0 +1,164 @@
+//! Seeing things that aren't there.
+//!
+//! A hallucinating player sees every other mob as some random species, and
+//! every pile of loot as holding random gold and a random item. Only what
+//! the player observes is scrambled, never the world itself: a skeleton
+//! seen as a behemoth still fights like a skeleton. Each thing keeps the
+//! same false look all turn, so observing twice doesn't flicker, and takes
+//! on a new one the next turn. A mob looks the same whether it is seen or
+//! sensed, and messages and descriptions name what the player sees.
+
+use loot::{Loot, TIERS};
+use prelude::*;
+use std::hash::{Hash, Hasher};
+use util::{mix_seed, StableHasher};
+use world::mob::Species;
+
+/// Every species a hallucinating player might mistake a mob for.
+const FIGMENTS: [Species; 8] = [
+    Species::Hero,
+    Species::Skeleton,
+    Species::Behemoth,
+    Species::Spider,
+    Species::Archer,
+    Species::Thief,
+    Species::Necromancer,
+    Species::Serpent,
+];
+/// The most gold a hallucinating player might see in a pile.
+const FIGMENT_GOLD: u32 = 100;
+
+impl World {
+    /// Make a mob hallucinate for some turns, or longer if it already is.
+    pub fn hallucinate(&mut self, mob_id: MobId, turns: u32) {
+        let mob = &mut self[mob_id];
+        mob.hallucinating = mob.hallucinating.max(turns);
+    }
+
+    pub fn is_hallucinating(&self, mob_id: MobId) -> bool {
+        self[mob_id].hallucinating > 0
+    }
+
+    /// The species the active player sees a mob as.
+    ///
+    /// Players always know what they are themselves.
+    pub fn seen_species(&self, mob_id: MobId) -> Species {
+        let me = self.active_player();
+        if mob_id == me || !self.is_hallucinating(me) {
+            return self[mob_id].species;
+        }
+        FIGMENTS[(self.figment(mob_id) % FIGMENTS.len() as u64) as usize]
+    }
+
+    /// The loot the active player sees on a tile.
+    pub fn apparent_loot(&self, pos: Pos, loot: Loot) -> Loot {
+        if !self.is_hallucinating(self.active_player()) {
+            return loot;
+        }
+        let figment = self.figment(pos);
+        let item = match (figment >> 32) % (TIERS.len() as u64 + 1) {
+            0 => None,
+            tier => Some(TIERS[tier as usize - 1]),
+        };
+        Loot {
+            gold: (figment % u64::from(FIGMENT_GOLD + 1)) as u32,
+            item,
+        }
+    }
+
+    /// A number that stays the same for one thing all turn, but changes
+    /// from thing to thing and turn to turn.
+    fn figment<K: Hash>(&self, key: K) -> u64 {
+        let mut hasher = StableHasher::new();
+        key.hash(&mut hasher);
+        mix_seed(self.seed(), mix_seed(self.turn(), hasher.finish()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::tile::{Terrain, TileView};
+    use level::GenParams;
+    use std::collections::HashSet;
+    use world::mob::Mob;
+
+    #[test]
+    fn test_hallucination_only_scrambles_observation() {
+        let mut world = World::from_seed(37, GenParams::default());
+        remove_npcs(&mut world);
+        let player = world.active_player();
+        let center = world[player].pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let skeleton_pos: Pos = center + Direction::East * 2;
+        let skeleton = world.npcs.insert(Mob::new(skeleton_pos, Species::Skeleton));
+        world.level[skeleton_pos].mob_id = Some(skeleton);
+        let loot_pos: Pos = center + Direction::West * 2;
+        let loot = Loot {
+            gold: 7,
+            item: None,
+        };
+        world.level[loot_pos].loot = Some(loot);
+        world.update_fov();
+        let observation = world.observation();
+        assert_eq!(observation.mobs[0].species, Species::Skeleton);
+        assert_eq!(observation.loot[0].loot, loot);
+
+        world.hallucinate(player, 50);
+        let hash = world.state_hash();
+        let mut species = HashSet::new();
+        let mut gold = HashSet::new();
+        for _ in 0..20 {
+            let observation = world.observation();
+            assert!(observation.hallucinating);
+            assert_eq!(observation.me.species, Species::Hero);
+            assert_eq!(observation.mobs[0].pos, skeleton_pos);
+            assert_eq!(observation.mobs, world.observation().mobs);
+            assert_eq!(observation.loot, world.observation().loot);
+            species.insert(observation.mobs[0].species);
+            gold.insert(observation.loot[0].loot.gold);
+            world.turn += 1;
+        }
+        assert!(species.len() > 1);
+        assert!(gold.len() > 1);
+        world.turn -= 20;
+        assert_eq!(world.state_hash(), hash);
+        assert_eq!(world[skeleton].species, Species::Skeleton);
+
+        world[player].hallucinating = 0;
+        let observation = world.observation();
+        assert_eq!(observation.mobs[0].species, Species::Skeleton);
+        assert_eq!(observation.loot[0].loot, loot);
+    }
+
+    #[test]
+    fn test_figments_agree_everywhere() {
+        let mut world = World::from_seed(37, GenParams::default());
+        remove_npcs(&mut world);
+        let player = world.active_player();
+        let center = world[player].pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let archer_pos: Pos = center + Direction::East * 2;
+        let archer = world.npcs.insert(Mob::new(archer_pos, Species::Archer));
+        world.level[archer_pos].mob_id = Some(archer);
+        world.grant_telepathy(player, 50);
+        world.hallucinate(player, 50);
+        world.update_fov();
+        for _ in 0..10 {
+            let seen = world.seen_species(archer);
+            assert_eq!(world.observation().mobs[0].species, seen);
+            assert_eq!(world.describe(archer), format!("the {}", seen.name()));
+            let fov_index = world.fov_index(world.active);
+            world.fovs[fov_index][archer_pos] = TileView::None;
+            assert_eq!(world.sensed()[0].species, seen);
+            world.update_fov();
+            world.turn += 1;
+        }
+    }
+}
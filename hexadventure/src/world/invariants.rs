@@ -0,0 +1,267 @@
+//! Checks that the world is consistent with itself.
+//!
+//! Nothing here should ever fail. Debug builds check the invariants at
+//! the end of every tick, and so do the fuzz targets, so a bug shows up
+//! where it happens rather than turns later. Since the fuzz targets also
+//! check worlds loaded from damaged saves, the checks never index blindly.
+
+use prelude::*;
+use std::fmt;
+use world::mob;
+
+/// Something about the world that should never be true.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Violation {
+    /// There are no players at all.
+    NoPlayers,
+    /// The player whose turn it is doesn't exist.
+    NoActivePlayer { active: usize, players: usize },
+    /// There is neither one shared field of view nor one per player.
+    FovCount { fovs: usize, players: usize },
+    /// A field of view doesn't cover the whole grid.
+    FovSize { index: usize, len: usize },
+    /// There isn't one repeated action slot per player.
+    PlanCount { plans: usize, players: usize },
+    /// A tile says a mob is on it, but that mob is dead or elsewhere.
+    StrayMobId { pos: Pos, mob_id: MobId },
+    /// A tile says a mob is on it, but there is no such mob.
+    UnknownMobId { pos: Pos, mob_id: MobId },
+    /// A living mob isn't on a tile it thinks it takes up.
+    Misplaced { mob_id: MobId, pos: Pos },
+    /// A living mob is on terrain it can't stand on.
+    Impassable { mob_id: MobId, pos: Pos },
+    /// An event is queued after one due later.
+    EventsOutOfOrder { turn: u64 },
+    /// An event should already have run.
+    OverdueEvent { turn: u64 },
+    /// A tile holds a pile of loot with nothing in it.
+    EmptyLoot(Pos),
+    /// Loot lies where no one can reach it.
+    LootOnImpassable(Pos),
+}
+
+impl World {
+    /// Every way the world is inconsistent with itself.
+    pub fn validate_invariants(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        let players = self.players().len();
+        if players == 0 {
+            violations.push(Violation::NoPlayers);
+        }
+        if self.active >= players {
+            violations.push(Violation::NoActivePlayer {
+                active: self.active,
+                players,
+            });
+        }
+        if self.fovs.len() != 1 && self.fovs.len() != players {
+            violations.push(Violation::FovCount {
+                fovs: self.fovs.len(),
+                players,
+            });
+        }
+        for (index, fov) in self.fovs.iter().enumerate() {
+            let len = fov.iter().len();
+            if len != grid::WIDTH * grid::HEIGHT {
+                violations.push(Violation::FovSize { index, len });
+            }
+        }
+        if self.plans.len() != players {
+            violations.push(Violation::PlanCount {
+                plans: self.plans.len(),
+                players,
+            });
+        }
+        for pos in grid::positions() {
+            if let Some(mob_id) = self.level[pos].mob_id {
+                match self.get_mob(mob_id) {
+                    None => violations.push(Violation::UnknownMobId { pos, mob_id }),
+                    Some(mob) if !mob.alive || !mob.footprint().contains(&pos) => {
+                        violations.push(Violation::StrayMobId { pos, mob_id })
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        let mut mob_ids: Vec<_> = (0..self.players().len()).map(MobId::player).collect();
+        mob::for_each(self, |mob_id| mob_ids.push(mob_id));
+        for mob_id in mob_ids {
+            let mob = &self[mob_id];
+            if !mob.alive {
+                continue;
+            }
+            for pos in mob.footprint() {
+                if !grid::contains(pos) || self.level[pos].mob_id != Some(mob_id) {
+                    violations.push(Violation::Misplaced { mob_id, pos });
+                } else if !self.level[pos].terrain.passable() {
+                    violations.push(Violation::Impassable { mob_id, pos });
+                }
+            }
+        }
+        let mut last_turn = 0;
+        for scheduled in self.scheduled_events() {
+            if scheduled.turn < last_turn {
+                violations.push(Violation::EventsOutOfOrder {
+                    turn: scheduled.turn,
+                });
+            }
+            if scheduled.turn <= self.turn() {
+                violations.push(Violation::OverdueEvent {
+                    turn: scheduled.turn,
+                });
+            }
+            last_turn = scheduled.turn;
+        }
+        for pos in grid::positions() {
+            let tile = &self.level[pos];
+            if let Some(loot) = tile.loot {
+                if loot.gold == 0 && loot.item.is_none() {
+                    violations.push(Violation::EmptyLoot(pos));
+                }
+                if !tile.terrain.passable() {
+                    violations.push(Violation::LootOnImpassable(pos));
+                }
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::NoPlayers => write!(f, "there are no players"),
+            Violation::NoActivePlayer { active, players } => write!(
+                f,
+                "player {} is active but there are only {}",
+                active, players
+            ),
+            Violation::FovCount { fovs, players } => {
+                write!(f, "{} fields of view for {} players", fovs, players)
+            }
+            Violation::FovSize { index, len } => {
+                write!(f, "field of view {} has {} tiles", index, len)
+            }
+            Violation::PlanCount { plans, players } => {
+                write!(f, "{} repeated actions for {} players", plans, players)
+            }
+            Violation::StrayMobId { pos, mob_id } => {
+                write!(f, "{:?} at {:?} isn't there", mob_id, pos)
+            }
+            Violation::UnknownMobId { pos, mob_id } => {
+                write!(f, "{:?} at {:?} doesn't exist", mob_id, pos)
+            }
+            Violation::Misplaced { mob_id, pos } => {
+                write!(f, "{:?} thinks it is at {:?} but isn't", mob_id, pos)
+            }
+            Violation::Impassable { mob_id, pos } => {
+                write!(f, "{:?} stands on impassable terrain at {:?}", mob_id, pos)
+            }
+            Violation::EventsOutOfOrder { turn } => {
+                write!(f, "an event on turn {} is queued out of order", turn)
+            }
+            Violation::OverdueEvent { turn } => write!(f, "an event on turn {} never ran", turn),
+            Violation::EmptyLoot(pos) => write!(f, "empty loot at {:?}", pos),
+            Violation::LootOnImpassable(pos) => {
+                write!(f, "loot on impassable terrain at {:?}", pos)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bincode;
+    use grid::DIRECTIONS;
+    use level::tile::{Terrain, TileView};
+    use level::GenParams;
+    use loot::Loot;
+    use rand::{IsaacRng, Rng};
+    use world::action::Action;
+
+    #[test]
+    fn test_invariants_hold_over_random_play() {
+        let mut world = World::from_seed(11, GenParams::default());
+        let mut rng = IsaacRng::new_from_u64(11);
+        assert_eq!(world.validate_invariants(), Ok(()));
+        for _ in 0..500 {
+            if world.is_over() {
+                break;
+            }
+            let action = match rng.gen_range(0, 7) {
+                6 => Action::Rest,
+                i => Action::Walk(DIRECTIONS[i]),
+            };
+            let _ = world.perform_confirmed(action);
+            assert_eq!(world.validate_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_event_and_loot_violations_found() {
+        let mut world = World::from_seed(11, GenParams::default());
+        world.events[0].turn = 0;
+        let pos = world.player().pos + Direction::West;
+        world.level[pos].terrain = Terrain::Wall;
+        world.level[pos].mob_id = None;
+        world.level[pos].loot = Some(Loot {
+            gold: 0,
+            item: None,
+        });
+        let violations = world.validate_invariants().unwrap_err();
+        assert!(violations.contains(&Violation::OverdueEvent { turn: 0 }));
+        assert!(violations.contains(&Violation::EmptyLoot(pos)));
+        assert!(violations.contains(&Violation::LootOnImpassable(pos)));
+    }
+
+    #[test]
+    fn test_player_and_fov_violations_found() {
+        let mut world = World::from_seed(11, GenParams::default());
+        world.active = 3;
+        let short = bincode::serialize(&vec![TileView::None, TileView::Visible]).unwrap();
+        world.fovs.push(bincode::deserialize(&short).unwrap());
+        let violations = world.validate_invariants().unwrap_err();
+        assert!(violations.contains(&Violation::NoActivePlayer {
+            active: 3,
+            players: 1
+        }));
+        assert!(violations.contains(&Violation::FovCount {
+            fovs: 2,
+            players: 1
+        }));
+        assert!(violations.contains(&Violation::FovSize { index: 1, len: 2 }));
+    }
+
+    #[test]
+    fn test_stray_mob_id_found() {
+        let mut world = World::from_seed(11, GenParams::default());
+        let player = world.active_player();
+        let pos = world[player].pos + Direction::East;
+        world.level[pos].mob_id = Some(player);
+        assert_eq!(
+            world.validate_invariants(),
+            Err(vec![Violation::StrayMobId {
+                pos,
+                mob_id: player
+            }])
+        );
+    }
+
+    #[test]
+    fn test_unknown_mob_id_found() {
+        let mut world = World::from_seed(11, GenParams::default());
+        let pos = world.player().pos + Direction::East;
+        let ghost = MobId::player(7);
+        world.level[pos].mob_id = Some(ghost);
+        assert_eq!(
+            world.validate_invariants(),
+            Err(vec![Violation::UnknownMobId { pos, mob_id: ghost }])
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! A turn-by-turn record of actions and state hashes, for debugging desyncs.
+//!
+//! Each line of a journal reads `turn hash action`, e.g. `12 00c0ffee00c0ffee walk east`.
+//! Replaying the actions of a journal on a world created the same way
+//! must reproduce every hash. The first line whose hash differs is where
+//! the two runs diverged.
+
+use grid::DIRECTIONS;
+use prelude::*;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use util::StableHasher;
+use world::action::Action;
+use world::engrave::Inscription;
+use world::mob;
+use world::modifier::STANCES;
+
+/// The recorded actions of a run.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+/// One action and the state it led to.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The turn after the action.
+    pub turn: u64,
+    /// The hash of the world after the action.
+    pub hash: u64,
+    pub action: Action,
+}
+
+/// Where a replay first differed from its journal.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Divergence {
+    /// The index of the entry that didn't match.
+    pub entry: usize,
+    pub turn: u64,
+    pub expected: u64,
+    pub found: u64,
+}
+
+/// A line of a journal that couldn't be read.
+#[derive(PartialEq, Eq, Debug)]
+pub struct ParseError {
+    /// The line number, starting from 1.
+    pub line: usize,
+}
+
+impl World {
+    /// Start recording a journal of every action taken from now on.
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Journal::default());
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Hash everything about the world that actions can change.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.turn().hash(&mut hasher);
+        self.depth().hash(&mut hasher);
+        self.active_player().hash(&mut hasher);
+        for pos in grid::positions() {
+            self.level[pos].terrain.hash(&mut hasher);
+            self.level[pos].mob_id.hash(&mut hasher);
+            self.level[pos].loot.hash(&mut hasher);
+            self.level[pos].decal.hash(&mut hasher);
+            self.level[pos].battered.hash(&mut hasher);
+            self.level[pos].corpse.hash(&mut hasher);
+        }
+        self.players().hash(&mut hasher);
+        self.engravings.hash(&mut hasher);
+        mob::for_each(self, |mob_id| self[mob_id].hash(&mut hasher));
+        hasher.finish()
+    }
+
+    pub(super) fn record(&mut self, action: Action) {
+        if self.journal.is_some() {
+            let entry = JournalEntry {
+                turn: self.turn(),
+                hash: self.state_hash(),
+                action,
+            };
+            if let Some(ref mut journal) = self.journal {
+                journal.entries.push(entry);
+            }
+        }
+    }
+}
+
+impl Journal {
+    /// Write the journal out one entry per line.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            writeln!(
+                text,
+                "{} {:016x} {}",
+                entry.turn,
+                entry.hash,
+                format_action(entry.action)
+            )
+            .unwrap();
+        }
+        text
+    }
+
+    /// Read a journal written by `to_text`.
+    ///
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut entries = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_entry(line).ok_or(ParseError { line: index + 1 })?;
+            entries.push(entry);
+        }
+        Ok(Journal { entries })
+    }
+
+    /// Replay the journal's actions on a world, checking each hash.
+    ///
+    /// The world must be created the same way as the one that was recorded.
+    pub fn verify(&self, mut world: World) -> Result<(), Divergence> {
+        for (index, entry) in self.entries.iter().enumerate() {
+            let _ = world.perform_confirmed(entry.action);
+            let found = world.state_hash();
+            if world.turn() != entry.turn || found != entry.hash {
+                return Err(Divergence {
+                    entry: index,
+                    turn: entry.turn,
+                    expected: entry.hash,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_action(action: Action) -> String {
+    match action {
+        Action::Rest => "rest".to_owned(),
+        Action::Walk(direction) => format!("walk {}", direction_name(direction)),
+        Action::Sneak(direction) => format!("sneak {}", direction_name(direction)),
+        Action::Refuel => "refuel".to_owned(),
+        Action::Engrave(inscription) => format!("engrave {}", inscription.as_str()),
+        Action::Stance(stance) => format!("stance {}", stance.name()),
+    }
+}
+
+fn parse_entry(line: &str) -> Option<JournalEntry> {
+    let mut words = line.split_whitespace();
+    let turn = words.next()?.parse().ok()?;
+    let hash = u64::from_str_radix(words.next()?, 16).ok()?;
+    let action = match words.next()? {
+        "rest" => Action::Rest,
+        "refuel" => Action::Refuel,
+        "engrave" => {
+            let text: Vec<&str> = words.by_ref().collect();
+            Action::Engrave(Inscription::new(&text.join(" "))?)
+        }
+        "stance" => {
+            let name = words.next()?;
+            Action::Stance(*STANCES.iter().find(|stance| stance.name() == name)?)
+        }
+        word @ "walk" | word @ "sneak" => {
+            let name = words.next()?;
+            let direction = *DIRECTIONS
+                .iter()
+                .find(|&&direction| direction_name(direction) == name)?;
+            if word == "walk" {
+                Action::Walk(direction)
+            } else {
+                Action::Sneak(direction)
+            }
+        }
+        _ => return None,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some(JournalEntry { turn, hash, action })
+}
+
+fn direction_name(direction: Direction) -> String {
+    format!("{:?}", direction).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+    use world::modifier::Stance;
+
+    fn play(seed: u64) -> World {
+        let mut world = World::from_seed(seed, GenParams::default());
+        world.start_journal();
+        let _ = world.perform_confirmed(Action::Stance(Stance::Stealth));
+        for i in 0..20 {
+            let direction = DIRECTIONS[(i * 7) % 6];
+            let action = if i % 5 < 2 {
+                Action::Sneak(direction)
+            } else {
+                Action::Walk(direction)
+            };
+            let _ = world.perform_confirmed(action);
+        }
+        let inscription = Inscription::new("was  here").unwrap();
+        let _ = world.perform_confirmed(Action::Engrave(inscription));
+        world
+    }
+
+    #[test]
+    fn test_replay_matches() {
+        let world = play(11);
+        let text = world.journal().unwrap().to_text();
+        let journal = Journal::parse(&text).unwrap();
+        assert_eq!(journal.entries, world.journal().unwrap().entries);
+        assert_eq!(
+            journal.verify(World::from_seed(11, GenParams::default())),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_divergence_found() {
+        let world = play(12);
+        let mut journal = world.journal().unwrap().clone();
+        journal.entries[5].hash ^= 1;
+        let divergence = journal
+            .verify(World::from_seed(12, GenParams::default()))
+            .unwrap_err();
+        assert_eq!(divergence.entry, 5);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            Journal::parse("# seed 3\n1 00ff rest\n2 00ff jump").err(),
+            Some(ParseError { line: 3 })
+        );
+    }
+}
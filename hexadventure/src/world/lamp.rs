@@ -0,0 +1,197 @@
+//! The lights players carry, and the fuel that keeps them burning.
+//!
+//! A player sees as far as their light reaches, and beyond that only the
+//! tiles lit by something else, such as glowing moss. A torch burns for a
+//! while, guttering for its last turns so it lights less and less, then
+//! goes out for good. A lantern burns steadily as long as it has oil, and
+//! is refilled from the flasks its carrier brings along. A player whose
+//! light has gone out sees only the tiles next to them.
+
+use prelude::*;
+use world::mob::capitalize;
+use world::modifier::Attribute;
+
+/// How many turns a fresh torch burns.
+pub const TORCH_TURNS: u32 = 1000;
+/// For how many of its last turns a torch gutters, lighting less and less.
+pub const GUTTER_TURNS: u32 = 100;
+/// How far a torch lights before it starts to gutter.
+pub const TORCH_RADIUS: u32 = 7;
+/// How far a lantern lights.
+pub const LANTERN_RADIUS: u32 = 8;
+/// How many turns of oil a flask holds. A lantern holds as much.
+pub const FLASK_OIL: u32 = 500;
+/// How far a player sees without a light.
+pub const DARK_RADIUS: u32 = 1;
+/// How many flasks of oil each player starts with.
+const STARTING_FLASKS: u32 = 2;
+
+/// A light a mob carries.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum LightSource {
+    Torch { turns_left: u32 },
+    Lantern { oil: u32 },
+}
+
+impl LightSource {
+    pub fn torch() -> Self {
+        LightSource::Torch {
+            turns_left: TORCH_TURNS,
+        }
+    }
+
+    /// A lantern full of oil.
+    pub fn lantern() -> Self {
+        LightSource::Lantern { oil: FLASK_OIL }
+    }
+
+    pub fn is_lit(&self) -> bool {
+        match *self {
+            LightSource::Torch { turns_left } => turns_left > 0,
+            LightSource::Lantern { oil } => oil > 0,
+        }
+    }
+
+    /// How far the light reaches.
+    pub fn radius(&self) -> u32 {
+        match *self {
+            LightSource::Torch { turns_left } if turns_left >= GUTTER_TURNS => TORCH_RADIUS,
+            LightSource::Torch { turns_left } if turns_left > 0 => {
+                // Rounded up, so a lit torch always lights more than the dark.
+                let reach = (TORCH_RADIUS - DARK_RADIUS) * turns_left;
+                DARK_RADIUS + (reach + GUTTER_TURNS - 1) / GUTTER_TURNS
+            }
+            LightSource::Lantern { oil } if oil > 0 => LANTERN_RADIUS,
+            _ => DARK_RADIUS,
+        }
+    }
+
+    fn burn(&mut self) {
+        match self {
+            LightSource::Torch { turns_left } => *turns_left = turns_left.saturating_sub(1),
+            LightSource::Lantern { oil } => *oil = oil.saturating_sub(1),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LightSource::Torch { .. } => "torch",
+            LightSource::Lantern { .. } => "lantern",
+        }
+    }
+}
+
+impl World {
+    /// How far a mob can see by its own light, or around it if blind.
+    pub fn sight_radius(&self, mob_id: MobId) -> u32 {
+        let radius = self.attribute(mob_id, Attribute::SightRadius, DARK_RADIUS as i32);
+        radius.max(0) as u32
+    }
+
+    /// Give a player the light and oil every player starts with.
+    pub(super) fn equip_light(&mut self, mob_id: MobId) {
+        self[mob_id].light = Some(LightSource::lantern());
+        self[mob_id].oil_flasks = STARTING_FLASKS;
+    }
+
+    /// Burn a turn's worth of the active player's light.
+    pub(super) fn burn_light(&mut self) {
+        let player = self.active_player();
+        let mut light = match self[player].light {
+            Some(light) if light.is_lit() => light,
+            _ => return,
+        };
+        light.burn();
+        self[player].light = Some(light);
+        let news = match light {
+            LightSource::Torch { turns_left } if turns_left == GUTTER_TURNS => "starts to gutter",
+            LightSource::Torch { turns_left: 0 } => "burns out",
+            LightSource::Lantern { oil: 0 } => "runs dry",
+            _ => return,
+        };
+        let message = format!(
+            "{}'s {} {}.",
+            capitalize(&self.describe(player)),
+            light.name(),
+            news
+        );
+        let pos = self[player].pos;
+        self.report(pos, message);
+    }
+
+    /// Refill a mob's lantern from one of its flasks of oil.
+    ///
+    /// Oil that doesn't fit is spilled.
+    pub(crate) fn refuel(&mut self, mob_id: MobId) -> Result<(), ()> {
+        let mob = &mut self[mob_id];
+        match mob.light {
+            Some(LightSource::Lantern { oil }) if oil < FLASK_OIL && mob.oil_flasks > 0 => {
+                mob.oil_flasks -= 1;
+                mob.light = Some(LightSource::lantern());
+            }
+            _ => return Err(()),
+        }
+        let message = format!(
+            "{} refills the lantern.",
+            capitalize(&self.describe(mob_id))
+        );
+        let pos = self[mob_id].pos;
+        self.report(pos, message);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs_near;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::action::{Action, ActionError};
+    use world::light::MAX_LIGHT;
+
+    #[test]
+    fn test_torch_gutters_and_goes_out() {
+        let mut torch = LightSource::torch();
+        assert_eq!(torch.radius(), TORCH_RADIUS);
+        let mut radii = Vec::new();
+        while torch.is_lit() {
+            torch.burn();
+            radii.push(torch.radius());
+        }
+        assert_eq!(radii.len() as u32, TORCH_TURNS);
+        assert!(radii.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(radii[radii.len() - 2] > DARK_RADIUS);
+        assert_eq!(torch.radius(), DARK_RADIUS);
+    }
+
+    #[test]
+    fn test_lantern_refilled_and_dark_sight() {
+        let mut world = World::from_seed(32, GenParams::default());
+        let player = world.active_player();
+        let center = world[player].pos;
+        remove_npcs_near(&mut world, center, 10);
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 10) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        assert_eq!(world.perform(Action::Refuel), Err(ActionError::Invalid));
+        world[player].light = Some(LightSource::Lantern { oil: 1 });
+        world.perform(Action::Rest).unwrap();
+        assert_eq!(world.sight_radius(player), DARK_RADIUS);
+        let far: Pos = center + Direction::East * 3;
+        assert!(!world.is_visible(far));
+        assert!(world.is_visible(center + Direction::East));
+        assert_eq!(world.light(center), 0);
+
+        world.perform(Action::Refuel).unwrap();
+        assert_eq!(world[player].oil_flasks, STARTING_FLASKS - 1);
+        assert_eq!(world.sight_radius(player), LANTERN_RADIUS);
+        assert!(world.is_visible(far));
+        assert_eq!(world.light(center), MAX_LIGHT);
+        assert_eq!(world.light(far), MAX_LIGHT);
+        let edge: Pos = center + Direction::East * LANTERN_RADIUS;
+        assert_eq!(world.light(edge), 1);
+        assert_eq!(world.light(edge + Direction::East), 0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Scripted events that play out across a whole level over many turns.
+//!
+//! A `LevelEvent` is a script of stages. Each `Stage` is a few `Beat`s, such
+//! as a noise, terrain turning into other terrain or mobs arriving, and how
+//! long until the next stage. The scheduler runs one stage at a time as a
+//! `WorldEvent::Level` that keeps its place in the script, so an event in
+//! progress is saved with the world and stops if the party leaves the level.
+//!
+//! - A flood rises from a spring: the floor turns to shallow water further
+//!   out every stage, and the shallows behind it deepen into water too deep
+//!   to wade.
+//! - A cave-in brings the roof down around a point a ring at a time, after
+//!   a warning.
+//! - An invasion sends bigger and bigger waves of mobs down the stairs.
+//!
+//! Terrain never turns impassable under a mob or a pile of loot, so nothing
+//! ends up stuck in rock or deep water, and stairs and doors are left
+//! alone. A player sealed in by the changes is dug out by the usual collapse.
+
+use level::tile::Terrain;
+use prelude::*;
+use world::events::WorldEvent;
+use world::mob::Species;
+
+/// How many stages a flood rises for.
+pub const FLOOD_STAGES: u32 = 8;
+/// How many turns pass between each stage of a flood.
+pub const FLOOD_TURNS: u64 = 10;
+/// How much further the shallows reach every stage of a flood.
+const FLOOD_RISE: u32 = 2;
+/// How far a cave-in brings the roof down around its center.
+pub const CAVE_IN_RADIUS: u32 = 3;
+/// How many turns of warning a cave-in gives.
+pub const CAVE_IN_WARNING: u64 = 5;
+/// How many waves an invasion sends.
+pub const INVASION_WAVES: u32 = 3;
+/// How many turns pass between each wave of an invasion.
+pub const INVASION_TURNS: u64 = 15;
+
+/// The terrain floods and cave-ins can cover.
+const OPEN_GROUND: [Terrain; 6] = [
+    Terrain::Floor,
+    Terrain::ShortGrass,
+    Terrain::TallGrass,
+    Terrain::Brownberry,
+    Terrain::Web,
+    Terrain::ShallowWater,
+];
+
+/// Something that happens to a whole level, a stage at a time.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum LevelEvent {
+    /// Water rising from a spring.
+    Flood { spring: Pos },
+    /// The roof falling in around a point.
+    CaveIn { center: Pos },
+    /// Waves of a species coming down the stairs.
+    Invasion { species: Species },
+}
+
+/// One stage of a level event's script.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Stage {
+    pub beats: Vec<Beat>,
+    /// How many turns until the next stage, or `None` if this is the last.
+    pub next: Option<u64>,
+}
+
+/// One thing a stage of a level event does.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Beat {
+    /// A noise the players hear, unless they are all deaf.
+    Noise(&'static str),
+    /// Turn every tile of some terrain near a position into another terrain.
+    Convert {
+        center: Pos,
+        radius: u32,
+        from: &'static [Terrain],
+        to: Terrain,
+    },
+    /// Mobs coming down the stairs from above.
+    Arrive { species: Species, count: u32 },
+}
+
+impl LevelEvent {
+    /// A stage of the event's script, or `None` past the last one.
+    pub fn stage(self, index: u32) -> Option<Stage> {
+        match self {
+            LevelEvent::Flood { spring } => {
+                if index >= FLOOD_STAGES {
+                    return None;
+                }
+                let mut beats = Vec::new();
+                if index == 0 {
+                    beats.push(Beat::Noise("You hear water gurgling up through the rock."));
+                }
+                beats.push(Beat::Convert {
+                    center: spring,
+                    radius: (index + 1) * FLOOD_RISE,
+                    from: &OPEN_GROUND,
+                    to: Terrain::ShallowWater,
+                });
+                if index >= 2 {
+                    beats.push(Beat::Convert {
+                        center: spring,
+                        radius: (index - 1) * FLOOD_RISE,
+                        from: &[Terrain::ShallowWater],
+                        to: Terrain::Water,
+                    });
+                }
+                let last = index + 1 == FLOOD_STAGES;
+                if last {
+                    beats.push(Beat::Noise("The rushing of water dies down."));
+                }
+                Some(Stage {
+                    beats,
+                    next: if last { None } else { Some(FLOOD_TURNS) },
+                })
+            }
+            LevelEvent::CaveIn { center } => {
+                if index > CAVE_IN_RADIUS + 1 {
+                    return None;
+                }
+                if index == 0 {
+                    return Some(Stage {
+                        beats: vec![Beat::Noise("The roof groans overhead.")],
+                        next: Some(CAVE_IN_WARNING),
+                    });
+                }
+                let mut beats = vec![Beat::Convert {
+                    center,
+                    radius: index - 1,
+                    from: &OPEN_GROUND,
+                    to: Terrain::Wall,
+                }];
+                if index == 1 {
+                    beats.push(Beat::Noise("You hear rocks crashing down."));
+                }
+                let last = index == CAVE_IN_RADIUS + 1;
+                Some(Stage {
+                    beats,
+                    next: if last { None } else { Some(1) },
+                })
+            }
+            LevelEvent::Invasion { species } => {
+                if index > INVASION_WAVES {
+                    return None;
+                }
+                if index == 0 {
+                    return Some(Stage {
+                        beats: vec![Beat::Noise("War drums echo down the stairs.")],
+                        next: Some(INVASION_TURNS),
+                    });
+                }
+                let beats = vec![
+                    Beat::Arrive {
+                        species,
+                        count: index,
+                    },
+                    Beat::Noise("You hear footsteps on the stairs above."),
+                ];
+                let last = index == INVASION_WAVES;
+                Some(Stage {
+                    beats,
+                    next: if last { None } else { Some(INVASION_TURNS) },
+                })
+            }
+        }
+    }
+}
+
+impl World {
+    /// Start a level event on the current level, after some turns.
+    pub fn start_level_event(&mut self, event: LevelEvent, delay: u64) {
+        let depth = self.depth();
+        let turn = self.turn() + delay;
+        self.schedule(
+            turn,
+            WorldEvent::Level {
+                depth,
+                event,
+                stage: 0,
+            },
+        );
+    }
+
+    /// Play a stage of a level event and schedule the next, if the party
+    /// is still on the level.
+    pub(super) fn run_level_event(&mut self, depth: u32, event: LevelEvent, index: u32) {
+        if depth != self.depth() {
+            return;
+        }
+        let stage = match event.stage(index) {
+            Some(stage) => stage,
+            None => return,
+        };
+        for beat in stage.beats {
+            self.play(beat);
+        }
+        if let Some(turns) = stage.next {
+            let turn = self.turn() + turns;
+            let next = WorldEvent::Level {
+                depth,
+                event,
+                stage: index + 1,
+            };
+            self.schedule(turn, next);
+        }
+    }
+
+    fn play(&mut self, beat: Beat) {
+        match beat {
+            Beat::Noise(text) => self.hear(text.to_owned()),
+            Beat::Convert {
+                center,
+                radius,
+                from,
+                to,
+            } => {
+                let covered: Vec<Pos> = grid::positions()
+                    .filter(|pos| pos.distance(center) <= radius)
+                    .filter(|&pos| {
+                        let tile = &self.level[pos];
+                        from.contains(&tile.terrain)
+                            && tile.terrain != to
+                            && (to.passable()
+                                || (tile.mob_id.is_none()
+                                    && tile.loot.is_none()
+                                    && tile.rune.is_none()))
+                    })
+                    .collect();
+                for pos in covered {
+                    self.set_terrain(pos, to);
+                }
+            }
+            Beat::Arrive { species, count } => {
+                for _ in 0..count {
+                    self.send_down_stairs(species);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use world::mob;
+    use world::seal::Rune;
+
+    fn count_npcs(world: &World) -> usize {
+        let mut count = 0;
+        mob::for_each(world, |mob_id| {
+            if world[mob_id].alive {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    fn expected_total() -> usize {
+        (1..=INVASION_WAVES).sum::<u32>() as usize
+    }
+
+    #[test]
+    fn test_flood_rises_and_deepens() {
+        let mut world = open_world(38, 8);
+        let spring: Pos = world.player().pos + Direction::East * 4;
+        world.start_level_event(LevelEvent::Flood { spring }, 0);
+        world.run_due_events();
+        assert_eq!(world.level[spring].terrain, Terrain::ShallowWater);
+        assert!(world.messages().last().unwrap().text.contains("water"));
+        for _ in 0..FLOOD_STAGES * FLOOD_TURNS as u32 {
+            world.turn += 1;
+            world.run_due_events();
+        }
+        assert!(world
+            .scheduled_events()
+            .iter()
+            .all(|scheduled| !matches!(scheduled.event, WorldEvent::Level { .. })));
+        assert_eq!(world.level[spring].terrain, Terrain::Water);
+        // The player is never drowned where they stand.
+        let player = world.player().pos;
+        assert_eq!(world.level[player].terrain, Terrain::ShallowWater);
+        assert!(world.validate_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_cave_in_spares_mobs() {
+        let mut world = open_world(38, 8);
+        let player = world.player().pos;
+        world.start_level_event(LevelEvent::CaveIn { center: player }, 0);
+        world.run_due_events();
+        let east: Pos = player + Direction::East;
+        assert_eq!(world.level[east].terrain, Terrain::Floor);
+        for _ in 0..CAVE_IN_WARNING + u64::from(CAVE_IN_RADIUS) + 1 {
+            world.turn += 1;
+            world.run_due_events();
+        }
+        assert_eq!(world.level[player].terrain, Terrain::Floor);
+        for pos in (1..=CAVE_IN_RADIUS).flat_map(|size| player.ring(size)) {
+            assert!(!world.level[pos].terrain.passable());
+        }
+        world.check_sealed();
+        assert!(world.sealed_player().is_some());
+        assert!(world.validate_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_cave_in_spares_runes() {
+        let mut world = open_world(38, 8);
+        let player = world.player().pos;
+        let east: Pos = player + Direction::East;
+        world.level[east].rune = Some(Rune { depth: 5 });
+        world.start_level_event(LevelEvent::CaveIn { center: player }, 0);
+        for _ in 0..CAVE_IN_WARNING + u64::from(CAVE_IN_RADIUS) + 1 {
+            world.turn += 1;
+            world.run_due_events();
+        }
+        assert_eq!(world.level[east].terrain, Terrain::Floor);
+        assert_eq!(world.level[east].rune, Some(Rune { depth: 5 }));
+    }
+
+    #[test]
+    fn test_invasion_comes_in_waves() {
+        let mut world = open_world(38, 8);
+        let species = Species::Archer;
+        world.start_level_event(LevelEvent::Invasion { species }, 0);
+        world.run_due_events();
+        assert_eq!(count_npcs(&world), 0);
+        for wave in 1..=INVASION_WAVES {
+            world.turn += INVASION_TURNS;
+            world.run_due_events();
+            let expected = (1..=wave).sum::<u32>() as usize;
+            assert_eq!(count_npcs(&world), expected);
+        }
+        mob::for_each(&world, |mob_id| {
+            if world[mob_id].alive {
+                assert_eq!(world[mob_id].species, species);
+            }
+        });
+
+        let event = WorldEvent::Level {
+            depth: world.depth() + 1,
+            event: LevelEvent::Invasion { species },
+            stage: 1,
+        };
+        let turn = world.turn();
+        world.schedule(turn, event);
+        world.run_due_events();
+        assert_eq!(count_npcs(&world), expected_total());
+    }
+}
@@ -0,0 +1,83 @@
+//! How brightly each tile is lit, and the mobs that care.
+//!
+//! The caves are dark except around the pale moss, which glows, and the
+//! lights players carry. Mobs that prefer some level of light path around
+//! tiles lit more or less than they like, and hit harder from tiles lit
+//! just right.
+
+use prelude::*;
+
+/// The brightest a tile can be lit.
+pub const MAX_LIGHT: u32 = 2;
+/// The extra cost of a step for each level of light it strays from a mob's preference.
+pub const LIGHT_PENALTY: u32 = 3;
+/// The extra damage dealt by a mob attacking from the light it prefers.
+pub const LIGHT_BONUS: u32 = 2;
+
+impl World {
+    /// How brightly a tile is lit, from 0 in darkness up to `MAX_LIGHT`.
+    pub fn light(&self, pos: Pos) -> u32 {
+        let mut light = self.level[pos].terrain.glow();
+        for distance in 1..=MAX_LIGHT {
+            for source in pos.ring(distance).filter(|&pos| grid::contains(pos)) {
+                let glow = self.level[source].terrain.glow();
+                light = light.max(glow.saturating_sub(distance));
+            }
+        }
+        // A carried light is brightest out to its last tile, which it lights dimly.
+        for player in self.players().iter().filter(|player| player.alive) {
+            if let Some(carried) = player.light.filter(|light| light.is_lit()) {
+                let reach = (carried.radius() + 1).saturating_sub(pos.distance(player.pos));
+                light = light.max(reach.min(MAX_LIGHT));
+            }
+        }
+        light
+    }
+
+    /// How far the light at a tile strays from the light a mob prefers.
+    pub fn light_discomfort(&self, mob_id: MobId, pos: Pos) -> u32 {
+        match self[mob_id].species.light_preference() {
+            Some(preferred) => self.light(pos).abs_diff(preferred),
+            None => 0,
+        }
+    }
+
+    /// The extra damage a mob deals from a tile.
+    pub(crate) fn light_bonus(&self, mob_id: MobId, pos: Pos) -> u32 {
+        match self[mob_id].species.light_preference() {
+            Some(preferred) if self.light(pos) == preferred => LIGHT_BONUS,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::mob::Species;
+
+    #[test]
+    fn test_moss_glows() {
+        let mut world = World::from_seed(9, GenParams::default());
+        let player = world.active_player();
+        world[player].light = None;
+        let center = world[player].pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 6) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        world.level[center].terrain = Terrain::ShortGrass;
+        assert_eq!(world.light(center), 2);
+        assert_eq!(world.light(center + Direction::East), 1);
+        assert_eq!(world.light(center + Direction::East * 2), 0);
+
+        let pos = center + Direction::West * 4;
+        let spider = world.spawn(Species::Spider, pos).unwrap();
+        assert_eq!(world.light_discomfort(spider, center), 2);
+        assert_eq!(world.light_bonus(spider, pos), LIGHT_BONUS);
+        world.level[pos + Direction::West].terrain = Terrain::ShortGrass;
+        assert_eq!(world.light_bonus(spider, pos), 0);
+    }
+}
@@ -0,0 +1,101 @@
+//! The log of what players have seen happen.
+
+use prelude::*;
+
+/// How many of the latest messages an observation includes.
+pub const RECENT_MESSAGES: usize = 10;
+/// How many messages the log keeps before forgetting the oldest.
+pub const MESSAGE_LOG_SIZE: usize = 500;
+
+/// A line in the message log.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub turn: u64,
+    pub text: String,
+    /// The indices of the players who saw or heard it happen.
+    pub witnesses: Vec<usize>,
+}
+
+impl World {
+    /// Log a message about something that happened at a position.
+    ///
+    /// Only the players who can see the position are told, and the message
+    /// is dropped if none of them can.
+    pub(crate) fn report(&mut self, pos: Pos, text: String) {
+        let witnesses = (0..self.players.len())
+            .filter(|&index| self.fovs[self.fov_index(index)][pos].is_visible())
+            .collect();
+        self.log(text, witnesses);
+    }
+
+    /// Log a message for some players, unless there are none.
+    pub(super) fn log(&mut self, text: String, witnesses: Vec<usize>) {
+        if witnesses.is_empty() {
+            return;
+        }
+        if self.messages.len() == MESSAGE_LOG_SIZE {
+            self.messages.remove(0);
+        }
+        let turn = self.turn();
+        self.messages.push(Message {
+            turn,
+            text,
+            witnesses,
+        });
+    }
+
+    /// The latest messages logged, up to `MESSAGE_LOG_SIZE`, oldest first.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// The messages a player was told of, oldest first.
+    pub fn messages_for(&self, player: usize) -> Vec<&Message> {
+        self.messages
+            .iter()
+            .filter(|message| message.witnesses.contains(&player))
+            .collect()
+    }
+
+    /// How a message should refer to a mob, going by what the active
+    /// player sees it as.
+    pub(crate) fn describe(&self, mob_id: MobId) -> String {
+        match mob_id.player_index() {
+            Some(index) if self.players().len() > 1 => format!("player {}", index + 1),
+            _ => format!("the {}", self.seen_species(mob_id).name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::TileView;
+    use level::GenParams;
+    use world::Party;
+
+    #[test]
+    fn test_messages_reach_only_witnesses() {
+        let party = Party {
+            size: 2,
+            shared_fov: false,
+        };
+        let mut world = World::with_party(14, GenParams::default(), party);
+        let pos = world[MobId::player(0)].pos;
+        world.fovs[1][pos] = TileView::None;
+        world.report(pos, "Only the first player sees this.".to_owned());
+        assert_eq!(world.messages_for(0).len(), 1);
+        assert!(world.messages_for(1).is_empty());
+    }
+
+    #[test]
+    fn test_message_log_capped() {
+        let mut world = World::from_seed(14, GenParams::default());
+        for i in 0..=MESSAGE_LOG_SIZE {
+            world.log(i.to_string(), vec![0]);
+        }
+        assert_eq!(world.messages().len(), MESSAGE_LOG_SIZE);
+        assert_eq!(world.messages()[0].text, "1");
+    }
+}
@@ -1,12 +1,21 @@
+use content::Content;
+use loot::{Loot, LootWeights, MobLoot};
 use prelude::*;
+use rng::Stream;
+use std::mem::take;
 use std::ops::{Index, IndexMut};
+use world::chronicle::MilestoneKind;
+use world::lamp::LightSource;
+use world::modifier::Stance;
+use world::seal::Rune;
 
+/// The id of the first player.
 pub const PLAYER_ID: MobId = MobId {
-    inner: InnerMobId::Player,
+    inner: InnerMobId::Player(0),
 };
 
 /// Represents a mob, or "moving object," i.e. the player or a monster
-#[derive(Serialize, Deserialize)]
+#[derive(Hash, Serialize, Deserialize)]
 pub struct Mob {
     pub pos: Pos,
     pub facing: Direction,
@@ -17,6 +26,31 @@ pub struct Mob {
     pub health: u32,
     pub max_health: u32,
     pub alive: bool,
+    /// Caught in a web, and unable to move until breaking free.
+    pub entangled: bool,
+    /// The direction a sneaking mob is creeping in, if it is halfway through a quiet step.
+    pub creeping: Option<Direction>,
+    /// Where the mob last heard a player, if it hasn't looked there yet.
+    pub heard: Option<Pos>,
+    /// The piles of loot the mob has picked up or stolen, dropped when it dies.
+    pub carried: Vec<Loot>,
+    /// The necromancer that raised this mob, if it was raised from a corpse.
+    pub master: Option<MobId>,
+    /// The light the mob carries, if any.
+    pub light: Option<LightSource>,
+    /// How many flasks of oil the mob carries for its lantern.
+    pub oil_flasks: u32,
+    /// For how many more turns the mob is blind.
+    pub blind: u32,
+    /// For how many more turns the mob is deaf.
+    pub deaf: u32,
+    /// For how many more turns the mob senses every mind on the level.
+    pub telepathic: u32,
+    /// For how many more turns the mob sees things as they aren't.
+    pub hallucinating: u32,
+    pub stance: Stance,
+    /// The rune this mob guards, if it is the guardian of sealed stairs.
+    pub rune: Option<Rune>,
 }
 
 /// The identity of a mob
@@ -25,7 +59,7 @@ pub struct Mob {
 /// interior mutability. Instead of using Cell or Refcell, this interior
 /// mutability is achieved with a central mob owner whose mutability follows
 /// regular borrow checker rules.
-#[derive(Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
 pub struct MobId {
     inner: InnerMobId,
 }
@@ -39,16 +73,56 @@ pub struct Npcs {
     npcs: Vec<Mob>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub enum Species {
-    Hero,
-    Skeleton,
+registry! {
+    #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+    pub enum Species {
+        Hero => "hero",
+        Skeleton => "skeleton",
+        /// A boss too big for one tile.
+        Behemoth => "behemoth",
+        /// Spins webs, and walks through them freely.
+        Spider => "spider",
+        /// Shoots from a distance, and backs away from anything that gets close.
+        Archer => "archer",
+        /// Snatches what players carry, then runs for the stairs.
+        Thief => "thief",
+        /// Raises the dead, and hides behind them.
+        Necromancer => "necromancer",
+        /// A boss three tiles long, head first.
+        Serpent => "serpent",
+    }
+}
+
+/// How many tiles a mob takes up, and how they lie.
+///
+/// A mob's position is the center of its footprint, and the footprint turns
+/// with the way the mob faces. Every tile of the footprint holds the mob's
+/// id, so it can be attacked through any of them.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Size {
+    /// One tile.
+    Small,
+    /// A head and the two tiles behind it, in a line.
+    Long,
+    /// A tile and the six around it.
+    Huge,
+}
+
+/// What a mob does when a closed door is in its way.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum DoorSkill {
+    /// Opens it, taking a turn.
+    Open,
+    /// Batters it until it breaks, taking a turn for each blow.
+    Smash,
+    /// Can't get through, and has to go around.
+    Blocked,
 }
 
 /// Identifies a mob
-#[derive(Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
 enum InnerMobId {
-    Player,
+    Player(usize),
     Npc(usize),
 }
 
@@ -64,22 +138,356 @@ impl Mob {
             health: 100,
             max_health: 100,
             alive: true,
+            entangled: false,
+            creeping: None,
+            heard: None,
+            carried: Vec::new(),
+            master: None,
+            light: None,
+            oil_flasks: 0,
+            blind: 0,
+            deaf: 0,
+            telepathic: 0,
+            hallucinating: 0,
+            stance: Stance::Normal,
+            rune: None,
+        }
+    }
+
+    /// A new mob with the base stats content gives its species, if it
+    /// defines them.
+    pub fn spawned(pos: Pos, species: Species, content: &Content) -> Self {
+        let mut mob = Mob::new(pos, species);
+        if let Some(def) = content.species_def(species) {
+            mob.max_health = def.max_health;
+            mob.health = def.max_health;
+            mob.max_guard = def.max_guard;
+            mob.guard = def.max_guard;
+        }
+        mob
+    }
+
+    /// The tiles this mob takes up.
+    pub fn footprint(&self) -> Vec<Pos> {
+        self.species.size().footprint(self.pos, self.facing)
+    }
+
+    /// Whether stepping in a direction backs the mob away from a blow it is
+    /// recovering from, keeping its face to the front.
+    ///
+    /// A mob whose footprint turns with it can't back up, and turns around instead.
+    pub fn backs_away(&self, direction: Direction) -> bool {
+        self.guard_recovery > 0
+            && self.facing == direction.rotate(3)
+            && self.species.size().symmetric()
+    }
+}
+
+impl Species {
+    pub fn size(self) -> Size {
+        match self {
+            Species::Behemoth => Size::Huge,
+            Species::Serpent => Size::Long,
+            Species::Hero
+            | Species::Skeleton
+            | Species::Spider
+            | Species::Archer
+            | Species::Thief
+            | Species::Necromancer => Size::Small,
+        }
+    }
+
+    /// The light this species is most at home in, if it cares.
+    pub fn light_preference(self) -> Option<u32> {
+        match self {
+            Species::Spider => Some(0),
+            Species::Hero
+            | Species::Skeleton
+            | Species::Behemoth
+            | Species::Archer
+            | Species::Thief
+            | Species::Necromancer
+            | Species::Serpent => None,
+        }
+    }
+
+    /// How far this species can shoot, if it has a ranged attack.
+    pub fn range(self) -> Option<u32> {
+        match self {
+            Species::Archer => Some(5),
+            Species::Hero
+            | Species::Skeleton
+            | Species::Behemoth
+            | Species::Spider
+            | Species::Thief
+            | Species::Necromancer
+            | Species::Serpent => None,
+        }
+    }
+
+    /// What this species does when a closed door is in its way.
+    pub fn door_skill(self) -> DoorSkill {
+        match self {
+            Species::Hero
+            | Species::Skeleton
+            | Species::Archer
+            | Species::Thief
+            | Species::Necromancer => DoorSkill::Open,
+            Species::Behemoth => DoorSkill::Smash,
+            Species::Spider | Species::Serpent => DoorSkill::Blocked,
+        }
+    }
+
+    /// Whether webs catch mobs of this species.
+    pub fn caught_by_webs(self) -> bool {
+        self != Species::Spider
+    }
+
+    /// Whether mobs of this species steal from those they hit.
+    pub fn steals(self) -> bool {
+        self == Species::Thief
+    }
+
+    /// Whether mobs of this species raise corpses.
+    pub fn raises_dead(self) -> bool {
+        self == Species::Necromancer
+    }
+
+    /// Whether mobs of this species are dead things walking, which detect life misses.
+    pub fn undead(self) -> bool {
+        self == Species::Skeleton
+    }
+
+    /// Whether mobs of this species are weak enough to keep away from wards.
+    pub fn fears_wards(self) -> bool {
+        match self {
+            Species::Skeleton | Species::Spider | Species::Archer | Species::Thief => true,
+            Species::Hero | Species::Behemoth | Species::Necromancer | Species::Serpent => false,
+        }
+    }
+
+    /// Whether mobs of this species have minds telepathy can sense.
+    pub fn has_mind(self) -> bool {
+        match self {
+            Species::Hero
+            | Species::Behemoth
+            | Species::Archer
+            | Species::Thief
+            | Species::Necromancer
+            | Species::Serpent => true,
+            Species::Skeleton | Species::Spider => false,
+        }
+    }
+}
+
+impl Size {
+    /// Where the tiles of the footprint lie from its center, as how many
+    /// steps the way the mob faces and how many a sixth of a turn clockwise
+    /// from it.
+    fn offsets(self) -> &'static [(i32, i32)] {
+        match self {
+            Size::Small => &[(0, 0)],
+            Size::Long => &[(0, 0), (-1, 0), (-2, 0)],
+            Size::Huge => &[(0, 0), (1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1), (1, -1)],
+        }
+    }
+
+    /// How far the footprint reaches from its center.
+    pub fn radius(self) -> u32 {
+        self.footprint(grid::center(), Direction::East)
+            .into_iter()
+            .map(|pos| pos.distance(grid::center()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// How far the footprint reaches ahead of its center.
+    pub fn reach(self) -> u32 {
+        self.offsets()
+            .iter()
+            .map(|&(ahead, right)| (ahead + right).max(0) as u32)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether the footprint looks the same whichever way the mob faces.
+    pub fn symmetric(self) -> bool {
+        let facing_east = self.footprint(grid::center(), Direction::East);
+        grid::DIRECTIONS.iter().all(|&facing| {
+            self.footprint(grid::center(), facing)
+                .iter()
+                .all(|pos| facing_east.contains(pos))
+        })
+    }
+
+    /// The tiles taken up by a mob of this size centered on a position and
+    /// facing a direction.
+    pub fn footprint(self, center: Pos, facing: Direction) -> Vec<Pos> {
+        self.offsets()
+            .iter()
+            .map(|&(ahead, right)| center + (facing * ahead + facing.rotate(1) * right))
+            .collect()
+    }
+
+    /// Whether a mob of this size centered on a position and facing a
+    /// direction takes up a tile.
+    pub fn covers(self, center: Pos, facing: Direction, pos: Pos) -> bool {
+        self.footprint(center, facing).contains(&pos)
+    }
+}
+
+impl World {
+    /// Add an npc centered on a position, if every tile it would take up is free.
+    ///
+    /// It faces east, like every new mob.
+    pub fn spawn(&mut self, species: Species, center: Pos) -> Option<MobId> {
+        let footprint = species.size().footprint(center, Direction::East);
+        let free = footprint.into_iter().all(|pos| {
+            grid::contains(pos)
+                && self.level[pos].terrain.passable()
+                && self.level[pos].mob_id.is_none()
+        });
+        if !free {
+            return None;
+        }
+        let mob_id = self
+            .npcs
+            .insert(Mob::spawned(center, species, self.architect.content()));
+        self.move_mob(mob_id, center);
+        Some(mob_id)
+    }
+
+    /// Whether a mob could stand centered on a position facing a direction,
+    /// ignoring other mobs.
+    pub fn fits(&self, mob_id: MobId, center: Pos, facing: Direction) -> bool {
+        self[mob_id]
+            .species
+            .size()
+            .footprint(center, facing)
+            .into_iter()
+            .all(|pos| grid::contains(pos) && self.level[pos].terrain.passable())
+    }
+
+    /// Another mob in the way of a mob centered on a position facing a
+    /// direction, and where it is.
+    pub fn blocker(&self, mob_id: MobId, center: Pos, facing: Direction) -> Option<(MobId, Pos)> {
+        self[mob_id]
+            .species
+            .size()
+            .footprint(center, facing)
+            .into_iter()
+            .filter(|&pos| grid::contains(pos))
+            .filter_map(|pos| self.level[pos].mob_id.map(|other| (other, pos)))
+            .find(|&(other, _)| other != mob_id)
+    }
+
+    /// The way a mob would face after stepping to a position: toward it if
+    /// it is a step away, and the way it faces now otherwise.
+    pub fn facing_toward(&self, mob_id: MobId, center: Pos) -> Direction {
+        let mob = &self[mob_id];
+        if mob.pos.distance(center) == 1 {
+            (center - mob.pos).direction()
+        } else {
+            mob.facing
+        }
+    }
+
+    /// Move a mob, taking it off the tiles it leaves and onto the ones it enters.
+    pub(crate) fn move_mob(&mut self, mob_id: MobId, center: Pos) {
+        let facing = self[mob_id].facing;
+        self.move_mob_facing(mob_id, center, facing);
+    }
+
+    /// Move a mob and turn it to face a direction.
+    pub(crate) fn move_mob_facing(&mut self, mob_id: MobId, center: Pos, facing: Direction) {
+        self.lift(mob_id);
+        self[mob_id].pos = center;
+        self[mob_id].facing = facing;
+        for pos in self[mob_id].footprint() {
+            self.level[pos].mob_id = Some(mob_id);
+        }
+    }
+
+    /// Turn a mob in place to face a direction.
+    ///
+    /// A mob whose footprint wouldn't fit turned that way, or would run
+    /// into another mob, keeps facing the way it did.
+    pub(crate) fn turn_mob(&mut self, mob_id: MobId, facing: Direction) {
+        if self[mob_id].species.size().symmetric() {
+            self[mob_id].facing = facing;
+            return;
+        }
+        let center = self[mob_id].pos;
+        if self.fits(mob_id, center, facing) && self.blocker(mob_id, center, facing).is_none() {
+            self.move_mob_facing(mob_id, center, facing);
+        }
+    }
+
+    /// Take a mob off every tile it takes up.
+    pub(crate) fn lift(&mut self, mob_id: MobId) {
+        for pos in self[mob_id].footprint() {
+            if grid::contains(pos) && self.level[pos].mob_id == Some(mob_id) {
+                self.level[pos].mob_id = None;
+            }
         }
     }
 }
 
 impl MobId {
+    /// The id of the player with a given index.
+    pub fn player(index: usize) -> Self {
+        MobId {
+            inner: InnerMobId::Player(index),
+        }
+    }
+
     pub fn is_player(&self) -> bool {
         match self.inner {
-            InnerMobId::Player => true,
+            InnerMobId::Player(_) => true,
             InnerMobId::Npc(_) => false,
         }
     }
 
+    /// The index of this mob among the players, if it is one.
+    pub fn player_index(&self) -> Option<usize> {
+        match self.inner {
+            InnerMobId::Player(index) => Some(index),
+            InnerMobId::Npc(_) => None,
+        }
+    }
+
     pub fn die(self, world: &mut World) {
         let mob_pos = world[self].pos;
-        world.level[mob_pos].mob_id = None;
+        let message = format!("{} dies.", capitalize(&world.describe(self)));
+        world.report(mob_pos, message);
+        world.focus_on_event(mob_pos);
+        world.lift(self);
         world[self].alive = false;
+        world.recap_death(self);
+        world.drop_rune(self);
+        world.note(match self.player_index() {
+            Some(index) => MilestoneKind::PlayerDied(index),
+            None => MilestoneKind::Killed(world[self].species),
+        });
+        for minion in world.minions(self) {
+            minion.die(world);
+        }
+        if world[self].master.is_some() {
+            // The raised leave nothing behind.
+            return;
+        }
+        if world[self].species.size() == Size::Small {
+            world.level[mob_pos].corpse = Some(world[self].species);
+        }
+        let depth = world.depth();
+        let drop = MobLoot::of(world[self].species).roll(
+            &LootWeights::default(),
+            depth,
+            world.rng(Stream::Items),
+        );
+        for loot in take(&mut world[self].carried).into_iter().chain(drop) {
+            world.drop_loot(mob_pos, loot);
+        }
     }
 
     fn new(index: usize) -> Self {
@@ -103,17 +511,29 @@ impl Npcs {
     }
 }
 
-/// Iterates over each npc
+/// Capitalize the first letter of a message.
+pub(crate) fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Iterates over each npc, in the order they were inserted
 ///
 /// Since the closure needs to be able to borrow World mutably, this
 /// function can't borrow MobOwner. That's why we use a while loop instead
 /// of an iterator and why this function is implemented for World.
+///
+/// Npcs inserted by the closure aren't visited.
 pub fn for_each_mut<F>(world: &mut World, mut f: F)
 where
     F: FnMut(MobId, &mut World),
 {
+    let len = world.npcs.npcs.len();
     let mut i = 0;
-    while i < world.npcs.npcs.len() {
+    while i < len {
         let id = MobId::new(i);
         f(id, world);
         i += 1;
@@ -132,22 +552,146 @@ where
     }
 }
 
+impl World {
+    /// The mob with an id, or `None` if there is no such mob, as in a damaged save.
+    pub fn get_mob(&self, id: MobId) -> Option<&Mob> {
+        match id.inner {
+            InnerMobId::Player(index) => self.players.get(index),
+            InnerMobId::Npc(index) => self.npcs.npcs.get(index),
+        }
+    }
+}
+
 impl Index<MobId> for World {
     type Output = Mob;
 
     fn index(&self, id: MobId) -> &Mob {
         match id.inner {
-            InnerMobId::Player => &self.player,
+            InnerMobId::Player(index) => &self.players[index],
             InnerMobId::Npc(index) => &self.npcs.npcs[index],
         }
     }
 }
 
+impl Index<MobId> for Npcs {
+    type Output = Mob;
+
+    fn index(&self, id: MobId) -> &Mob {
+        match id.inner {
+            InnerMobId::Npc(index) => &self.npcs[index],
+            InnerMobId::Player(_) => panic!("Players aren't npcs."),
+        }
+    }
+}
+
 impl IndexMut<MobId> for World {
     fn index_mut(&mut self, id: MobId) -> &mut Mob {
         match id.inner {
-            InnerMobId::Player => &mut self.player,
+            InnerMobId::Player(index) => &mut self.players[index],
             InnerMobId::Npc(index) => &mut self.npcs.npcs[index],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::{open_world, remove_npcs_near};
+
+    use grid::DIRECTIONS;
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::action::{self, Action};
+    use world::ai;
+
+    /// A world with a behemoth in open ground and the player next to it.
+    fn behemoth_world() -> (World, MobId) {
+        let mut world = World::from_seed(6, GenParams::default());
+        let player = world.active_player();
+        let center = world[player].pos + Direction::West * 2;
+        remove_npcs_near(&mut world, center, 3);
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let behemoth = world.spawn(Species::Behemoth, center).unwrap();
+        (world, behemoth)
+    }
+
+    #[test]
+    fn test_huge_mob_takes_up_footprint() {
+        let (mut world, behemoth) = behemoth_world();
+        let center = world[behemoth].pos;
+        assert_eq!(world[behemoth].footprint().len(), 7);
+        assert_eq!(world.validate_invariants(), Ok(()));
+        assert_eq!(
+            world.spawn(Species::Skeleton, center + Direction::West),
+            None
+        );
+
+        action::walk(behemoth, Direction::West, &mut world).unwrap();
+        assert_eq!(world.level[center + Direction::East].mob_id, None);
+        assert_eq!(world.level[center].mob_id, Some(behemoth));
+        assert_eq!(world.validate_invariants(), Ok(()));
+
+        let target = world.player().pos;
+        ai::chase(behemoth, target, &mut world).unwrap();
+        assert_eq!(world[behemoth].pos, center);
+        assert_eq!(world.validate_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_huge_mob_hit_through_any_tile() {
+        let (mut world, behemoth) = behemoth_world();
+        world[behemoth].guard = 0;
+        world[behemoth].health = 1;
+        let player = world.active_player();
+        let edge = world[behemoth].pos + Direction::East;
+        assert_eq!(
+            world.blocker(player, edge, Direction::West),
+            Some((behemoth, edge))
+        );
+        world
+            .perform_confirmed(Action::Walk(Direction::West))
+            .unwrap();
+        assert!(!world[behemoth].alive);
+        assert!(DIRECTIONS
+            .iter()
+            .all(|&direction| world.level[edge - Direction::East + direction]
+                .mob_id
+                .is_none()));
+        assert_eq!(world.validate_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_long_mob_turns_with_facing() {
+        let mut world = open_world(6, 5);
+        let head = world.player().pos + Direction::West * 2;
+        let serpent = world.spawn(Species::Serpent, head).unwrap();
+        let tail: Pos = head + Direction::West * 2;
+        assert_eq!(
+            world[serpent].footprint(),
+            vec![head, head + Direction::West, tail]
+        );
+        assert_eq!(world.validate_invariants(), Ok(()));
+
+        action::walk(serpent, Direction::Northwest, &mut world).unwrap();
+        let turned = head + Direction::Northwest;
+        assert_eq!(
+            world[serpent].footprint(),
+            vec![turned, head, head + Direction::Southeast]
+        );
+        assert_eq!(world.level[tail].mob_id, None);
+        assert_eq!(world.validate_invariants(), Ok(()));
+
+        let wall: Pos = turned + Direction::Southwest + Direction::Northeast * 2;
+        world.level[wall].terrain = Terrain::Wall;
+        assert!(action::walk(serpent, Direction::Southwest, &mut world).is_err());
+        assert_eq!(world[serpent].pos, turned);
+
+        let target = world.player().pos;
+        for _ in 0..3 {
+            let _ = ai::chase(serpent, target, &mut world);
+            assert_eq!(world.validate_invariants(), Ok(()));
+        }
+    }
+}
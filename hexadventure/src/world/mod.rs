@@ -1,54 +1,526 @@
-use self::mob::{Mob, Npcs, Species::Hero, PLAYER_ID};
+use self::auto::{GameOptions, Plan};
+use self::chronicle::{Milestone, MilestoneKind};
+use self::combat_log::AttackRecord;
+use self::engrave::Engraving;
+use self::events::ScheduledEvent;
+use self::journal::Journal;
+use self::message::Message;
+use self::mob::{Mob, Npcs, Species::Hero};
+use self::pacing::LevelProgress;
+use self::portal::PortalLink;
+use self::recall::Sighting;
+use self::recap::{Blow, DeathRecap, Vitals};
+use self::reveal::Vision;
+use self::route::StairLink;
+use self::schedule::TurnSystem;
+use self::seal::Rune;
+use self::senses::LifePulse;
+use astar::{FlowField, PathCache, SearchStats};
+use camera::Focus;
+use content::Content;
 use fov::calc_fov;
 use level::place_mob;
-use level::tile::{Tile, TileView};
-use level::Architect;
+use level::space::classify;
+use level::tile::{Terrain, Tile, TileView};
+use level::{Architect, GenParams, GenPass, GenProgress};
+use names;
 use prelude::*;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, IsaacRng, Rng};
+use rng::{RngRegistry, Stream};
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
 
 pub mod action;
 mod ai;
+pub mod ambience;
+mod arrival;
+pub mod auto;
+mod changes;
+pub mod chronicle;
+pub mod combat_log;
+pub mod danger;
+mod decal;
+mod door;
+pub mod engrave;
+pub mod events;
+pub mod examine;
+mod hallucination;
+pub mod invariants;
+pub mod journal;
+pub mod lamp;
+pub mod level_event;
+pub mod light;
+pub mod message;
 pub mod mob;
-mod schedule;
+pub mod modifier;
+mod morgue;
+pub mod necromancy;
+pub mod observation;
+mod pacing;
+pub mod portal;
+pub mod predict;
+pub mod preview;
+mod pursuit;
+mod ranged;
+pub mod recall;
+pub mod recap;
+mod rescue;
+pub mod reveal;
+pub mod route;
+pub mod schedule;
+pub mod seal;
+pub mod senses;
+mod sound;
+#[cfg(test)]
+mod testing;
+mod thief;
+mod web;
 
 #[derive(Serialize, Deserialize)]
 pub struct World {
+    #[serde(with = "grid::rle")]
     pub level: Grid<Tile>,
-    pub fov: Grid<TileView>,
-    pub player: Mob,
+    /// One field of view per player, or a single one if it is shared.
+    fovs: Vec<Grid<TileView>>,
+    players: Vec<Mob>,
+    /// The index of the player whose turn it is.
+    active: usize,
     npcs: Npcs,
     architect: Architect,
+    rngs: RngRegistry,
+    turn: u64,
+    messages: Vec<Message>,
+    journal: Option<Journal>,
+    options: GameOptions,
+    focus: Focus,
+    progress: LevelProgress,
+    /// Events still to come, soonest first.
+    events: Vec<ScheduledEvent>,
+    /// The interrupted repeated action of each player.
+    plans: Vec<Option<Plan>>,
+    /// Mobs that escaped down the stairs, waiting to be put on the next level.
+    fugitives: Vec<Mob>,
+    /// Npcs that followed a player down the stairs, waiting to be put beside them.
+    followers: Vec<Mob>,
+    /// How many flights down the stairs in a row had hostiles following.
+    flights: u32,
+    /// Landmarks the players have seen, on this level and those left behind.
+    sightings: Vec<Sighting>,
+    /// Every flight of stairs the players have taken.
+    stair_links: Vec<StairLink>,
+    /// The living things last sensed by detect life, until the pulse fades.
+    life_pulse: Option<LifePulse>,
+    /// The messages the players have engraved, on every level.
+    engravings: Vec<Engraving>,
+    /// The clairvoyant visions the players have.
+    visions: Vec<Vision>,
+    /// The latest attacks, in detail, while the detailed combat log is on.
+    combat_log: Vec<AttackRecord>,
+    /// The blows struck at players over the last few turns.
+    recent_blows: Vec<Blow>,
+    /// The guard and health of the players at the end of each of the last few turns.
+    vitals: Vec<Vitals>,
+    /// How each player who has died so far died.
+    death_recaps: Vec<DeathRecap>,
+    /// The pairs of portals on this level, and leading to the one below.
+    portals: Vec<PortalLink>,
+    /// The runes the party has found, which open the sealed stairs.
+    runes: Vec<Rune>,
+    /// The notable events of the run, in the order they happened.
+    milestones: Vec<Milestone>,
+    /// Tells this run apart from every other, even one played from the same seed.
+    run_id: u64,
+    /// The way to the players, worked out before npcs act. Not saved.
+    #[serde(skip)]
+    pursuit: Option<FlowField>,
+    /// The step each npc in a pack plans to take this round, worked out with the
+    /// pursuit. Not saved.
+    #[serde(skip)]
+    pack_steps: HashMap<MobId, Pos>,
+    /// Bumped whenever the terrain of the level changes. Not saved.
+    #[serde(skip)]
+    terrain_revision: u64,
+    /// Bumped whenever the players learn something about the terrain. Not saved.
+    #[serde(skip)]
+    knowledge_revision: u64,
+    /// Paths the players travelled along on the current revisions. Not saved.
+    #[serde(skip)]
+    path_cache: PathCache,
+    /// Systems registered to run every turn. Not saved.
+    #[serde(skip)]
+    turn_systems: Vec<Box<dyn TurnSystem>>,
+    /// What the searches of the last turn did, while recording them. Not saved.
+    #[serde(skip)]
+    path_stats: Option<SearchStats>,
+    /// The current level as it was generated. Not saved, but regenerated when needed.
+    #[serde(skip)]
+    generated_level: Option<Grid<Tile>>,
+}
+
+/// Who is playing.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Party {
+    /// How many player characters take turns.
+    pub size: usize,
+    /// Whether the players see everything any of them can see.
+    pub shared_fov: bool,
 }
 
 impl World {
     pub fn new() -> Self {
-        let seed = thread_rng().gen();
-        let mut architect = Architect::new(seed);
+        World::with_params(GenParams::default())
+    }
+
+    /// Create a world whose levels are generated with the given parameters.
+    pub fn with_params(params: GenParams) -> Self {
+        World::from_seed(thread_rng().gen(), params)
+    }
+
+    /// Create a world whose randomness all derives from one seed.
+    pub fn from_seed(seed: u64, params: GenParams) -> Self {
+        World::with_party(seed, params, Party::solo())
+    }
+
+    /// Create a world where several players take turns.
+    ///
+    /// The players share one level. Whoever takes the stairs down brings
+    /// the whole party along.
+    pub fn with_party(seed: u64, params: GenParams, party: Party) -> Self {
+        World::generate(seed, params, party, Content::default(), None)
+    }
+
+    /// Create a world whose mobs spawn from the given content, e.g. a mod's.
+    pub fn with_content(seed: u64, params: GenParams, party: Party, content: Content) -> Self {
+        World::generate(seed, params, party, content, None)
+    }
+
+    /// Create a world, reporting how generating each level is going.
+    ///
+    /// Worlds can be sent between threads, so a frontend can create one on
+    /// a loading thread and show the progress it receives.
+    pub fn with_progress(
+        seed: u64,
+        params: GenParams,
+        party: Party,
+        progress: Sender<GenProgress>,
+    ) -> Self {
+        World::generate(seed, params, party, Content::default(), Some(progress))
+    }
+
+    fn generate(
+        seed: u64,
+        params: GenParams,
+        party: Party,
+        content: Content,
+        progress: Option<Sender<GenProgress>>,
+    ) -> Self {
+        assert!(party.size > 0, "A party needs at least one player.");
+        let mut rngs = RngRegistry::new(seed);
+        let mut architect =
+            Architect::new(rngs.stream_seed(Stream::Architect), params, content);
+        if let Some(progress) = progress {
+            architect.report_progress(progress);
+        }
         let (mut level, npcs) = architect.generate();
-        let player_pos = place_mob(&mut level, grid::center(), PLAYER_ID, &mut thread_rng());
+        let generated_level = Some(level.clone());
+        let players = (0..party.size)
+            .map(|index| {
+                let pos = place_mob(
+                    &mut level,
+                    grid::center(),
+                    MobId::player(index),
+                    rngs.get(Stream::Spawns),
+                );
+                Mob::spawned(pos, Hero, architect.content())
+            })
+            .collect();
+        let fov_count = if party.shared_fov { 1 } else { party.size };
         let mut world = World {
             level,
-            player: Mob::new(player_pos, Hero),
+            fovs: (0..fov_count).map(|_| Grid::new(|_| TileView::None)).collect(),
+            players,
+            active: 0,
             npcs,
-            fov: Grid::new(|_| TileView::None),
             architect,
+            rngs,
+            turn: 0,
+            messages: Vec::new(),
+            journal: None,
+            options: GameOptions::default(),
+            focus: Focus::Player,
+            progress: LevelProgress::default(),
+            events: Vec::new(),
+            plans: vec![None; party.size],
+            fugitives: Vec::new(),
+            followers: Vec::new(),
+            flights: 0,
+            sightings: Vec::new(),
+            stair_links: Vec::new(),
+            life_pulse: None,
+            engravings: Vec::new(),
+            visions: Vec::new(),
+            combat_log: Vec::new(),
+            recent_blows: Vec::new(),
+            vitals: Vec::new(),
+            death_recaps: Vec::new(),
+            portals: Vec::new(),
+            runes: Vec::new(),
+            milestones: Vec::new(),
+            run_id: thread_rng().gen(),
+            pursuit: None,
+            pack_steps: HashMap::new(),
+            terrain_revision: 0,
+            knowledge_revision: 0,
+            path_cache: PathCache::new(),
+            turn_systems: Vec::new(),
+            path_stats: None,
+            generated_level,
         };
+        for index in 0..party.size {
+            world.equip_light(MobId::player(index));
+        }
+        world.note(MilestoneKind::Entered);
+        world.reclassify();
+        world.link_portals();
+        world.place_rune();
+        world.progress = LevelProgress::start(&world);
+        world.schedule_level_events();
         world.update_fov();
         world
     }
 
+    /// The id of the player whose turn it is.
+    pub fn active_player(&self) -> MobId {
+        MobId::player(self.active)
+    }
+
+    /// The player whose turn it is.
+    pub fn player(&self) -> &Mob {
+        &self.players[self.active]
+    }
+
+    pub fn players(&self) -> &[Mob] {
+        &self.players
+    }
+
+    /// What the player whose turn it is can see and remembers.
+    pub fn fov(&self) -> &Grid<TileView> {
+        &self.fovs[self.fov_index(self.active)]
+    }
+
+    /// Whether any player can see a position.
+    pub fn is_visible(&self, pos: Pos) -> bool {
+        self.fovs.iter().any(|fov| fov[pos].is_visible())
+    }
+
+    /// Whether every player has died.
+    pub fn is_over(&self) -> bool {
+        self.players.iter().all(|player| !player.alive)
+    }
+
+    /// The master seed of this run.
+    pub fn seed(&self) -> u64 {
+        self.rngs.seed()
+    }
+
+    /// An id drawn when the run started, unlike the seed unique to this run.
+    pub fn run_id(&self) -> u64 {
+        self.run_id
+    }
+
+    /// The random number generator of a subsystem.
+    pub fn rng(&mut self, stream: Stream) -> &mut IsaacRng {
+        self.rngs.get(stream)
+    }
+
+    /// How many rounds have begun since the run started, each one a turn of
+    /// every living player.
+    pub fn turn(&self) -> u64 {
+        self.turn
+    }
+
+    /// How deep the current level is. The first level has a depth of 1.
+    pub fn depth(&self) -> u32 {
+        self.architect.depth()
+    }
+
+    /// The content mobs spawn from.
+    pub fn content(&self) -> &Content {
+        self.architect.content()
+    }
+
+    /// A number that changes whenever the terrain of the level does.
+    pub fn terrain_revision(&self) -> u64 {
+        self.terrain_revision
+    }
+
+    /// A number that changes whenever the terrain of the level, or what the
+    /// players know of it, does.
+    pub fn map_revision(&self) -> u64 {
+        // Both only ever go up, so their sum changes whenever either does.
+        self.terrain_revision + self.knowledge_revision
+    }
+
+    /// The seed the level at a given depth was generated from.
+    pub fn level_seed(&self, depth: u32) -> u64 {
+        self.architect.level_seed(depth)
+    }
+
+    /// The name of the level at a given depth, made up from its seed.
+    pub fn level_name(&self, depth: u32) -> String {
+        names::place_name(self.level_seed(depth))
+    }
+
+    /// Regenerate the level at a given depth exactly as this run generated it.
+    ///
+    /// `None` for depth 0, for depths the run hasn't reached yet, and after
+    /// loading until every generation pass is registered again.
+    pub fn regenerate_level(&self, depth: u32) -> Option<(Grid<Tile>, Npcs)> {
+        self.architect.regenerate_level(depth)
+    }
+
+    /// Run an extra generation pass on every level generated from now on.
+    ///
+    /// Passes aren't saved, so they must be registered again after loading,
+    /// under the same names, before levels can be regenerated.
+    pub fn register_gen_pass(&mut self, pass: Box<dyn GenPass>) {
+        self.architect.register_pass(pass);
+    }
+
+    /// Report how generating each level from now on is going.
+    ///
+    /// This isn't saved, so it must be set again after loading.
+    pub fn report_gen_progress(&mut self, progress: Sender<GenProgress>) {
+        self.architect.report_progress(progress);
+    }
+
+    /// What the camera should be looking at.
+    pub fn focus(&self) -> Focus {
+        self.focus
+    }
+
+    /// Draw the camera to something that happened, if a player can see it.
+    pub(crate) fn focus_on_event(&mut self, pos: Pos) {
+        if self.is_visible(pos) {
+            self.focus = Focus::Event(pos);
+        }
+    }
+
+    fn fov_index(&self, player: usize) -> usize {
+        if self.fovs.len() == 1 {
+            0
+        } else {
+            player
+        }
+    }
+
+    /// Move every living player to a new level, gathered around a position
+    /// with `leader` closest to it.
+    pub(crate) fn enter_level(&mut self, level: Grid<Tile>, npcs: Npcs, leader: MobId, center: Pos) {
+        self.level = level;
+        self.npcs = npcs;
+        self.terrain_revision += 1;
+        self.life_pulse = None;
+        self.visions.clear();
+        let mut party = vec![leader];
+        party.extend(
+            (0..self.players.len())
+                .map(MobId::player)
+                .filter(|&id| id != leader && self[id].alive),
+        );
+        for id in party {
+            let old_pos = self[id].pos;
+            let new_pos = self.arrive(id, center);
+            self[id].facing = (new_pos - old_pos).direction();
+            self[id].pos = new_pos;
+            self[id].entangled = false;
+        }
+        self.place_followers(center);
+        self.place_fugitives(center);
+        self.note(MilestoneKind::Entered);
+        self.reclassify();
+        self.link_portals();
+        self.place_rune();
+        self.progress = LevelProgress::start(self);
+        self.schedule_level_events();
+        self.clear_fov();
+        self.update_fov();
+    }
+
+    /// Work out again which tiles of the level are corridor, room or cavern.
+    pub(crate) fn reclassify(&mut self) {
+        let terrain = Grid::new(|pos| self.level[pos].terrain);
+        let spaces = classify(&terrain);
+        for pos in grid::positions() {
+            self.level[pos].space = spaces[pos];
+        }
+    }
+
+    /// Forget the current level, e.g. after moving to a new one.
+    fn clear_fov(&mut self) {
+        for fov in &mut self.fovs {
+            *fov = Grid::new(|_| TileView::None);
+        }
+    }
+
     fn update_fov(&mut self) {
+        let lit = Grid::new(|pos| self.light(pos) > 0);
         let level = &self.level;
-        let fov = &mut self.fov;
-        for pos in grid::positions() {
-            if fov[pos].is_visible() {
-                fov[pos] = TileView::Remembered(self.level[pos].terrain);
+        let mut learned = false;
+        for fov in &mut self.fovs {
+            for pos in grid::positions() {
+                if fov[pos].is_visible() {
+                    fov[pos] = TileView::Remembered(level[pos].terrain);
+                }
             }
         }
-        calc_fov(
-            self.player.pos,
-            |pos| level[pos].terrain.transparent(),
-            |pos| fov[pos] = TileView::Visible,
-        );
+        for index in 0..self.players.len() {
+            if !self.players[index].alive {
+                continue;
+            }
+            let radius = self.sight_radius(MobId::player(index));
+            let blind = self.is_blind(MobId::player(index));
+            let fov_index = self.fov_index(index);
+            let fov = &mut self.fovs[fov_index];
+            let origin = self.players[index].pos;
+            // Players see as far as their light reaches, and lit tiles further away
+            // unless they are blind.
+            calc_fov(
+                origin,
+                |pos| level[pos].terrain.transparent(),
+                |pos| {
+                    if pos.distance(origin) <= radius || (lit[pos] && !blind) {
+                        learned |= see(&mut fov[pos], level[pos].terrain);
+                    }
+                },
+            );
+        }
+        let saw_visions = self.see_visions();
+        if learned || saw_visions {
+            self.knowledge_revision += 1;
+        }
+        self.remember_landmarks();
+    }
+}
+
+/// Bring a tile into view, returning whether that shows the player anything
+/// they didn't already know about its terrain.
+fn see(view: &mut TileView, terrain: Terrain) -> bool {
+    let news = match *view {
+        TileView::Remembered(known) | TileView::Mapped(known) => known != terrain,
+        TileView::Visible => false,
+        TileView::None => true,
+    };
+    *view = TileView::Visible;
+    news
+}
+
+impl Party {
+    /// A single player.
+    pub fn solo() -> Self {
+        Party {
+            size: 1,
+            shared_fov: false,
+        }
     }
 }
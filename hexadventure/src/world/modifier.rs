@@ -0,0 +1,403 @@
+//! Modifiers to what mobs do, and the stances players take.
+//!
+//! Everything that changes a mob's attributes says so with `Modifier`s:
+//! its species, the light it carries, its stance, the statuses it is under
+//! and the terrain it stands on. Formulas never stack these themselves, but
+//! ask `World::attribute` for the modified value of an attribute, which
+//! applies every modifier the same way:
+//!
+//! 1. Flat modifiers are added to the base value.
+//! 2. Percent modifiers are added up, and scale the result, rounding toward zero.
+//! 3. The lowest cap, if any, limits it.
+//!
+//! Each step sums or takes the minimum, so the order modifiers are collected
+//! in never matters.
+//!
+//! Players change stance as an action, which takes the turn. Npcs always
+//! stand normally.
+
+use level::tile::Terrain;
+use prelude::*;
+use world::lamp::{LightSource, DARK_RADIUS};
+use world::mob::capitalize;
+use world::sound::{GRASS_MUFFLE, SPLASH, WEB_RUSTLE};
+
+/// The percent chance a strike hits before modifiers.
+pub const HIT_CHANCE: i32 = 100;
+
+/// Something about a mob that modifiers change.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Attribute {
+    /// The percent chance of a strike hitting.
+    Accuracy,
+    /// The damage a hit deals.
+    Damage,
+    /// Taken from the damage the mob is dealt.
+    Defense,
+    /// How many tiles the mob's footsteps carry.
+    Noise,
+    /// Below zero, every step is a creep and takes two turns, as if sneaking.
+    Speed,
+    /// How far the mob sees by its own light.
+    SightRadius,
+}
+
+/// How a modifier changes an attribute.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Effect {
+    Flat(i32),
+    Percent(i32),
+    /// The most the attribute can be.
+    Cap(i32),
+}
+
+/// Where a modifier comes from.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Source {
+    /// The mob's species.
+    Intrinsic,
+    /// What the mob carries.
+    Equipment,
+    Stance,
+    Status,
+    /// The tile the mob stands on.
+    Terrain,
+    /// How the mob is acting right now, such as sneaking.
+    Action,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Modifier {
+    pub source: Source,
+    pub attribute: Attribute,
+    pub effect: Effect,
+}
+
+impl Modifier {
+    pub const fn new(source: Source, attribute: Attribute, effect: Effect) -> Self {
+        Modifier {
+            source,
+            attribute,
+            effect,
+        }
+    }
+}
+
+/// Every modifier that applies to a mob.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Modifiers {
+    modifiers: Vec<Modifier>,
+}
+
+impl Modifiers {
+    pub fn push(&mut self, modifier: Modifier) {
+        self.modifiers.push(modifier);
+    }
+
+    pub fn extend_from_slice(&mut self, modifiers: &[Modifier]) {
+        self.modifiers.extend_from_slice(modifiers);
+    }
+
+    /// The modifiers of one attribute.
+    pub fn of(&self, attribute: Attribute) -> impl Iterator<Item = &Modifier> {
+        self.modifiers
+            .iter()
+            .filter(move |modifier| modifier.attribute == attribute)
+    }
+
+    /// Apply the modifiers of an attribute to its base value.
+    pub fn apply(&self, attribute: Attribute, base: i32) -> i32 {
+        let mut flat = 0;
+        let mut percent = 0;
+        let mut cap = None;
+        for modifier in self.of(attribute) {
+            match modifier.effect {
+                Effect::Flat(amount) => flat += amount,
+                Effect::Percent(amount) => percent += amount,
+                Effect::Cap(limit) => cap = Some(cap.map_or(limit, |cap: i32| cap.min(limit))),
+            }
+        }
+        let value = (base + flat) * (100 + percent) / 100;
+        cap.map_or(value, |cap| value.min(cap))
+    }
+}
+
+/// How a player carries themselves.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+pub enum Stance {
+    #[default]
+    Normal,
+    /// Shields up: harder to hurt, but clumsy to strike from.
+    Defensive,
+    /// All in: hits harder and louder, and leaves an opening.
+    Aggressive,
+    /// Slow and quiet.
+    Stealth,
+}
+
+/// Every stance, in the order they are cycled through.
+pub const STANCES: [Stance; 4] = [
+    Stance::Normal,
+    Stance::Defensive,
+    Stance::Aggressive,
+    Stance::Stealth,
+];
+
+const DEFENSIVE: [Modifier; 2] = [
+    Modifier::new(Source::Stance, Attribute::Accuracy, Effect::Flat(-25)),
+    Modifier::new(Source::Stance, Attribute::Defense, Effect::Flat(3)),
+];
+const AGGRESSIVE: [Modifier; 3] = [
+    Modifier::new(Source::Stance, Attribute::Damage, Effect::Flat(3)),
+    Modifier::new(Source::Stance, Attribute::Defense, Effect::Flat(-2)),
+    Modifier::new(Source::Stance, Attribute::Noise, Effect::Flat(1)),
+];
+const STEALTH: [Modifier; 3] = [
+    Modifier::new(Source::Stance, Attribute::Damage, Effect::Flat(-1)),
+    Modifier::new(Source::Stance, Attribute::Noise, Effect::Flat(-2)),
+    Modifier::new(Source::Stance, Attribute::Speed, Effect::Flat(-1)),
+];
+/// A blind mob strikes wildly, and sees only what it touches.
+const BLIND: [Modifier; 2] = [
+    Modifier::new(Source::Status, Attribute::Accuracy, Effect::Flat(-30)),
+    Modifier::new(
+        Source::Status,
+        Attribute::SightRadius,
+        Effect::Cap(DARK_RADIUS as i32),
+    ),
+];
+/// A mob caught in a web is easier to hurt.
+const ENTANGLED: [Modifier; 1] = [Modifier::new(
+    Source::Status,
+    Attribute::Defense,
+    Effect::Flat(-1),
+)];
+
+impl Stance {
+    pub fn modifiers(self) -> &'static [Modifier] {
+        match self {
+            Stance::Normal => &[],
+            Stance::Defensive => &DEFENSIVE,
+            Stance::Aggressive => &AGGRESSIVE,
+            Stance::Stealth => &STEALTH,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stance::Normal => "normal",
+            Stance::Defensive => "defensive",
+            Stance::Aggressive => "aggressive",
+            Stance::Stealth => "stealth",
+        }
+    }
+
+    /// The stance after this one in `STANCES`, wrapping around.
+    pub fn next(self) -> Self {
+        let index = STANCES.iter().position(|&stance| stance == self).unwrap();
+        STANCES[(index + 1) % STANCES.len()]
+    }
+}
+
+impl LightSource {
+    /// A light lets its carrier see further, and a torch takes the hand a
+    /// lantern would hang from a belt.
+    pub fn modifiers(&self) -> Vec<Modifier> {
+        let reach = self.radius() as i32 - DARK_RADIUS as i32;
+        let mut modifiers = vec![Modifier::new(
+            Source::Equipment,
+            Attribute::SightRadius,
+            Effect::Flat(reach),
+        )];
+        if let LightSource::Torch { .. } = self {
+            modifiers.push(Modifier::new(
+                Source::Equipment,
+                Attribute::Defense,
+                Effect::Flat(-1),
+            ));
+        }
+        modifiers
+    }
+}
+
+impl World {
+    /// Every modifier that applies to a mob where it stands.
+    pub fn modifiers(&self, mob_id: MobId) -> Modifiers {
+        self.modifiers_at(mob_id, self[mob_id].pos)
+    }
+
+    /// Every modifier that would apply to a mob standing on a tile.
+    pub fn modifiers_at(&self, mob_id: MobId, pos: Pos) -> Modifiers {
+        let mob = &self[mob_id];
+        let mut modifiers = Modifiers::default();
+        let size = mob.species.size().radius() as i32;
+        if size > 0 {
+            modifiers.push(Modifier::new(
+                Source::Intrinsic,
+                Attribute::Noise,
+                Effect::Flat(2 * size),
+            ));
+        }
+        if let Some(light) = mob.light {
+            modifiers.extend_from_slice(&light.modifiers());
+        }
+        modifiers.extend_from_slice(mob.stance.modifiers());
+        if mob.blind > 0 {
+            modifiers.extend_from_slice(&BLIND);
+        }
+        if mob.entangled {
+            modifiers.extend_from_slice(&ENTANGLED);
+        }
+        let noise = match self.level[pos].terrain {
+            Terrain::ShortGrass | Terrain::TallGrass => -(GRASS_MUFFLE as i32),
+            Terrain::Web => WEB_RUSTLE as i32,
+            Terrain::ShallowWater => SPLASH as i32,
+            _ => 0,
+        };
+        if noise != 0 {
+            modifiers.push(Modifier::new(
+                Source::Terrain,
+                Attribute::Noise,
+                Effect::Flat(noise),
+            ));
+        }
+        let bonus = self.light_bonus(mob_id, pos);
+        if bonus > 0 {
+            modifiers.push(Modifier::new(
+                Source::Terrain,
+                Attribute::Damage,
+                Effect::Flat(bonus as i32),
+            ));
+        }
+        modifiers
+    }
+
+    /// The modified value of one of a mob's attributes.
+    pub fn attribute(&self, mob_id: MobId, attribute: Attribute, base: i32) -> i32 {
+        self.modifiers(mob_id).apply(attribute, base)
+    }
+
+    /// The percent chance a mob's strikes hit.
+    pub fn hit_chance(&self, mob_id: MobId) -> u32 {
+        self.attribute(mob_id, Attribute::Accuracy, HIT_CHANCE)
+            .clamp(0, 100) as u32
+    }
+
+    /// How much damage a rolled hit from one mob deals another.
+    ///
+    /// A hit always deals at least one damage.
+    pub fn modified_damage(&self, mob_id: MobId, target: MobId, rolled: u32) -> u32 {
+        let damage = self.attribute(mob_id, Attribute::Damage, rolled as i32)
+            - self.attribute(target, Attribute::Defense, 0);
+        damage.max(1) as u32
+    }
+
+    /// Whether a mob's steps take two turns.
+    pub fn is_slowed(&self, mob_id: MobId) -> bool {
+        self.attribute(mob_id, Attribute::Speed, 0) < 0
+    }
+
+    /// Take up a stance.
+    pub(crate) fn take_stance(&mut self, mob_id: MobId, stance: Stance) -> Result<(), ()> {
+        if self[mob_id].stance == stance {
+            return Err(());
+        }
+        self[mob_id].stance = stance;
+        let message = format!(
+            "{} takes a {} stance.",
+            capitalize(&self.describe(mob_id)),
+            stance.name()
+        );
+        let pos = self[mob_id].pos;
+        self.report(pos, message);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use world::action::{Action, ActionError};
+    use world::lamp::TORCH_RADIUS;
+    use world::mob::{Mob, Species};
+
+    #[test]
+    fn test_modifiers_add_up() {
+        let mut world = open_world(34, 8);
+        let player = world.active_player();
+        assert_eq!(world.hit_chance(player), 100);
+        assert_eq!(world.attribute(player, Attribute::Defense, 0), 0);
+
+        world.perform(Action::Stance(Stance::Defensive)).unwrap();
+        world[player].light = Some(LightSource::torch());
+        world[player].entangled = true;
+        assert_eq!(world.attribute(player, Attribute::Defense, 0), 3 - 1 - 1);
+        assert_eq!(world.hit_chance(player), 75);
+        assert_eq!(world.sight_radius(player), TORCH_RADIUS);
+        world[player].blind = 1;
+        assert_eq!(world.hit_chance(player), 45);
+        assert_eq!(world.sight_radius(player), DARK_RADIUS);
+        let sources: Vec<Source> = world
+            .modifiers(player)
+            .of(Attribute::Defense)
+            .map(|modifier| modifier.source)
+            .collect();
+        assert_eq!(
+            sources,
+            vec![Source::Equipment, Source::Stance, Source::Status]
+        );
+        assert_eq!(
+            world.perform(Action::Stance(Stance::Defensive)),
+            Err(ActionError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_flat_then_percent_then_cap() {
+        let mut modifiers = Modifiers::default();
+        let modifier = |effect| Modifier::new(Source::Status, Attribute::Noise, effect);
+        modifiers.push(modifier(Effect::Percent(-50)));
+        modifiers.push(modifier(Effect::Cap(6)));
+        modifiers.push(modifier(Effect::Flat(3)));
+        modifiers.push(modifier(Effect::Cap(4)));
+        modifiers.push(modifier(Effect::Percent(25)));
+        assert_eq!(modifiers.apply(Attribute::Noise, 7), 4);
+        assert_eq!(modifiers.apply(Attribute::Noise, 1), 3);
+        assert_eq!(modifiers.apply(Attribute::Speed, 1), 1);
+    }
+
+    #[test]
+    fn test_stances_change_damage_and_noise() {
+        let mut world = open_world(34, 8);
+        let player = world.active_player();
+        let center = world[player].pos;
+        let skeleton_pos: Pos = center + Direction::East * 2;
+        let skeleton = world.npcs.insert(Mob::new(skeleton_pos, Species::Skeleton));
+        world.level[skeleton_pos].mob_id = Some(skeleton);
+        let quiet = world.footstep_noise(player, center, false);
+
+        world.perform(Action::Stance(Stance::Aggressive)).unwrap();
+        assert_eq!(world.modified_damage(player, skeleton, 5), 8);
+        assert_eq!(world.modified_damage(skeleton, player, 5), 7);
+        assert_eq!(world.footstep_noise(player, center, false), quiet + 1);
+
+        world.perform(Action::Stance(Stance::Stealth)).unwrap();
+        assert_eq!(world.modified_damage(player, skeleton, 1), 1);
+        assert_eq!(world.footstep_noise(player, center, false), quiet - 2);
+        assert_eq!(Stance::Stealth.next(), Stance::Normal);
+    }
+
+    #[test]
+    fn test_stealth_steps_take_two_turns() {
+        let mut world = open_world(34, 8);
+        let player = world.active_player();
+        let center = world[player].pos;
+        world.perform(Action::Stance(Stance::Stealth)).unwrap();
+        world.perform(Action::Walk(Direction::West)).unwrap();
+        assert_eq!(world[player].pos, center);
+        world.perform(Action::Walk(Direction::West)).unwrap();
+        assert_eq!(world[player].pos, center + Direction::West);
+    }
+}
@@ -0,0 +1,55 @@
+//! A plain text summary of a run.
+
+use ascii::{self, GlyphTheme};
+use prelude::*;
+use protocol::Snapshot;
+use std::fmt::Write;
+
+impl World {
+    /// Describe the run so far.
+    ///
+    /// Includes the name of every level visited and the seed needed to
+    /// regenerate it, a map of the last level as the player knew it, and
+    /// the detailed combat log if it was kept.
+    pub fn morgue(&self) -> String {
+        let mut morgue = String::new();
+        let status = if self.is_over() { "Dead" } else { "Alive" };
+        let depth = self.depth();
+        let name = self.level_name(depth);
+        writeln!(morgue, "{} on level {}, {}", status, depth, name).unwrap();
+        writeln!(morgue, "Seed: {}", self.seed()).unwrap();
+        for depth in 1..=self.depth() {
+            let fell_back = self.architect.fallback_depths().contains(&depth);
+            let note = if fell_back { " (simple layout)" } else { "" };
+            writeln!(
+                morgue,
+                "  Level {} ({}) seed: {}{}",
+                depth,
+                self.level_name(depth),
+                self.level_seed(depth),
+                note
+            )
+            .unwrap();
+        }
+        let snapshot = Snapshot::of(self);
+        let theme = GlyphTheme::classic();
+        writeln!(morgue).unwrap();
+        morgue.push_str(&ascii::render(&snapshot, &theme, None));
+        writeln!(morgue).unwrap();
+        morgue.push_str(&ascii::legend(&snapshot, &theme));
+        for recap in self.death_recaps() {
+            writeln!(morgue).unwrap();
+            for line in recap.describe() {
+                writeln!(morgue, "{}", line).unwrap();
+            }
+        }
+        if !self.combat_log().is_empty() {
+            writeln!(morgue).unwrap();
+            writeln!(morgue, "Combat log:").unwrap();
+            for record in self.combat_log() {
+                writeln!(morgue, "  {}", record.describe()).unwrap();
+            }
+        }
+        morgue
+    }
+}
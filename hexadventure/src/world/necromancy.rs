@@ -0,0 +1,99 @@
+//! Corpses, and the necromancers that raise them.
+//!
+//! Mobs that fit on one tile leave a corpse where they die. A necromancer
+//! next to a corpse can raise it as a skeleton that fights for it, using the
+//! corpse up. Necromancers seek out corpses and keep their minions between
+//! themselves and the players. The raised leave nothing behind when they
+//! fall, and fall apart when their necromancer dies.
+
+use prelude::*;
+use world::mob::{self, capitalize, Species};
+
+/// The extra cost of a step that puts a necromancer in front of its minions.
+pub const EXPOSED_PENALTY: u32 = 4;
+
+impl World {
+    /// Raise the corpse on a tile as a minion of a necromancer.
+    pub(crate) fn raise(&mut self, necromancer: MobId, pos: Pos) -> Result<(), ()> {
+        let species = self.level[pos].corpse.ok_or(())?;
+        let minion = self.spawn(Species::Skeleton, pos).ok_or(())?;
+        self.level[pos].corpse = None;
+        self[minion].master = Some(necromancer);
+        let message = format!(
+            "{} raises the {} corpse.",
+            capitalize(&self.describe(necromancer)),
+            species.name()
+        );
+        self.report(pos, message);
+        Ok(())
+    }
+
+    /// The living mobs a necromancer has raised.
+    pub fn minions(&self, master: MobId) -> Vec<MobId> {
+        let mut minions = Vec::new();
+        mob::for_each(self, |mob_id| {
+            if self[mob_id].alive && self[mob_id].master == Some(master) {
+                minions.push(mob_id);
+            }
+        });
+        minions
+    }
+
+    /// Whether a tile holds a corpse that could be raised right now.
+    pub fn raisable(&self, pos: Pos) -> bool {
+        grid::contains(pos)
+            && self.level[pos].corpse.is_some()
+            && self.level[pos].terrain.passable()
+            && self.level[pos].mob_id.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use world::ai;
+
+    #[test]
+    fn test_corpse_raised_and_falls_with_master() {
+        let mut world = open_world(25, 8);
+        let center = world.player().pos;
+        let pos: Pos = center + Direction::West * 5;
+        let skeleton = world.spawn(Species::Skeleton, pos).unwrap();
+        skeleton.die(&mut world);
+        assert_eq!(world.level[pos].corpse, Some(Species::Skeleton));
+
+        let necromancer = world
+            .spawn(Species::Necromancer, pos + Direction::West * 3)
+            .unwrap();
+        ai::act(necromancer, &mut world).unwrap();
+        ai::act(necromancer, &mut world).unwrap();
+        assert_eq!(world[necromancer].pos.distance(pos), 1);
+        ai::act(necromancer, &mut world).unwrap();
+        let minions = world.minions(necromancer);
+        assert_eq!(minions.len(), 1);
+        assert_eq!(world[minions[0]].pos, pos);
+        assert_eq!(world.level[pos].corpse, None);
+
+        necromancer.die(&mut world);
+        assert!(!world[minions[0]].alive);
+        assert_eq!(world.level[pos].corpse, None);
+        assert_eq!(world.level[pos].mob_id, None);
+    }
+
+    #[test]
+    fn test_necromancer_keeps_behind_minions() {
+        let mut world = open_world(25, 8);
+        let center = world.player().pos;
+        let necromancer = world
+            .spawn(Species::Necromancer, center + Direction::East * 3)
+            .unwrap();
+        let minion = world
+            .spawn(Species::Skeleton, center + Direction::East * 4)
+            .unwrap();
+        world[minion].master = Some(necromancer);
+        ai::act(necromancer, &mut world).unwrap();
+        assert_eq!(world[necromancer].pos.distance(center), 4);
+    }
+}
@@ -0,0 +1,179 @@
+//! What a player can legitimately know.
+//!
+//! Bots and frontends should read the world through an `Observation`
+//! rather than through its fields, so they can't see more than the player.
+//! What they observe isn't always the truth: a hallucinating player sees
+//! mobs and loot as they aren't.
+
+use level::tile::{Terrain, TileView};
+use loot::Loot;
+use prelude::*;
+use world::ambience::Ambience;
+use world::message::{Message, RECENT_MESSAGES};
+use world::mob::{self, Species};
+use world::modifier::Stance;
+use world::seal::Seal;
+use world::senses::Sensed;
+
+/// Everything the active player knows on one turn.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Observation {
+    pub turn: u64,
+    pub depth: u32,
+    /// The name of the current level.
+    pub level_name: String,
+    pub tiles: Grid<SeenTile>,
+    /// The active player.
+    pub me: MobView,
+    /// Every other mob the active player can see.
+    pub mobs: Vec<MobView>,
+    /// The latest messages, oldest first.
+    pub messages: Vec<Message>,
+    /// What the active player's surroundings are like.
+    pub ambience: Ambience,
+    /// Whether the active player is blind, and sees only what's next to them.
+    pub blind: bool,
+    /// Whether the active player is deaf, and hears no noises.
+    pub deaf: bool,
+    /// Mobs the active player senses through walls, but can't see.
+    pub sensed: Vec<Sensed>,
+    /// The stance the active player has taken.
+    pub stance: Stance,
+    /// Whether the active player is hallucinating, and sees the other mobs
+    /// and the loot as they aren't.
+    pub hallucinating: bool,
+    /// The loot on every tile the active player can see.
+    pub loot: Vec<SeenLoot>,
+    /// The sealed stairs the party has come to, and whether it holds their runes.
+    pub seals: Vec<Seal>,
+}
+
+/// What the player knows about a tile.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum SeenTile {
+    Visible(Terrain),
+    Remembered(Terrain),
+    Unknown,
+    /// Known by magic, but never seen.
+    Mapped(Terrain),
+}
+
+/// Loot the player can see.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct SeenLoot {
+    pub pos: Pos,
+    pub loot: Loot,
+}
+
+/// What the player can see of a mob.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct MobView {
+    pub pos: Pos,
+    pub facing: Direction,
+    pub species: Species,
+    pub health: u32,
+    pub max_health: u32,
+    pub guard: u32,
+    pub max_guard: u32,
+}
+
+impl World {
+    /// Capture what the active player can observe.
+    pub fn observation(&self) -> Observation {
+        let fov = self.fov();
+        let tiles = Grid::new(|pos| match fov[pos] {
+            TileView::Visible => SeenTile::Visible(self.level[pos].terrain),
+            TileView::Remembered(terrain) => SeenTile::Remembered(terrain),
+            TileView::Mapped(terrain) => SeenTile::Mapped(terrain),
+            TileView::None => SeenTile::Unknown,
+        });
+        let me = self.active_player();
+        let mut mobs = Vec::new();
+        for (index, player) in self.players().iter().enumerate() {
+            if MobId::player(index) != me && player.alive && fov[player.pos].is_visible() {
+                mobs.push(self.mob_view(MobId::player(index)));
+            }
+        }
+        mob::for_each(self, |mob_id| {
+            let mob = &self[mob_id];
+            if mob.alive && fov[mob.pos].is_visible() {
+                mobs.push(self.mob_view(mob_id));
+            }
+        });
+        let loot = grid::positions()
+            .filter(|&pos| fov[pos].is_visible())
+            .filter_map(|pos| {
+                self.level[pos].loot.map(|loot| SeenLoot {
+                    pos,
+                    loot: self.apparent_loot(pos, loot),
+                })
+            })
+            .collect();
+        let messages = self.messages_for(self.active);
+        let recent = messages.len().saturating_sub(RECENT_MESSAGES);
+        Observation {
+            turn: self.turn(),
+            depth: self.depth(),
+            level_name: self.level_name(self.depth()),
+            tiles,
+            me: MobView::of(&self[me]),
+            mobs,
+            messages: messages[recent..]
+                .iter()
+                .map(|&message| message.clone())
+                .collect(),
+            ambience: self.ambience(),
+            blind: self.is_blind(me),
+            deaf: self.is_deaf(me),
+            sensed: self.sensed(),
+            stance: self[me].stance,
+            hallucinating: self.is_hallucinating(me),
+            loot,
+            seals: self.seals(),
+        }
+    }
+
+    /// What the active player sees of another mob.
+    fn mob_view(&self, mob_id: MobId) -> MobView {
+        let mut view = MobView::of(&self[mob_id]);
+        view.species = self.seen_species(mob_id);
+        view
+    }
+}
+
+impl MobView {
+    fn of(mob: &Mob) -> Self {
+        MobView {
+            pos: mob.pos,
+            facing: mob.facing,
+            species: mob.species,
+            health: mob.health,
+            max_health: mob.max_health,
+            guard: mob.guard,
+            max_guard: mob.max_guard,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+
+    #[test]
+    fn test_hidden_mobs_not_observed() {
+        let world = World::from_seed(5, GenParams::default());
+        let observation = world.observation();
+        for mob in &observation.mobs {
+            assert!(world.fov()[mob.pos].is_visible());
+        }
+        mob::for_each(&world, |mob_id| {
+            let pos = world[mob_id].pos;
+            if !world.fov()[pos].is_visible() {
+                assert!(observation.mobs.iter().all(|mob| mob.pos != pos));
+                assert!(observation.tiles[pos] != SeenTile::Visible(world.level[pos].terrain));
+            }
+        });
+    }
+}
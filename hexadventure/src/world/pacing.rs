@@ -0,0 +1,84 @@
+//! Optional pacing that adapts the number of spawns to how the player is doing.
+//!
+//! With pacing off, which is the default, levels are the same as without it,
+//! so runs on the same seed stay comparable.
+
+use prelude::*;
+
+/// How the party stood when it entered the current level.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(super) struct LevelProgress {
+    /// The turn the level was entered on.
+    start_turn: u64,
+    /// The total health of the living players when the level was entered.
+    start_health: u32,
+    /// The total maximum health of the living players when the level was entered.
+    max_health: u32,
+}
+
+/// A level cleared in at most this many turns counts as cleared quickly.
+const QUICK_TURNS: u64 = 200;
+/// Losing at most this percentage of health on a level counts as doing well.
+const LIGHT_LOSS_PERCENT: u32 = 10;
+/// Losing at least this percentage of health on a level counts as struggling.
+const HEAVY_LOSS_PERCENT: u32 = 50;
+
+impl LevelProgress {
+    pub(super) fn start(world: &World) -> Self {
+        let living = world.players().iter().filter(|player| player.alive);
+        let (health, max_health) = living.fold((0, 0), |(health, max), player| {
+            (health + player.health, max + player.max_health)
+        });
+        LevelProgress {
+            start_turn: world.turn(),
+            start_health: health,
+            max_health,
+        }
+    }
+}
+
+impl World {
+    /// How many more or fewer mobs the next level should spawn with.
+    ///
+    /// Always 0 unless adaptive pacing is turned on, and never more than one either way.
+    pub(super) fn pacing_adjustment(&self) -> i32 {
+        if !self.options.adaptive_pacing {
+            return 0;
+        }
+        let progress = &self.progress;
+        let health: u32 = self
+            .players()
+            .iter()
+            .filter(|player| player.alive)
+            .map(|player| player.health)
+            .sum();
+        let lost = progress.start_health.saturating_sub(health);
+        let lost_percent = lost * 100 / progress.max_health.max(1);
+        let turns = self.turn() - progress.start_turn;
+        if lost_percent >= HEAVY_LOSS_PERCENT {
+            -1
+        } else if lost_percent <= LIGHT_LOSS_PERCENT && turns <= QUICK_TURNS {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+
+    #[test]
+    fn test_pacing() {
+        let mut world = World::from_seed(9, GenParams::default());
+        assert_eq!(world.pacing_adjustment(), 0);
+        world.options_mut().adaptive_pacing = true;
+        assert_eq!(world.pacing_adjustment(), 1);
+        let player = world.active_player();
+        world[player].health /= 3;
+        assert_eq!(world.pacing_adjustment(), -1);
+    }
+}
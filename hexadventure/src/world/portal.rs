@@ -0,0 +1,278 @@
+//! Portals, which carry whatever steps on one to its twin.
+//!
+//! Portals come in linked pairs. Generation now and then opens a pair far
+//! apart on a level, and the world links the two when the level is entered.
+//! `open_portal` opens a pair anywhere, as a spell would.
+//!
+//! Stepping onto a portal carries a mob to its twin, or beside it if the
+//! twin is taken. A portal whose twin is on the level below works like a
+//! flight of stairs that arrives wherever the twin is: a player who steps in
+//! takes the party down, and an npc that steps in escapes to wait for them
+//! there. The links are saved with the world, so one leading to a level not
+//! yet generated still leads there after loading. Levels left behind are
+//! gone, so links that touch them are forgotten.
+
+use level::tile::Terrain;
+use prelude::*;
+use std::collections::HashMap;
+use world::action;
+use world::mob::capitalize;
+
+/// One end of a pair of portals.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PortalEnd {
+    pub depth: u32,
+    pub pos: Pos,
+}
+
+/// A pair of portals, each leading to the other.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PortalLink {
+    pub ends: [PortalEnd; 2],
+}
+
+impl PortalLink {
+    /// The other end of the link, if one of its ends is `end`.
+    pub fn twin(&self, end: PortalEnd) -> Option<PortalEnd> {
+        match self.ends {
+            [a, b] if a == end => Some(b),
+            [a, b] if b == end => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a portal can be opened on terrain.
+fn holds_portal(terrain: Terrain) -> bool {
+    matches!(
+        terrain,
+        Terrain::Floor | Terrain::ShortGrass | Terrain::TallGrass | Terrain::Brownberry
+    )
+}
+
+impl World {
+    /// Every pair of portals that still leads somewhere.
+    pub fn portal_links(&self) -> &[PortalLink] {
+        &self.portals
+    }
+
+    /// Where the portal at a position on the current level leads.
+    pub fn portal_twin(&self, pos: Pos) -> Option<PortalEnd> {
+        let end = PortalEnd {
+            depth: self.depth(),
+            pos,
+        };
+        self.portals.iter().filter_map(|link| link.twin(end)).next()
+    }
+
+    /// Where each portal on the current level leads, for those that lead
+    /// elsewhere on it.
+    pub fn local_portals(&self) -> HashMap<Pos, Pos> {
+        let depth = self.depth();
+        let mut portals = HashMap::new();
+        for link in &self.portals {
+            let [a, b] = link.ends;
+            if a.depth == depth && b.depth == depth {
+                portals.insert(a.pos, b.pos);
+                portals.insert(b.pos, a.pos);
+            }
+        }
+        portals
+    }
+
+    /// Open a pair of portals, one here on the current level and its twin
+    /// there, on this level or the one below.
+    ///
+    /// Portals only open on bare floor or grass. A twin on the level below
+    /// is only where the party arrives, and never leads back up, and can't
+    /// be opened past sealed stairs. Returns whether the portal opened.
+    pub fn open_portal(&mut self, here: Pos, there: PortalEnd) -> bool {
+        let depth = self.depth();
+        let open = |pos: Pos| grid::contains(pos) && holds_portal(self.level[pos].terrain);
+        let valid = open(here)
+            && grid::contains(there.pos)
+            && if there.depth == depth {
+                there.pos != here && open(there.pos)
+            } else {
+                there.depth == depth + 1 && !self.stairs_sealed()
+            };
+        if !valid {
+            return false;
+        }
+        self.portals.push(PortalLink {
+            ends: [PortalEnd { depth, pos: here }, there],
+        });
+        for end in &[PortalEnd { depth, pos: here }, there] {
+            if end.depth == depth {
+                self.set_terrain(end.pos, Terrain::Portal);
+                self.report(end.pos, "A portal shimmers open.".to_owned());
+            }
+        }
+        true
+    }
+
+    /// Forget the links that lead to levels left behind, and link up the
+    /// portals of a level that was just entered.
+    ///
+    /// The level's generated portals are linked in pairs, in the order
+    /// they lie on the grid.
+    pub(super) fn link_portals(&mut self) {
+        let depth = self.depth();
+        self.portals
+            .retain(|link| link.ends.iter().all(|end| end.depth >= depth));
+        let unlinked: Vec<Pos> = grid::positions()
+            .filter(|&pos| self.level[pos].terrain == Terrain::Portal)
+            .filter(|&pos| self.portal_twin(pos).is_none())
+            .collect();
+        for pair in unlinked.chunks(2) {
+            if let [a, b] = *pair {
+                self.portals.push(PortalLink {
+                    ends: [PortalEnd { depth, pos: a }, PortalEnd { depth, pos: b }],
+                });
+            }
+        }
+    }
+
+    /// Where a mob going through to a portal comes out: on the portal if
+    /// it is free, or else beside it. `None` if it is hemmed in, and the
+    /// mob stays where it stepped.
+    pub(super) fn portal_landing(&self, mob_id: MobId, twin: Pos) -> Option<Pos> {
+        let facing = self[mob_id].facing;
+        Some(twin).into_iter().chain(twin.ring(1)).find(|&pos| {
+            self.fits(mob_id, pos, facing) && self.blocker(mob_id, pos, facing).is_none()
+        })
+    }
+
+    /// Carry a mob that has just stepped from `approach` onto a portal
+    /// through it.
+    ///
+    /// Fails, like taking the stairs, if a player went through to the level below.
+    pub(crate) fn pass_through_portal(&mut self, mob_id: MobId, approach: Pos) -> Result<(), ()> {
+        let pos = self[mob_id].pos;
+        if self.level[pos].terrain != Terrain::Portal {
+            return Ok(());
+        }
+        let twin = match self.portal_twin(pos) {
+            Some(twin) => twin,
+            None => return Ok(()),
+        };
+        let name = capitalize(&self.describe(mob_id));
+        if twin.depth == self.depth() {
+            if let Some(landing) = self.portal_landing(mob_id, twin.pos) {
+                self.report(pos, format!("{} steps into the portal.", name));
+                self.move_mob(mob_id, landing);
+                self.report(landing, format!("{} steps out of the portal.", name));
+            }
+            Ok(())
+        } else if mob_id.is_player() {
+            self.gather_followers(mob_id);
+            action::descend_unchecked(mob_id, twin.pos, self);
+            self.link_stairs(approach, pos, mob_id);
+            Err(())
+        } else {
+            self.report(pos, format!("{} vanishes into the portal.", name));
+            let fugitive = self.carry_off(mob_id);
+            self.fugitives.push(fugitive);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use bincode;
+    use world::action::Action;
+    use world::ai;
+    use world::mob::{self, Mob, Species};
+
+    /// A world with no npcs or portals, open floor all over, and the
+    /// player in the middle.
+    fn cleared_world(seed: u64) -> World {
+        let mut world = open_world(seed, (grid::WIDTH + grid::HEIGHT) as u32);
+        world.portals.clear();
+        let player = world.active_player();
+        world.move_mob(player, grid::center());
+        world
+    }
+
+    #[test]
+    fn test_portals_carry_players_and_npcs() {
+        let mut world = cleared_world(39);
+        let center = grid::center();
+        let here: Pos = center + Direction::East;
+        let there: Pos = center + Direction::East * 10;
+        let depth = world.depth();
+        assert!(world.open_portal(here, PortalEnd { depth, pos: there }));
+        assert!(!world.open_portal(here, PortalEnd { depth, pos: center }));
+        assert_eq!(world.level[there].terrain, Terrain::Portal);
+        assert_eq!(world.local_portals()[&there], here);
+
+        world.perform(Action::Walk(Direction::East)).unwrap();
+        assert_eq!(world.player().pos, there);
+        world.perform(Action::Walk(Direction::West)).unwrap();
+        world.perform(Action::Walk(Direction::East)).unwrap();
+        assert_eq!(world.player().pos, here);
+
+        // A skeleton near the far portal takes it as a shortcut to the player.
+        let player = world.active_player();
+        world.move_mob(player, center + Direction::West);
+        let skeleton_pos: Pos = there + Direction::East;
+        let skeleton = world.npcs.insert(Mob::new(skeleton_pos, Species::Skeleton));
+        world.level[skeleton_pos].mob_id = Some(skeleton);
+        let target = world.player().pos;
+        ai::chase(skeleton, target, &mut world).unwrap();
+        assert_eq!(world[skeleton].pos, here);
+        assert!(world.validate_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_portal_down_survives_saving() {
+        let mut world = cleared_world(40);
+        let center = grid::center();
+        let here: Pos = center + Direction::East;
+        let there = PortalEnd {
+            depth: world.depth() + 1,
+            pos: center + Direction::West * 6,
+        };
+        assert!(!world.open_portal(
+            here,
+            PortalEnd {
+                depth: world.depth() + 2,
+                pos: there.pos,
+            }
+        ));
+        assert!(world.open_portal(here, there));
+        assert_ne!(world.level[there.pos].terrain, Terrain::Portal);
+
+        let saved = bincode::serialize(&world).unwrap();
+        let mut world: World = bincode::deserialize(&saved).unwrap();
+        assert_eq!(world.portal_twin(here), Some(there));
+
+        // A thief that steps in escapes to the level below.
+        let thief_pos: Pos = here + Direction::Northeast;
+        let thief = world.npcs.insert(Mob::new(thief_pos, Species::Thief));
+        world.level[thief_pos].mob_id = Some(thief);
+        let walked = action::walk(thief, Direction::Southwest, &mut world);
+        assert!(walked.is_ok());
+        assert!(!world[thief].alive);
+
+        let result = world.perform(Action::Walk(Direction::East));
+        assert!(result.is_err());
+        assert_eq!(world.depth(), there.depth);
+        assert!(world.player().pos.distance(there.pos) <= 1);
+        assert_eq!(world.stair_links().last().unwrap().stairs, here);
+        assert!(world.portal_links().is_empty());
+        // The thief waits a little way from where the party arrived.
+        let mut waiting = false;
+        mob::for_each(&world, |mob_id| {
+            let mob = &world[mob_id];
+            if mob.alive && mob.species == Species::Thief && mob.pos.distance(there.pos) <= 8 {
+                waiting = true;
+            }
+        });
+        assert!(waiting);
+    }
+}
@@ -0,0 +1,213 @@
+//! Predicting the consequences of an action without taking it.
+
+use level::tile::Terrain;
+use prelude::*;
+use world::action::{damage_range, Action};
+use world::door::DOOR_STRENGTH;
+use world::lamp::{LightSource, FLASK_OIL};
+use world::mob::DoorSkill;
+
+/// What an action is expected to do.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Prediction {
+    pub outcome: Outcome,
+    /// Every tile the action changes.
+    pub tiles_affected: Vec<Pos>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Outcome {
+    /// The action can't be taken.
+    Blocked,
+    /// Time passes and nothing else happens.
+    Wait,
+    /// The mob moves to a tile.
+    Move { to: Pos },
+    /// The mob creeps towards a tile, and reaches it if it keeps sneaking.
+    Creep { to: Pos },
+    /// The mob is caught in a web and tries to break free.
+    Struggle {
+        /// The percent chance of breaking free.
+        escape_chance: u32,
+    },
+    /// The mob steps back and regains some guard.
+    Retreat { to: Pos, guard_regained: u32 },
+    /// The mob attacks another.
+    Attack {
+        target: MobId,
+        /// The least and most guard the target loses.
+        guard_damage: (u32, u32),
+        /// The least and most health the target loses.
+        health_damage: (u32, u32),
+        /// Whether the attack might kill the target.
+        may_kill: bool,
+    },
+    /// The mob opens a closed door in its way.
+    Open { door: Pos },
+    /// The mob smashes at a closed door in its way.
+    Batter {
+        door: Pos,
+        /// How many more blows the door takes before it breaks, after this one.
+        blows_left: u32,
+    },
+    /// The player takes the stairs, or a portal, down.
+    Descend,
+}
+
+impl World {
+    /// Predict what an action by the active player would do.
+    ///
+    /// The world is only read, never changed.
+    pub fn predict(&self, action: Action) -> Prediction {
+        let mob_id = self.active_player();
+        match self.pace(action) {
+            Action::Rest => Prediction::new(Outcome::Wait, vec![]),
+            Action::Refuel => {
+                let mob = &self[mob_id];
+                let refillable = match mob.light {
+                    Some(LightSource::Lantern { oil }) => oil < FLASK_OIL && mob.oil_flasks > 0,
+                    _ => false,
+                };
+                if refillable {
+                    Prediction::new(Outcome::Wait, vec![])
+                } else {
+                    Prediction::new(Outcome::Blocked, vec![])
+                }
+            }
+            Action::Engrave(_) => {
+                if self.can_engrave(mob_id) {
+                    Prediction::new(Outcome::Wait, vec![])
+                } else {
+                    Prediction::new(Outcome::Blocked, vec![])
+                }
+            }
+            Action::Stance(stance) => {
+                if self[mob_id].stance != stance {
+                    Prediction::new(Outcome::Wait, vec![])
+                } else {
+                    Prediction::new(Outcome::Blocked, vec![])
+                }
+            }
+            Action::Walk(direction) => self.predict_walk(mob_id, direction),
+            Action::Sneak(direction) => {
+                let prediction = self.predict_walk(mob_id, direction);
+                match prediction.outcome {
+                    Outcome::Move { to } | Outcome::Retreat { to, .. }
+                        if self[mob_id].creeping != Some(direction) =>
+                    {
+                        Prediction::new(Outcome::Creep { to }, vec![])
+                    }
+                    _ => prediction,
+                }
+            }
+        }
+    }
+
+    fn predict_walk(&self, mob_id: MobId, direction: Direction) -> Prediction {
+        let mob = &self[mob_id];
+        let target_pos = mob.pos + direction;
+        let tile = self.level[target_pos];
+        if let Some(target) = tile.mob_id {
+            if mob_id.is_player() == target.is_player() {
+                return Prediction::new(Outcome::Blocked, vec![]);
+            }
+            let (min, max) = damage_range();
+            let min = if self.hit_chance(mob_id) < 100 {
+                0
+            } else {
+                self.modified_damage(mob_id, target, min)
+            };
+            let max = self.modified_damage(mob_id, target, max);
+            let guard = self[target].guard;
+            let health = self[target].health;
+            let outcome = Outcome::Attack {
+                target,
+                guard_damage: (min.min(guard), max.min(guard)),
+                health_damage: (
+                    min.saturating_sub(guard).min(health),
+                    max.saturating_sub(guard).min(health),
+                ),
+                may_kill: max.saturating_sub(guard) >= health,
+            };
+            Prediction::new(outcome, vec![target_pos])
+        } else if mob.entangled {
+            let outcome = Outcome::Struggle {
+                escape_chance: mob.escape_chance(),
+            };
+            Prediction::new(outcome, vec![mob.pos])
+        } else if tile.terrain.passable() {
+            let mut to = target_pos;
+            if tile.terrain == Terrain::Portal {
+                match self.portal_twin(target_pos) {
+                    Some(twin) if twin.depth == self.depth() => {
+                        to = self.portal_landing(mob_id, twin.pos).unwrap_or(target_pos);
+                    }
+                    Some(_) if mob_id.is_player() => {
+                        return Prediction::new(Outcome::Descend, vec![]);
+                    }
+                    _ => {}
+                }
+            }
+            let outcome = if mob.backs_away(direction) {
+                Outcome::Retreat {
+                    to,
+                    guard_regained: mob.guard_recovery,
+                }
+            } else {
+                Outcome::Move { to }
+            };
+            Prediction::new(outcome, vec![mob.pos, target_pos])
+        } else if let Some(door) = self.door_in_way(mob_id, target_pos, direction) {
+            let outcome = match mob.species.door_skill() {
+                DoorSkill::Open => Outcome::Open { door },
+                DoorSkill::Smash => Outcome::Batter {
+                    door,
+                    blows_left: DOOR_STRENGTH - 1 - self.level[door].battered,
+                },
+                DoorSkill::Blocked => Outcome::Blocked,
+            };
+            Prediction::new(outcome, vec![door])
+        } else if tile.terrain == Terrain::Exit && mob_id.is_player() && !self.stairs_sealed() {
+            Prediction::new(Outcome::Descend, vec![])
+        } else {
+            Prediction::new(Outcome::Blocked, vec![])
+        }
+    }
+}
+
+impl Prediction {
+    fn new(outcome: Outcome, tiles_affected: Vec<Pos>) -> Self {
+        Prediction {
+            outcome,
+            tiles_affected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid::DIRECTIONS;
+    use level::GenParams;
+
+    #[test]
+    fn test_prediction_matches_walk() {
+        let mut world = World::from_seed(21, GenParams::default());
+        for i in 0..30 {
+            let action = Action::Walk(DIRECTIONS[(i * 5) % 6]);
+            let hash = world.state_hash();
+            let prediction = world.predict(action);
+            assert_eq!(hash, world.state_hash());
+            let result = world.perform_confirmed(action);
+            match prediction.outcome {
+                Outcome::Blocked | Outcome::Descend => assert!(result.is_err()),
+                Outcome::Move { to } | Outcome::Retreat { to, .. } => {
+                    assert!(result.is_ok());
+                    assert_eq!(world.player().pos, to);
+                }
+                _ => assert!(result.is_ok()),
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Showing the player where travelling would take them.
+//!
+//! Previews use the same path as `World::travel_to`, so they never disagree with it.
+
+use prelude::*;
+
+/// The path travelling to a destination would take.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PathPreview {
+    /// Every step after the player's own position, ending at the destination.
+    pub steps: Vec<PreviewStep>,
+    /// How many turns the whole path takes.
+    pub total_cost: u32,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PreviewStep {
+    pub pos: Pos,
+    /// How many turns this step takes.
+    pub cost: u32,
+    pub hazards: Vec<Hazard>,
+}
+
+/// Something the player should know about a step before taking it.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Hazard {
+    /// A visible hostile is next to the step.
+    HostileAdjacent(Pos),
+}
+
+impl World {
+    /// Preview the path the active player would travel to reach a destination.
+    ///
+    /// `None` if the player doesn't know a way there.
+    pub fn preview_path(&self, destination: Pos) -> Option<PathPreview> {
+        let path = self.travel_path(destination)?;
+        let hostiles = self.visible_hostiles();
+        let steps: Vec<PreviewStep> = path
+            .windows(2)
+            .map(|pair| {
+                let pos = pair[1];
+                let hazards = hostiles
+                    .iter()
+                    .filter(|hostile| hostile.distance(pos) == 1)
+                    .map(|&hostile| Hazard::HostileAdjacent(hostile))
+                    .collect();
+                PreviewStep {
+                    pos,
+                    cost: pair[0].distance(pos),
+                    hazards,
+                }
+            })
+            .collect();
+        let total_cost = steps.iter().map(|step| step.cost).sum();
+        Some(PathPreview { steps, total_cost })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::GenParams;
+    use world::auto::Stop;
+
+    #[test]
+    fn test_preview_matches_travel() {
+        let mut world = World::from_seed(6, GenParams::default());
+        remove_npcs(&mut world);
+        let start = world.player().pos;
+        let destination = grid::positions()
+            .filter(|&pos| world.is_visible(pos) && world.level[pos].terrain.passable())
+            .max_by_key(|&pos| pos.distance(start))
+            .unwrap();
+        let preview = world.preview_path(destination).unwrap();
+        assert_eq!(preview.steps.last().unwrap().pos, destination);
+        assert!(preview.steps.iter().all(|step| step.hazards.is_empty()));
+
+        assert_eq!(world.travel_to(destination), Stop::Done);
+        assert_eq!(world.player().pos, destination);
+        assert_eq!(world.turn(), u64::from(preview.total_cost));
+    }
+}
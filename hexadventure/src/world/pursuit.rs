@@ -0,0 +1,126 @@
+//! Hostiles that chase the party down the stairs.
+//!
+//! Npcs standing next to a player who takes the stairs follow them down,
+//! turning up right beside the stairs on the next level. A party that keeps
+//! fleeing down the stairs with hostiles on its heels arrives winded.
+
+use prelude::*;
+use std::mem::take;
+use world::mob::Size;
+
+/// How many flights in a row with hostiles following leave the party winded.
+pub const WINDED_FLIGHTS: u32 = 2;
+
+impl World {
+    /// Take the npcs next to a player off the level, to follow them down the stairs.
+    ///
+    /// Huge mobs don't fit down the stairs, and minions don't leave their master.
+    pub(super) fn gather_followers(&mut self, leader: MobId) {
+        let mut followers = Vec::new();
+        for pos in self[leader].pos.neighbors() {
+            if let Some(mob_id) = self.level[pos].mob_id {
+                let mob = &self[mob_id];
+                let eligible = !mob_id.is_player()
+                    && mob.alive
+                    && !mob.entangled
+                    && mob.master.is_none()
+                    && mob.species.size() == Size::Small;
+                if eligible && !followers.contains(&mob_id) {
+                    followers.push(mob_id);
+                }
+            }
+        }
+        for mob_id in followers {
+            let follower = self.carry_off(mob_id);
+            self.followers.push(follower);
+        }
+    }
+
+    /// Put the npcs that followed the party on this level, as close to the stairs as they fit.
+    ///
+    /// Winds the party if this is another flight in a row with hostiles following.
+    pub(super) fn place_followers(&mut self, center: Pos) {
+        let followers = take(&mut self.followers);
+        if followers.is_empty() {
+            self.flights = 0;
+            return;
+        }
+        let count = followers.len();
+        for follower in followers {
+            let mob_id = self.npcs.insert(follower);
+            self.arrive(mob_id, center);
+        }
+        let message = if count == 1 {
+            "An enemy follows you down the stairs!".to_owned()
+        } else {
+            format!("{} enemies follow you down the stairs!", count)
+        };
+        self.report(center, message);
+        self.flights += 1;
+        if self.flights >= WINDED_FLIGHTS {
+            for player in &mut self.players {
+                player.guard = 0;
+            }
+            self.report(center, "You arrive winded from the chase.".to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::GenParams;
+    use world::action::Action;
+    use world::mob::{self, Species};
+
+    /// Put the active player next to the stairs down, with a skeleton beside them.
+    fn chased_to_stairs(world: &mut World) -> (Direction, MobId) {
+        remove_npcs(world);
+        let exit = world.exit().unwrap();
+        let stand = exit
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain.passable())
+            .unwrap();
+        let player = world.active_player();
+        world.move_mob(player, stand);
+        let beside = stand
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain.passable() && world.level[pos].mob_id.is_none())
+            .unwrap();
+        let skeleton = world.spawn(Species::Skeleton, beside).unwrap();
+        ((exit - stand).direction(), skeleton)
+    }
+
+    #[test]
+    fn test_hostile_follows_down_stairs() {
+        let mut world = World::from_seed(24, GenParams::default());
+        let (direction, skeleton) = chased_to_stairs(&mut world);
+        world[skeleton].health = 37;
+        let _ = world.perform_confirmed(Action::Walk(direction));
+        assert_eq!(world.depth(), 2);
+        let arrival = world.player().pos;
+        let mut follower = None;
+        mob::for_each(&world, |mob_id| {
+            if world[mob_id].alive && world[mob_id].health == 37 {
+                follower = Some(mob_id);
+            }
+        });
+        let follower = follower.unwrap();
+        assert_eq!(world[follower].species, Species::Skeleton);
+        assert!(world[follower].pos.distance(arrival) <= 2);
+        assert!(world.player().guard > 0);
+    }
+
+    #[test]
+    fn test_repeated_flight_winds_party() {
+        let mut world = World::from_seed(24, GenParams::default());
+        let (direction, _) = chased_to_stairs(&mut world);
+        let _ = world.perform_confirmed(Action::Walk(direction));
+        let (direction, _) = chased_to_stairs(&mut world);
+        let _ = world.perform_confirmed(Action::Walk(direction));
+        assert_eq!(world.depth(), 3);
+        assert_eq!(world.player().guard, 0);
+    }
+}
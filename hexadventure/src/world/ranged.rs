@@ -0,0 +1,123 @@
+//! Shooting, and the lines shots fly along.
+//!
+//! A shot flies in a straight line and stops at the first wall, patch of
+//! tall grass or mob in its way. Mobs that shoot keep their distance: they
+//! back away from targets that get close, and move to get a clear shot
+//! when something is in the way.
+
+use prelude::*;
+use world::action;
+
+impl World {
+    /// The first tile between two positions that would stop a shot, if any.
+    ///
+    /// Neither end is checked, so a shot can leave its shooter and reach its target.
+    pub fn projectile_blocker(&self, from: Pos, to: Pos) -> Option<Pos> {
+        if from.distance(to) <= 1 {
+            return None;
+        }
+        grid::line(from, to)
+            .skip(1)
+            .take_while(|&pos| pos != to)
+            .find(|&pos| {
+                !grid::contains(pos)
+                    || !self.level[pos].terrain.transparent()
+                    || self.level[pos].mob_id.is_some()
+            })
+    }
+
+    /// Whether a mob could shoot a tile from where it stands.
+    pub fn can_shoot(&self, mob_id: MobId, target: Pos) -> bool {
+        let mob_pos = self[mob_id].pos;
+        match self[mob_id].species.range() {
+            Some(range) => {
+                mob_pos != target
+                    && mob_pos.distance(target) <= range
+                    && self.projectile_blocker(mob_pos, target).is_none()
+            }
+            None => false,
+        }
+    }
+}
+
+/// Shoot a hostile mob at a tile.
+pub fn shoot(mob_id: MobId, target_pos: Pos, world: &mut World) -> Result<(), ()> {
+    if !world.can_shoot(mob_id, target_pos) {
+        return Err(());
+    }
+    match world.level[target_pos].mob_id {
+        Some(target) if mob_id.is_player() != target.is_player() => {
+            action::strike(mob_id, target, target_pos, "shoots", world);
+            Ok(())
+        }
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use level::tile::Terrain;
+    use world::ai;
+    use world::mob::Species;
+
+    #[test]
+    fn test_shots_stopped_by_walls_and_mobs() {
+        let mut world = open_world(21, 8);
+        let center = world.player().pos;
+        let far = center + Direction::East * 4;
+        assert_eq!(world.projectile_blocker(center, far), None);
+        assert_eq!(world.projectile_blocker(center, center), None);
+        let wall: Pos = center + Direction::East * 2;
+        world.level[wall].terrain = Terrain::Wall;
+        assert_eq!(world.projectile_blocker(center, far), Some(wall));
+        world.level[wall].terrain = Terrain::Floor;
+        world.spawn(Species::Skeleton, center + Direction::East * 3);
+        assert_eq!(
+            world.projectile_blocker(center, far),
+            Some(center + Direction::East * 3)
+        );
+    }
+
+    #[test]
+    fn test_archer_shoots_from_range() {
+        let mut world = open_world(21, 8);
+        let player = world.active_player();
+        let center = world[player].pos;
+        let archer = world
+            .spawn(Species::Archer, center + Direction::East * 4)
+            .unwrap();
+        ai::act(archer, &mut world).unwrap();
+        assert_eq!(world[archer].pos, center + Direction::East * 4);
+        assert!(world[player].guard < world[player].max_guard);
+    }
+
+    #[test]
+    fn test_archer_backs_away() {
+        let mut world = open_world(21, 8);
+        let player = world.active_player();
+        let center = world[player].pos;
+        let archer = world
+            .spawn(Species::Archer, center + Direction::East)
+            .unwrap();
+        ai::act(archer, &mut world).unwrap();
+        assert_eq!(world[archer].pos.distance(center), 2);
+        assert_eq!(world[player].guard, world[player].max_guard);
+    }
+
+    #[test]
+    fn test_archer_moves_around_ally() {
+        let mut world = open_world(21, 8);
+        let player = world.active_player();
+        let center = world[player].pos;
+        world.spawn(Species::Skeleton, center + Direction::East * 2);
+        let archer = world
+            .spawn(Species::Archer, center + Direction::East * 4)
+            .unwrap();
+        ai::act(archer, &mut world).unwrap();
+        assert_ne!(world[archer].pos, center + Direction::East * 4);
+        assert_eq!(world[player].guard, world[player].max_guard);
+    }
+}
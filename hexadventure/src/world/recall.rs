@@ -0,0 +1,156 @@
+//! Remembering where things were seen, across every level visited.
+//!
+//! Whenever the players see a tile, whatever notable is on it replaces what
+//! was remembered there. Tiles of levels left behind keep what was last
+//! seen on them, so a player can look up where they left loot they couldn't
+//! carry. Landmarks can be travelled to by the stairs the players know.
+
+use level::tile::Terrain;
+use loot::{Loot, Tier};
+use prelude::*;
+use std::mem::take;
+use world::auto::Stop;
+use world::engrave::Inscription;
+
+/// Something notable a player saw.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Landmark {
+    Loot(Loot),
+    StairsDown,
+    StairsUp,
+    Engraving(Inscription),
+}
+
+/// A landmark and where it was last seen.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Sighting {
+    pub depth: u32,
+    pub pos: Pos,
+    pub landmark: Landmark,
+}
+
+impl World {
+    /// Every remembered landmark that matches, shallowest level first.
+    pub fn recall<F: Fn(&Landmark) -> bool>(&self, matches: F) -> Vec<Sighting> {
+        let mut sightings: Vec<Sighting> = self
+            .sightings
+            .iter()
+            .filter(|sighting| matches(&sighting.landmark))
+            .cloned()
+            .collect();
+        sightings.sort_by_key(|sighting| sighting.depth);
+        sightings
+    }
+
+    /// Every remembered pile of loot with an item of at least a tier.
+    pub fn recall_items(&self, min_tier: Tier) -> Vec<Sighting> {
+        self.recall(|landmark| match landmark {
+            Landmark::Loot(loot) => loot.item.is_some_and(|tier| tier >= min_tier),
+            _ => false,
+        })
+    }
+
+    /// The stairs down that were seen but not taken.
+    ///
+    /// Taking the stairs down leaves the level for good, so only those on
+    /// the current level can still lead somewhere new.
+    pub fn recall_unexplored_stairs(&self) -> Vec<Sighting> {
+        let depth = self.depth();
+        self.recall(|landmark| *landmark == Landmark::StairsDown)
+            .into_iter()
+            .filter(|sighting| sighting.depth == depth)
+            .collect()
+    }
+
+    /// Travel to a remembered landmark, across levels if need be.
+    ///
+    /// Stops as `Blocked` if no known stairs lead to its level.
+    pub fn travel_to_sighting(&mut self, sighting: &Sighting) -> Stop {
+        self.travel_route(sighting.depth, sighting.pos)
+    }
+
+    /// Remember what is on every tile the players can see, forgetting
+    /// whatever used to be there.
+    pub(super) fn remember_landmarks(&mut self) {
+        let depth = self.depth();
+        let mut sightings = take(&mut self.sightings);
+        sightings.retain(|sighting| sighting.depth != depth || !self.is_visible(sighting.pos));
+        for pos in grid::positions().filter(|&pos| self.is_visible(pos)) {
+            if let Some(landmark) = self.landmark(pos) {
+                sightings.push(Sighting {
+                    depth,
+                    pos,
+                    landmark,
+                });
+            }
+        }
+        self.sightings = sightings;
+    }
+
+    fn landmark(&self, pos: Pos) -> Option<Landmark> {
+        let tile = &self.level[pos];
+        if let Some(loot) = tile.loot {
+            return Some(Landmark::Loot(loot));
+        }
+        if let Some(inscription) = self.engraving(pos) {
+            return Some(Landmark::Engraving(inscription));
+        }
+        match tile.terrain {
+            Terrain::Exit => Some(Landmark::StairsDown),
+            Terrain::Entrance => Some(Landmark::StairsUp),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::GenParams;
+    use world::action::Action;
+
+    #[test]
+    fn test_loot_remembered_on_levels_left_behind() {
+        let mut world = World::from_seed(30, GenParams::default());
+        remove_npcs(&mut world);
+        let player = world.player().pos;
+        let pile = player
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain.passable())
+            .unwrap();
+        let treasure = Loot {
+            gold: 4,
+            item: Some(Tier::Rare),
+        };
+        world.level[pile].loot = Some(treasure);
+        let _ = world.perform_confirmed(Action::Rest);
+        let expected = Sighting {
+            depth: 1,
+            pos: pile,
+            landmark: Landmark::Loot(treasure),
+        };
+        assert_eq!(world.recall_items(Tier::Uncommon), vec![expected]);
+        assert!(world.recall_items(Tier::Artifact).is_empty());
+
+        let exit = world.exit().unwrap();
+        let beside = exit
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain.passable() && world.level[pos].mob_id.is_none())
+            .unwrap();
+        let active = world.active_player();
+        world.move_mob(active, beside);
+        world.update_fov();
+        assert_eq!(world.recall_unexplored_stairs().len(), 1);
+        let _ = world.perform_confirmed(Action::Walk((exit - beside).direction()));
+        assert_eq!(world.depth(), 2);
+
+        assert_eq!(world.recall_items(Tier::Uncommon), vec![expected]);
+        assert!(world
+            .recall_unexplored_stairs()
+            .iter()
+            .all(|sighting| sighting.depth == 2));
+        assert_eq!(world.travel_to_sighting(&expected), Stop::Blocked);
+    }
+}
@@ -0,0 +1,275 @@
+//! What killed a player, and how.
+//!
+//! The world keeps a short rolling history: every blow struck at a player,
+//! and each living player's guard and health at the end of every turn, both
+//! for the last `RECAP_TURNS` turns. When a player dies, that history
+//! becomes a `DeathRecap` of the killing blow, the damage that led up to it,
+//! the statuses they died under and how their health fell. The morgue
+//! includes every recap.
+
+use prelude::*;
+use world::mob::capitalize;
+use world::modifier::Stance;
+
+/// How many of the latest turns the history, and so each recap, covers.
+pub const RECAP_TURNS: u64 = 10;
+
+/// A blow struck at a player.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct Blow {
+    pub turn: u64,
+    /// The index of the player struck.
+    pub player: usize,
+    pub attacker: String,
+    /// How the blow was struck, e.g. "hits".
+    pub verb: String,
+    pub damage: u32,
+    pub guard_lost: u32,
+    pub health_lost: u32,
+}
+
+impl Blow {
+    /// A line describing the blow, for reading.
+    pub fn describe(&self) -> String {
+        format!(
+            "Turn {}: {} {} for {} ({} guard, {} health)",
+            self.turn,
+            capitalize(&self.attacker),
+            self.verb,
+            self.damage,
+            self.guard_lost,
+            self.health_lost
+        )
+    }
+}
+
+/// A player's guard and health at the end of a turn.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Vitals {
+    pub turn: u64,
+    /// The index of the player.
+    pub player: usize,
+    pub guard: u32,
+    pub health: u32,
+}
+
+/// How a player died.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct DeathRecap {
+    /// The index of the player who died.
+    pub player: usize,
+    pub turn: u64,
+    pub depth: u32,
+    /// The blow that took the last of their health, if a blow did.
+    pub killing_blow: Option<Blow>,
+    /// Every blow struck at them over the last turns, oldest first, the
+    /// killing blow included.
+    pub blows: Vec<Blow>,
+    /// What they were suffering from or doing when they died, e.g. "blind for 3 turns".
+    pub statuses: Vec<String>,
+    /// Their guard and health at the end of each of the last turns, oldest
+    /// first, ending with the moment they died.
+    pub timeline: Vec<Vitals>,
+}
+
+impl DeathRecap {
+    /// The recap as lines, for reading.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let cause = match &self.killing_blow {
+            Some(blow) => format!("killed by {}", blow.attacker),
+            None => "died".to_owned(),
+        };
+        lines.push(format!(
+            "Player {} {} on level {}, turn {}.",
+            self.player + 1,
+            cause,
+            self.depth,
+            self.turn
+        ));
+        if !self.statuses.is_empty() {
+            lines.push(format!("Statuses: {}.", self.statuses.join(", ")));
+        }
+        if !self.blows.is_empty() {
+            let total: u32 = self.blows.iter().map(|blow| blow.damage).sum();
+            lines.push(format!(
+                "Took {} damage over the last {} turns:",
+                total, RECAP_TURNS
+            ));
+            for blow in &self.blows {
+                lines.push(format!("  {}", blow.describe()));
+            }
+        }
+        let health: Vec<String> = self
+            .timeline
+            .iter()
+            .map(|vitals| format!("{}/{}", vitals.guard, vitals.health))
+            .collect();
+        lines.push(format!("Guard/health by turn: {}", health.join(" ")));
+        lines
+    }
+}
+
+impl World {
+    /// How each player who has died so far died, in the order they died.
+    pub fn death_recaps(&self) -> &[DeathRecap] {
+        &self.death_recaps
+    }
+
+    /// Remember a blow about to be dealt, if it is struck at a player.
+    pub(crate) fn record_blow(&mut self, attacker: MobId, target: MobId, verb: &str, damage: u32) {
+        let player = match target.player_index() {
+            Some(player) => player,
+            None => return,
+        };
+        let guard_lost = damage.min(self[target].guard);
+        let health_lost = (damage - guard_lost).min(self[target].health);
+        let blow = Blow {
+            turn: self.turn,
+            player,
+            attacker: self.describe(attacker),
+            verb: verb.to_owned(),
+            damage,
+            guard_lost,
+            health_lost,
+        };
+        self.recent_blows.push(blow);
+    }
+
+    /// Remember the guard and health of every living player, and forget
+    /// what happened too long ago to be recapped.
+    pub(super) fn record_vitals(&mut self) {
+        let turn = self.turn;
+        for (player, mob) in self.players.iter().enumerate() {
+            if mob.alive {
+                self.vitals.push(Vitals {
+                    turn,
+                    player,
+                    guard: mob.guard,
+                    health: mob.health,
+                });
+            }
+        }
+        let oldest = (turn + 1).saturating_sub(RECAP_TURNS);
+        self.recent_blows.retain(|blow| blow.turn >= oldest);
+        self.vitals.retain(|vitals| vitals.turn >= oldest);
+    }
+
+    /// Recap how a player who has just died came to die.
+    pub(super) fn recap_death(&mut self, mob_id: MobId) {
+        let player = match mob_id.player_index() {
+            Some(player) => player,
+            None => return,
+        };
+        let oldest = (self.turn + 1).saturating_sub(RECAP_TURNS);
+        let blows: Vec<Blow> = self
+            .recent_blows
+            .iter()
+            .filter(|blow| blow.player == player && blow.turn >= oldest)
+            .cloned()
+            .collect();
+        let killing_blow = blows
+            .last()
+            .filter(|blow| blow.turn == self.turn && blow.health_lost > 0)
+            .cloned();
+        let mut timeline: Vec<Vitals> = self
+            .vitals
+            .iter()
+            .filter(|vitals| vitals.player == player && vitals.turn >= oldest)
+            .cloned()
+            .collect();
+        timeline.push(Vitals {
+            turn: self.turn,
+            player,
+            guard: self[mob_id].guard,
+            health: self[mob_id].health,
+        });
+        let recap = DeathRecap {
+            player,
+            turn: self.turn,
+            depth: self.depth(),
+            killing_blow,
+            blows,
+            statuses: self.statuses(mob_id),
+            timeline,
+        };
+        self.death_recaps.push(recap);
+    }
+
+    /// What a mob is suffering from or doing, in words.
+    fn statuses(&self, mob_id: MobId) -> Vec<String> {
+        let mob = &self[mob_id];
+        let mut statuses = Vec::new();
+        if mob.blind > 0 {
+            statuses.push(format!("blind for {} turns", mob.blind));
+        }
+        if mob.deaf > 0 {
+            statuses.push(format!("deaf for {} turns", mob.deaf));
+        }
+        if mob.telepathic > 0 {
+            statuses.push(format!("telepathic for {} turns", mob.telepathic));
+        }
+        if mob.hallucinating > 0 {
+            statuses.push(format!("hallucinating for {} turns", mob.hallucinating));
+        }
+        if mob.entangled {
+            statuses.push("caught in a web".to_owned());
+        }
+        if mob.stance != Stance::Normal {
+            statuses.push(format!("in a {} stance", mob.stance.name()));
+        }
+        match mob.light {
+            Some(light) if light.is_lit() => {}
+            _ => statuses.push("in the dark".to_owned()),
+        }
+        statuses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::action::Action;
+    use world::mob::{Mob, Species};
+
+    #[test]
+    fn test_death_recapped() {
+        let mut world = World::from_seed(36, GenParams::default());
+        remove_npcs(&mut world);
+        let player = world.active_player();
+        let center = world[player].pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 3) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let skeleton_pos: Pos = center + Direction::East;
+        let skeleton = world.npcs.insert(Mob::new(skeleton_pos, Species::Skeleton));
+        world.level[skeleton_pos].mob_id = Some(skeleton);
+        world[player].entangled = true;
+        world.update_fov();
+
+        let mut turns = 0;
+        while !world.is_over() {
+            world.perform(Action::Rest).unwrap();
+            turns += 1;
+            assert!(turns < 1000, "The skeleton never killed the player.");
+        }
+        let recap = &world.death_recaps()[0];
+        assert_eq!(recap.player, 0);
+        let killing_blow = recap.killing_blow.as_ref().unwrap();
+        assert_eq!(killing_blow.attacker, world.describe(skeleton));
+        assert_eq!(recap.blows.last(), Some(killing_blow));
+        assert!(recap
+            .blows
+            .iter()
+            .all(|blow| blow.turn + RECAP_TURNS > recap.turn));
+        assert!(recap.statuses.iter().any(|status| status.contains("web")));
+        let last = recap.timeline.last().unwrap();
+        assert_eq!((last.turn, last.health), (recap.turn, 0));
+        assert!(recap.timeline.len() > 1);
+        assert!(world.morgue().contains("killed by"));
+    }
+}
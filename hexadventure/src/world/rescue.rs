@@ -0,0 +1,107 @@
+//! Noticing when a player is sealed away from the stairs, and digging them out.
+//!
+//! A sealed player is rescued by a slow collapse, which opens one tile
+//! toward the stairs every `COLLAPSE_TURNS` until the way is clear.
+
+use floodfill::{self, Regions};
+use level::tile::Terrain;
+use prelude::*;
+use std::collections::HashSet;
+use world::events::WorldEvent;
+
+/// How many turns pass between each tile a collapse opens.
+pub const COLLAPSE_TURNS: u64 = 20;
+
+impl World {
+    /// Every position a player can walk to, ignoring mobs in the way.
+    pub fn reachable_from(&self, pos: Pos) -> HashSet<Pos> {
+        floodfill::reachable_from(pos, |pos| walkable(self.level[pos].terrain))
+    }
+
+    /// A living player who can't reach the stairs down, if there is one.
+    pub fn sealed_player(&self) -> Option<MobId> {
+        let regions = Regions::new(|pos| walkable(self.level[pos].terrain));
+        let exits: Vec<Pos> = grid::positions()
+            .filter(|&pos| self.level[pos].terrain == Terrain::Exit)
+            .collect();
+        (0..self.players().len()).map(MobId::player).find(|&id| {
+            self[id].alive
+                && !exits
+                    .iter()
+                    .any(|&exit| regions.connected(self[id].pos, exit))
+        })
+    }
+
+    /// Start a collapse if a player is sealed in and one isn't already coming.
+    pub(super) fn check_sealed(&mut self) {
+        let depth = self.depth();
+        let collapse = WorldEvent::Collapse { depth };
+        let pending = self
+            .scheduled_events()
+            .iter()
+            .any(|scheduled| scheduled.event == collapse);
+        if !pending && self.sealed_player().is_some() {
+            let turn = self.turn() + COLLAPSE_TURNS;
+            self.schedule(turn, collapse);
+        }
+    }
+
+    /// Open the first tile blocking the way from a sealed player to the stairs.
+    pub(super) fn collapse(&mut self) {
+        let player = match self.sealed_player() {
+            Some(player) => player,
+            None => return,
+        };
+        let exit = match grid::positions().find(|&pos| self.level[pos].terrain == Terrain::Exit) {
+            Some(exit) => exit,
+            None => return,
+        };
+        let reachable = self.reachable_from(self[player].pos);
+        let closest = grid::positions()
+            .filter(|pos| reachable.contains(pos))
+            .min_by_key(|&pos| pos.distance(exit));
+        let closest = match closest {
+            Some(closest) => closest,
+            None => return,
+        };
+        let blocked = closest
+            .to(exit)
+            .into_iter()
+            .find(|&pos| !walkable(self.level[pos].terrain));
+        if let Some(pos) = blocked {
+            self.set_terrain(pos, Terrain::Floor);
+            self.report(pos, "The rock gives way with a groan.".to_owned());
+        }
+    }
+}
+
+fn walkable(terrain: Terrain) -> bool {
+    terrain.passable_once_opened() || terrain == Terrain::Exit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::GenParams;
+
+    #[test]
+    fn test_sealed_player_dug_out() {
+        let mut world = World::from_seed(8, GenParams::default());
+        assert_eq!(world.sealed_player(), None);
+        let pos = world.player().pos;
+        for pos in pos.neighbors() {
+            world.level[pos].terrain = Terrain::Wall;
+        }
+        let player = world.active_player();
+        assert_eq!(world.sealed_player(), Some(player));
+        world.check_sealed();
+        for _ in 0..grid::WIDTH * grid::HEIGHT {
+            if world.sealed_player().is_none() {
+                break;
+            }
+            world.collapse();
+        }
+        assert_eq!(world.sealed_player(), None);
+    }
+}
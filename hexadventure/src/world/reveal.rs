@@ -0,0 +1,185 @@
+//! Magic that shows the players parts of the level they haven't seen.
+//!
+//! Magic mapping writes the terrain around a point, or of the whole level,
+//! into a player's memory as mapped: known, but never seen. Mapped tiles
+//! are kept apart from remembered ones, so frontends can draw them
+//! differently and the knowledge can be forgotten on its own, and they
+//! become remembered like any other tile once seen. Clairvoyance instead
+//! lets a player see around a distant point for a while, as if they stood
+//! there.
+
+use fov::calc_fov;
+use level::tile::TileView;
+use prelude::*;
+use world::see;
+
+/// How far a player sees around the point of a clairvoyant vision.
+pub const CLAIRVOYANCE_RADIUS: u32 = 4;
+
+/// A player's sight of a distant point.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Vision {
+    player: usize,
+    pub center: Pos,
+    pub turns_left: u32,
+}
+
+impl World {
+    /// Map the terrain within a radius of a point, or the whole level
+    /// without one, into a player's memory.
+    ///
+    /// Tiles the player has seen are left as they are.
+    pub fn magic_map(&mut self, mob_id: MobId, center: Pos, radius: Option<u32>) {
+        let index = match mob_id.player_index() {
+            Some(index) => index,
+            None => return,
+        };
+        let fov_index = self.fov_index(index);
+        let level = &self.level;
+        let fov = &mut self.fovs[fov_index];
+        for pos in grid::positions() {
+            if radius.is_some_and(|radius| pos.distance(center) > radius) {
+                continue;
+            }
+            if let TileView::None | TileView::Mapped(_) = fov[pos] {
+                fov[pos] = TileView::Mapped(level[pos].terrain);
+            }
+        }
+        self.knowledge_revision += 1;
+    }
+
+    /// Forget every tile a player knows only from magic mapping.
+    pub fn forget_mapped(&mut self, mob_id: MobId) {
+        let index = match mob_id.player_index() {
+            Some(index) => index,
+            None => return,
+        };
+        let fov_index = self.fov_index(index);
+        let fov = &mut self.fovs[fov_index];
+        for pos in grid::positions() {
+            if let TileView::Mapped(_) = fov[pos] {
+                fov[pos] = TileView::None;
+            }
+        }
+        self.knowledge_revision += 1;
+    }
+
+    /// Let a player see around a distant point for some turns.
+    pub fn clairvoyance(&mut self, mob_id: MobId, center: Pos, turns: u32) {
+        let player = match mob_id.player_index() {
+            Some(index) => index,
+            None => return,
+        };
+        self.visions.push(Vision {
+            player,
+            center,
+            turns_left: turns,
+        });
+        self.update_fov();
+    }
+
+    /// The visions the active player has.
+    pub fn visions(&self) -> Vec<Vision> {
+        self.visions
+            .iter()
+            .filter(|vision| vision.player == self.active)
+            .cloned()
+            .collect()
+    }
+
+    /// Show each living player what can be seen around their visions.
+    ///
+    /// Returns whether the visions showed the players anything new.
+    pub(super) fn see_visions(&mut self) -> bool {
+        let level = &self.level;
+        let mut learned = false;
+        for vision in &self.visions {
+            if !self.players[vision.player].alive {
+                continue;
+            }
+            let fov_index = self.fov_index(vision.player);
+            let fov = &mut self.fovs[fov_index];
+            let center = vision.center;
+            calc_fov(
+                center,
+                |pos| level[pos].terrain.transparent(),
+                |pos| {
+                    if pos.distance(center) <= CLAIRVOYANCE_RADIUS {
+                        learned |= see(&mut fov[pos], level[pos].terrain);
+                    }
+                },
+            );
+        }
+        learned
+    }
+
+    /// Count down the active player's visions by a turn, ending those that are over.
+    pub(super) fn count_down_visions(&mut self) {
+        let active = self.active;
+        for vision in self
+            .visions
+            .iter_mut()
+            .filter(|vision| vision.player == active)
+        {
+            vision.turns_left = vision.turns_left.saturating_sub(1);
+        }
+        self.visions.retain(|vision| vision.turns_left > 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::action::Action;
+    use world::observation::SeenTile;
+
+    #[test]
+    fn test_magic_mapping_is_known_but_unseen() {
+        let mut world = World::from_seed(34, GenParams::default());
+        let player = world.active_player();
+        let far = grid::positions()
+            .find(|&pos| world.fov()[pos] == TileView::None)
+            .unwrap();
+        world.magic_map(player, far, Some(2));
+        let terrain = world.level[far].terrain;
+        assert!(world.fov()[far] == TileView::Mapped(terrain));
+        assert!(!world.is_visible(far));
+        assert_eq!(world.observation().tiles[far], SeenTile::Mapped(terrain));
+        let seen = world.player().pos;
+        assert!(world.fov()[seen].is_visible());
+
+        world.forget_mapped(player);
+        assert!(world.fov()[far] == TileView::None);
+        world.magic_map(player, far, None);
+        assert!(grid::positions().all(|pos| world.fov()[pos] != TileView::None));
+        assert!(world.fov()[seen].is_visible());
+    }
+
+    #[test]
+    fn test_clairvoyance_sees_far_away_for_a_while() {
+        let mut world = World::from_seed(34, GenParams::default());
+        let player = world.active_player();
+        let center = world.player().pos;
+        let far = grid::positions()
+            .find(|&pos| {
+                world.level[pos].terrain == Terrain::Floor
+                    && pos.distance(center) > 12
+                    && pos
+                        .neighbors()
+                        .all(|pos| world.fov()[pos] == TileView::None)
+            })
+            .unwrap();
+        world.clairvoyance(player, far, 2);
+        assert!(world.is_visible(far));
+        assert_eq!(world.visions().len(), 1);
+        world.perform(Action::Rest).unwrap();
+        assert!(world.is_visible(far));
+        world.perform(Action::Rest).unwrap();
+        assert!(world.visions().is_empty());
+        assert!(!world.is_visible(far));
+        assert!(world.fov()[far] == TileView::Remembered(Terrain::Floor));
+    }
+}
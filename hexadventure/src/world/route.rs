@@ -0,0 +1,180 @@
+//! Travelling to remembered places on other levels.
+//!
+//! Every flight of stairs a player takes is recorded as a link between two
+//! levels. A route to a place on another level walks to the stairs of each
+//! link in turn, takes them, and carries on from where it arrives. Each leg
+//! only uses what the player remembers of its level, and the whole trip is
+//! abandoned as soon as a leg turns out to be blocked.
+//!
+//! Stairs only lead down so far, and taking them makes a new level, so the
+//! links never lead back to a level with remembered places on it.
+
+use prelude::*;
+use std::collections::VecDeque;
+use world::action::Action;
+use world::auto::Stop;
+
+/// A flight of stairs a player has taken.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct StairLink {
+    pub from: u32,
+    /// Where the player stood when they took the stairs.
+    pub approach: Pos,
+    pub stairs: Pos,
+    pub to: u32,
+    /// Where the player arrived on the other level.
+    pub arrival: Pos,
+}
+
+/// One level's worth of a route.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct Leg {
+    pub depth: u32,
+    pub destination: Pos,
+    /// The stairs to take from the destination, unless this is the last leg.
+    pub stairs: Option<Pos>,
+}
+
+impl World {
+    /// The stairs the players have taken, in order.
+    pub fn stair_links(&self) -> &[StairLink] {
+        &self.stair_links
+    }
+
+    /// Record that a player took the stairs from one position to the level they're on now.
+    pub(super) fn link_stairs(&mut self, approach: Pos, stairs: Pos, mob_id: MobId) {
+        let to = self.depth();
+        self.stair_links.push(StairLink {
+            from: to - 1,
+            approach,
+            stairs,
+            to,
+            arrival: self[mob_id].pos,
+        });
+    }
+
+    /// The legs of a route from the active player to a place, using known stairs.
+    pub fn route_to(&self, depth: u32, destination: Pos) -> Option<Vec<Leg>> {
+        plan_route(&self.stair_links, self.depth(), depth, destination)
+    }
+
+    /// Travel along a route, taking the stairs at the end of each leg.
+    ///
+    /// Stops as `Blocked` if there is no route, or if a leg can't be
+    /// followed because the level isn't as the player remembers it.
+    pub fn travel_route(&mut self, depth: u32, destination: Pos) -> Stop {
+        let legs = match self.route_to(depth, destination) {
+            Some(legs) => legs,
+            None => return Stop::Blocked,
+        };
+        for leg in legs {
+            if leg.depth != self.depth() {
+                return Stop::Blocked;
+            }
+            let stop = self.travel_to(leg.destination);
+            if stop != Stop::Done {
+                return stop;
+            }
+            if let Some(stairs) = leg.stairs {
+                let direction = (stairs - self.player().pos).direction();
+                let _ = self.perform(Action::Walk(direction));
+            }
+        }
+        Stop::Done
+    }
+}
+
+/// The shortest route over known stairs from one level to a place on another.
+fn plan_route(links: &[StairLink], from: u32, depth: u32, destination: Pos) -> Option<Vec<Leg>> {
+    let mut parents: Vec<(u32, Option<&StairLink>)> = vec![(from, None)];
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(level) = queue.pop_front() {
+        if level == depth {
+            break;
+        }
+        for link in links.iter().filter(|link| link.from == level) {
+            if parents.iter().all(|&(seen, _)| seen != link.to) {
+                parents.push((link.to, Some(link)));
+                queue.push_back(link.to);
+            }
+        }
+    }
+    let mut legs = vec![Leg {
+        depth,
+        destination,
+        stairs: None,
+    }];
+    let mut level = depth;
+    while level != from {
+        let link = parents
+            .iter()
+            .find(|&&(seen, _)| seen == level)
+            .and_then(|&(_, link)| link)?;
+        legs.push(Leg {
+            depth: link.from,
+            destination: link.approach,
+            stairs: Some(link.stairs),
+        });
+        level = link.from;
+    }
+    legs.reverse();
+    Some(legs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::tile::Terrain;
+    use level::GenParams;
+
+    #[test]
+    fn test_route_follows_known_stairs() {
+        let pos = |steps: i32| grid::center() + Direction::East * steps;
+        let link = |from: u32, to: u32| StairLink {
+            from,
+            approach: pos(from as i32),
+            stairs: pos(from as i32 + 1),
+            to,
+            arrival: pos(-(to as i32)),
+        };
+        let links = [link(1, 2), link(2, 3), link(1, 4)];
+        let route = plan_route(&links, 1, 3, pos(5)).unwrap();
+        let depths: Vec<u32> = route.iter().map(|leg| leg.depth).collect();
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(route[0].destination, pos(1));
+        assert_eq!(route[1].stairs, Some(pos(3)));
+        assert_eq!(route[2].destination, pos(5));
+        assert_eq!(route[2].stairs, None);
+        assert_eq!(plan_route(&links, 1, 1, pos(5)).unwrap().len(), 1);
+        assert_eq!(plan_route(&links, 3, 1, pos(5)), None);
+    }
+
+    #[test]
+    fn test_stairs_taken_are_linked() {
+        let mut world = World::from_seed(31, GenParams::default());
+        let exit = world.exit().unwrap();
+        let beside = exit
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain.passable() && world.level[pos].mob_id.is_none())
+            .unwrap();
+        let player = world.active_player();
+        world.move_mob(player, beside);
+        let _ = world.perform_confirmed(Action::Walk((exit - beside).direction()));
+        assert_eq!(world.depth(), 2);
+        let link = world.stair_links()[0];
+        assert_eq!((link.from, link.to), (1, 2));
+        assert_eq!((link.approach, link.stairs), (beside, exit));
+        assert_eq!(link.arrival, world.player().pos);
+
+        assert_eq!(world.route_to(1, beside), None);
+        assert_eq!(world.travel_route(1, beside), Stop::Blocked);
+        let here = world.player().pos;
+        let near = here
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain == Terrain::Floor)
+            .unwrap();
+        assert_eq!(world.route_to(2, near).unwrap().len(), 1);
+    }
+}
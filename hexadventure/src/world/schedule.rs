@@ -1,16 +1,385 @@
+//! The order everything happens in during a turn.
+//!
+//! Ending a player's turn runs each `Phase` in turn:
+//!
+//! 1. Upkeep: if a new round is starting, the turn counter advances and
+//!    decals fade. The active player's light burns, and their sense
+//!    statuses and visions wear off a little.
+//! 2. Environment: at the start of a round spiders spin webs. Then
+//!    scheduled events that are due run,
+//!    soonest first, and those due on the same turn in the order they were
+//!    scheduled, and a collapse is scheduled if a player is sealed in.
+//!    Every field of view is then updated.
+//! 3. Actions: if every living player has now had a turn, the way to the
+//!    players is worked out and the packs chasing them plan their steps
+//!    together. Each npc then acts once in the order it was spawned,
+//!    deciding from what players could see in step 2.
+//! 4. Cleanup: each living player's guard and health are recorded, for
+//!    recapping their death should they die.
+//!
+//! A round is every living player taking a turn, and `World::turn` counts
+//! rounds, so timers run at the same pace however many players there are.
+//!
+//! Systems registered with `World::register_turn_system` run at the end of
+//! their phase, in the order they were registered.
+//!
+//! With `World::record_path_stats` on, what the path searches and flow
+//! fields of the whole turn did is added up for `World::path_stats`.
+//!
+//! Everything an action does resolves before the next actor moves, so
+//! there is no simultaneous movement or damage:
+//!
+//! - When two mobs step toward the same tile, the one spawned first gets
+//!   it, and the other finds it taken and chooses again.
+//! - Damage applies as it is dealt. A mob killed by damage dies at once, and
+//!   a dead mob or player never takes another action.
+//! - An npc spawned during step 3 first acts the next round.
+//!
+//! Debug builds then check `World::validate_invariants`.
+
+use astar::{self, FlowField, SearchStats};
 use prelude::*;
+use std::mem::{replace, take};
 use world::ai;
 use world::mob;
 
+/// A part of the turn.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+pub enum Phase {
+    /// Bookkeeping and statuses that wear off with time.
+    Upkeep,
+    /// The level itself changing, and players seeing the result.
+    Environment,
+    /// Npcs acting.
+    Actions,
+    /// Tidying up after everything else.
+    Cleanup,
+}
+
+/// Every phase, in the order a turn runs them.
+pub const PHASES: [Phase; 4] = [
+    Phase::Upkeep,
+    Phase::Environment,
+    Phase::Actions,
+    Phase::Cleanup,
+];
+
+/// Something that happens every turn, in one phase of it.
+///
+/// Systems are `Send` so a world can be sent between threads.
+pub trait TurnSystem: Send {
+    /// A short name used when reporting timings.
+    fn name(&self) -> &str;
+
+    fn phase(&self) -> Phase;
+
+    fn run(&self, world: &mut World);
+}
+
 impl World {
+    /// End the turn of the active player.
+    ///
+    /// Players take turns in order, skipping the dead.
+    /// Npcs act once every player has had a turn.
     pub fn tick(&mut self) {
-        self.update_fov();
-        self.player.guard_recovery = 0;
-        mob::for_each_mut(self, |mob_id, world| {
-            if world[mob_id].alive {
-                ai::act(mob_id, world).unwrap();
-                world[mob_id].guard_recovery = 0;
+        let recording = self.path_stats.is_some();
+        if recording {
+            astar::start_recording();
+        }
+        for &phase in &PHASES {
+            self.run_phase(phase);
+        }
+        if recording {
+            self.path_stats = astar::stop_recording();
+        }
+        if cfg!(debug_assertions) {
+            if let Err(violations) = self.validate_invariants() {
+                panic!("Invariants broken on turn {}: {:?}", self.turn, violations);
+            }
+        }
+    }
+
+    /// Run one phase of the turn, followed by the systems registered for it.
+    ///
+    /// `tick` runs every phase in order. Running them one at a time is
+    /// only meant for timing and testing them.
+    pub fn run_phase(&mut self, phase: Phase) {
+        match phase {
+            Phase::Upkeep => {
+                if self.starts_round() {
+                    self.turn += 1;
+                    self.fade_decals();
+                }
+                self.burn_light();
+                self.count_down_senses();
+                self.count_down_visions();
+            }
+            Phase::Environment => {
+                if self.starts_round() {
+                    self.spin_webs();
+                }
+                self.run_due_events();
+                self.check_sealed();
+                self.update_fov();
+            }
+            Phase::Actions => {
+                let player = self.active_player();
+                self[player].guard_recovery = 0;
+                if self.next_player() {
+                    self.update_pursuit();
+                    mob::for_each_mut(self, |mob_id, world| {
+                        if world[mob_id].alive {
+                            ai::act(mob_id, world).unwrap();
+                            world[mob_id].guard_recovery = 0;
+                        }
+                    });
+                    self.pass_from_dead();
+                }
+            }
+            Phase::Cleanup => self.record_vitals(),
+        }
+        let systems = take(&mut self.turn_systems);
+        for system in systems.iter().filter(|system| system.phase() == phase) {
+            system.run(self);
+        }
+        let registered = replace(&mut self.turn_systems, systems);
+        self.turn_systems.extend(registered);
+    }
+
+    /// Work out the way to the living players from every tile, for npcs
+    /// chasing them, and the steps of the packs among them.
+    fn update_pursuit(&mut self) {
+        let targets: Vec<Pos> = self
+            .players
+            .iter()
+            .filter(|player| player.alive)
+            .map(|player| player.pos)
+            .collect();
+        let level = &self.level;
+        let field = FlowField::new(&targets, |pos| level[pos].terrain.passable_once_opened());
+        self.pursuit = Some(field);
+        self.pack_steps = ai::plan_pack(self);
+    }
+
+    /// Run a system every turn from now on, at the end of its phase.
+    ///
+    /// Systems aren't saved, so they must be registered again after loading.
+    pub fn register_turn_system(&mut self, system: Box<dyn TurnSystem>) {
+        self.turn_systems.push(system);
+    }
+
+    /// Start or stop adding up what the path searches of each turn do.
+    pub fn record_path_stats(&mut self, on: bool) {
+        self.path_stats = if on {
+            Some(SearchStats::default())
+        } else {
+            None
+        };
+    }
+
+    /// What the path searches and flow fields of the last turn did, if recording.
+    pub fn path_stats(&self) -> Option<SearchStats> {
+        self.path_stats
+    }
+
+    /// Whether the active player is the first living one, whose turn starts
+    /// a round.
+    fn starts_round(&self) -> bool {
+        self.players[..self.active]
+            .iter()
+            .all(|player| !player.alive)
+    }
+
+    /// Pass the turn on from an active player the npcs have just killed.
+    ///
+    /// The active player is the first living one, so the players passed
+    /// over are dead too and the round goes on rather than ending.
+    fn pass_from_dead(&mut self) {
+        if !self.players[self.active].alive && !self.is_over() {
+            self.next_player();
+        }
+    }
+
+    /// Pass the turn to the next living player.
+    ///
+    /// Returns whether every player has now had a turn.
+    fn next_player(&mut self) -> bool {
+        let count = self.players.len();
+        let mut round_over = false;
+        for _ in 0..count {
+            self.active += 1;
+            if self.active == count {
+                self.active = 0;
+                round_over = true;
+            }
+            if self.players[self.active].alive {
+                break;
+            }
+        }
+        round_over
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::{open_floor, remove_npcs};
+
+    use level::tile::Terrain;
+    use level::GenParams;
+    use world::action::Action;
+    use world::mob::Species;
+    use world::Party;
+
+    #[test]
+    fn test_players_alternate() {
+        let party = Party {
+            size: 2,
+            shared_fov: true,
+        };
+        let mut world = World::with_party(3, GenParams::default(), party);
+        assert_eq!(world.active_player(), MobId::player(0));
+        world.tick();
+        assert_eq!(world.active_player(), MobId::player(1));
+        assert_eq!(world.turn(), 1);
+        world.tick();
+        assert_eq!(world.active_player(), MobId::player(0));
+        assert_eq!(world.turn(), 1);
+        world.tick();
+        assert_eq!(world.turn(), 2);
+    }
+
+    #[test]
+    fn test_dead_players_skipped() {
+        let party = Party {
+            size: 3,
+            shared_fov: false,
+        };
+        let mut world = World::with_party(4, GenParams::default(), party);
+        MobId::player(1).die(&mut world);
+        world.tick();
+        assert_eq!(world.active_player(), MobId::player(2));
+        assert!(!world.is_over());
+    }
+
+    #[test]
+    fn test_active_player_killed_by_npcs_skipped() {
+        let party = Party {
+            size: 2,
+            shared_fov: false,
+        };
+        let mut world = World::with_party(4, GenParams::default(), party);
+        remove_npcs(&mut world);
+        let first = MobId::player(0);
+        let second = MobId::player(1);
+        let center = world[first].pos;
+        open_floor(&mut world, center, 3);
+        let beside = center
+            .neighbors()
+            .find(|&pos| world.level[pos].mob_id.is_none() && pos.distance(world[second].pos) > 1)
+            .unwrap();
+        let skeleton = world.spawn(Species::Skeleton, beside).unwrap();
+        world[first].guard = 0;
+        world[first].health = 1;
+        world.tick();
+        world.tick();
+        assert!(!world[first].alive);
+        assert!(world[skeleton].alive);
+        assert_eq!(world.active_player(), second);
+        assert_eq!(world.turn(), 1);
+        for turn in 2..7 {
+            world.perform_confirmed(Action::Rest).unwrap();
+            assert_eq!(world.active_player(), second);
+            assert_eq!(world.turn(), turn);
+            assert_eq!(world.validate_invariants(), Ok(()));
+        }
+        second.die(&mut world);
+        assert!(world.perform_confirmed(Action::Rest).is_err());
+    }
+
+    /// Counts the npcs still alive on the level.
+    struct Census;
+
+    impl TurnSystem for Census {
+        fn name(&self) -> &str {
+            "census"
+        }
+
+        fn phase(&self) -> Phase {
+            Phase::Cleanup
+        }
+
+        fn run(&self, world: &mut World) {
+            let mut alive = 0;
+            mob::for_each(world, |mob_id| {
+                if world[mob_id].alive {
+                    alive += 1;
+                }
+            });
+            world.report(world.player().pos, format!("{} alive", alive));
+        }
+    }
+
+    #[test]
+    fn test_registered_system_runs_each_turn() {
+        let mut world = World::from_seed(5, GenParams::default());
+        world.register_turn_system(Box::new(Census));
+        world.tick();
+        world.tick();
+        let reports = world
+            .messages()
+            .iter()
+            .filter(|message| message.text.ends_with(" alive"))
+            .count();
+        assert_eq!(reports, 2);
+    }
+
+    #[test]
+    fn test_phases_make_up_a_tick() {
+        let mut ticked = World::from_seed(6, GenParams::default());
+        let mut phased = World::from_seed(6, GenParams::default());
+        for _ in 0..5 {
+            ticked.tick();
+            for &phase in &PHASES {
+                phased.run_phase(phase);
             }
+        }
+        assert_eq!(ticked.turn(), phased.turn());
+        assert_eq!(ticked.state_hash(), phased.state_hash());
+    }
+
+    #[test]
+    fn test_npcs_chase_around_obstacles() {
+        let mut world = World::from_seed(7, GenParams::default());
+        remove_npcs(&mut world);
+        let center = world.player().pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 5) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        // Water in every step that looks like it closes in, but not in the way.
+        let start: Pos = center + Direction::East * 3;
+        for pos in start.neighbors().filter(|pos| pos.distance(center) == 2) {
+            world.level[pos].terrain = Terrain::Water;
+        }
+        world.reclassify();
+        let skeleton = world.spawn(Species::Skeleton, start).unwrap();
+        world.tick();
+        assert_ne!(world[skeleton].pos, start);
+        assert_eq!(world[skeleton].pos.distance(center), 3);
+    }
+
+    #[test]
+    fn test_npcs_spawned_mid_round_wait() {
+        let mut world = World::from_seed(4, GenParams::default());
+        let mut visited = 0;
+        let pos = world.player().pos;
+        mob::for_each_mut(&mut world, |_, world| {
+            visited += 1;
+            world.npcs.insert(Mob::new(pos, Species::Skeleton));
         });
+        let mut count = 0;
+        mob::for_each(&world, |_| count += 1);
+        assert!(visited > 0);
+        assert_eq!(count, visited * 2);
     }
 }
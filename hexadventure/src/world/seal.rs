@@ -0,0 +1,251 @@
+//! Sealed stairs, and the runes that open them.
+//!
+//! Every few levels the stairs down are sealed, and only the rune of that
+//! level lets the party past. The rune is carried by the strongest mob on
+//! the level, its guardian, which drops it where it dies or leaves by the
+//! stairs. A level left without mobs has its rune lying by the stairs. A
+//! player who steps onto a rune takes it for the whole party, for the rest
+//! of the run.
+
+use level::tile::Terrain;
+use prelude::*;
+use world::chronicle::MilestoneKind;
+use world::mob::{self, capitalize};
+
+/// How many levels apart the sealed stairs are.
+pub const SEAL_INTERVAL: u32 = 5;
+
+/// The key to the sealed stairs of a level.
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Rune {
+    pub depth: u32,
+}
+
+/// Sealed stairs the party has come to, and whether it can pass them.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Seal {
+    pub depth: u32,
+    pub rune_found: bool,
+}
+
+/// Whether the stairs down of a level are sealed.
+pub fn is_sealed(depth: u32) -> bool {
+    depth % SEAL_INTERVAL == 0
+}
+
+impl World {
+    /// The runes the party has found.
+    pub fn runes(&self) -> &[Rune] {
+        &self.runes
+    }
+
+    /// The sealed stairs on this level and those left behind, shallowest first.
+    pub fn seals(&self) -> Vec<Seal> {
+        (1..=self.depth())
+            .filter(|&depth| is_sealed(depth))
+            .map(|depth| Seal {
+                depth,
+                rune_found: self.runes.contains(&Rune { depth }),
+            })
+            .collect()
+    }
+
+    /// Whether the stairs down of the current level refuse the party.
+    pub fn stairs_sealed(&self) -> bool {
+        let depth = self.depth();
+        is_sealed(depth) && !self.runes.contains(&Rune { depth })
+    }
+
+    /// Hand the rune of a level that was just entered to its guardian, or
+    /// lay it by the stairs if there is none.
+    pub(super) fn place_rune(&mut self) {
+        let depth = self.depth();
+        if !is_sealed(depth) {
+            return;
+        }
+        let mut guardian: Option<MobId> = None;
+        mob::for_each(self, |mob_id| {
+            let strongest = match guardian {
+                Some(best) => self[mob_id].max_health > self[best].max_health,
+                None => true,
+            };
+            if self[mob_id].alive && strongest {
+                guardian = Some(mob_id);
+            }
+        });
+        let rune = Some(Rune { depth });
+        if let Some(guardian) = guardian {
+            self[guardian].rune = rune;
+            return;
+        }
+        let exit = match self.exit() {
+            Some(exit) => exit,
+            None => return,
+        };
+        let spot = grid::positions()
+            .filter(|&pos| self.can_hold_rune(pos))
+            .min_by_key(|pos| pos.distance(exit));
+        if let Some(pos) = spot {
+            self.level[pos].rune = rune;
+        }
+    }
+
+    /// Whether a rune left on a tile can be picked up by walking onto it.
+    fn can_hold_rune(&self, pos: Pos) -> bool {
+        let tile = &self.level[pos];
+        let terrain_ok = match tile.terrain {
+            Terrain::ShallowWater | Terrain::Web | Terrain::Portal => false,
+            terrain => terrain.passable(),
+        };
+        terrain_ok && tile.mob_id.is_none() && tile.rune.is_none()
+    }
+
+    /// Leave the rune a mob carries on its tile, as it dies or leaves the level.
+    pub(crate) fn drop_rune(&mut self, mob_id: MobId) {
+        if let Some(rune) = self[mob_id].rune.take() {
+            let pos = self[mob_id].pos;
+            self.level[pos].rune = Some(rune);
+            self.report(pos, "A rune clatters to the ground.".to_owned());
+        }
+    }
+
+    /// Take any rune on the tile a player has just stepped onto.
+    pub(crate) fn pick_up_rune(&mut self, mob_id: MobId) {
+        if !mob_id.is_player() {
+            return;
+        }
+        let pos = self[mob_id].pos;
+        if let Some(rune) = self.level[pos].rune.take() {
+            self.runes.push(rune);
+            self.note(MilestoneKind::TookRune);
+            let message = format!(
+                "{} takes the rune of depth {}.",
+                capitalize(&self.describe(mob_id)),
+                rune.depth
+            );
+            self.report(pos, message);
+        }
+    }
+
+    /// Turn a player back from sealed stairs. Returns whether they were sealed.
+    pub(crate) fn refuse_passage(&mut self, mob_id: MobId, stairs: Pos) -> bool {
+        if !self.stairs_sealed() || self.level[stairs].terrain != Terrain::Exit {
+            return false;
+        }
+        let message = format!(
+            "The stairs are sealed. {} needs the rune of depth {}.",
+            capitalize(&self.describe(mob_id)),
+            self.depth()
+        );
+        self.report(stairs, message);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::GenParams;
+    use world::action::{self, Action};
+
+    fn descend_to_seal(seed: u64) -> World {
+        let mut world = World::from_seed(seed, GenParams::default());
+        let player = world.active_player();
+        while world.depth() < SEAL_INTERVAL {
+            let exit = world.exit().unwrap();
+            action::descend_unchecked(player, exit, &mut world);
+        }
+        world
+    }
+
+    fn guardian(world: &World) -> Option<MobId> {
+        let mut guardian = None;
+        mob::for_each(world, |mob_id| {
+            if world[mob_id].rune.is_some() {
+                guardian = Some(mob_id);
+            }
+        });
+        guardian
+    }
+
+    #[test]
+    fn test_sealed_stairs_open_with_the_rune() {
+        let mut world = descend_to_seal(31);
+        assert_eq!(
+            world.seals(),
+            vec![Seal {
+                depth: SEAL_INTERVAL,
+                rune_found: false,
+            }]
+        );
+        let player = world.active_player();
+        let exit = world.exit().unwrap();
+        let beside = exit
+            .neighbors()
+            .find(|&pos| world.level[pos].terrain.passable() && world.level[pos].mob_id.is_none())
+            .unwrap();
+        world.move_mob(player, beside);
+        let toward_exit = Action::Walk((exit - beside).direction());
+        assert!(world.perform_confirmed(toward_exit).is_err());
+        assert_eq!(world.depth(), SEAL_INTERVAL);
+
+        let guardian = guardian(&world).unwrap();
+        let spot = world[guardian].pos;
+        guardian.die(&mut world);
+        assert_eq!(
+            world.level[spot].rune,
+            Some(Rune {
+                depth: SEAL_INTERVAL
+            })
+        );
+        world.move_mob(player, spot);
+        world.pick_up_rune(player);
+        assert!(world.seals()[0].rune_found);
+
+        world.move_mob(player, beside);
+        let _ = world.perform_confirmed(toward_exit);
+        assert_eq!(world.depth(), SEAL_INTERVAL + 1);
+    }
+
+    #[test]
+    fn test_rune_without_guardian_lies_on_dry_free_floor() {
+        let mut world = descend_to_seal(31);
+        remove_npcs(&mut world);
+        let exit = world.exit().unwrap();
+        for pos in exit.neighbors().filter(|&pos| grid::contains(pos)) {
+            if world.level[pos].terrain.passable() {
+                world.level[pos].terrain = Terrain::ShallowWater;
+            }
+        }
+        world.place_rune();
+        let spot = grid::positions()
+            .find(|&pos| world.level[pos].rune.is_some())
+            .unwrap();
+        assert!(spot.distance(exit) > 1);
+        assert!(world.level[spot].terrain.passable());
+        assert_ne!(world.level[spot].terrain, Terrain::ShallowWater);
+        assert!(world.level[spot].mob_id.is_none());
+    }
+
+    #[test]
+    fn test_rune_is_kept_in_saves() {
+        let mut world = descend_to_seal(32);
+        let guardian = guardian(&world).unwrap();
+        let player = world.active_player();
+        let spot = world[guardian].pos;
+        guardian.die(&mut world);
+        world.move_mob(player, spot);
+        world.pick_up_rune(player);
+        let bytes = bincode::serialize(&world).unwrap();
+        let loaded: World = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(
+            loaded.runes(),
+            &[Rune {
+                depth: SEAL_INTERVAL
+            }]
+        );
+        assert!(!loaded.stairs_sealed());
+    }
+}
@@ -0,0 +1,248 @@
+//! Statuses that dull or sharpen a player's senses for a while.
+//!
+//! A blind player sees only the tiles next to them, whatever light there
+//! is, and otherwise goes by what they remember. A deaf player hears
+//! nothing, so noises that would be reported to them are left out of the
+//! messages. A hallucinating player sees mobs and loot as they aren't, as
+//! `hallucination` describes. Each status counts down on its player's turns and wears off
+//! when it reaches zero.
+//!
+//! Mobs can also be sensed without being seen, through walls. A telepathic
+//! player senses every mind on the level for as long as it lasts, and
+//! detecting life senses every living thing once, where it stood at the
+//! time. What is sensed is kept apart from what is seen, since a sensed
+//! mob's tile is no better known than before.
+
+use prelude::*;
+use world::mob::{self, capitalize, Species};
+
+/// For how many turns after it is cast a pulse of detect life can be sensed.
+pub const PULSE_TURNS: u64 = 1;
+
+/// A mob sensed without being seen.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Sensed {
+    pub mob_id: MobId,
+    pub pos: Pos,
+    pub species: Species,
+}
+
+/// The living things a pulse of detect life found.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct LifePulse {
+    turn: u64,
+    found: Vec<Sensed>,
+}
+
+impl World {
+    /// Blind a mob for some turns, or longer if it already is.
+    pub fn blind(&mut self, mob_id: MobId, turns: u32) {
+        let mob = &mut self[mob_id];
+        mob.blind = mob.blind.max(turns);
+        self.update_fov();
+    }
+
+    /// Deafen a mob for some turns, or longer if it already is.
+    pub fn deafen(&mut self, mob_id: MobId, turns: u32) {
+        let mob = &mut self[mob_id];
+        mob.deaf = mob.deaf.max(turns);
+    }
+
+    pub fn is_blind(&self, mob_id: MobId) -> bool {
+        self[mob_id].blind > 0
+    }
+
+    pub fn is_deaf(&self, mob_id: MobId) -> bool {
+        self[mob_id].deaf > 0
+    }
+
+    /// Let a mob sense every mind on the level for some turns, or longer if it already can.
+    pub fn grant_telepathy(&mut self, mob_id: MobId, turns: u32) {
+        let mob = &mut self[mob_id];
+        mob.telepathic = mob.telepathic.max(turns);
+    }
+
+    pub fn is_telepathic(&self, mob_id: MobId) -> bool {
+        self[mob_id].telepathic > 0
+    }
+
+    /// Sense every living thing on the level once, on behalf of a mob.
+    pub fn detect_life(&mut self, mob_id: MobId) {
+        let mut found = Vec::new();
+        mob::for_each(self, |mob_id| {
+            let npc = &self[mob_id];
+            if npc.alive && !npc.species.undead() {
+                found.push(Sensed {
+                    mob_id,
+                    pos: npc.pos,
+                    species: npc.species,
+                });
+            }
+        });
+        let message = format!(
+            "{} senses {} living things.",
+            capitalize(&self.describe(mob_id)),
+            found.len()
+        );
+        self.life_pulse = Some(LifePulse {
+            turn: self.turn(),
+            found,
+        });
+        let pos = self[mob_id].pos;
+        self.report(pos, message);
+    }
+
+    /// The mobs the active player senses but can't see, as they look to them.
+    pub fn sensed(&self) -> Vec<Sensed> {
+        let me = self.active_player();
+        let fov = self.fov();
+        let mut sensed = Vec::new();
+        if self.is_telepathic(me) {
+            mob::for_each(self, |mob_id| {
+                let npc = &self[mob_id];
+                if npc.alive && npc.species.has_mind() && !fov[npc.pos].is_visible() {
+                    sensed.push(Sensed {
+                        mob_id,
+                        pos: npc.pos,
+                        species: self.seen_species(mob_id),
+                    });
+                }
+            });
+        }
+        if let Some(pulse) = &self.life_pulse {
+            for &found in &pulse.found {
+                let found = Sensed {
+                    species: self.seen_species(found.mob_id),
+                    ..found
+                };
+                if !fov[found.pos].is_visible() && !sensed.contains(&found) {
+                    sensed.push(found);
+                }
+            }
+        }
+        sensed
+    }
+
+    /// Report a noise to the players, unless none of them can hear.
+    pub(super) fn hear(&mut self, text: String) {
+        let listeners = (0..self.players.len())
+            .filter(|&index| {
+                let player = MobId::player(index);
+                self[player].alive && !self.is_deaf(player)
+            })
+            .collect();
+        self.log(text, listeners);
+    }
+
+    /// Count down the active player's sense statuses by a turn, and let
+    /// an old pulse of detect life fade.
+    pub(super) fn count_down_senses(&mut self) {
+        let player = self.active_player();
+        let mob = &mut self[player];
+        mob.blind = mob.blind.saturating_sub(1);
+        mob.deaf = mob.deaf.saturating_sub(1);
+        mob.telepathic = mob.telepathic.saturating_sub(1);
+        mob.hallucinating = mob.hallucinating.saturating_sub(1);
+        let turn = self.turn();
+        if self
+            .life_pulse
+            .as_ref()
+            .is_some_and(|pulse| pulse.turn + PULSE_TURNS < turn)
+        {
+            self.life_pulse = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use level::tile::{Terrain, TileView};
+    use world::action::Action;
+    use world::mob::Mob;
+    use world::observation::SeenTile;
+
+    #[test]
+    fn test_blind_player_sees_only_adjacent_tiles() {
+        let mut world = open_world(33, 10);
+        let player = world.active_player();
+        let center = world[player].pos;
+        let far: Pos = center + Direction::East * 3;
+        assert!(world.is_visible(far));
+
+        world.blind(player, 2);
+        assert!(world.is_visible(center + Direction::East));
+        assert!(!world.is_visible(far));
+        assert!(world.fov()[far] == TileView::Remembered(Terrain::Floor));
+        let observation = world.observation();
+        assert!(observation.blind);
+        assert_eq!(observation.tiles[far], SeenTile::Remembered(Terrain::Floor));
+
+        world.perform(Action::Rest).unwrap();
+        assert!(world.is_blind(player));
+        world.perform(Action::Rest).unwrap();
+        assert!(!world.is_blind(player));
+        world.update_fov();
+        assert!(world.is_visible(far));
+    }
+
+    #[test]
+    fn test_deaf_players_hear_nothing() {
+        let mut world = open_world(33, 10);
+        let player = world.active_player();
+        world.deafen(player, 5);
+        assert!(world.observation().deaf);
+        let before = world.messages().len();
+        world.hear("You hear a distant rumble.".to_owned());
+        assert_eq!(world.messages().len(), before);
+
+        world[player].deaf = 0;
+        world.hear("You hear a distant rumble.".to_owned());
+        assert_eq!(world.messages().len(), before + 1);
+    }
+
+    #[test]
+    fn test_telepathy_and_detect_life_sense_through_walls() {
+        let mut world = open_world(33, 10);
+        let player = world.active_player();
+        let center = world[player].pos;
+        for pos in center.ring(11) {
+            world.level[pos].terrain = Terrain::Wall;
+        }
+        let archer_pos: Pos = center + Direction::East * 12;
+        let spider_pos: Pos = archer_pos + Direction::Northeast;
+        let skeleton_pos: Pos = center + Direction::West * 12;
+        for &(pos, species) in &[
+            (archer_pos, Species::Archer),
+            (spider_pos, Species::Spider),
+            (skeleton_pos, Species::Skeleton),
+        ] {
+            world.level[pos].terrain = Terrain::Floor;
+            let mob_id = world.npcs.insert(Mob::new(pos, species));
+            world.level[pos].mob_id = Some(mob_id);
+        }
+        world.update_fov();
+        assert!(!world.is_visible(archer_pos));
+        assert!(world.sensed().is_empty());
+
+        let species = |world: &World| -> Vec<Species> {
+            let mut species: Vec<Species> = world.sensed().iter().map(|s| s.species).collect();
+            species.sort_by_key(|species| species.name());
+            species
+        };
+        world.grant_telepathy(player, 3);
+        assert_eq!(species(&world), vec![Species::Archer]);
+        assert_eq!(world.observation().sensed, world.sensed());
+
+        world.detect_life(player);
+        assert_eq!(species(&world), vec![Species::Archer, Species::Spider]);
+        world.perform(Action::Rest).unwrap();
+        assert_eq!(species(&world), vec![Species::Archer, Species::Spider]);
+        world.perform(Action::Rest).unwrap();
+        assert_eq!(species(&world), vec![Species::Archer]);
+        world.perform(Action::Rest).unwrap();
+        assert!(world.sensed().is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+//! Footsteps, and the npcs that hear them.
+//!
+//! Every step a player takes makes a noise that carries some number of
+//! tiles. Npcs within earshot go to look where it came from, even if no
+//! player can see them.
+
+use prelude::*;
+use world::mob;
+use world::modifier::{Attribute, Effect, Modifier, Source};
+
+/// How far an ordinary footstep carries.
+pub const FOOTSTEP_RADIUS: u32 = 4;
+/// How much less far a step on grass carries.
+pub const GRASS_MUFFLE: u32 = 2;
+/// How much further a step into a web carries.
+pub const WEB_RUSTLE: u32 = 2;
+/// How much further a step through shallow water carries.
+pub const SPLASH: u32 = 2;
+/// Quiet steps carry half as far.
+const SNEAKING: Modifier = Modifier::new(Source::Action, Attribute::Noise, Effect::Percent(-50));
+
+impl World {
+    /// How far the noise of a mob stepping onto a tile carries.
+    ///
+    /// Movement asks this and nothing else how loud a step is. Quiet steps
+    /// carry half as far, and huge mobs are louder.
+    pub fn footstep_noise(&self, mob_id: MobId, to: Pos, quiet: bool) -> u32 {
+        let mut modifiers = self.modifiers_at(mob_id, to);
+        if quiet {
+            modifiers.push(SNEAKING);
+        }
+        let radius = modifiers.apply(Attribute::Noise, FOOTSTEP_RADIUS as i32);
+        radius.max(0) as u32
+    }
+
+    /// Let every npc within earshot hear a mob that has just stepped.
+    ///
+    /// Only players are listened for.
+    pub(crate) fn footstep(&mut self, mob_id: MobId, quiet: bool) {
+        if !mob_id.is_player() {
+            return;
+        }
+        let pos = self[mob_id].pos;
+        let radius = self.footstep_noise(mob_id, pos, quiet);
+        mob::for_each_mut(self, |npc, world| {
+            if world[npc].alive && world[npc].pos.distance(pos) <= radius {
+                world[npc].heard = Some(pos);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::open_world;
+
+    use level::tile::Terrain;
+    use world::action::Action;
+    use world::mob::Species;
+
+    /// A world on open floor with a skeleton a few tiles from the player.
+    fn listening_world() -> (World, MobId) {
+        let mut world = open_world(12, 7);
+        let center = world.player().pos;
+        let npc = world
+            .spawn(Species::Skeleton, center + Direction::West * 5)
+            .unwrap();
+        (world, npc)
+    }
+
+    #[test]
+    fn test_footstep_noise() {
+        let (mut world, _) = listening_world();
+        let player = world.active_player();
+        let pos = world[player].pos;
+        assert_eq!(world.footstep_noise(player, pos, false), FOOTSTEP_RADIUS);
+        assert_eq!(world.footstep_noise(player, pos, true), FOOTSTEP_RADIUS / 2);
+        world.level[pos].terrain = Terrain::ShortGrass;
+        assert_eq!(
+            world.footstep_noise(player, pos, false),
+            FOOTSTEP_RADIUS - GRASS_MUFFLE
+        );
+    }
+
+    #[test]
+    fn test_sneaking_takes_two_turns_and_goes_unheard() {
+        let (mut world, npc) = listening_world();
+        let player = world.active_player();
+        let start = world[player].pos;
+        world
+            .perform_confirmed(Action::Sneak(Direction::East))
+            .unwrap();
+        assert_eq!(world[player].pos, start);
+        world
+            .perform_confirmed(Action::Sneak(Direction::East))
+            .unwrap();
+        assert_eq!(world[player].pos, start + Direction::East);
+
+        world[npc].heard = None;
+        world.footstep(player, true);
+        assert_eq!(world[npc].heard, None);
+        world.footstep(player, false);
+        assert_eq!(world[npc].heard, Some(start + Direction::East));
+    }
+}
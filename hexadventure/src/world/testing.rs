@@ -0,0 +1,49 @@
+//! Fixtures shared by the tests of the world's modules.
+
+use level::tile::Terrain;
+use level::GenParams;
+use prelude::*;
+use world::mob;
+
+/// Take every npc off the level, leaving only the players.
+pub fn remove_npcs(world: &mut World) {
+    let mut npcs = Vec::new();
+    mob::for_each(world, |mob_id| npcs.push(mob_id));
+    for mob_id in npcs {
+        world.lift(mob_id);
+        world[mob_id].alive = false;
+    }
+}
+
+/// Take the npcs within a distance of a position off the level.
+pub fn remove_npcs_near(world: &mut World, center: Pos, radius: u32) {
+    let mut npcs = Vec::new();
+    mob::for_each(world, |mob_id| npcs.push(mob_id));
+    for mob_id in npcs {
+        if world[mob_id].pos.distance(center) <= radius {
+            world.lift(mob_id);
+            world[mob_id].alive = false;
+        }
+    }
+}
+
+/// Turn the tiles within a distance of a position into bare floor.
+///
+/// The edge of the grid stays as it is, so nothing can walk off it.
+pub fn open_floor(world: &mut World, center: Pos, radius: u32) {
+    for pos in grid::inner_positions().filter(|pos| pos.distance(center) <= radius) {
+        world.level[pos].terrain = Terrain::Floor;
+        world.level[pos].loot = None;
+    }
+}
+
+/// A world with nothing but the players in it, on open floor around the
+/// active player.
+pub fn open_world(seed: u64, radius: u32) -> World {
+    let mut world = World::from_seed(seed, GenParams::default());
+    remove_npcs(&mut world);
+    let center = world.player().pos;
+    open_floor(&mut world, center, radius);
+    world.update_fov();
+    world
+}
@@ -0,0 +1,232 @@
+//! Carried loot, and the thieves that run off with it.
+//!
+//! Players pick up loot they step on and carry it with them. A thief that
+//! hits a player may snatch one of the piles they carry, and from then on
+//! it stops fighting and runs for the stairs down. A thief that reaches the
+//! stairs escapes to the next level, turning up a little way ahead of the
+//! party when they follow, still on the run. Killing a thief drops
+//! everything it carries.
+
+use level::tile::Terrain;
+use loot::Loot;
+use prelude::*;
+use rand::Rng;
+use rng::Stream;
+use std::mem::take;
+use world::chronicle::MilestoneKind;
+use world::mob::capitalize;
+
+/// The percent chance that a thief's hit also takes a pile of loot.
+pub const STEAL_CHANCE: u32 = 50;
+/// How far ahead of the party an escaped thief turns up on the next level.
+const FUGITIVE_LEAD: u32 = 6;
+
+impl World {
+    /// Pick up any loot on the tile a player has just stepped onto.
+    pub(crate) fn pick_up(&mut self, mob_id: MobId) {
+        if !mob_id.is_player() {
+            return;
+        }
+        self.pick_up_rune(mob_id);
+        let pos = self[mob_id].pos;
+        if let Some(loot) = self.level[pos].loot.take() {
+            self[mob_id].carried.push(loot);
+            self.note(MilestoneKind::Found(loot));
+            let message = format!("{} picks up the loot.", capitalize(&self.describe(mob_id)));
+            self.report(pos, message);
+        }
+    }
+
+    /// Leave a pile of loot on a tile, or on the nearest tile it can share
+    /// if both it and the pile already there hold an item.
+    pub(crate) fn drop_loot(&mut self, pos: Pos, loot: Loot) {
+        let merged = |tile_loot: Option<Loot>| match tile_loot {
+            Some(tile_loot) => tile_loot.merge(loot),
+            None => Some(loot),
+        };
+        let spot = grid::positions()
+            .filter(|&spot| {
+                self.level[spot].terrain.passable() && merged(self.level[spot].loot).is_some()
+            })
+            .min_by_key(|spot| spot.distance(pos));
+        if let Some(spot) = spot {
+            self.level[spot].loot = merged(self.level[spot].loot);
+        }
+    }
+
+    /// Maybe take a random pile of loot from a target a thief has just hit.
+    pub(crate) fn steal(&mut self, thief: MobId, target: MobId) {
+        let count = self[target].carried.len();
+        if count == 0 || self.rng(Stream::Combat).gen_range(0, 100) >= STEAL_CHANCE {
+            return;
+        }
+        // Rolled as a u32, since 64-bit rolls from the isaac rng can misread its buffer.
+        let index = self.rng(Stream::Combat).gen_range(0, count as u32) as usize;
+        let loot = self[target].carried.remove(index);
+        self[thief].carried.push(loot);
+        let message = format!(
+            "{} snatches something from {}!",
+            capitalize(&self.describe(thief)),
+            self.describe(target)
+        );
+        let pos = self[thief].pos;
+        self.report(pos, message);
+    }
+
+    /// Take a mob off the level by the stairs down, to turn up on the next one.
+    pub(crate) fn escape(&mut self, mob_id: MobId) {
+        let pos = self[mob_id].pos;
+        let message = format!(
+            "{} escapes down the stairs.",
+            capitalize(&self.describe(mob_id))
+        );
+        self.report(pos, message);
+        let fugitive = self.carry_off(mob_id);
+        self.fugitives.push(fugitive);
+    }
+
+    /// Take an npc off the level, returning a copy of it to put on the next one.
+    pub(super) fn carry_off(&mut self, mob_id: MobId) -> Mob {
+        self.drop_rune(mob_id);
+        self.lift(mob_id);
+        self[mob_id].alive = false;
+        let mut copy = Mob::new(self[mob_id].pos, self[mob_id].species);
+        copy.health = self[mob_id].health;
+        copy.guard = self[mob_id].guard;
+        copy.carried = take(&mut self[mob_id].carried);
+        copy
+    }
+
+    /// Put the mobs that escaped the last level on this one, a little way from the party.
+    pub(super) fn place_fugitives(&mut self, center: Pos) {
+        let lead = center
+            .ring(FUGITIVE_LEAD)
+            .find(|&pos| grid::contains(pos) && self.level[pos].terrain.passable())
+            .unwrap_or(center);
+        for fugitive in take(&mut self.fugitives) {
+            let mob_id = self.npcs.insert(fugitive);
+            self.arrive(mob_id, lead);
+        }
+    }
+
+    /// The stairs down of the current level.
+    pub(crate) fn exit(&self) -> Option<Pos> {
+        grid::positions().find(|&pos| self.level[pos].terrain == Terrain::Exit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::testing::remove_npcs;
+
+    use level::GenParams;
+    use loot::{Tier, TIERS};
+    use world::action::{self, Action};
+    use world::ai;
+    use world::mob::{self, Species};
+
+    fn treasure() -> Loot {
+        Loot {
+            gold: 7,
+            item: Some(Tier::Rare),
+        }
+    }
+
+    #[test]
+    fn test_loot_picked_up_and_stolen() {
+        let mut world = World::from_seed(24, GenParams::default());
+        remove_npcs(&mut world);
+        let player = world.active_player();
+        let center = world[player].pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 4) {
+            world.level[pos].terrain = Terrain::Floor;
+        }
+        let east: Pos = center + Direction::East;
+        world.level[east].loot = Some(treasure());
+        world
+            .perform_confirmed(Action::Walk(Direction::East))
+            .unwrap();
+        assert_eq!(world.level[east].loot, None);
+        assert_eq!(world[player].carried, vec![treasure()]);
+
+        let thief = world.spawn(Species::Thief, east + Direction::East).unwrap();
+        for _ in 0..20 {
+            action::walk(thief, Direction::West, &mut world).unwrap();
+            if !world[thief].carried.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(world[thief].carried, vec![treasure()]);
+        assert!(world[player].carried.is_empty());
+    }
+
+    #[test]
+    fn test_thief_escapes_and_is_followed() {
+        let mut world = World::from_seed(24, GenParams::default());
+        remove_npcs(&mut world);
+        let exit = world.exit().unwrap();
+        let free = |world: &World, pos: Pos| {
+            world.level[pos].terrain.passable() && world.level[pos].mob_id.is_none()
+        };
+        let beside = exit.neighbors().find(|&pos| free(&world, pos)).unwrap();
+        let thief = world.spawn(Species::Thief, beside).unwrap();
+        world[thief].carried.push(treasure());
+        ai::act(thief, &mut world).unwrap();
+        assert!(!world[thief].alive);
+        assert_eq!(world.level[beside].mob_id, None);
+
+        let player = world.active_player();
+        world.move_mob(player, beside);
+        let _ = world.perform_confirmed(Action::Walk((exit - beside).direction()));
+        assert_eq!(world.depth(), 2);
+        let mut fugitive = None;
+        mob::for_each(&world, |mob_id| {
+            if world[mob_id].alive && !world[mob_id].carried.is_empty() {
+                fugitive = Some(mob_id);
+            }
+        });
+        let fugitive = fugitive.unwrap();
+        assert_eq!(world[fugitive].carried, vec![treasure()]);
+        let pos = world[fugitive].pos;
+        fugitive.die(&mut world);
+        let dropped = world.level[pos].loot.unwrap();
+        assert_eq!(dropped.item, Some(Tier::Rare));
+        assert!(dropped.gold >= treasure().gold);
+    }
+
+    #[test]
+    fn test_dead_thief_drops_every_item() {
+        let mut world = World::from_seed(24, GenParams::default());
+        remove_npcs(&mut world);
+        let center = world[world.active_player()].pos;
+        for pos in grid::positions().filter(|pos| pos.distance(center) <= 4) {
+            world.level[pos].terrain = Terrain::Floor;
+            world.level[pos].loot = None;
+        }
+        let pos = center + Direction::East + Direction::East;
+        let thief = world.spawn(Species::Thief, pos).unwrap();
+        let common = Loot {
+            gold: 3,
+            item: Some(Tier::Common),
+        };
+        world[thief].carried = vec![treasure(), common];
+        world.level[pos].loot = Some(Loot {
+            gold: 1,
+            item: Some(Tier::Uncommon),
+        });
+        let count_items = |world: &World, tier| {
+            grid::positions()
+                .filter(|&pos| world.level[pos].loot.and_then(|loot| loot.item) == Some(tier))
+                .count()
+        };
+        let before: Vec<_> = TIERS
+            .iter()
+            .map(|&tier| count_items(&world, tier))
+            .collect();
+        thief.die(&mut world);
+        assert!(count_items(&world, Tier::Common) > before[0]);
+        assert!(count_items(&world, Tier::Uncommon) >= before[1]);
+        assert!(count_items(&world, Tier::Rare) > before[2]);
+    }
+}
@@ -0,0 +1,127 @@
+//! Webs, and the mobs caught in them.
+//!
+//! A mob that walks into a web is caught, and every move it tries is spent
+//! struggling until it breaks free. It can still attack. Breaking free tears
+//! the web. Spiders spin webs where they stand and are never caught.
+
+use level::tile::Terrain;
+use prelude::*;
+use rand::Rng;
+use rng::Stream;
+use world::mob::{self, capitalize, Species};
+
+/// How many turns pass between a spider spinning one web and the next.
+pub const SPIN_INTERVAL: u64 = 10;
+
+impl Mob {
+    /// The percent chance of breaking free of a web in one try.
+    ///
+    /// A mob with its guard up keeps its footing and is likelier to get loose.
+    pub fn escape_chance(&self) -> u32 {
+        25 + 50 * self.guard / self.max_guard.max(1)
+    }
+}
+
+impl World {
+    /// Catch a mob that has just stepped into a web.
+    pub(crate) fn entangle(&mut self, mob_id: MobId) {
+        let pos = self[mob_id].pos;
+        if self.level[pos].terrain == Terrain::Web && self[mob_id].species.caught_by_webs() {
+            self[mob_id].entangled = true;
+            let message = format!("{} is caught in a web.", capitalize(&self.describe(mob_id)));
+            self.report(pos, message);
+        }
+    }
+
+    /// Spend a turn trying to break free of a web.
+    pub(crate) fn struggle(&mut self, mob_id: MobId) {
+        let pos = self[mob_id].pos;
+        let name = capitalize(&self.describe(mob_id));
+        let chance = self[mob_id].escape_chance();
+        if self.rng(Stream::Combat).gen_range(0, 100) < chance {
+            self[mob_id].entangled = false;
+            if self.level[pos].terrain == Terrain::Web {
+                self.set_terrain(pos, Terrain::Floor);
+            }
+            self.report(pos, format!("{} breaks free.", name));
+        } else {
+            self.report(pos, format!("{} struggles in the web.", name));
+        }
+    }
+
+    /// Every so often, have each spider spin a web where it stands.
+    pub(super) fn spin_webs(&mut self) {
+        if self.turn % SPIN_INTERVAL != 0 {
+            return;
+        }
+        let mut spiders = Vec::new();
+        mob::for_each(self, |mob_id| spiders.push(mob_id));
+        for mob_id in spiders {
+            let (alive, species, pos) =
+                (self[mob_id].alive, self[mob_id].species, self[mob_id].pos);
+            if alive && species == Species::Spider {
+                let terrain = self.level[pos].terrain;
+                if terrain == Terrain::Floor || terrain == Terrain::ShortGrass {
+                    self.set_terrain(pos, Terrain::Web);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grid::DIRECTIONS;
+    use level::GenParams;
+    use world::action::{self, Action};
+
+    #[test]
+    fn test_caught_until_free() {
+        let mut world = World::from_seed(14, GenParams::default());
+        let player = world.active_player();
+        let start = world[player].pos;
+        let (web, direction) = DIRECTIONS
+            .iter()
+            .map(|&direction| (start + direction, direction))
+            .find(|&(pos, _)| {
+                world.level[pos].terrain.passable() && world.level[pos].mob_id.is_none()
+            })
+            .unwrap();
+        world.level[web].terrain = Terrain::Web;
+        world.perform_confirmed(Action::Walk(direction)).unwrap();
+        assert!(world[player].entangled);
+        assert_eq!(world[player].pos, web);
+
+        let mut tries = 0;
+        while world[player].entangled {
+            action::walk(player, direction.rotate(3), &mut world).unwrap();
+            assert_eq!(world[player].pos, web);
+            tries += 1;
+            assert!(tries < 100);
+        }
+        assert_eq!(world.level[web].terrain, Terrain::Floor);
+    }
+
+    #[test]
+    fn test_spiders_spin_and_walk_webs() {
+        let mut world = World::from_seed(14, GenParams::default());
+        let player = world.active_player();
+        let pos = world[player]
+            .pos
+            .ring(4)
+            .find(|&pos| {
+                grid::contains(pos)
+                    && world.level[pos].terrain == Terrain::Floor
+                    && world.level[pos].mob_id.is_none()
+            })
+            .unwrap();
+        let spider = world.spawn(Species::Spider, pos).unwrap();
+        world.turn = SPIN_INTERVAL;
+        world.spin_webs();
+        assert_eq!(world.level[pos].terrain, Terrain::Web);
+        world.entangle(spider);
+        assert!(!world[spider].entangled);
+    }
+}
@@ -1,8 +1,5 @@
 extern crate bincode;
-use bincode::{deserialize_from, serialize_into};
-
-extern crate app_dirs;
-use app_dirs::{app_root, AppDataType, AppInfo};
+use bincode::{deserialize, serialize};
 
 extern crate ggez;
 use ggez::conf::{Conf, WindowMode, WindowSetup};
@@ -16,25 +13,30 @@ use ggez::{Context, GameResult};
 extern crate image;
 
 extern crate hexadventure;
+use hexadventure::content::Content;
 use hexadventure::grid::{pos_to_location, Location};
 use hexadventure::level::tile::TileView;
 use hexadventure::prelude::*;
-use hexadventure::world::action;
-use hexadventure::world::mob::PLAYER_ID;
+use hexadventure::profile::{Profile, StoredProfile};
+use hexadventure::save;
+use hexadventure::storage::{DirStorage, MemoryStorage, Storage};
+use hexadventure::world::action::{Action, ActionError};
+use hexadventure::world::engrave::{Inscription, WARD};
 
 mod sprite;
-use sprite::{color_from_tile, darken, sprite_from_species, sprite_src, Sprite};
+use sprite::{
+    color_from_decal, color_from_tile, darken, mapped_color, sensed_color, sprite_from_species,
+    sprite_src, Sprite,
+};
 
 mod side;
 
 use std::error::Error;
-use std::fs::File;
 
 const SAVE_NAME: &str = "save.bincode";
-const APP_INFO: AppInfo = AppInfo {
-    name: "hex-adventure",
-    author: "as-f",
-};
+/// The save before the latest one, kept in case the latest is damaged.
+const BACKUP_NAME: &str = "save.bincode.bak";
+const PROFILE_NAME: &str = "profile.bincode";
 
 enum Arrow {
     None,
@@ -50,6 +52,10 @@ struct MainState {
     redraw: bool,
     dests: Grid<Point2>,
     pressed_arrow: Arrow,
+    /// A dangerous action waiting to be repeated as confirmation, and why.
+    pending: Option<(Action, String)>,
+    /// Whether moves are made quietly, at half speed.
+    quiet: bool,
 }
 
 fn pos_to_point2(pos: Pos) -> Point2 {
@@ -58,39 +64,71 @@ fn pos_to_point2(pos: Pos) -> Point2 {
 }
 
 impl MainState {
-    fn new(ctx: &mut Context) -> Self {
+    fn new(ctx: &mut Context, world: World) -> Self {
         let spritebatch = sprite::load_spritebatch(ctx);
         let mut dests = Grid::new(|_pos| Point2::new(0.0, 0.0));
         for pos in grid::positions() {
             dests[pos] = pos_to_point2(pos);
         }
-        let world = match load_world() {
-            Ok(world) => world,
-            _ => World::new(),
-        };
         MainState {
             world,
             spritebatch,
             redraw: true,
             dests,
             pressed_arrow: Arrow::None,
+            pending: None,
+            quiet: false,
         }
     }
 }
 
-fn load_world() -> Result<World, Box<Error>> {
-    let mut path = app_root(AppDataType::UserData, &APP_INFO)?;
-    path.push(SAVE_NAME);
-    let file = File::open(path)?;
-    let game = deserialize_from(file)?;
+fn load_world(storage: &Storage) -> Result<World, Box<Error>> {
+    if storage.read(SAVE_NAME)?.is_none() {
+        return Err("no save".into());
+    }
+    match load_world_from(storage, SAVE_NAME) {
+        Ok(world) => Ok(world),
+        Err(e) => {
+            println!("Error in loading game: {}", e);
+            let world = load_world_from(storage, BACKUP_NAME)?;
+            println!("Loaded the previous save instead.");
+            Ok(world)
+        }
+    }
+}
+
+fn load_world_from(storage: &Storage, name: &str) -> Result<World, Box<Error>> {
+    let bytes = storage.read(name)?.ok_or("no save")?;
+    let payload = save::open(&bytes, Content::default().fingerprint())?;
+    let game = deserialize(payload)?;
     Ok(game)
 }
 
-fn save_world(game: &World) -> Result<(), Box<Error>> {
-    let mut path = app_root(AppDataType::UserData, &APP_INFO)?;
-    path.push(SAVE_NAME);
-    let file = File::create(path)?;
-    serialize_into(file, game)?;
+/// Save a world, keeping the previous save as the backup.
+///
+/// A previous save that doesn't load is written over instead, so a damaged
+/// save never replaces a backup that was fine.
+fn save_world(storage: &mut Storage, game: &World) -> Result<(), Box<Error>> {
+    if load_world_from(&*storage, SAVE_NAME).is_ok() {
+        storage.rename(SAVE_NAME, BACKUP_NAME)?;
+    }
+    let payload = serialize(game)?;
+    storage.write(SAVE_NAME, &save::seal(&payload, game.content().fingerprint()))?;
+    Ok(())
+}
+
+/// The stored profile, or `None` if there isn't one yet.
+fn load_profile(storage: &Storage) -> Result<Option<Profile>, Box<Error>> {
+    let bytes = match storage.read(PROFILE_NAME)? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+    let stored: StoredProfile = deserialize(&bytes)?;
+    Ok(Some(stored.migrate()))
+}
+
+fn save_profile(storage: &mut Storage, profile: &Profile) -> Result<(), Box<Error>> {
+    storage.write(PROFILE_NAME, &serialize(&profile.store())?)?;
     Ok(())
 }
 
@@ -115,6 +153,32 @@ impl MainState {
     }
 }
 
+impl MainState {
+    /// Take an action, asking for it to be repeated first if it is dangerous.
+    fn perform(&mut self, action: Action) {
+        let deaths = self.world.death_recaps().len();
+        let confirmed = match self.pending {
+            Some((pending, _)) => pending == action,
+            None => false,
+        };
+        let result = if confirmed {
+            self.world.perform_confirmed(action)
+        } else {
+            self.world.perform(action)
+        };
+        self.pending = match result {
+            Err(ActionError::NeedsConfirmation(danger)) => Some((action, danger.to_string())),
+            _ => None,
+        };
+        for recap in &self.world.death_recaps()[deaths..] {
+            for line in recap.describe() {
+                println!("{}", line);
+            }
+        }
+        self.redraw = true;
+    }
+}
+
 impl EventHandler for MainState {
     fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
         Ok(())
@@ -131,21 +195,26 @@ impl EventHandler for MainState {
             ctx,
             Point2::new((grid::WIDTH * 18 + 9) as f32, 0.0),
             &self.world,
+            self.pending.as_ref().map(|(_, reason)| reason.as_str()),
             &mut self.spritebatch,
         )?;
         for pos in grid::positions() {
-            match self.world.fov[pos] {
+            match self.world.fov()[pos] {
                 TileView::Visible => {
                     if let Some(mob_id) = self.world.level[pos].mob_id {
-                        let sprite = sprite_from_species(&self.world[mob_id].species);
+                        let sprite = sprite_from_species(&self.world.seen_species(mob_id));
                         let flip = match self.world[mob_id].facing {
                             Direction::West | Direction::Northwest | Direction::Southwest => false,
                             Direction::East | Direction::Northeast | Direction::Southeast => true,
                         };
                         self.draw_tile(sprite, pos, graphics::WHITE, flip);
                     } else {
-                        let terrain = self.world.level[pos].terrain;
-                        self.draw_tile(Sprite::from(terrain), pos, color_from_tile(terrain), false);
+                        let tile = self.world.level[pos];
+                        let color = match tile.decal {
+                            Some(decal) => color_from_decal(decal),
+                            None => color_from_tile(tile.terrain),
+                        };
+                        self.draw_tile(Sprite::from(tile.terrain), pos, color, false);
                     }
                 }
                 TileView::Remembered(terrain) => self.draw_tile(
@@ -154,9 +223,19 @@ impl EventHandler for MainState {
                     darken(color_from_tile(terrain)),
                     false,
                 ),
+                TileView::Mapped(terrain) => self.draw_tile(
+                    Sprite::from(terrain),
+                    pos,
+                    mapped_color(color_from_tile(terrain)),
+                    false,
+                ),
                 TileView::None => {}
             };
         }
+        for sensed in self.world.sensed() {
+            let sprite = sprite_from_species(&sensed.species);
+            self.draw_tile(sprite, sensed.pos, sensed_color(), false);
+        }
         graphics::draw(ctx, &self.spritebatch, Point2::new(0.0, 0.0), 0.0)?;
         graphics::present(ctx);
         Ok(())
@@ -177,6 +256,25 @@ impl EventHandler for MainState {
             Keycode::Z => Some(Action::Walk(Direction::Southwest)),
             Keycode::X => Some(Action::Walk(Direction::Southeast)),
             Keycode::S => Some(Action::Rest),
+            Keycode::F => Some(Action::Refuel),
+            // There is no way to type a message yet, so only the ward can be engraved.
+            Keycode::V => Inscription::new(WARD).map(Action::Engrave),
+            Keycode::R => Some(Action::Stance(self.world.player().stance.next())),
+            Keycode::Q => {
+                self.quiet = !self.quiet;
+                None
+            }
+            Keycode::C => {
+                let options = self.world.options_mut();
+                options.combat_detail = !options.combat_detail;
+                None
+            }
+            Keycode::L => {
+                for record in self.world.combat_log() {
+                    println!("{}", record.describe());
+                }
+                None
+            }
             Keycode::Up => {
                 let (action, pressed_arrow) = match self.pressed_arrow {
                     Arrow::None | Arrow::Up => (None, Arrow::Up),
@@ -234,14 +332,11 @@ impl EventHandler for MainState {
             _ => None,
         };
         if let Some(action) = action {
-            let success = match action {
-                Action::Rest => action::rest(PLAYER_ID, &mut self.world),
-                Action::Walk(direction) => action::walk(PLAYER_ID, direction, &mut self.world),
+            let action = match action {
+                Action::Walk(direction) if self.quiet => Action::Sneak(direction),
+                action => action,
             };
-            if success.is_ok() {
-                self.world.tick();
-            }
-            self.redraw = true;
+            self.perform(action);
         }
     }
 
@@ -290,33 +385,64 @@ impl EventHandler for MainState {
             _ => None,
         };
         if let Some(action) = action {
-            let success = match action {
-                Action::Rest => action::rest(PLAYER_ID, &mut self.world),
-                Action::Walk(direction) => action::walk(PLAYER_ID, direction, &mut self.world),
+            let action = match action {
+                Action::Walk(direction) if self.quiet => Action::Sneak(direction),
+                action => action,
             };
-            if success.is_ok() {
-                self.world.tick();
-            }
-            self.redraw = true;
+            self.perform(action);
         }
     }
 }
-enum Action {
-    Rest,
-    Walk(Direction),
-    // MeleeAttack(Direction),
-}
-
 fn main() {
+    if let Err(errors) = Content::default().lint() {
+        for error in errors {
+            println!("Content error: {}", error);
+        }
+    }
     let mut ctx = Context::load_from_conf("hex-adventure", "as-f", conf())
         .expect("Failed to load context from configuration.");
     graphics::set_default_filter(&mut ctx, graphics::FilterMode::Nearest);
     graphics::set_background_color(&mut ctx, graphics::BLACK);
-    let mut state = MainState::new(&mut ctx);
+    let mut storage: Box<Storage> = match DirStorage::open_default() {
+        Some(storage) => Box::new(storage),
+        None => {
+            println!("Nowhere to save to, so the game won't be saved.");
+            Box::new(MemoryStorage::new())
+        }
+    };
+    // A profile that can't be read is left alone rather than replaced by a
+    // fresh one, so a newer game or a fixed build can still read it.
+    let (mut profile, profile_writable) = match load_profile(&*storage) {
+        Ok(profile) => (profile.unwrap_or_default(), true),
+        Err(e) => {
+            println!("Error in loading profile, so it won't be updated: {}", e);
+            (Profile::default(), false)
+        }
+    };
+    let world = match load_world(&*storage) {
+        Ok(world) => world,
+        _ => {
+            let mut world = World::new();
+            profile.apply(&mut world);
+            world
+        }
+    };
+    let mut state = MainState::new(&mut ctx, world);
     if let Err(e) = event::run(&mut ctx, &mut state) {
         println!("Error encountered: {}", e);
     }
-    if let Err(e) = save_world(&state.world) {
+    if state.world.is_over() {
+        print!("{}", state.world.morgue());
+        for achievement in profile.record_run(&state.world) {
+            println!("Achievement: {}", achievement);
+        }
+        if profile_writable {
+            if let Err(e) = save_profile(&mut *storage, &profile) {
+                println!("Error in saving profile: {}", e);
+            }
+        }
+    }
+    if let Err(e) = save_world(&mut *storage, &state.world) {
         println!("Error in saving game: {}", e);
     }
 }
@@ -19,6 +19,7 @@ impl Sidebar {
         ctx: &mut Context,
         dest: Point2,
         world: &World,
+        prompt: Option<&str>,
         spritebatch: &mut SpriteBatch,
     ) -> GameResult<()> {
         let width = WIDTH as f32 * 9.0;
@@ -39,12 +40,12 @@ impl Sidebar {
             },
         )?;
         draw_str(
-            &format!("Health: {}", world.player.health),
+            &format!("Health: {}", world.player().health),
             spritebatch,
             Point2::new(dest.x + 18.0, dest.y + 16.0),
         )?;
         draw_str(
-            &format!("Guard: {}", world.player.guard),
+            &format!("Guard: {}", world.player().guard),
             spritebatch,
             Point2::new(dest.x + 18.0, dest.y + 32.0),
         )?;
@@ -63,6 +64,27 @@ impl Sidebar {
             );
             i += 1;
         });
+        for (index, seal) in world.seals().iter().rev().enumerate() {
+            let rune = if seal.rune_found { "found" } else { "missing" };
+            draw_str(
+                &format!("Seal {}: rune {}", seal.depth, rune),
+                spritebatch,
+                Point2::new(dest.x + 18.0, dest.y + height - 80.0 - 16.0 * index as f32),
+            )?;
+        }
+        draw_str(
+            &world.level_name(world.depth()),
+            spritebatch,
+            Point2::new(dest.x + 18.0, dest.y + height - 16.0),
+        )?;
+        if let Some(prompt) = prompt {
+            draw_str(prompt, spritebatch, Point2::new(dest.x + 18.0, dest.y + height - 48.0))?;
+            draw_str(
+                "Repeat to confirm.",
+                spritebatch,
+                Point2::new(dest.x + 18.0, dest.y + height - 32.0),
+            )?;
+        }
         // for (index, mob) in game.mobs.npcs.iter().enumerate() {
         //     draw_str(&format!("Guard: {}", mob.guard), spritebatch, Point2::new(dest.x + 18.0, dest.y + 32.0 + 16.0 * index as f32))?;
         // }
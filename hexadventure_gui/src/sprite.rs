@@ -6,7 +6,7 @@ use image;
 use image::GenericImage;
 use image::ImageFormat;
 
-use hexadventure::level::tile::Terrain;
+use hexadventure::level::tile::{Decal, DecalKind, Terrain};
 use hexadventure::world::mob::Species;
 
 pub enum Sprite {
@@ -32,7 +32,14 @@ impl From<Terrain> for Sprite {
             Terrain::Brownberry => Sprite::Brownberry,
             Terrain::Exit => Sprite::Exit,
             Terrain::Entrance => Sprite::Entrance,
-            Terrain::Water => Sprite::Water,
+            Terrain::Water | Terrain::ShallowWater => Sprite::Water,
+            // There is no web sprite yet, so webs are drawn as pale grass.
+            Terrain::Web => Sprite::ShortGrass,
+            // Nor any door sprites. Closed doors are drawn as walls, open ones as floor.
+            Terrain::Door => Sprite::Wall,
+            Terrain::OpenDoor | Terrain::BrokenDoor => Sprite::Floor,
+            // Nor a portal sprite, so portals are drawn as tinted floor.
+            Terrain::Portal => Sprite::Floor,
         }
     }
 }
@@ -40,7 +47,15 @@ impl From<Terrain> for Sprite {
 pub fn sprite_from_species(species: &Species) -> Sprite {
     match species {
         Species::Hero => Sprite::Player,
-        Species::Skeleton => Sprite::Skeleton,
+        // There are no sprites yet for anything but skeletons among the npcs.
+        // Every tile a behemoth or serpent takes up is drawn.
+        Species::Skeleton
+        | Species::Behemoth
+        | Species::Spider
+        | Species::Archer
+        | Species::Thief
+        | Species::Necromancer
+        | Species::Serpent => Sprite::Skeleton,
     }
 }
 
@@ -51,9 +66,33 @@ pub fn color_from_tile(terrain: Terrain) -> Color {
         Terrain::Floor => Color::new(0.75, 0.75, 0.75, 1.0),
         ShortGrass | TallGrass | Brownberry => Color::new(0.0, 0.75, 0.0, 1.0),
         Terrain::Water => Color::new(0.0, 0.5, 1.0, 1.0),
+        Terrain::ShallowWater => Color::new(0.4, 0.7, 1.0, 1.0),
+        Terrain::Web => Color::new(0.9, 0.9, 0.85, 1.0),
+        Door | OpenDoor | BrokenDoor => Color::new(0.6, 0.4, 0.2, 1.0),
+        Terrain::Portal => Color::new(0.8, 0.4, 1.0, 1.0),
     }
 }
 
+pub fn color_from_decal(decal: Decal) -> Color {
+    match decal.kind {
+        DecalKind::Blood => Color::new(0.6, 0.0, 0.0, 1.0),
+    }
+}
+
+/// How a tile known only from magic mapping is drawn: faint and cold.
+pub fn mapped_color(color: Color) -> Color {
+    let color = darken(darken(color));
+    Color {
+        b: color.b + 0.2,
+        ..color
+    }
+}
+
+/// The tint of mobs that are sensed rather than seen.
+pub fn sensed_color() -> Color {
+    Color::new(0.7, 0.4, 1.0, 0.8)
+}
+
 pub fn darken(color: Color) -> Color {
     Color {
         r: color.r / 2.0,